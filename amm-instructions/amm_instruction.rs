@@ -1,567 +0,0 @@
-//! Instruction types
-
-#![allow(clippy::too_many_arguments)]
-
-use crate::curve::{base::SwapCurve, fees::Fees};
-use crate::error::AmmError;
-use solana_program::{
-    instruction::{AccountMeta, Instruction},
-    program_error::ProgramError,
-    program_pack::Pack,
-    pubkey::Pubkey,
-};
-use std::convert::TryInto;
-use std::mem::size_of;
-
-#[cfg(feature = "fuzz")]
-use arbitrary::Arbitrary;
-
-/// Initialize instruction data
-#[repr(C)]
-#[derive(Debug, PartialEq)]
-pub struct InitializeInstruction {
-    /// nonce used to create valid program address
-    pub nonce: u8,
-}
-
-/// Swap instruction data
-#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
-#[repr(C)]
-#[derive(Clone, Debug, PartialEq)]
-pub struct SwapInstruction {
-    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
-    pub amount_in: u64,
-    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
-    pub minimum_amount_out: u64,
-}
-
-/// Instruction instruction data
-#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
-#[repr(C)]
-#[derive(Clone, Debug, PartialEq)]
-pub struct DepositInstruction {
-    /// Pool token amount to transfer. token_a and token_b amount are set by
-    /// the current exchange rate and size of the pool
-    pub pool_token_amount: u64,
-    /// Maximum token A amount to deposit, prevents excessive slippage
-    pub maximum_token_a_amount: u64,
-    /// Maximum token B amount to deposit, prevents excessive slippage
-    pub maximum_token_b_amount: u64,
-}
-
-/// WithdrawInstruction instruction data
-#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
-#[repr(C)]
-#[derive(Clone, Debug, PartialEq)]
-pub struct WithdrawInstruction {
-    /// Amount of pool tokens to burn. User receives an output of token a
-    /// and b based on the percentage of the pool tokens that are returned.
-    pub pool_token_amount: u64,
-    /// Minimum amount of token A to receive, prevents excessive slippage
-    pub minimum_token_a_amount: u64,
-    /// Minimum amount of token B to receive, prevents excessive slippage
-    pub minimum_token_b_amount: u64,
-}
-
-/// Deposit one token type, exact amount in instruction data
-#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
-#[repr(C)]
-#[derive(Clone, Debug, PartialEq)]
-pub struct DepositSingleTokenTypeExactAmountIn {
-    /// Token amount to deposit
-    pub source_token_amount: u64,
-    /// Pool token amount to receive in exchange. The amount is set by
-    /// the current exchange rate and size of the pool
-    pub minimum_pool_token_amount: u64,
-}
-
-/// WithdrawAllTokenTypes instruction data
-#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
-#[repr(C)]
-#[derive(Clone, Debug, PartialEq)]
-pub struct WithdrawSingleTokenTypeExactAmountOut {
-    /// Amount of token A or B to receive
-    pub destination_token_amount: u64,
-    /// Maximum amount of pool tokens to burn. User receives an output of token A
-    /// or B based on the percentage of the pool tokens that are returned.
-    pub maximum_pool_token_amount: u64,
-}
-
-/// Instructions supported by the token swap program.
-#[repr(C)]
-#[derive(Debug, PartialEq)]
-pub enum AmmInstruction {
-    ///   Initializes a new AmmInfo.
-    ///
-    ///   0. `[writable, signer]` New Token-swap to create.
-    ///   1. `[]` swap authority derived from `create_program_address(&[Token-swap account])`
-    ///   2. `[]` AMMID of this account`
-    ///   3. `[]` token_a Account. Must be non zero, owned by swap authority.
-    ///   4. `[]` token_b Account. Must be non zero, owned by swap authority.
-    ///   5. `[writable]` Pool Token Mint. Must be empty, owned by swap authority.
-    ///   6. `[]` Token A Account to transfer fees when swap.
-    ///   7. `[]` Token B Account to transfer fees when swap.
-    ///   Must be empty, not owned by swap authority
-    ///   8. `[writable]` Pool Token Account to deposit the initial pool token
-    ///   supply.  Must be empty, not owned by swap authority.
-    ///   9. '[]` Token program id
-    ///   10. []  Dex Program ID
-    ///   11. []  Market ID
-    Initialize(InitializeInstruction),
-
-    ///   Swap the tokens in the pool.
-    ///
-    ///   0. `[]` Token-swap
-    ///   1. `[]` swap authority
-    ///   2. `[]` user transfer authority
-    ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
-    ///   4. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
-    ///   5. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
-    ///   6. `[writable]` token_(A|B) DESTINATION Account assigned to USER as the owner.
-    ///   7. `[writable]` Pool token mint, to generate trading fees
-    ///   8. `[writable]` Fee token account, to receive trading fees
-    ///   9. `[writable]` Fee wallet account, to receive fees when swap from SOL
-    ///   10. '[]` Token program id
-    ///   11 `[]  System Program ID to send SOL
-    Swap(SwapInstruction),
-
-    ///   Deposit both types of tokens into the pool.  The output is a "pool"
-    ///   token representing ownership in the pool. Inputs are converted to
-    ///   the current ratio.
-    ///
-    ///   0. `[]` Token-swap
-    ///   1. `[]` swap authority
-    ///   2. `[]` user transfer authority
-    ///   3. `[writable]` token_a user transfer authority can transfer amount,
-    ///   4. `[writable]` token_b user transfer authority can transfer amount,
-    ///   5. `[writable]` token_a Base Account to deposit into.
-    ///   6. `[writable]` token_b Base Account to deposit into.
-    ///   7. `[writable]` Pool MINT account, swap authority is the owner.
-    ///   8. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
-    ///   9. '[]` Token program id
-    DepositAllTokenTypes(DepositInstruction),
-
-    ///   Withdraw both types of tokens from the pool at the current ratio, given
-    ///   pool tokens.  The pool tokens are burned in exchange for an equivalent
-    ///   amount of token A and B.
-    ///
-    ///   0. `[]` Token-swap
-    ///   1. `[]` swap authority
-    ///   2. `[]` user transfer authority
-    ///   3. `[writable]` Pool mint account, swap authority is the owner
-    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
-    ///   5. `[writable]` token_a Swap Account to withdraw FROM.
-    ///   6. `[writable]` token_b Swap Account to withdraw FROM.
-    ///   7. `[writable]` token_a user Account to credit.
-    ///   8. `[writable]` token_b user Account to credit.
-    ///   9. `[writable]` Fee account, to receive withdrawal fees
-    ///   10 '[]` Token program id
-    WithdrawAllTokenTypes(WithdrawInstruction),
-
-    ///   Deposit one type of tokens into the pool.  The output is a "pool" token
-    ///   representing ownership into the pool. Input token is converted as if
-    ///   a swap and deposit all token types were performed.
-    ///
-    ///   0. `[]` Token-swap
-    ///   1. `[]` swap authority
-    ///   2. `[]` user transfer authority
-    ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
-    ///   4. `[writable]` token_a Swap Account, may deposit INTO.
-    ///   5. `[writable]` token_b Swap Account, may deposit INTO.
-    ///   6. `[writable]` Pool MINT account, swap authority is the owner.
-    ///   7. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
-    ///   8. '[]` Token program id
-    DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
-
-    ///   Withdraw one token type from the pool at the current ratio given the
-    ///   exact amount out expected.
-    ///
-    ///   0. `[]` Token-swap
-    ///   1. `[]` swap authority
-    ///   2. `[]` user transfer authority
-    ///   3. `[writable]` Pool mint account, swap authority is the owner
-    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
-    ///   5. `[writable]` token_a Swap Account to potentially withdraw from.
-    ///   6. `[writable]` token_b Swap Account to potentially withdraw from.
-    ///   7. `[writable]` token_(A|B) User Account to credit
-    ///   8. `[writable]` Fee account, to receive withdrawal fees
-    ///   9. '[]` Token program id
-    WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
-}
-
-impl AmmInstruction {
-    /// Unpacks a byte buffer into a [AmmInstruction](enum.AmmInstruction.html).
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (&tag, rest) = input.split_first().ok_or(AmmError::InvalidInstruction)?;
-        Ok(match tag {
-            0 => {// Initial 
-                if rest.len() == 1 {
-                    let (&nonce, _rest) = rest.split_first().ok_or(AmmError::InvalidInstruction)?;
-                    Self::Initialize(InitializeInstruction {
-                        nonce,
-                    })
-                } else {
-                    return Err(AmmError::InvalidInstruction.into());
-                }
-            }
-            1 => {
-                let (amount_in, rest) = Self::unpack_u64(rest)?;
-                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
-                Self::Swap(SwapInstruction {
-                    amount_in,
-                    minimum_amount_out,
-                })
-            }
-            2 => {
-                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
-                let (maximum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositAllTokenTypes(DepositInstruction {
-                    pool_token_amount,
-                    maximum_token_a_amount,
-                    maximum_token_b_amount,
-                })
-            }
-            3 => {
-                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
-                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawAllTokenTypes(WithdrawInstruction {
-                    pool_token_amount,
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                })
-            }
-            4 => {
-                let (source_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (minimum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                })
-            }
-            5 => {
-                let (destination_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (maximum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut {
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                })
-            }
-            _ => return Err(AmmError::InvalidInstruction.into()),
-        })
-    }
-
-    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
-        if input.len() >= 8 {
-            let (amount, rest) = input.split_at(8);
-            let amount = amount
-                .get(..8)
-                .and_then(|slice| slice.try_into().ok())
-                .map(u64::from_le_bytes)
-                .ok_or(AmmError::InvalidInstruction)?;
-            Ok((amount, rest))
-        } else {
-            Err(AmmError::InvalidInstruction.into())
-        }
-    }
-
-    /// Packs a [AmmInstruction](enum.AmmInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match &*self {
-            Self::Initialize(InitializeInstruction {
-                nonce,
-            }) => {
-                buf.push(0);
-                buf.push(*nonce);
-            }
-            Self::Swap(SwapInstruction {
-                amount_in,
-                minimum_amount_out,
-            }) => {
-                buf.push(1);
-                buf.extend_from_slice(&amount_in.to_le_bytes());
-                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
-            }
-            Self::DepositAllTokenTypes(DepositInstruction {
-                pool_token_amount,
-                maximum_token_a_amount,
-                maximum_token_b_amount,
-            }) => {
-                buf.push(2);
-                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
-                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
-                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
-            }
-            Self::WithdrawAllTokenTypes(WithdrawInstruction {
-                pool_token_amount,
-                minimum_token_a_amount,
-                minimum_token_b_amount,
-            }) => {
-                buf.push(3);
-                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
-                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
-                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
-            }
-            Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
-                source_token_amount,
-                minimum_pool_token_amount,
-            }) => {
-                buf.push(4);
-                buf.extend_from_slice(&source_token_amount.to_le_bytes());
-                buf.extend_from_slice(&minimum_pool_token_amount.to_le_bytes());
-            }
-            Self::WithdrawSingleTokenTypeExactAmountOut(
-                WithdrawSingleTokenTypeExactAmountOut {
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                },
-            ) => {
-                buf.push(5);
-                buf.extend_from_slice(&destination_token_amount.to_le_bytes());
-                buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
-            }
-        }
-        buf
-    }
-}
-
-/// Creates an 'initialize' instruction.
-pub fn initialize(
-    program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    state_pubkey: &Pubkey,
-    amm_id: &Pubkey,
-    token_a_pubkey: &Pubkey,
-    token_b_pubkey: &Pubkey,
-    pool_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-
-    market_pubkey: &Pubkey,
-    dex_pubkey: &Pubkey,
-
-    nonce: u8,
-) -> Result<Instruction, ProgramError> {
-    let init_data = AmmInstruction::Initialize(InitializeInstruction {
-        nonce,
-    });
-    let data = init_data.pack();
-
-    let accounts = vec![
-        AccountMeta::new(*swap_pubkey, true),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*state_pubkey, false),
-        AccountMeta::new_readonly(*amm_id, false),
-        AccountMeta::new_readonly(*token_a_pubkey, false),
-        AccountMeta::new_readonly(*token_b_pubkey, false),
-        AccountMeta::new(*pool_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        
-        AccountMeta::new(*market_pubkey, false),
-
-        AccountMeta::new_readonly(*token_program_id, false),
-        AccountMeta::new_readonly(*dex_pubkey, false),
-
-    ];
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
-
-/// Creates a 'deposit_all_token_types' instruction.
-pub fn deposit_all_token_types(
-    program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    state_pubkey: &Pubkey,
-    deposit_token_a_pubkey: &Pubkey,
-    deposit_token_b_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: DepositInstruction,
-) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::DepositAllTokenTypes(instruction).pack();
-
-    let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new_readonly(*state_pubkey, false),
-        AccountMeta::new(*deposit_token_a_pubkey, false),
-        AccountMeta::new(*deposit_token_b_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
-    ];
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
-
-/// Creates a 'withdraw_all_token_types' instruction.
-pub fn withdraw_all_token_types(
-    program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    state_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    source_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    destination_token_a_pubkey: &Pubkey,
-    destination_token_b_pubkey: &Pubkey,
-    instruction: WithdrawInstruction,
-) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::WithdrawAllTokenTypes(instruction).pack();
-
-    let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new_readonly(*state_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*source_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*destination_token_a_pubkey, false),
-        AccountMeta::new(*destination_token_b_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
-    ];
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
-
-/// Creates a 'deposit_single_token_type_exact_amount_in' instruction.
-pub fn deposit_single_token_type_exact_amount_in(
-    program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    source_token_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: DepositSingleTokenTypeExactAmountIn,
-) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
-
-    let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*source_token_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
-    ];
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
-
-/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction.
-pub fn withdraw_single_token_type_exact_amount_out(
-    program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    pool_token_source_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: WithdrawSingleTokenTypeExactAmountOut,
-) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
-
-    let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*pool_token_source_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
-    ];
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
-
-/// Creates a 'swap' instruction.
-pub fn swap(
-    program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    state_pubkey: &Pubkey,
-    source_pubkey: &Pubkey,
-    swap_source_pubkey: &Pubkey,
-    swap_destination_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    fee_account_pubkey: &Pubkey,
-    instruction: SwapInstruction,
-) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::Swap(instruction).pack();
-
-    let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new_readonly(*state_pubkey, true),
-        
-        AccountMeta::new(*source_pubkey, false),
-        AccountMeta::new(*swap_source_pubkey, false),
-        AccountMeta::new(*swap_destination_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        
-        AccountMeta::new(*pool_mint_pubkey, false),
-        
-        AccountMeta::new(*fee_account_pubkey, false),
-
-        AccountMeta::new_readonly(*token_program_id, false),
-    ];
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
@@ -0,0 +1,374 @@
+//! Base curve implementation
+
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{Pack, Sealed},
+};
+
+use crate::curve::{
+    calculator::{CurveCalculator, SwapWithoutFeesResult, TradeDirection},
+    constant_price::ConstantPriceCurve,
+    constant_product::ConstantProductCurve,
+    fees::Fees,
+    offset::OffsetCurve,
+    stable::StableCurve,
+};
+use crate::{error::AmmError, state::check_account_len};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+#[cfg(feature = "fuzz")]
+use arbitrary::Arbitrary;
+#[cfg(feature = "borsh")]
+use borsh::{
+    schema::{Declaration, Definition, Fields},
+    BorshSchema,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "borsh")]
+use std::collections::HashMap;
+
+/// Curve types supported by the token-swap program.
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CurveType {
+    /// Uniswap-style constant product curve, invariant = token_a_amount * token_b_amount
+    ConstantProduct = 0,
+    /// Flat line, always providing 1:1 from one token to another
+    ConstantPrice = 1,
+    /// Stable, like uniswap, but with wide zone of 1:1 instead of one point
+    Stable = 2,
+    /// Offset curve, like Uniswap, but the token B side has a faked offset
+    Offset = 3,
+}
+
+/// Encodes all results of swapping from a source token to a destination token
+#[derive(Debug, PartialEq)]
+pub struct SwapResult {
+    /// New amount of source token
+    pub new_swap_source_amount: u128,
+    /// New amount of destination token
+    pub new_swap_destination_amount: u128,
+    /// Amount of source token swapped (includes fees)
+    pub source_amount_swapped: u128,
+    /// Amount of destination token swapped
+    pub destination_amount_swapped: u128,
+    /// Amount of source tokens going to pool holders
+    pub trade_fee: u128,
+    /// Amount of source tokens going to owner
+    pub owner_fee: u128,
+}
+
+/// Concrete struct to wrap around the trait object which performs calculation.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SwapCurve {
+    /// The type of curve contained in the calculator, helpful for outside
+    /// queries
+    pub curve_type: CurveType,
+    /// The actual calculator, represented as a trait object to allow for many
+    /// different types of curves
+    pub calculator: Arc<dyn CurveCalculator + Sync + Send>,
+}
+
+impl SwapCurve {
+    /// Subtract fees and calculate how much destination token will be provided
+    /// given an amount of source token.
+    pub fn swap(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Option<SwapResult> {
+        // debit the fee to calculate the amount swapped
+        let trade_fee = fees.trading_fee(source_amount)?;
+        let owner_fee = fees.owner_trading_fee(source_amount)?;
+
+        let total_fees = trade_fee.checked_add(owner_fee)?;
+        let source_amount_less_fees = source_amount.checked_sub(total_fees)?;
+
+        let SwapWithoutFeesResult {
+            source_amount_swapped,
+            destination_amount_swapped,
+        } = self.calculator.swap_without_fees(
+            source_amount_less_fees,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+        )?;
+
+        let source_amount_swapped = source_amount_swapped.checked_add(total_fees)?;
+        Some(SwapResult {
+            new_swap_source_amount: swap_source_amount.checked_add(source_amount_swapped)?,
+            new_swap_destination_amount: swap_destination_amount
+                .checked_sub(destination_amount_swapped)?,
+            source_amount_swapped,
+            destination_amount_swapped,
+            trade_fee,
+            owner_fee,
+        })
+    }
+
+    /// Unpacks the curve packed at `offset` of some account data, e.g.
+    /// `ProgramState::SWAP_CURVE_OFFSET`, without unpacking the rest of the
+    /// account. Fails with `InvalidAccountLength` when the data is too short.
+    pub fn unpack_at(account_data: &[u8], offset: usize) -> Result<Self, ProgramError> {
+        let input = offset
+            .checked_add(Self::LEN)
+            .and_then(|end| account_data.get(offset..end))
+            .ok_or(AmmError::InvalidAccountLength)?;
+        Self::unpack_from_slice(input)
+    }
+}
+
+/// Default implementation for SwapCurve cannot be derived because of
+/// the contained Arc.
+impl Default for SwapCurve {
+    fn default() -> Self {
+        let curve_type: CurveType = Default::default();
+        let calculator: ConstantProductCurve = Default::default();
+        Self {
+            curve_type,
+            calculator: Arc::new(calculator),
+        }
+    }
+}
+
+/// Clone takes advantage of pack / unpack to get around the difficulty of
+/// cloning dynamic objects.
+/// Note that this is only to be used for testing.
+impl Clone for SwapCurve {
+    fn clone(&self) -> Self {
+        let mut packed_self = [0u8; Self::LEN];
+        Self::pack_into_slice(self, &mut packed_self);
+        Self::unpack_from_slice(&packed_self).unwrap()
+    }
+}
+
+/// Simple implementation for PartialEq which assumes that the output of
+/// `Pack` is enough to guarantee equality
+impl PartialEq for SwapCurve {
+    fn eq(&self, other: &Self) -> bool {
+        let mut packed_self = [0u8; Self::LEN];
+        Self::pack_into_slice(self, &mut packed_self);
+        let mut packed_other = [0u8; Self::LEN];
+        Self::pack_into_slice(other, &mut packed_other);
+        packed_self[..] == packed_other[..]
+    }
+}
+
+/// The schema is the packed representation, the calculator parameters
+/// depending on the curve type.
+#[cfg(feature = "borsh")]
+impl BorshSchema for SwapCurve {
+    fn add_definitions_recursively(definitions: &mut HashMap<Declaration, Definition>) {
+        let fields = Fields::NamedFields(vec![
+            ("curve_type".to_string(), u8::declaration()),
+            ("calculator".to_string(), <[u8; 32]>::declaration()),
+        ]);
+        Self::add_definition(
+            Self::declaration(),
+            Definition::Struct { fields },
+            definitions,
+        );
+        u8::add_definitions_recursively(definitions);
+        <[u8; 32]>::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        "SwapCurve".to_string()
+    }
+}
+
+/// Serialized form of a SwapCurve, the curve type tagging the parameters of
+/// its calculator
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "curve_type")]
+enum SwapCurveRepr {
+    ConstantProduct,
+    ConstantPrice(ConstantPriceCurve),
+    Stable(StableCurve),
+    Offset(OffsetCurve),
+}
+
+/// The calculator is only reachable as a trait object, so its parameters are
+/// recovered from the packed representation.
+#[cfg(feature = "serde")]
+impl Serialize for SwapCurve {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+
+        let mut packed_self = [0u8; Self::LEN];
+        Self::pack_into_slice(self, &mut packed_self);
+        let calculator = &packed_self[1..];
+        let repr = match self.curve_type {
+            CurveType::ConstantProduct => SwapCurveRepr::ConstantProduct,
+            CurveType::ConstantPrice => SwapCurveRepr::ConstantPrice(
+                ConstantPriceCurve::unpack_from_slice(calculator).map_err(S::Error::custom)?,
+            ),
+            CurveType::Stable => SwapCurveRepr::Stable(
+                StableCurve::unpack_from_slice(calculator).map_err(S::Error::custom)?,
+            ),
+            CurveType::Offset => SwapCurveRepr::Offset(
+                OffsetCurve::unpack_from_slice(calculator).map_err(S::Error::custom)?,
+            ),
+        };
+        repr.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for SwapCurve {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match SwapCurveRepr::deserialize(deserializer)? {
+            SwapCurveRepr::ConstantProduct => Self {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Arc::new(ConstantProductCurve),
+            },
+            SwapCurveRepr::ConstantPrice(calculator) => Self {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Arc::new(calculator),
+            },
+            SwapCurveRepr::Stable(calculator) => Self {
+                curve_type: CurveType::Stable,
+                calculator: Arc::new(calculator),
+            },
+            SwapCurveRepr::Offset(calculator) => Self {
+                curve_type: CurveType::Offset,
+                calculator: Arc::new(calculator),
+            },
+        })
+    }
+}
+
+impl Sealed for SwapCurve {}
+impl Pack for SwapCurve {
+    /// Size of encoding of all curve parameters, which include fees and any other
+    /// constants used to calculate swaps, deposits, and withdrawals.
+    /// This includes 1 byte for the type, and 32 for the calculator to use as
+    /// it needs.  Some calculators may be smaller than 32 bytes.
+    const LEN: usize = 33;
+
+    /// Unpacks a byte buffer into a SwapCurve
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let input = array_ref![input, 0, 33];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (curve_type, calculator) = array_refs![input, 1, 32];
+        let curve_type = curve_type[0].try_into()?;
+        Ok(Self {
+            curve_type,
+            calculator: match curve_type {
+                CurveType::ConstantProduct => {
+                    Arc::new(ConstantProductCurve::unpack_from_slice(calculator)?)
+                }
+                CurveType::ConstantPrice => {
+                    Arc::new(ConstantPriceCurve::unpack_from_slice(calculator)?)
+                }
+                CurveType::Stable => Arc::new(StableCurve::unpack_from_slice(calculator)?),
+                CurveType::Offset => Arc::new(OffsetCurve::unpack_from_slice(calculator)?),
+            },
+        })
+    }
+
+    /// Pack SwapCurve into a byte buffer
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 33];
+        let (curve_type, calculator) = mut_array_refs![output, 1, 32];
+        curve_type[0] = self.curve_type as u8;
+        self.calculator.pack_into_slice(&mut calculator[..]);
+    }
+}
+
+/// Sensible default of CurveType to ConstantProduct, the most popular and
+/// well-known curve type.
+impl Default for CurveType {
+    fn default() -> Self {
+        CurveType::ConstantProduct
+    }
+}
+
+impl TryFrom<u8> for CurveType {
+    type Error = ProgramError;
+
+    fn try_from(curve_type: u8) -> Result<Self, Self::Error> {
+        match curve_type {
+            0 => Ok(CurveType::ConstantProduct),
+            1 => Ok(CurveType::ConstantPrice),
+            2 => Ok(CurveType::Stable),
+            3 => Ok(CurveType::Offset),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_swap_curve() {
+        let curves: Vec<(CurveType, Arc<dyn CurveCalculator + Sync + Send>, Vec<u8>)> = vec![
+            (
+                CurveType::ConstantProduct,
+                Arc::new(ConstantProductCurve),
+                vec![],
+            ),
+            (
+                CurveType::ConstantPrice,
+                Arc::new(ConstantPriceCurve {
+                    token_b_price: 0x0102_0304_0506_0708,
+                }),
+                vec![8, 7, 6, 5, 4, 3, 2, 1],
+            ),
+            (
+                CurveType::Stable,
+                Arc::new(StableCurve { amp: 100 }),
+                100u64.to_le_bytes().to_vec(),
+            ),
+            (
+                CurveType::Offset,
+                Arc::new(OffsetCurve {
+                    token_b_offset: u64::MAX,
+                }),
+                vec![u8::MAX; 8],
+            ),
+        ];
+        for (curve_type, calculator, parameters) in curves {
+            let curve = SwapCurve {
+                curve_type,
+                calculator,
+            };
+            let mut packed = [0u8; SwapCurve::LEN];
+            curve.pack_into_slice(&mut packed);
+            let mut expected = vec![curve_type as u8];
+            expected.extend_from_slice(&parameters);
+            expected.resize(SwapCurve::LEN, 0);
+            assert_eq!(packed.to_vec(), expected);
+
+            let unpacked = SwapCurve::unpack_from_slice(&packed).unwrap();
+            assert_eq!(unpacked.curve_type, curve_type);
+            let mut repacked = [0u8; SwapCurve::LEN];
+            unpacked.pack_into_slice(&mut repacked);
+            assert_eq!(repacked, packed);
+        }
+    }
+
+    #[test]
+    fn test_unpack_unknown_curve_type() {
+        let mut packed = [0u8; SwapCurve::LEN];
+        packed[0] = 4;
+        assert_eq!(
+            SwapCurve::unpack_from_slice(&packed).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+}
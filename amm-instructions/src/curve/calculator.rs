@@ -0,0 +1,304 @@
+//! Swap calculations
+
+use crate::error::AmmError;
+use std::fmt::Debug;
+
+#[cfg(feature = "fuzz")]
+use arbitrary::Arbitrary;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Initial amount of pool tokens for swap contract, hard-coded to something
+/// "sensible" given a maximum of u128.
+/// Note that on Ethereum, Uniswap uses the geometric mean of all provided
+/// input amounts, and Balancer uses 100 * 10 ^ 18.
+pub const INITIAL_SWAP_POOL_AMOUNT: u128 = 1_000_000_000;
+
+/// Hardcode the number of token types in a pool, used to calculate the
+/// equivalent pool tokens for the owner trading fee.
+pub const TOKENS_IN_POOL: u128 = 2;
+
+/// Helper function for mapping to AmmError::CalculationFailure
+pub fn map_zero_to_none(x: u128) -> Option<u128> {
+    if x == 0 {
+        None
+    } else {
+        Some(x)
+    }
+}
+
+/// Divides `dividend` by `divisor`, rounding the quotient up.
+///
+/// Returns the quotient together with the smallest divisor that still yields
+/// that quotient, so that `quotient * divisor` never exceeds what the caller
+/// actually owns. Dividing a small number by a much larger one rounds to
+/// zero or one depending on whether the dividend is at least half the
+/// divisor, instead of always returning one.
+pub fn checked_ceil_div(dividend: u128, divisor: u128) -> Option<(u128, u128)> {
+    let mut quotient = dividend.checked_div(divisor)?;
+    if quotient == 0 {
+        if dividend.checked_mul(2)? >= divisor {
+            return Some((1, 0));
+        } else {
+            return Some((0, 0));
+        }
+    }
+
+    let mut divisor = divisor;
+    let remainder = dividend.checked_rem(divisor)?;
+    if remainder > 0 {
+        quotient = quotient.checked_add(1)?;
+        divisor = dividend.checked_div(quotient)?;
+        let remainder = dividend.checked_rem(quotient)?;
+        if remainder > 0 {
+            divisor = divisor.checked_add(1)?;
+        }
+    }
+    Some((quotient, divisor))
+}
+
+/// Integer square root of `value`, rounded in the given direction
+pub fn checked_sqrt(value: u128, round_direction: RoundDirection) -> Option<u128> {
+    if value < 2 {
+        return Some(value);
+    }
+    let mut root = value / 2;
+    let mut next = (root + value / root) / 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    match round_direction {
+        RoundDirection::Floor => Some(root),
+        RoundDirection::Ceiling => {
+            if root.checked_mul(root)? < value {
+                root.checked_add(1)
+            } else {
+                Some(root)
+            }
+        }
+    }
+}
+
+/// Calculates `value * numerator / denominator` rounded in the given
+/// direction, without requiring the full product to fit in a u128.
+pub fn checked_mul_div(
+    value: u128,
+    numerator: u128,
+    denominator: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let quotient = value.checked_div(denominator)?;
+    let remainder = value.checked_rem(denominator)?;
+    let partial = remainder.checked_mul(numerator)?;
+    let result = quotient
+        .checked_mul(numerator)?
+        .checked_add(partial.checked_div(denominator)?)?;
+    match round_direction {
+        RoundDirection::Floor => Some(result),
+        RoundDirection::Ceiling => {
+            if partial.checked_rem(denominator)? > 0 {
+                result.checked_add(1)
+            } else {
+                Some(result)
+            }
+        }
+    }
+}
+
+/// The direction of a trade, since curves can be specialized to treat each
+/// token differently (by adding offsets or weights)
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum TradeDirection {
+    /// Input token A, output token B
+    AtoB,
+    /// Input token B, output token A
+    BtoA,
+}
+
+/// The direction to round.  Used for pool token to trading token conversions to
+/// avoid losing value on any deposit or withdrawal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoundDirection {
+    /// Floor the value, ie. 1.9 => 1, 1.1 => 1, 1.5 => 1
+    Floor,
+    /// Ceiling the value, ie. 1.9 => 2, 1.1 => 2, 1.5 => 2
+    Ceiling,
+}
+
+impl TradeDirection {
+    /// Given a trade direction, gives the opposite direction of the trade, so
+    /// A to B becomes B to A, and vice versa
+    pub fn opposite(&self) -> TradeDirection {
+        match self {
+            TradeDirection::AtoB => TradeDirection::BtoA,
+            TradeDirection::BtoA => TradeDirection::AtoB,
+        }
+    }
+}
+
+/// Encodes all results of swapping from a source token to a destination token
+#[derive(Debug, PartialEq)]
+pub struct SwapWithoutFeesResult {
+    /// Amount of source token swapped
+    pub source_amount_swapped: u128,
+    /// Amount of destination token swapped
+    pub destination_amount_swapped: u128,
+}
+
+/// Encodes results of depositing both sides at once
+#[derive(Debug, PartialEq)]
+pub struct TradingTokenResult {
+    /// Amount of token A
+    pub token_a_amount: u128,
+    /// Amount of token B
+    pub token_b_amount: u128,
+}
+
+/// Trait for packing of trait objects, required because structs that implement
+/// `Pack` cannot be used as trait objects (as `dyn Pack`).
+pub trait DynPack {
+    /// Only required function is to pack given a trait object
+    fn pack_into_slice(&self, dst: &mut [u8]);
+}
+
+/// Trait representing operations required on a swap curve
+pub trait CurveCalculator: Debug + DynPack {
+    /// Calculate how much destination token will be provided given an amount
+    /// of source token.
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult>;
+
+    /// Calculate how much source token is required to receive exactly
+    /// `destination_amount` of destination token, the inverse of
+    /// `swap_without_fees`.
+    ///
+    /// The default implementation searches for the smallest source amount
+    /// whose output covers the requested amount, which works for any curve
+    /// whose output grows with its input.  The returned result is the one
+    /// produced by `swap_without_fees` for that source amount, so the
+    /// destination amount may slightly exceed the request.
+    fn swap_exact_out_without_fees(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        if destination_amount == 0 || destination_amount >= swap_destination_amount {
+            return None;
+        }
+        let covers = |source_amount: u128| {
+            self.swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                trade_direction,
+            )
+            .filter(|result| result.destination_amount_swapped >= destination_amount)
+        };
+        // double the source amount until it covers the request, then bisect
+        let mut high = 1u128;
+        while covers(high).is_none() {
+            if high >= u64::MAX as u128 {
+                return None;
+            }
+            high = high.checked_mul(2)?.min(u64::MAX as u128);
+        }
+        let mut low = high / 2;
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if covers(middle).is_some() {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        covers(high)
+    }
+
+    /// Get the supply for a new pool
+    /// The default implementation is a Balancer-style fixed initial supply
+    fn new_pool_supply(&self) -> u128 {
+        INITIAL_SWAP_POOL_AMOUNT
+    }
+
+    /// Get the amount of trading tokens for the given amount of pool tokens,
+    /// provided the total trading tokens and supply of pool tokens.
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult>;
+
+    /// Get the amount of pool tokens for the deposited amount of token A or B.
+    ///
+    /// This is used for single-sided deposits.  It essentially performs a swap
+    /// followed by a deposit.  Because a swap is implicitly performed, this will
+    /// change the spot price of the pool.
+    ///
+    /// See more background for the calculation at:
+    /// https://balancer.finance/whitepaper/#single-asset-deposit-withdrawal
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128>;
+
+    /// Get the amount of pool tokens for the withdrawn amount of token A or B.
+    ///
+    /// This is used for single-sided withdrawals and owner trade fee
+    /// calculation. It essentially performs a withdrawal followed by a swap.
+    /// Because a swap is implicitly performed, this will change the spot price
+    /// of the pool.
+    ///
+    /// See more background for the calculation at:
+    /// https://balancer.finance/whitepaper/#single-asset-deposit-withdrawal
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128>;
+
+    /// Validate that the given curve has no invalid parameters
+    fn validate(&self) -> Result<(), AmmError>;
+
+    /// Validate the given supply on initialization. This is useful for curves
+    /// that allow zero supply on one or both sides, since the standard constant
+    /// product curve must have a non-zero supply on both sides.
+    fn validate_supply(&self, token_a_amount: u64, token_b_amount: u64) -> Result<(), AmmError> {
+        if token_a_amount == 0 {
+            return Err(AmmError::EmptySupply);
+        }
+        if token_b_amount == 0 {
+            return Err(AmmError::EmptySupply);
+        }
+        Ok(())
+    }
+
+    /// Some curves function best and prevent attacks if we prevent deposits
+    /// after initialization.  For example, the offset curve in `offset.rs`,
+    /// which fakes supply on one side of the swap, allows the swap creator
+    /// to steal value from all other depositors.
+    fn allows_deposits(&self) -> bool {
+        true
+    }
+}
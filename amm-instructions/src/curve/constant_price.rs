@@ -0,0 +1,222 @@
+//! Simple constant price swap curve, set at init
+
+use crate::{
+    curve::calculator::{
+        checked_ceil_div, map_zero_to_none, CurveCalculator, DynPack, RoundDirection,
+        SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+    },
+    error::AmmError,
+    state::check_account_len,
+};
+use arrayref::{array_mut_ref, array_ref};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// ConstantPriceCurve struct implementing CurveCalculator
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConstantPriceCurve {
+    /// Amount of token A required to get 1 token B
+    pub token_b_price: u64,
+}
+
+/// Get the amount of pool tokens for the given amount of token A or B.
+///
+/// The constant product implementation uses the Balancer formulas found at
+/// <https://balancer.finance/whitepaper/#single-asset-deposit>, specifically
+/// in the case for 2 tokens, each weighted at 1/2.
+pub fn trading_tokens_to_pool_tokens(
+    token_b_price: u64,
+    source_amount: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let token_b_price = token_b_price as u128;
+    let given_value = match trade_direction {
+        TradeDirection::AtoB => source_amount,
+        TradeDirection::BtoA => source_amount.checked_mul(token_b_price)?,
+    };
+    let total_value = swap_token_b_amount
+        .checked_mul(token_b_price)?
+        .checked_add(swap_token_a_amount)?;
+    let pool_tokens = pool_supply.checked_mul(given_value)?;
+    let pool_tokens = match round_direction {
+        RoundDirection::Floor => pool_tokens.checked_div(total_value)?,
+        RoundDirection::Ceiling => checked_ceil_div(pool_tokens, total_value)?.0,
+    };
+    Some(pool_tokens)
+}
+
+impl CurveCalculator for ConstantPriceCurve {
+    /// Constant price curve always returns 1:1
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        _swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let token_b_price = self.token_b_price as u128;
+
+        let (source_amount_swapped, destination_amount_swapped) = match trade_direction {
+            TradeDirection::BtoA => (source_amount, source_amount.checked_mul(token_b_price)?),
+            TradeDirection::AtoB => {
+                let destination_amount_swapped = source_amount.checked_div(token_b_price)?;
+                let mut source_amount_swapped = source_amount;
+
+                // if there is a remainder from buying token B, floor
+                // token_a_amount to avoid taking too many tokens, but
+                // don't recalculate the fees
+                let remainder = source_amount_swapped.checked_rem(token_b_price)?;
+                if remainder > 0 {
+                    source_amount_swapped = source_amount.checked_sub(remainder)?;
+                }
+
+                (source_amount_swapped, destination_amount_swapped)
+            }
+        };
+        let source_amount_swapped = map_zero_to_none(source_amount_swapped)?;
+        let destination_amount_swapped = map_zero_to_none(destination_amount_swapped)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Get the amount of trading tokens for the given amount of pool tokens,
+    /// provided the total trading tokens and supply of pool tokens.
+    /// For the constant price curve, the total value of the pool is weighted
+    /// by the price of token B.
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        let token_b_price = self.token_b_price as u128;
+        let total_value = swap_token_b_amount
+            .checked_mul(token_b_price)?
+            .checked_add(swap_token_a_amount)?;
+
+        let (token_a_amount, token_b_amount) = match round_direction {
+            RoundDirection::Floor => {
+                let token_a_amount = pool_tokens
+                    .checked_mul(total_value)?
+                    .checked_div(pool_token_supply)?;
+                let token_b_amount = pool_tokens
+                    .checked_mul(total_value)?
+                    .checked_div(token_b_price)?
+                    .checked_div(pool_token_supply)?;
+                (token_a_amount, token_b_amount)
+            }
+            RoundDirection::Ceiling => {
+                let (token_a_amount, _) =
+                    checked_ceil_div(pool_tokens.checked_mul(total_value)?, pool_token_supply)?;
+                let (pool_value_as_token_b, _) =
+                    checked_ceil_div(pool_tokens.checked_mul(total_value)?, token_b_price)?;
+                let (token_b_amount, _) =
+                    checked_ceil_div(pool_value_as_token_b, pool_token_supply)?;
+                (token_a_amount, token_b_amount)
+            }
+        };
+        Some(TradingTokenResult {
+            token_a_amount,
+            token_b_amount,
+        })
+    }
+
+    /// Get the amount of pool tokens for the given amount of token A and B,
+    /// taking into account the price of token B.
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        trading_tokens_to_pool_tokens(
+            self.token_b_price,
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            round_direction,
+        )
+    }
+
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        trading_tokens_to_pool_tokens(
+            self.token_b_price,
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            round_direction,
+        )
+    }
+
+    fn validate(&self) -> Result<(), AmmError> {
+        if self.token_b_price == 0 {
+            Err(AmmError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn validate_supply(&self, token_a_amount: u64, _token_b_amount: u64) -> Result<(), AmmError> {
+        if token_a_amount == 0 {
+            return Err(AmmError::EmptySupply);
+        }
+        Ok(())
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for ConstantPriceCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for ConstantPriceCurve {}
+impl Pack for ConstantPriceCurve {
+    const LEN: usize = 8;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ConstantPriceCurve, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let token_b_price = array_ref![input, 0, 8];
+        Ok(Self {
+            token_b_price: u64::from_le_bytes(*token_b_price),
+        })
+    }
+}
+
+impl DynPack for ConstantPriceCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let token_b_price = array_mut_ref![output, 0, 8];
+        *token_b_price = self.token_b_price.to_le_bytes();
+    }
+}
@@ -0,0 +1,338 @@
+//! The Uniswap invariant calculator.
+
+use crate::{
+    curve::calculator::{
+        checked_ceil_div, checked_mul_div, checked_sqrt, map_zero_to_none, CurveCalculator, DynPack, RoundDirection,
+        SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+    },
+    error::AmmError,
+};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// ConstantProductCurve struct implementing CurveCalculator
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConstantProductCurve;
+
+/// The constant product swap calculation, factored out of its class for reuse.
+///
+/// This is guaranteed to work for all values such that:
+///  - 1 <= swap_source_amount * swap_destination_amount <= u128::MAX
+///  - 1 <= source_amount <= u64::MAX
+pub fn swap(
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+) -> Option<SwapWithoutFeesResult> {
+    let invariant = swap_source_amount.checked_mul(swap_destination_amount)?;
+
+    let new_swap_source_amount = swap_source_amount.checked_add(source_amount)?;
+    let (new_swap_destination_amount, new_swap_source_amount) =
+        checked_ceil_div(invariant, new_swap_source_amount)?;
+
+    let source_amount_swapped = new_swap_source_amount.checked_sub(swap_source_amount)?;
+    let destination_amount_swapped =
+        map_zero_to_none(swap_destination_amount.checked_sub(new_swap_destination_amount)?)?;
+
+    Some(SwapWithoutFeesResult {
+        source_amount_swapped,
+        destination_amount_swapped,
+    })
+}
+
+/// Get the amount of trading tokens for the given amount of pool tokens,
+/// provided the total trading tokens and supply of pool tokens.
+///
+/// The constant product implementation is a simple ratio calculation for how many
+/// trading tokens correspond to a certain number of pool tokens
+pub fn pool_tokens_to_trading_tokens(
+    pool_tokens: u128,
+    pool_token_supply: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    round_direction: RoundDirection,
+) -> Option<TradingTokenResult> {
+    let mut token_a_amount = pool_tokens
+        .checked_mul(swap_token_a_amount)?
+        .checked_div(pool_token_supply)?;
+    let mut token_b_amount = pool_tokens
+        .checked_mul(swap_token_b_amount)?
+        .checked_div(pool_token_supply)?;
+    let (token_a_amount, token_b_amount) = match round_direction {
+        RoundDirection::Floor => (token_a_amount, token_b_amount),
+        RoundDirection::Ceiling => {
+            let token_a_remainder = pool_tokens
+                .checked_mul(swap_token_a_amount)?
+                .checked_rem(pool_token_supply)?;
+            // Also check for 0 token A and B amount to avoid taking too much
+            // for tiny amounts of pool tokens.  For example, if someone asks
+            // for 1 pool token, which is worth 0.01 token A, we avoid the
+            // ceiling of taking 1 token A and instead return 0, for it to be
+            // rejected later in processing.
+            if token_a_remainder > 0 && token_a_amount > 0 {
+                token_a_amount += 1;
+            }
+            let token_b_remainder = pool_tokens
+                .checked_mul(swap_token_b_amount)?
+                .checked_rem(pool_token_supply)?;
+            if token_b_remainder > 0 && token_b_amount > 0 {
+                token_b_amount += 1;
+            }
+            (token_a_amount, token_b_amount)
+        }
+    };
+    Some(TradingTokenResult {
+        token_a_amount,
+        token_b_amount,
+    })
+}
+
+/// Get the amount of pool tokens for the deposited amount of token A or B.
+///
+/// The constant product implementation uses the Balancer formulas found at
+/// <https://balancer.finance/whitepaper/#single-asset-deposit>, specifically
+/// in the case for 2 tokens, each weighted at 1/2.
+///
+/// The new pool supply is `sqrt(pool_supply^2 * new_source / old_source)`,
+/// computed entirely in integers.  Every intermediate step is rounded in
+/// `round_direction`, so a floored result never exceeds the exact value and
+/// a ceiled result is never below it.
+pub fn deposit_single_token_type(
+    source_amount: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    if source_amount == 0 {
+        return Some(0);
+    }
+    let swap_source_amount = match trade_direction {
+        TradeDirection::AtoB => swap_token_a_amount,
+        TradeDirection::BtoA => swap_token_b_amount,
+    };
+    let new_swap_source_amount = swap_source_amount.checked_add(source_amount)?;
+    let squared_supply = checked_mul_div(
+        pool_supply.checked_mul(pool_supply)?,
+        new_swap_source_amount,
+        swap_source_amount,
+        round_direction,
+    )?;
+    let new_pool_supply = checked_sqrt(squared_supply, round_direction)?;
+    new_pool_supply.checked_sub(pool_supply)
+}
+
+/// Get the amount of pool tokens for the withdrawn amount of token A or B.
+///
+/// The constant product implementation uses the Balancer formulas found at
+/// <https://balancer.finance/whitepaper/#single-asset-withdrawal>, specifically
+/// in the case for 2 tokens, each weighted at 1/2.
+///
+/// The remaining pool supply is `sqrt(pool_supply^2 * new_source / old_source)`.
+/// Since the burned amount is the difference with the current supply, the
+/// intermediate steps are rounded opposite to `round_direction`.
+pub fn withdraw_single_token_type_exact_out(
+    source_amount: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    if source_amount == 0 {
+        return Some(0);
+    }
+    let swap_source_amount = match trade_direction {
+        TradeDirection::AtoB => swap_token_a_amount,
+        TradeDirection::BtoA => swap_token_b_amount,
+    };
+    let new_swap_source_amount = swap_source_amount.checked_sub(source_amount)?;
+    let remaining_direction = match round_direction {
+        RoundDirection::Floor => RoundDirection::Ceiling,
+        RoundDirection::Ceiling => RoundDirection::Floor,
+    };
+    let squared_supply = checked_mul_div(
+        pool_supply.checked_mul(pool_supply)?,
+        new_swap_source_amount,
+        swap_source_amount,
+        remaining_direction,
+    )?;
+    let remaining_pool_supply = checked_sqrt(squared_supply, remaining_direction)?;
+    pool_supply.checked_sub(remaining_pool_supply)
+}
+
+impl CurveCalculator for ConstantProductCurve {
+    /// Constant product swap ensures x * y = constant
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        swap(source_amount, swap_source_amount, swap_destination_amount)
+    }
+
+    /// The constant product implementation is a simple ratio calculation for how many
+    /// trading tokens correspond to a certain number of pool tokens
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        deposit_single_token_type(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            round_direction,
+        )
+    }
+
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        withdraw_single_token_type_exact_out(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            round_direction,
+        )
+    }
+
+    fn validate(&self) -> Result<(), AmmError> {
+        Ok(())
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for ConstantProductCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for ConstantProductCurve {}
+impl Pack for ConstantProductCurve {
+    const LEN: usize = 0;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(_input: &[u8]) -> Result<ConstantProductCurve, ProgramError> {
+        Ok(Self {})
+    }
+}
+
+impl DynPack for ConstantProductCurve {
+    fn pack_into_slice(&self, _output: &mut [u8]) {}
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        base::{CurveType, SwapCurve},
+        fees::Fees,
+    };
+    use proptest::prelude::*;
+    use std::sync::Arc;
+
+    /// Whether the pool product did not decrease, a product past `u128::MAX`
+    /// being over any product of `u64` amounts
+    fn invariant_kept(old_a: u128, old_b: u128, new_a: u128, new_b: u128) -> bool {
+        match new_a.checked_mul(new_b) {
+            Some(new_invariant) => new_invariant >= old_a * old_b,
+            None => true,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swap_keeps_invariant(
+            source_amount in 1..=u128::from(u64::MAX),
+            swap_source_amount in 1..=u128::from(u64::MAX),
+            swap_destination_amount in 1..=u128::from(u64::MAX),
+        ) {
+            if let Some(result) = swap(source_amount, swap_source_amount, swap_destination_amount) {
+                prop_assert!(result.source_amount_swapped <= source_amount);
+                prop_assert!(invariant_kept(
+                    swap_source_amount,
+                    swap_destination_amount,
+                    swap_source_amount + result.source_amount_swapped,
+                    swap_destination_amount - result.destination_amount_swapped,
+                ));
+            }
+        }
+
+        #[test]
+        fn swap_with_fees_keeps_invariant(
+            source_amount in 1..=u128::from(u64::MAX),
+            swap_source_amount in 1..=u128::from(u64::MAX),
+            swap_destination_amount in 1..=u128::from(u64::MAX),
+            trade_fee_numerator in 0..=100u32,
+            owner_trade_fee_numerator in 0..=100u32,
+        ) {
+            let curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Arc::new(ConstantProductCurve),
+            };
+            let fees = Fees {
+                trade_fee_numerator,
+                owner_trade_fee_numerator,
+                owner_withdraw_fee_numerator: 0,
+                host_fee_numerator: 0,
+                fee_denominator: 10_000,
+            };
+            if let Some(result) = curve.swap(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                &fees,
+            ) {
+                prop_assert!(invariant_kept(
+                    swap_source_amount,
+                    swap_destination_amount,
+                    result.new_swap_source_amount,
+                    result.new_swap_destination_amount,
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,514 @@
+//! All fee information, to be used for validation currently
+
+use crate::{error::AmmError, state::check_account_len};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+use std::{convert::TryFrom, fmt};
+#[cfg(feature = "borsh")]
+use borsh::BorshSchema;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Encapsulates all fee information and calculations for swap operations
+///
+/// Every fee is expressed as a numerator over the shared `fee_denominator`,
+/// which keeps the packed layout at 24 bytes:
+///
+/// | offset | size | field                        |
+/// |--------|------|------------------------------|
+/// | 0      | 4    | trade_fee_numerator          |
+/// | 4      | 4    | owner_trade_fee_numerator    |
+/// | 8      | 4    | owner_withdraw_fee_numerator |
+/// | 12     | 4    | host_fee_numerator           |
+/// | 16     | 8    | fee_denominator              |
+#[repr(C)]
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fees {
+    /// Trade fees are extra token amounts that are held inside the token
+    /// accounts during a trade, making the value of liquidity tokens rise.
+    /// Trade fee numerator
+    pub trade_fee_numerator: u32,
+
+    /// Owner trading fees are extra token amounts that are held inside the token
+    /// accounts during a trade, with the equivalent in pool tokens minted to
+    /// the owner of the program.
+    /// Owner trade fee numerator
+    pub owner_trade_fee_numerator: u32,
+
+    /// Owner withdraw fees are extra liquidity pool token amounts that are
+    /// sent to the owner on every withdrawal.
+    /// Owner withdraw fee numerator
+    pub owner_withdraw_fee_numerator: u32,
+
+    /// Host fees are a proportion of the owner trading fees, sent to an
+    /// extra account provided during the trade.
+    /// Host trading fee numerator
+    pub host_fee_numerator: u32,
+
+    /// Denominator shared by every fee above
+    pub fee_denominator: u64,
+}
+
+/// Denominator of the fees built by [Fees::from_bps], one basis point being
+/// 1/10_000
+pub const FEE_BPS_DENOMINATOR: u64 = 10_000;
+
+/// Fees in basis points, see [Fees::from_bps] and [Fees::as_bps]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FeesBps {
+    /// Trade fee
+    pub trade_bps: u16,
+    /// Owner trade fee
+    pub owner_trade_bps: u16,
+    /// Owner withdraw fee
+    pub owner_withdraw_bps: u16,
+    /// Host fee, a proportion of the owner trade fee
+    pub host_bps: u16,
+}
+
+/// Fees as percentages, e.g.
+/// `trade=0.25% owner=0.05% owner_withdraw=0.00% host=20.00%`
+impl fmt::Display for FeesBps {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |bps: u16| format!("{}.{:02}%", bps / 100, bps % 100);
+        write!(
+            f,
+            "trade={} owner={} owner_withdraw={} host={}",
+            percent(self.trade_bps),
+            percent(self.owner_trade_bps),
+            percent(self.owner_withdraw_bps),
+            percent(self.host_bps)
+        )
+    }
+}
+
+/// Helper function for calculating swap fee
+pub fn calculate_fee(
+    token_amount: u128,
+    fee_numerator: u128,
+    fee_denominator: u128,
+) -> Option<u128> {
+    if fee_numerator == 0 || token_amount == 0 {
+        Some(0)
+    } else {
+        let fee = token_amount
+            .checked_mul(fee_numerator)?
+            .checked_div(fee_denominator)?;
+        if fee == 0 {
+            Some(1) // minimum fee of one token
+        } else {
+            Some(fee)
+        }
+    }
+}
+
+/// Maximum Token-2022 transfer fee, in basis points
+pub const MAX_TRANSFER_FEE_BASIS_POINTS: u16 = 10_000;
+
+/// Amount received by the destination of a transfer of `amount` on a
+/// Token-2022 mint with the transfer fee extension, to quote swaps and
+/// deposits on such mints. The fee is rounded up and capped at
+/// `maximum_fee`, as done by the token program
+pub fn amount_after_transfer_fee(
+    amount: u64,
+    transfer_fee_basis_points: u16,
+    maximum_fee: u64,
+) -> Option<u64> {
+    if transfer_fee_basis_points > MAX_TRANSFER_FEE_BASIS_POINTS {
+        return None;
+    }
+    if transfer_fee_basis_points == 0 || amount == 0 {
+        return Some(amount);
+    }
+    let denominator = u128::from(MAX_TRANSFER_FEE_BASIS_POINTS);
+    let fee = u128::from(amount)
+        .checked_mul(u128::from(transfer_fee_basis_points))?
+        .checked_add(denominator - 1)?
+        .checked_div(denominator)?;
+    let fee = u64::try_from(fee).ok()?.min(maximum_fee);
+    amount.checked_sub(fee)
+}
+
+fn validate_fraction(numerator: u32, denominator: u64) -> Result<(), AmmError> {
+    if u64::from(numerator) > denominator {
+        Err(AmmError::InvalidFee)
+    } else {
+        Ok(())
+    }
+}
+
+impl Fees {
+    /// Calculate the withdraw fee in pool tokens
+    pub fn owner_withdraw_fee(&self, pool_tokens: u128) -> Option<u128> {
+        calculate_fee(
+            pool_tokens,
+            u128::from(self.owner_withdraw_fee_numerator),
+            u128::from(self.fee_denominator),
+        )
+    }
+
+    /// Calculate the withdraw fee in pool tokens actually charged by the
+    /// program. The fee is waived when `exempt`, i.e. when the pool tokens are
+    /// burnt from the pool fee account itself, as when the fee owner withdraws
+    pub fn withdraw_fee(&self, pool_tokens: u128, exempt: bool) -> Option<u128> {
+        if exempt {
+            Some(0)
+        } else {
+            self.owner_withdraw_fee(pool_tokens)
+        }
+    }
+
+    /// Calculate the trading fee in trading tokens
+    pub fn trading_fee(&self, trading_tokens: u128) -> Option<u128> {
+        calculate_fee(
+            trading_tokens,
+            u128::from(self.trade_fee_numerator),
+            u128::from(self.fee_denominator),
+        )
+    }
+
+    /// Calculate the owner trading fee in trading tokens
+    pub fn owner_trading_fee(&self, trading_tokens: u128) -> Option<u128> {
+        calculate_fee(
+            trading_tokens,
+            u128::from(self.owner_trade_fee_numerator),
+            u128::from(self.fee_denominator),
+        )
+    }
+
+    /// Calculate the source amount which, once the trading and owner trading
+    /// fees are taken, leaves at least `amount_after_fees` to be swapped.
+    /// Used to quote the input of an exact-out swap.
+    pub fn pre_trading_fee_amount(&self, amount_after_fees: u128) -> Option<u128> {
+        if amount_after_fees == 0 {
+            return Some(0);
+        }
+        let fee_denominator = u128::from(self.fee_denominator);
+        let fee_numerator = u128::from(self.trade_fee_numerator)
+            .checked_add(u128::from(self.owner_trade_fee_numerator))?;
+        if fee_numerator == 0 {
+            return Some(amount_after_fees);
+        }
+        let remaining_numerator = fee_denominator.checked_sub(fee_numerator)?;
+        let mut amount = amount_after_fees
+            .checked_mul(fee_denominator)?
+            .checked_add(remaining_numerator.checked_sub(1)?)?
+            .checked_div(remaining_numerator)?;
+        // the minimum fee of one token can leave the estimate a little short
+        loop {
+            let fees = self
+                .trading_fee(amount)?
+                .checked_add(self.owner_trading_fee(amount)?)?;
+            if amount.checked_sub(fees)? >= amount_after_fees {
+                return Some(amount);
+            }
+            amount = amount.checked_add(1)?;
+        }
+    }
+
+    /// Calculate the host fee based on the owner fee, only used in production
+    /// situations where a program is hosted by multiple frontends
+    pub fn host_fee(&self, owner_fee: u128) -> Option<u128> {
+        calculate_fee(
+            owner_fee,
+            u128::from(self.host_fee_numerator),
+            u128::from(self.fee_denominator),
+        )
+    }
+
+    /// Validate that the fees are reasonable
+    pub fn validate(&self) -> Result<(), AmmError> {
+        if self.fee_denominator == 0 {
+            return Err(AmmError::InvalidFee);
+        }
+        validate_fraction(self.trade_fee_numerator, self.fee_denominator)?;
+        validate_fraction(self.owner_trade_fee_numerator, self.fee_denominator)?;
+        validate_fraction(self.owner_withdraw_fee_numerator, self.fee_denominator)?;
+        validate_fraction(self.host_fee_numerator, self.fee_denominator)?;
+        Ok(())
+    }
+
+    /// Fees of the given basis points over [FEE_BPS_DENOMINATOR]. Fails with
+    /// `InvalidFee` when a fee is over 100%, i.e. 10_000 basis points
+    pub fn from_bps(bps: FeesBps) -> Result<Self, AmmError> {
+        let fees = Self {
+            trade_fee_numerator: u32::from(bps.trade_bps),
+            owner_trade_fee_numerator: u32::from(bps.owner_trade_bps),
+            owner_withdraw_fee_numerator: u32::from(bps.owner_withdraw_bps),
+            host_fee_numerator: u32::from(bps.host_bps),
+            fee_denominator: FEE_BPS_DENOMINATOR,
+        };
+        fees.validate()?;
+        Ok(fees)
+    }
+
+    /// Fees in basis points, rounded down to a whole basis point: 1/400 is
+    /// exactly 25 basis points, 1/3 is 3_333 and 1/30_000 is 0. When every
+    /// fraction is a whole number of basis points, `Fees::from_bps` of the
+    /// result gives the same fractions over [FEE_BPS_DENOMINATOR].
+    /// Fees over `u16::MAX` basis points, which `validate` rejects, are
+    /// capped, and all fees are zero when the denominator is.
+    pub fn as_bps(&self) -> FeesBps {
+        let bps = |numerator: u32| {
+            (u64::from(numerator) * FEE_BPS_DENOMINATOR)
+                .checked_div(self.fee_denominator)
+                .map_or(0, |bps| u16::try_from(bps).unwrap_or(u16::MAX))
+        };
+        FeesBps {
+            trade_bps: bps(self.trade_fee_numerator),
+            owner_trade_bps: bps(self.owner_trade_fee_numerator),
+            owner_withdraw_bps: bps(self.owner_withdraw_fee_numerator),
+            host_bps: bps(self.host_fee_numerator),
+        }
+    }
+
+    /// Unpacks the fees packed at `offset` of some account data, e.g.
+    /// `ProgramState::FEES_OFFSET`, without unpacking the rest of the account.
+    /// Fails with `InvalidAccountLength` when the data is too short.
+    pub fn unpack_at(account_data: &[u8], offset: usize) -> Result<Self, ProgramError> {
+        let input = offset
+            .checked_add(Self::LEN)
+            .and_then(|end| account_data.get(offset..end))
+            .ok_or(AmmError::InvalidAccountLength)?;
+        Self::unpack_from_slice(input)
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for Fees {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+/// Fees as fractions of the denominator, e.g.
+/// `trade: 25/10000, owner_trade: 5/10000, owner_withdraw: 0/10000, host: 20/10000`
+impl fmt::Display for Fees {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "trade: {}/{}, owner_trade: {}/{}, owner_withdraw: {}/{}, host: {}/{}",
+            self.trade_fee_numerator,
+            self.fee_denominator,
+            self.owner_trade_fee_numerator,
+            self.fee_denominator,
+            self.owner_withdraw_fee_numerator,
+            self.fee_denominator,
+            self.host_fee_numerator,
+            self.fee_denominator
+        )
+    }
+}
+
+impl Sealed for Fees {}
+impl Pack for Fees {
+    const LEN: usize = 24;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 24];
+        let (
+            trade_fee_numerator,
+            owner_trade_fee_numerator,
+            owner_withdraw_fee_numerator,
+            host_fee_numerator,
+            fee_denominator,
+        ) = mut_array_refs![output, 4, 4, 4, 4, 8];
+        *trade_fee_numerator = self.trade_fee_numerator.to_le_bytes();
+        *owner_trade_fee_numerator = self.owner_trade_fee_numerator.to_le_bytes();
+        *owner_withdraw_fee_numerator = self.owner_withdraw_fee_numerator.to_le_bytes();
+        *host_fee_numerator = self.host_fee_numerator.to_le_bytes();
+        *fee_denominator = self.fee_denominator.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Fees, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let input = array_ref![input, 0, 24];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            trade_fee_numerator,
+            owner_trade_fee_numerator,
+            owner_withdraw_fee_numerator,
+            host_fee_numerator,
+            fee_denominator,
+        ) = array_refs![input, 4, 4, 4, 4, 8];
+        Ok(Self {
+            trade_fee_numerator: u32::from_le_bytes(*trade_fee_numerator),
+            owner_trade_fee_numerator: u32::from_le_bytes(*owner_trade_fee_numerator),
+            owner_withdraw_fee_numerator: u32::from_le_bytes(*owner_withdraw_fee_numerator),
+            host_fee_numerator: u32::from_le_bytes(*host_fee_numerator),
+            fee_denominator: u64::from_le_bytes(*fee_denominator),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_fees() -> Fees {
+        Fees {
+            trade_fee_numerator: 25,
+            owner_trade_fee_numerator: 5,
+            owner_withdraw_fee_numerator: 1,
+            host_fee_numerator: 0x0102_0304,
+            fee_denominator: 0x0102_0304_0506_0708,
+        }
+    }
+
+    #[test]
+    fn test_pack_fees() {
+        let fees = test_fees();
+        #[rustfmt::skip]
+        let expected: [u8; Fees::LEN] = [
+            25, 0, 0, 0,
+            5, 0, 0, 0,
+            1, 0, 0, 0,
+            4, 3, 2, 1,
+            8, 7, 6, 5, 4, 3, 2, 1,
+        ];
+        let mut packed = [0u8; Fees::LEN];
+        fees.pack_into_slice(&mut packed);
+        assert_eq!(packed, expected);
+        assert_eq!(Fees::unpack_from_slice(&expected).unwrap(), fees);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(test_fees().validate(), Ok(()));
+        assert_eq!(Fees::default().validate(), Err(AmmError::InvalidFee));
+
+        let fees = Fees {
+            fee_denominator: 0,
+            ..test_fees()
+        };
+        assert_eq!(fees.validate(), Err(AmmError::InvalidFee));
+
+        let fees = Fees {
+            trade_fee_numerator: 10,
+            owner_trade_fee_numerator: 10,
+            owner_withdraw_fee_numerator: 10,
+            host_fee_numerator: 10,
+            fee_denominator: 10,
+        };
+        assert_eq!(fees.validate(), Ok(()));
+        let over = [
+            Fees {
+                trade_fee_numerator: 11,
+                ..fees
+            },
+            Fees {
+                owner_trade_fee_numerator: 11,
+                ..fees
+            },
+            Fees {
+                owner_withdraw_fee_numerator: 11,
+                ..fees
+            },
+            Fees {
+                host_fee_numerator: 11,
+                ..fees
+            },
+        ];
+        for fees in over.iter() {
+            assert_eq!(fees.validate(), Err(AmmError::InvalidFee));
+        }
+    }
+
+    #[test]
+    fn test_bps() {
+        let bps = |trade_bps, owner_trade_bps, owner_withdraw_bps, host_bps| FeesBps {
+            trade_bps,
+            owner_trade_bps,
+            owner_withdraw_bps,
+            host_bps,
+        };
+        // from no fee up to 100%
+        for fees_bps in [
+            bps(0, 0, 0, 0),
+            bps(25, 5, 0, 2_000),
+            bps(10_000, 10_000, 10_000, 10_000),
+        ] {
+            let fees = Fees::from_bps(fees_bps).unwrap();
+            assert_eq!(fees.fee_denominator, FEE_BPS_DENOMINATOR);
+            assert_eq!(fees.trade_fee_numerator, u32::from(fees_bps.trade_bps));
+            assert_eq!(fees.host_fee_numerator, u32::from(fees_bps.host_bps));
+            assert_eq!(fees.as_bps(), fees_bps);
+        }
+        assert_eq!(
+            Fees::from_bps(bps(0, 0, 0, 0)).unwrap().trading_fee(1_000),
+            Some(0)
+        );
+        assert_eq!(
+            Fees::from_bps(bps(10_000, 0, 0, 0))
+                .unwrap()
+                .trading_fee(1_000),
+            Some(1_000)
+        );
+        // over 100%
+        for fees_bps in [
+            bps(10_001, 0, 0, 0),
+            bps(0, 10_001, 0, 0),
+            bps(0, 0, 10_001, 0),
+            bps(0, 0, 0, u16::MAX),
+        ] {
+            assert_eq!(Fees::from_bps(fees_bps), Err(AmmError::InvalidFee));
+        }
+    }
+
+    #[test]
+    fn test_as_bps_rounding() {
+        let fees = |numerator: u32, fee_denominator| Fees {
+            trade_fee_numerator: numerator,
+            owner_trade_fee_numerator: numerator,
+            owner_withdraw_fee_numerator: 0,
+            host_fee_numerator: 0,
+            fee_denominator,
+        };
+        #[rustfmt::skip]
+        let cases = vec![
+            // whole basis points
+            (fees(1, 400), 25),
+            (fees(3, 1_000), 30),
+            (fees(10_000, 10_000), 10_000),
+            // rounded down
+            (fees(1, 3), 3_333),
+            (fees(2, 3), 6_666),
+            (fees(1, 30_000), 0),
+            (fees(29_999, 300_000_000), 0),
+            (fees(3, 20_000), 1),
+            // over 100%, capped
+            (fees(u32::MAX, 1), u16::MAX),
+            // no denominator
+            (fees(25, 0), 0),
+        ];
+        for (fees, expected_bps) in cases {
+            let fees_bps = fees.as_bps();
+            assert_eq!(fees_bps.trade_bps, expected_bps, "{}", fees);
+            assert_eq!(fees_bps.owner_trade_bps, expected_bps, "{}", fees);
+            assert_eq!(fees_bps.owner_withdraw_bps, 0);
+        }
+        // the rounded fractions do not round trip
+        let rounded = Fees::from_bps(fees(1, 3).as_bps()).unwrap();
+        assert_eq!(rounded.trade_fee_numerator, 3_333);
+        assert_eq!(rounded.fee_denominator, FEE_BPS_DENOMINATOR);
+    }
+
+    #[test]
+    fn test_display_bps() {
+        let fees_bps = FeesBps {
+            trade_bps: 25,
+            owner_trade_bps: 5,
+            owner_withdraw_bps: 0,
+            host_bps: 10_000,
+        };
+        assert_eq!(
+            fees_bps.to_string(),
+            "trade=0.25% owner=0.05% owner_withdraw=0.00% host=100.00%"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Quotes of deposits and withdrawals of both token types
+
+use crate::{
+    curve::{
+        calculator::{checked_mul_div, RoundDirection, TradingTokenResult},
+        constant_product::pool_tokens_to_trading_tokens,
+        fees::Fees,
+    },
+    error::AmmError,
+};
+
+/// Pool tokens received by a `DepositAllTokenTypes` spending at most
+/// `amount_a` of token A and `amount_b` of token B.
+///
+/// The program takes the ceiling of the trading tokens backing the requested
+/// pool tokens, so the quote is floored on the most constraining side:
+/// depositing the returned amount never requires more than the given amounts.
+pub fn pool_tokens_for_deposit(
+    pool_supply: u128,
+    reserve_a: u128,
+    reserve_b: u128,
+    amount_a: u128,
+    amount_b: u128,
+) -> Result<u128, AmmError> {
+    let pool_tokens_a = checked_mul_div(amount_a, pool_supply, reserve_a, RoundDirection::Floor)
+        .ok_or(AmmError::CalculationFailure)?;
+    let pool_tokens_b = checked_mul_div(amount_b, pool_supply, reserve_b, RoundDirection::Floor)
+        .ok_or(AmmError::CalculationFailure)?;
+    Ok(pool_tokens_a.min(pool_tokens_b))
+}
+
+/// Tokens A and B received by a `WithdrawAllTokenTypes` burning `pool_tokens`.
+///
+/// As in the program, the owner withdraw fee is taken from `pool_tokens`
+/// first, unless `fee_exempt` because they are withdrawn from the pool fee
+/// account, see [Fees::withdraw_fee]. The remaining pool tokens are converted
+/// with the floor of their share of the reserves, and the amounts are capped
+/// by the reserves.
+pub fn tokens_for_withdraw(
+    pool_supply: u128,
+    reserve_a: u128,
+    reserve_b: u128,
+    pool_tokens: u128,
+    fees: &Fees,
+    fee_exempt: bool,
+) -> Result<TradingTokenResult, AmmError> {
+    let withdraw_fee = fees
+        .withdraw_fee(pool_tokens, fee_exempt)
+        .ok_or(AmmError::CalculationFailure)?;
+    let pool_tokens = pool_tokens
+        .checked_sub(withdraw_fee)
+        .ok_or(AmmError::CalculationFailure)?;
+    let results = pool_tokens_to_trading_tokens(
+        pool_tokens,
+        pool_supply,
+        reserve_a,
+        reserve_b,
+        RoundDirection::Floor,
+    )
+    .ok_or(AmmError::CalculationFailure)?;
+    Ok(TradingTokenResult {
+        token_a_amount: results.token_a_amount.min(reserve_a),
+        token_b_amount: results.token_b_amount.min(reserve_b),
+    })
+}
@@ -0,0 +1,386 @@
+//! The curve.fi invariant calculator.
+
+use crate::{
+    curve::calculator::{
+        checked_mul_div, CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult,
+        TradeDirection, TradingTokenResult,
+    },
+    error::AmmError,
+    state::check_account_len,
+};
+use arrayref::{array_mut_ref, array_ref};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+mod u256 {
+    #![allow(clippy::all)]
+    uint::construct_uint! {
+        /// 256-bit unsigned integer, the same type as `spl_math::uint::U256`
+        pub struct U256(4);
+    }
+}
+use u256::U256;
+
+const N_COINS: u8 = 2;
+const N_COINS_SQUARED: u8 = 4;
+
+/// Minimum amplification coefficient
+pub const MIN_AMP: u64 = 1;
+
+/// Maximum amplification coefficient
+pub const MAX_AMP: u64 = 1_000_000;
+
+/// Maximum number of Newton iterations when approximating the invariant
+const MAX_ITERATIONS: u8 = 32;
+
+/// Calculates A for deriving D
+///
+/// Per discussion with Michael Egorov A should be multiplied by n**n
+fn compute_a(amp: u64) -> Option<u64> {
+    amp.checked_mul(N_COINS as u64)
+}
+
+/// Returns self to the power of b
+fn checked_u8_power(a: &U256, b: u8) -> Option<U256> {
+    let mut result = *a;
+    for _ in 1..b {
+        result = result.checked_mul(*a)?;
+    }
+    Some(result)
+}
+
+/// Returns self multiplied by b
+fn checked_u8_mul(a: &U256, b: u8) -> Option<U256> {
+    let mut result = *a;
+    for _ in 1..b {
+        result = result.checked_add(*a)?;
+    }
+    Some(result)
+}
+
+/// StableCurve struct implementing CurveCalculator
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StableCurve {
+    /// Amplifier constant
+    pub amp: u64,
+}
+
+/// d = (leverage * sum_x + d_product * n_coins) * initial_d / ((leverage - 1) * initial_d + (n_coins + 1) * d_product)
+fn calculate_step(initial_d: &U256, leverage: u64, sum_x: u128, d_product: &U256) -> Option<U256> {
+    let leverage_mul = U256::from(leverage).checked_mul(sum_x.into())?;
+    let d_p_mul = checked_u8_mul(d_product, N_COINS)?;
+
+    let l_val = leverage_mul.checked_add(d_p_mul)?.checked_mul(*initial_d)?;
+
+    let leverage_sub = initial_d.checked_mul((leverage.checked_sub(1)?).into())?;
+    let n_coins_sum = checked_u8_mul(d_product, N_COINS.checked_add(1)?)?;
+
+    let r_val = leverage_sub.checked_add(n_coins_sum)?;
+
+    l_val.checked_div(r_val)
+}
+
+/// Compute stable swap invariant (D)
+/// Equation:
+/// A * sum(x_i) * n**n + D = A * D * n**n + D**(n+1) / (n**n * prod(x_i))
+fn compute_d(leverage: u64, amount_a: u128, amount_b: u128) -> Option<u128> {
+    let amount_a_times_coins =
+        checked_u8_mul(&U256::from(amount_a), N_COINS)?.checked_add(U256::one())?;
+    let amount_b_times_coins =
+        checked_u8_mul(&U256::from(amount_b), N_COINS)?.checked_add(U256::one())?;
+    let sum_x = amount_a.checked_add(amount_b)?; // sum(x_i), a.k.a S
+    if sum_x == 0 {
+        Some(0)
+    } else {
+        let mut d_previous: U256;
+        let mut d: U256 = sum_x.into();
+
+        // Newton's method to approximate D
+        for _ in 0..MAX_ITERATIONS {
+            let mut d_product = d;
+            d_product = d_product
+                .checked_mul(d)?
+                .checked_div(amount_a_times_coins)?;
+            d_product = d_product
+                .checked_mul(d)?
+                .checked_div(amount_b_times_coins)?;
+            d_previous = d;
+            //d = (leverage * sum_x + d_p * n_coins) * d / ((leverage - 1) * d + (n_coins + 1) * d_p);
+            d = calculate_step(&d, leverage, sum_x, &d_product)?;
+            // Equality with the precision of 1
+            if d == d_previous {
+                break;
+            }
+        }
+        u128::try_from(d).ok()
+    }
+}
+
+/// Compute swap amount `y` in proportion to `x`
+/// Solve for y:
+/// y**2 + y * (sum' - (A*n**n - 1) * D / (A * n**n)) = D ** (n + 1) / (n ** (2 * n) * prod' * A)
+/// y**2 + b*y = c
+fn compute_new_destination_amount(
+    leverage: u64,
+    new_source_amount: u128,
+    d_val: u128,
+) -> Option<u128> {
+    // Upscale to U256
+    let leverage: U256 = leverage.into();
+    let new_source_amount: U256 = new_source_amount.into();
+    let d_val: U256 = d_val.into();
+
+    // sum' = prod' = x
+    // c =  D ** (n + 1) / (n ** (2 * n) * prod' * A)
+    let c = checked_u8_power(&d_val, N_COINS.checked_add(1)?)?
+        .checked_div(checked_u8_mul(&new_source_amount, N_COINS_SQUARED)?.checked_mul(leverage)?)?;
+
+    // b = sum' - (A*n**n - 1) * D / (A * n**n)
+    let b = new_source_amount.checked_add(d_val.checked_div(leverage)?)?;
+
+    // Solve for y by approximating: y**2 + b*y = c
+    let mut y_prev: U256;
+    let mut y = d_val;
+    for _ in 0..MAX_ITERATIONS {
+        y_prev = y;
+        y = (checked_u8_power(&y, 2)?.checked_add(c)?)
+            .checked_div(checked_u8_mul(&y, 2)?.checked_add(b)?.checked_sub(d_val)?)?;
+        if y == y_prev {
+            break;
+        }
+    }
+    u128::try_from(y).ok()
+}
+
+impl CurveCalculator for StableCurve {
+    /// Stable curve
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        if source_amount == 0 {
+            return Some(SwapWithoutFeesResult {
+                source_amount_swapped: 0,
+                destination_amount_swapped: 0,
+            });
+        }
+        let leverage = compute_a(self.amp)?;
+
+        let new_source_amount = swap_source_amount.checked_add(source_amount)?;
+        let new_destination_amount = compute_new_destination_amount(
+            leverage,
+            new_source_amount,
+            compute_d(leverage, swap_source_amount, swap_destination_amount)?,
+        )?;
+
+        let amount_swapped = swap_destination_amount.checked_sub(new_destination_amount)?;
+
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped: amount_swapped,
+        })
+    }
+
+    /// Re-implementation of the constant product ratio calculation, since
+    /// the stable curve keeps the same proportional ownership of each side
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        let mut token_a_amount = pool_tokens
+            .checked_mul(swap_token_a_amount)?
+            .checked_div(pool_token_supply)?;
+        let mut token_b_amount = pool_tokens
+            .checked_mul(swap_token_b_amount)?
+            .checked_div(pool_token_supply)?;
+        let (token_a_amount, token_b_amount) = match round_direction {
+            RoundDirection::Floor => (token_a_amount, token_b_amount),
+            RoundDirection::Ceiling => {
+                let token_a_remainder = pool_tokens
+                    .checked_mul(swap_token_a_amount)?
+                    .checked_rem(pool_token_supply)?;
+                if token_a_remainder > 0 && token_a_amount > 0 {
+                    token_a_amount += 1;
+                }
+                let token_b_remainder = pool_tokens
+                    .checked_mul(swap_token_b_amount)?
+                    .checked_rem(pool_token_supply)?;
+                if token_b_remainder > 0 && token_b_amount > 0 {
+                    token_b_amount += 1;
+                }
+                (token_a_amount, token_b_amount)
+            }
+        };
+        Some(TradingTokenResult {
+            token_a_amount,
+            token_b_amount,
+        })
+    }
+
+    /// Get the amount of pool tokens for the given amount of token A or B,
+    /// in proportion to the growth of the invariant D
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        if source_amount == 0 {
+            return Some(0);
+        }
+        let leverage = compute_a(self.amp)?;
+        let d0 = compute_d(leverage, swap_token_a_amount, swap_token_b_amount)?;
+        let (deposit_token_amount, other_token_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let updated_deposit_token_amount = deposit_token_amount.checked_add(source_amount)?;
+        let d1 = compute_d(leverage, updated_deposit_token_amount, other_token_amount)?;
+        let diff = d1.checked_sub(d0)?;
+        checked_mul_div(diff, pool_supply, d0, round_direction)
+    }
+
+    /// Get the amount of pool tokens for the withdrawn amount of token A or B,
+    /// in proportion to the shrinking of the invariant D
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        if source_amount == 0 {
+            return Some(0);
+        }
+        let leverage = compute_a(self.amp)?;
+        let d0 = compute_d(leverage, swap_token_a_amount, swap_token_b_amount)?;
+        let (withdraw_token_amount, other_token_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let updated_withdraw_token_amount = withdraw_token_amount.checked_sub(source_amount)?;
+        let d1 = compute_d(leverage, updated_withdraw_token_amount, other_token_amount)?;
+        let diff = d0.checked_sub(d1)?;
+        checked_mul_div(diff, pool_supply, d0, round_direction)
+    }
+
+    fn validate(&self) -> Result<(), AmmError> {
+        if self.amp < MIN_AMP || self.amp > MAX_AMP {
+            Err(AmmError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for StableCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for StableCurve {}
+impl Pack for StableCurve {
+    const LEN: usize = 8;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<StableCurve, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let amp = array_ref![input, 0, 8];
+        Ok(Self {
+            amp: u64::from_le_bytes(*amp),
+        })
+    }
+}
+
+impl DynPack for StableCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let amp = array_mut_ref![output, 0, 8];
+        *amp = self.amp.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outputs of the curve.fi StableSwap simulation model, `dy` of
+    /// `tests/simulation.py` of curve-contract, which spl-token-swap checks
+    /// its stable curve against: (amp, source, destination, source amount,
+    /// destination amount swapped)
+    #[test]
+    fn test_swap_reference() {
+        #[rustfmt::skip]
+        let cases: [(u64, u128, u128, u128, u128); 11] = [
+            (1, 1_000_000, 1_000_000, 1_000, 1_000),
+            (1, 1_000_000, 1_000_000, 500_000, 396_088),
+            (10, 1_000_000, 1_000_000, 1_000, 1_000),
+            (100, 1_000_000, 1_000_000, 1_000, 1_000),
+            (100, 1_000_000, 1_000_000, 100_000, 99_901),
+            (100, 5_000_000, 1_000_000, 10_000, 9_591),
+            (100, 1_000_000, 5_000_000, 10_000, 10_418),
+            (85, 1_000_000_000_000, 1_000_000_000_000, 1_000_000_000, 999_988_372),
+            (1_000, 1_000_000_000, 1_000_000_000, 100_000_000, 99_989_911),
+            (1_000_000, 1_000_000, 1_000_000, 999_999, 999_293),
+            (2_000, 123_456_789, 987_654_321, 55_555_555, 55_744_543),
+        ];
+        for (amp, swap_source_amount, swap_destination_amount, source_amount, expected) in
+            cases.iter()
+        {
+            let result = StableCurve { amp: *amp }
+                .swap_without_fees(
+                    *source_amount,
+                    *swap_source_amount,
+                    *swap_destination_amount,
+                    TradeDirection::AtoB,
+                )
+                .unwrap();
+            assert_eq!(result.source_amount_swapped, *source_amount);
+            assert_eq!(result.destination_amount_swapped, *expected);
+        }
+    }
+
+    /// The `trading_token_conversion` cases of spl-token-swap: (token A,
+    /// token B, pool tokens, pool supply, token A amount, token B amount)
+    #[test]
+    fn test_pool_tokens_to_trading_tokens() {
+        let cases: [(u128, u128, u128, u128, u128, u128); 3] = [
+            (2, 49, 5, 10, 1, 25),
+            (100, 202, 5, 101, 5, 10),
+            (5, 501, 2, 10, 1, 101),
+        ];
+        for (token_a, token_b, pool_tokens, supply, expected_a, expected_b) in cases.iter() {
+            let result = StableCurve { amp: 1 }
+                .pool_tokens_to_trading_tokens(
+                    *pool_tokens,
+                    *supply,
+                    *token_a,
+                    *token_b,
+                    RoundDirection::Ceiling,
+                )
+                .unwrap();
+            assert_eq!(result.token_a_amount, *expected_a);
+            assert_eq!(result.token_b_amount, *expected_b);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the Amm program.
+///
+/// The discriminants are part of the program's public interface: clients
+/// match on `ProgramError::Custom(code)`, so variants must never be
+/// reordered and new ones are only appended.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum AmmError {
+    // 0.
+    /// The account cannot be initialized because it is already being used.
+    #[error("Swap account already in use")]
+    AlreadyInUse = 0,
+    /// The program address provided doesn't match the value generated by the program.
+    #[error("Invalid program address generated from nonce and key")]
+    InvalidProgramAddress = 1,
+    /// The owner of the input isn't set to the program address generated by the program.
+    #[error("Input account owner is not the program address")]
+    InvalidOwner = 2,
+    /// The owner of the pool token output is set to the program address generated by the program.
+    #[error("Output pool account owner cannot be the program address")]
+    InvalidOutputOwner = 3,
+    /// The deserialization of the account returned something besides State::Mint.
+    #[error("Deserialized account is not an SPL Token mint")]
+    ExpectedMint = 4,
+
+    // 5.
+    /// The deserialization of the account returned something besides State::Account.
+    #[error("Deserialized account is not an SPL Token account")]
+    ExpectedAccount = 5,
+    /// The input token account is empty.
+    #[error("Input token account empty")]
+    EmptySupply = 6,
+    /// The pool token mint has a non-zero supply.
+    #[error("Pool token mint has a non-zero supply")]
+    InvalidSupply = 7,
+    /// The provided token account has a delegate.
+    #[error("Token account has a delegate")]
+    InvalidDelegate = 8,
+    /// The input token is invalid for swap.
+    #[error("InvalidInput")]
+    InvalidInput = 9,
+
+    // 10.
+    /// Address of the provided swap token account is incorrect.
+    #[error("Address of the provided swap token account is incorrect")]
+    IncorrectSwapAccount = 10,
+    /// Address of the provided pool token mint is incorrect
+    #[error("Address of the provided pool token mint is incorrect")]
+    IncorrectPoolMint = 11,
+    /// The output token is invalid for swap.
+    #[error("InvalidOutput")]
+    InvalidOutput = 12,
+    /// General calculation failure due to overflow or underflow
+    #[error("General calculation failure due to overflow or underflow")]
+    CalculationFailure = 13,
+    /// Invalid instruction number passed in.
+    #[error("Invalid instruction")]
+    InvalidInstruction = 14,
+
+    // 15.
+    /// Swap input token accounts have the same mint
+    #[error("Swap input token accounts have the same mint")]
+    RepeatedMint = 15,
+    /// Swap instruction exceeds desired slippage limit
+    #[error("Swap instruction exceeds desired slippage limit")]
+    ExceededSlippage = 16,
+    /// The provided token account has a close authority.
+    #[error("Token account has a close authority")]
+    InvalidCloseAuthority = 17,
+    /// The pool token mint has a freeze authority.
+    #[error("Pool token mint has a freeze authority")]
+    InvalidFreezeAuthority = 18,
+    /// The pool fee token account is incorrect
+    #[error("Pool fee token account incorrect")]
+    IncorrectFeeAccount = 19,
+
+    // 20.
+    /// Given pool token amount results in zero trading tokens
+    #[error("Given pool token amount results in zero trading tokens")]
+    ZeroTradingTokens = 20,
+    /// The fee calculation failed due to overflow, underflow, or unexpected 0
+    #[error("Fee calculation failed due to overflow, underflow, or unexpected 0")]
+    FeeCalculationFailure = 21,
+    /// ConversionFailure
+    #[error("Conversion to u64 failed with an overflow or underflow")]
+    ConversionFailure = 22,
+    /// The provided fee does not match the program owner's constraints
+    #[error("The provided fee does not match the program owner's constraints")]
+    InvalidFee = 23,
+    /// The provided token program does not match the token program expected by the swap
+    #[error("The provided token program does not match the token program expected by the swap")]
+    IncorrectTokenProgramId = 24,
+
+    // 25.
+    /// The provided curve type is not supported by the program owner
+    #[error("The provided curve type is not supported by the program owner")]
+    UnsupportedCurveType = 25,
+    /// The provided curve parameters are invalid
+    #[error("The provided curve parameters are invalid")]
+    InvalidCurve = 26,
+    /// The operation cannot be performed on the given curve
+    #[error("The operation cannot be performed on the given curve")]
+    UnsupportedCurveOperation = 27,
+    /// The account data could not be decoded into the expected state
+    #[error("Invalid account data")]
+    InvalidAccountData = 28,
+    /// The provided program state account does not match the one stored in the swap
+    #[error("Address of the provided program state account is incorrect")]
+    IncorrectStateAccount = 29,
+
+    // 30.
+    /// The output mint of a route hop is not the input mint of the next hop
+    #[error("Output mint of a route hop does not match the input mint of the next hop")]
+    IncorrectRouteMint = 30,
+    /// The account data is shorter than the expected state layout
+    #[error("Account data is too short for the expected state layout")]
+    InvalidAccountLength = 31,
+    /// The signer is not the fee owner stored in the program state
+    #[error("The signer is not the fee owner of the program state")]
+    InvalidFeeOwner = 32,
+    /// The pool can only be closed by the state owner or the holder of the
+    /// whole pool token supply
+    #[error("Pool tokens are held by other accounts")]
+    PoolNotEmpty = 33,
+    /// The buffer is shorter than the packed instruction
+    #[error("Buffer is too small for the packed instruction")]
+    BufferTooSmall = 34,
+
+    // 35.
+    /// Both withdraw minimums are zero, or the maximum of pool tokens burnt
+    /// by a single token withdrawal is `u64::MAX`, without the caller opting
+    /// out of slippage protection
+    #[error("Withdrawal has no slippage protection")]
+    UnprotectedWithdraw = 35,
+    /// The swap minimum output is zero, accepting any output, without the
+    /// caller opting out of slippage protection
+    #[error("Swap has no slippage protection")]
+    UnprotectedSwap = 36,
+    /// The source of a single token deposit holds the token of the other
+    /// side of the pool
+    #[error("Source account is on the other side of the pool")]
+    IncorrectDepositSide = 37,
+}
+
+impl From<AmmError> for ProgramError {
+    fn from(e: AmmError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for AmmError {
+    fn type_of() -> &'static str {
+        "Amm Error"
+    }
+}
+
+impl PrintProgramError for AmmError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        msg!("Error: {}", self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clients match on the codes, which must not change
+    #[test]
+    fn test_error_codes() {
+        let codes = [
+            (AmmError::AlreadyInUse, 0),
+            (AmmError::InvalidProgramAddress, 1),
+            (AmmError::InvalidOwner, 2),
+            (AmmError::InvalidOutputOwner, 3),
+            (AmmError::ExpectedMint, 4),
+            (AmmError::ExpectedAccount, 5),
+            (AmmError::EmptySupply, 6),
+            (AmmError::InvalidSupply, 7),
+            (AmmError::InvalidDelegate, 8),
+            (AmmError::InvalidInput, 9),
+            (AmmError::IncorrectSwapAccount, 10),
+            (AmmError::IncorrectPoolMint, 11),
+            (AmmError::InvalidOutput, 12),
+            (AmmError::CalculationFailure, 13),
+            (AmmError::InvalidInstruction, 14),
+            (AmmError::RepeatedMint, 15),
+            (AmmError::ExceededSlippage, 16),
+            (AmmError::InvalidCloseAuthority, 17),
+            (AmmError::InvalidFreezeAuthority, 18),
+            (AmmError::IncorrectFeeAccount, 19),
+            (AmmError::ZeroTradingTokens, 20),
+            (AmmError::FeeCalculationFailure, 21),
+            (AmmError::ConversionFailure, 22),
+            (AmmError::InvalidFee, 23),
+            (AmmError::IncorrectTokenProgramId, 24),
+            (AmmError::UnsupportedCurveType, 25),
+            (AmmError::InvalidCurve, 26),
+            (AmmError::UnsupportedCurveOperation, 27),
+            (AmmError::InvalidAccountData, 28),
+            (AmmError::IncorrectStateAccount, 29),
+            (AmmError::IncorrectRouteMint, 30),
+            (AmmError::InvalidAccountLength, 31),
+            (AmmError::InvalidFeeOwner, 32),
+            (AmmError::PoolNotEmpty, 33),
+            (AmmError::BufferTooSmall, 34),
+            (AmmError::UnprotectedWithdraw, 35),
+            (AmmError::UnprotectedSwap, 36),
+            (AmmError::IncorrectDepositSide, 37),
+        ];
+        for (error, code) in codes.iter() {
+            assert_eq!(error.clone() as u32, *code);
+            assert_eq!(
+                ProgramError::from(error.clone()),
+                ProgramError::Custom(*code)
+            );
+            assert_eq!(AmmError::from_u32(*code).as_ref(), Some(error));
+        }
+        assert_eq!(AmmError::from_u32(codes.len() as u32), None);
+    }
+}
@@ -0,0 +1,5047 @@
+//! Instruction types
+//!
+//! The default of the instruction data structs has all amounts zero, a no-op
+//! that [AmmInstruction::unpack_strict] and the builders reject wherever they
+//! check the primary amount. The initialize data have no default, the nonce
+//! of the swap authority having no neutral value.
+
+#![allow(clippy::too_many_arguments)]
+
+pub use crate::curve::calculator::TradeDirection;
+use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::error::AmmError;
+use crate::state::{find_swap_authority, swap_authority, AmmStatus};
+use decode::{unpack_pubkey, unpack_u128, unpack_u64, unpack_u8};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::{instruction as token_instruction, native_mint};
+use std::{convert::TryFrom, fmt};
+
+#[cfg(feature = "fuzz")]
+use arbitrary::Arbitrary;
+#[cfg(feature = "borsh")]
+use borsh::BorshSchema;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "offchain")]
+use solana_sdk::signature::{Keypair, Signer};
+
+/// Initialize instruction data
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitializeInstruction {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// swap curve used by the new pool. When absent the pool trades on the
+    /// curve stored in the program state
+    pub swap_curve: Option<SwapCurve>,
+}
+
+/// Initialize2 instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Initialize2Instruction {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// Amount of token A the pool token_a Account must hold
+    pub initial_token_a: u64,
+    /// Amount of token B the pool token_b Account must hold
+    pub initial_token_b: u64,
+    /// Minimum amount of pool tokens minted to the creator, prevents the
+    /// initial liquidity from being changed before initialization
+    pub min_pool_tokens: u64,
+}
+
+/// Swap instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SwapInstruction {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+}
+
+/// Swap2 instruction data, the amounts of [SwapInstruction] widened for
+/// tokens with many decimals
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SwapInstruction2 {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u128,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u128,
+    /// Direction of the trade, packed as a trailing byte when set, `0` for
+    /// A to B and `1` for B to A, so that the program can check it against
+    /// the order of the vault accounts. Without it, the direction is only
+    /// implied by that order
+    pub direction: Option<TradeDirection>,
+}
+
+/// SwapExactOut instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SwapExactOutInstruction {
+    /// Exact amount of DESTINATION token to receive
+    pub amount_out: u64,
+    /// Maximum amount of SOURCE token to transfer, prevents excessive slippage
+    pub maximum_amount_in: u64,
+}
+
+/// Amount of pool tokens, telling it apart from a [TradingTokenAmount] in the
+/// typed constructors of the instruction data, e.g. [DepositInstruction::new]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PoolTokenAmount(pub u64);
+
+/// Amount of token A or B, telling it apart from a [PoolTokenAmount]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TradingTokenAmount(pub u64);
+
+impl From<u64> for PoolTokenAmount {
+    fn from(amount: u64) -> Self {
+        Self(amount)
+    }
+}
+
+impl From<PoolTokenAmount> for u64 {
+    fn from(amount: PoolTokenAmount) -> Self {
+        amount.0
+    }
+}
+
+impl From<u64> for TradingTokenAmount {
+    fn from(amount: u64) -> Self {
+        Self(amount)
+    }
+}
+
+impl From<TradingTokenAmount> for u64 {
+    fn from(amount: TradingTokenAmount) -> Self {
+        amount.0
+    }
+}
+
+/// Instruction instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DepositInstruction {
+    /// Pool token amount to transfer. token_a and token_b amount are set by
+    /// the current exchange rate and size of the pool
+    pub pool_token_amount: u64,
+    /// Maximum token A amount to deposit, prevents excessive slippage
+    pub maximum_token_a_amount: u64,
+    /// Maximum token B amount to deposit, prevents excessive slippage
+    pub maximum_token_b_amount: u64,
+}
+
+/// WithdrawInstruction instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WithdrawInstruction {
+    /// Amount of pool tokens to burn. User receives an output of token a
+    /// and b based on the percentage of the pool tokens that are returned.
+    pub pool_token_amount: u64,
+    /// Minimum amount of token A to receive, prevents excessive slippage.
+    /// Zero accepts any amount, see [WithdrawInstruction::no_slippage_protection]
+    pub minimum_token_a_amount: u64,
+    /// Minimum amount of token B to receive, prevents excessive slippage.
+    /// Zero accepts any amount
+    pub minimum_token_b_amount: u64,
+}
+
+/// Deposit one token type, exact amount in instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DepositSingleTokenTypeExactAmountIn {
+    /// Token amount to deposit
+    pub source_token_amount: u64,
+    /// Pool token amount to receive in exchange. The amount is set by
+    /// the current exchange rate and size of the pool
+    pub minimum_pool_token_amount: u64,
+}
+
+/// WithdrawAllTokenTypes instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WithdrawSingleTokenTypeExactAmountOut {
+    /// Amount of token A or B to receive
+    pub destination_token_amount: u64,
+    /// Maximum amount of pool tokens to burn. User receives an output of token A
+    /// or B based on the percentage of the pool tokens that are returned.
+    pub maximum_pool_token_amount: u64,
+}
+
+/// Compute units of the processing common to every instruction, unpacking
+/// and account checks.
+///
+/// A guess, as is [COMPUTE_UNITS_CURVE]: neither has been measured, the
+/// `compute_units` harness in `tests` only measuring them against the
+/// deployed program, which is not part of this crate. The harness fails
+/// when a measurement exceeds either.
+pub const COMPUTE_UNITS_BASE: u32 = 20_000;
+
+/// Compute units of one token program invocation, Token-2022 included.
+///
+/// Derived from the `compute_units` harness in `tests`: a Token-2022
+/// transfer, the most expensive token instruction invoked, consumes 6,114
+/// units, plus the 1,000 units charged by the runtime for the invocation,
+/// 7,114 rounded up.
+pub const COMPUTE_UNITS_PER_TOKEN_CPI: u32 = 7_500;
+
+/// Compute units of the curve calculation of a swap or a single-sided
+/// deposit or withdrawal, a guess like [COMPUTE_UNITS_BASE]
+pub const COMPUTE_UNITS_CURVE: u32 = 40_000;
+
+/// Compute units assumed for an instruction of another program, e.g. an
+/// associated token account creation or a token approval.
+///
+/// Derived from the `compute_units` harness in `tests`: an associated token
+/// account creation consumes about 20,400 units when the first bump seed
+/// gives its address, and 1,500 more for each bump searched past, 35,000
+/// covering nine of them.
+pub const COMPUTE_UNITS_OTHER: u32 = 35_000;
+
+/// Maximum compute unit limit of a transaction
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Instructions supported by the token swap program.
+///
+/// The first byte of the packed instruction is its `TAG_*` constant. Deployed
+/// clients depend on those values, so existing tags must never change and new
+/// variants must only be appended with the next free tag.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum AmmInstruction {
+    ///   Initializes a new AmmInfo.
+    ///
+    ///   0. `[writable, signer]` New Token-swap to create.
+    ///   1. `[]` swap authority derived from `create_program_address(&[Token-swap account])`
+    ///   2. `[]` Program state account
+    ///   3. `[]` AMMID of this account
+    ///   4. `[]` token_a Account. Must be non zero, owned by swap authority.
+    ///   5. `[]` token_b Account. Must be non zero, owned by swap authority.
+    ///   6. `[writable]` Pool Token Mint. Must be empty, owned by swap authority.
+    ///   7. `[]` Token A Account to transfer fees when swap.
+    ///   8. `[]` Token B Account to transfer fees when swap.
+    ///   9. `[writable]` Pool Token Account to deposit the initial pool token
+    ///      supply.  Must be empty, not owned by swap authority.
+    ///   10. `[writable]` Market ID
+    ///   11. `[]` Token program id
+    ///   12. `[]` Dex Program ID
+    ///
+    ///   The instruction data is the nonce, optionally followed by the 33-byte
+    ///   packed `SwapCurve` the pool should use.
+    Initialize(InitializeInstruction),
+
+    ///   Swap the tokens in the pool.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[]` Program state account
+    ///   4. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
+    ///   5. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
+    ///   6. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
+    ///   7. `[writable]` token_(A|B) DESTINATION Account assigned to USER as the owner.
+    ///   8. `[writable]` Pool token mint, to generate trading fees
+    ///   9. `[writable]` Fee token account, to receive trading fees
+    ///   10. `[writable]` (optional) Fee wallet account, to receive fees when swap from SOL
+    ///   11. `[]` Token program id
+    ///   12. `[]` (optional) System Program ID to send SOL
+    ///   13. `[writable]` (optional) Host fee account, a pool token account
+    ///       receiving a share of the trading fees
+    ///
+    ///   The fee wallet and the System Program are only passed, together, for
+    ///   pools trading SOL.  The host fee account is always the last account
+    ///   when present, at index 11 when the SOL accounts are omitted, and is
+    ///   detected from the number of accounts: the instruction data is the
+    ///   same with or without it.
+    ///
+    ///   A user transfer authority that is an spl-token multisig does not
+    ///   sign, its `[signer]` signers following every other account, as for
+    ///   the deposits and withdrawals. Only their signer flag tells them
+    ///   apart from the optional accounts, see
+    ///   `AmmInstruction::unpack_with_signers`.
+    Swap(SwapInstruction),
+
+    ///   Deposit both types of tokens into the pool.  The output is a "pool"
+    ///   token representing ownership in the pool. Inputs are converted to
+    ///   the current ratio.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[]` Program state account
+    ///   4. `[writable]` token_a user transfer authority can transfer amount,
+    ///   5. `[writable]` token_b user transfer authority can transfer amount,
+    ///   6. `[writable]` token_a Base Account to deposit into.
+    ///   7. `[writable]` token_b Base Account to deposit into.
+    ///   8. `[writable]` Pool MINT account, swap authority is the owner.
+    ///   9. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
+    ///   10. `[]` Token program id
+    DepositAllTokenTypes(DepositInstruction),
+
+    ///   Withdraw both types of tokens from the pool at the current ratio, given
+    ///   pool tokens.  The pool tokens are burned in exchange for an equivalent
+    ///   amount of token A and B.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[]` Program state account
+    ///   4. `[writable]` Pool mint account, swap authority is the owner
+    ///   5. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
+    ///   6. `[writable]` token_a Swap Account to withdraw FROM.
+    ///   7. `[writable]` token_b Swap Account to withdraw FROM.
+    ///   8. `[writable]` token_a user Account to credit.
+    ///   9. `[writable]` token_b user Account to credit.
+    ///   10. `[writable]` Fee account, to receive withdrawal fees
+    ///   11. `[]` Token program id
+    WithdrawAllTokenTypes(WithdrawInstruction),
+
+    ///   Deposit one type of tokens into the pool.  The output is a "pool" token
+    ///   representing ownership into the pool. Input token is converted as if
+    ///   a swap and deposit all token types were performed.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
+    ///   4. `[writable]` token_a Swap Account, may deposit INTO.
+    ///   5. `[writable]` token_b Swap Account, may deposit INTO.
+    ///   6. `[writable]` Pool MINT account, swap authority is the owner.
+    ///   7. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
+    ///   8. `[]` Token program id
+    DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
+
+    ///   Withdraw one token type from the pool at the current ratio given the
+    ///   exact amount out expected.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[writable]` Pool mint account, swap authority is the owner
+    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
+    ///   5. `[writable]` token_a Swap Account to potentially withdraw from.
+    ///   6. `[writable]` token_b Swap Account to potentially withdraw from.
+    ///   7. `[writable]` token_(A|B) User Account to credit
+    ///   8. `[writable]` Fee account, to receive withdrawal fees
+    ///   9. `[]` Token program id
+    WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Swap the tokens in the pool, receiving an exact amount of the
+    ///   DESTINATION token.  The SOURCE amount, fees included, is computed by
+    ///   the program and must not exceed `maximum_amount_in`.
+    ///
+    ///   Takes the same accounts, in the same order, as `Swap`.
+    SwapExactOut(SwapExactOutInstruction),
+
+    ///   Update the fees stored in the program state.
+    ///
+    ///   0. `[writable]` Program state account
+    ///   1. `[signer]` State owner
+    SetFees(Fees),
+
+    ///   Pause the pool, rejecting every swap, deposit and withdrawal until
+    ///   it is unpaused.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` Program state account
+    ///   2. `[signer]` State owner
+    Pause,
+
+    ///   Unpause a paused pool.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` Program state account
+    ///   2. `[signer]` State owner
+    Unpause,
+
+    ///   Withdraw the trading fees accumulated in a fee token account of the
+    ///   pool.  An amount of 0 withdraws the whole balance.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` Program state account
+    ///   3. `[signer]` Fee owner, as stored in the program state
+    ///   4. `[writable]` Fee token account of the pool
+    ///   5. `[writable]` Destination token account
+    ///   6. `[]` Token program id
+    WithdrawFees(u64),
+
+    ///   Propose a new state owner, who takes over once the transfer is
+    ///   accepted.  Proposing again replaces the pending owner.  The program
+    ///   state account must have the room for the pending owner, see
+    ///   [crate::state::ProgramStateV2] for migrating an original account.
+    ///
+    ///   0. `[writable]` Program state account
+    ///   1. `[signer]` State owner
+    TransferStateOwnership {
+        /// Proposed state owner
+        #[cfg_attr(feature = "serde", serde(with = "crate::state::pubkey_base58"))]
+        new_owner: Pubkey,
+    },
+
+    ///   Accept a pending state ownership transfer, making the pending owner
+    ///   the state owner.
+    ///
+    ///   0. `[writable]` Program state account
+    ///   1. `[signer]` Pending state owner
+    AcceptStateOwnership,
+
+    ///   Close the pool, transferring its remaining token A and B to the
+    ///   destinations and the rent of the token-swap account to the signer.
+    ///   The signer must be the state owner, or own a pool token account
+    ///   holding the whole pool token supply.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` Program state account
+    ///   3. `[writable, signer]` State owner or last liquidity provider,
+    ///      receiving the lamports of the token-swap account
+    ///   4. `[writable]` token_a Swap Account
+    ///   5. `[writable]` token_b Swap Account
+    ///   6. `[writable]` token_a destination
+    ///   7. `[writable]` token_b destination
+    ///   8. `[]` Pool token mint
+    ///   9. `[]` Token program id
+    ///   10. `[]` Optional pool token account of the signer holding the whole
+    ///       supply, required unless the signer is the state owner
+    ClosePool,
+
+    ///   Initialize the program state, shared by every pool of the program.
+    ///
+    ///   0. `[writable]` Program state account, allocated and owned by the
+    ///      program
+    ///   1. `[signer]` State owner
+    ///   2. `[]` Fee owner
+    InitializeProgramState {
+        /// Pool tokens minted to the creator of a new pool
+        initial_supply: u64,
+        /// Fees of the pools
+        fees: Fees,
+        /// Curve of the pools not carrying their own
+        curve: SwapCurve,
+    },
+
+    ///   Update the program state, replacing its fee owner, initial supply,
+    ///   fees and curve.
+    ///
+    ///   0. `[writable]` Program state account
+    ///   1. `[signer]` State owner
+    ///   2. `[]` New fee owner
+    UpdateProgramState {
+        /// Pool tokens minted to the creator of a new pool
+        initial_supply: u64,
+        /// Fees of the pools
+        fees: Fees,
+        /// Curve of the pools not carrying their own
+        curve: SwapCurve,
+    },
+
+    ///   Initializes a new AmmInfo, checking the initial liquidity.
+    ///
+    ///   Takes the same accounts, in the same order, as `Initialize`.  The
+    ///   pool token accounts must hold exactly `initial_token_a` and
+    ///   `initial_token_b`, and at least `min_pool_tokens` must be minted to
+    ///   the creator, otherwise the pool is not initialized.
+    ///
+    ///   Prefer it over `Initialize` whenever the pool token accounts are
+    ///   funded by an earlier transaction, so that a change of their balances
+    ///   in between fails the initialization instead of mispricing the pool.
+    ///   The pool trades on the curve stored in the program state.
+    Initialize2(Initialize2Instruction),
+
+    ///   Swap the tokens in the pool, with 16-byte amounts.
+    ///
+    ///   Takes the same accounts, in the same order, as `Swap`, host fee
+    ///   account included.
+    Swap2(SwapInstruction2),
+}
+
+/// Little-endian primitives of the instruction data, each returning the
+/// value and the rest of the input, and failing with `InvalidInstruction`
+/// when the input is too short.
+pub mod decode {
+    use crate::error::AmmError;
+    use solana_program::pubkey::Pubkey;
+    use std::convert::TryFrom;
+
+    /// Splits the first `N` bytes off `input`
+    fn unpack_array<const N: usize>(input: &[u8]) -> Result<([u8; N], &[u8]), AmmError> {
+        if input.len() < N {
+            return Err(AmmError::InvalidInstruction);
+        }
+        let (bytes, rest) = input.split_at(N);
+        let bytes = <[u8; N]>::try_from(bytes).map_err(|_| AmmError::InvalidInstruction)?;
+        Ok((bytes, rest))
+    }
+
+    /// Unpacks a `u8`
+    pub fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), AmmError> {
+        let (bytes, rest) = unpack_array::<1>(input)?;
+        Ok((bytes[0], rest))
+    }
+
+    /// Unpacks a `u16`
+    pub fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), AmmError> {
+        let (bytes, rest) = unpack_array(input)?;
+        Ok((u16::from_le_bytes(bytes), rest))
+    }
+
+    /// Unpacks a `u64`
+    pub fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), AmmError> {
+        let (bytes, rest) = unpack_array(input)?;
+        Ok((u64::from_le_bytes(bytes), rest))
+    }
+
+    /// Unpacks a `u128`
+    pub fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), AmmError> {
+        let (bytes, rest) = unpack_array(input)?;
+        Ok((u128::from_le_bytes(bytes), rest))
+    }
+
+    /// Unpacks an `i64`
+    pub fn unpack_i64(input: &[u8]) -> Result<(i64, &[u8]), AmmError> {
+        let (bytes, rest) = unpack_array(input)?;
+        Ok((i64::from_le_bytes(bytes), rest))
+    }
+
+    /// Unpacks a 32-byte pubkey
+    pub fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), AmmError> {
+        let (bytes, rest) = unpack_array(input)?;
+        Ok((Pubkey::new_from_array(bytes), rest))
+    }
+
+    /// Unpacks a `bool` packed as `0` or `1`, failing with
+    /// `InvalidInstruction` on any other byte
+    pub fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), AmmError> {
+        match unpack_u8(input)? {
+            (0, rest) => Ok((false, rest)),
+            (1, rest) => Ok((true, rest)),
+            _ => Err(AmmError::InvalidInstruction),
+        }
+    }
+}
+
+impl AmmInstruction {
+    /// Tag of [AmmInstruction::Initialize]
+    pub const TAG_INITIALIZE: u8 = 0;
+    /// Tag of [AmmInstruction::Swap]
+    pub const TAG_SWAP: u8 = 1;
+    /// Tag of [AmmInstruction::DepositAllTokenTypes]
+    pub const TAG_DEPOSIT_ALL_TOKEN_TYPES: u8 = 2;
+    /// Tag of [AmmInstruction::WithdrawAllTokenTypes]
+    pub const TAG_WITHDRAW_ALL_TOKEN_TYPES: u8 = 3;
+    /// Tag of [AmmInstruction::DepositSingleTokenTypeExactAmountIn]
+    pub const TAG_DEPOSIT_SINGLE_TOKEN_TYPE_EXACT_AMOUNT_IN: u8 = 4;
+    /// Tag of [AmmInstruction::WithdrawSingleTokenTypeExactAmountOut]
+    pub const TAG_WITHDRAW_SINGLE_TOKEN_TYPE_EXACT_AMOUNT_OUT: u8 = 5;
+    /// Tag of [AmmInstruction::SwapExactOut]
+    pub const TAG_SWAP_EXACT_OUT: u8 = 6;
+    /// Tag of [AmmInstruction::SetFees]
+    pub const TAG_SET_FEES: u8 = 7;
+    /// Tag of [AmmInstruction::Pause]
+    pub const TAG_PAUSE: u8 = 8;
+    /// Tag of [AmmInstruction::Unpause]
+    pub const TAG_UNPAUSE: u8 = 9;
+    /// Tag of [AmmInstruction::WithdrawFees]
+    pub const TAG_WITHDRAW_FEES: u8 = 10;
+    /// Tag of [AmmInstruction::TransferStateOwnership]
+    pub const TAG_TRANSFER_STATE_OWNERSHIP: u8 = 11;
+    /// Tag of [AmmInstruction::AcceptStateOwnership]
+    pub const TAG_ACCEPT_STATE_OWNERSHIP: u8 = 12;
+    /// Tag of [AmmInstruction::ClosePool]
+    pub const TAG_CLOSE_POOL: u8 = 13;
+    /// Tag of [AmmInstruction::InitializeProgramState]
+    pub const TAG_INITIALIZE_PROGRAM_STATE: u8 = 14;
+    /// Tag of [AmmInstruction::UpdateProgramState]
+    pub const TAG_UPDATE_PROGRAM_STATE: u8 = 15;
+    /// Tag of [AmmInstruction::Initialize2]
+    pub const TAG_INITIALIZE2: u8 = 16;
+    /// Tag of [AmmInstruction::Swap2]
+    pub const TAG_SWAP2: u8 = 17;
+
+    /// Largest packed size of any instruction, an `InitializeProgramState` or
+    /// `UpdateProgramState`
+    pub const MAX_PACKED_LEN: usize = 1 + 8 + Fees::LEN + SwapCurve::LEN;
+
+    /// Tag written as the first byte of the packed instruction
+    pub const fn tag(&self) -> u8 {
+        match self {
+            Self::Initialize(_) => Self::TAG_INITIALIZE,
+            Self::Swap(_) => Self::TAG_SWAP,
+            Self::DepositAllTokenTypes(_) => Self::TAG_DEPOSIT_ALL_TOKEN_TYPES,
+            Self::WithdrawAllTokenTypes(_) => Self::TAG_WITHDRAW_ALL_TOKEN_TYPES,
+            Self::DepositSingleTokenTypeExactAmountIn(_) => {
+                Self::TAG_DEPOSIT_SINGLE_TOKEN_TYPE_EXACT_AMOUNT_IN
+            }
+            Self::WithdrawSingleTokenTypeExactAmountOut(_) => {
+                Self::TAG_WITHDRAW_SINGLE_TOKEN_TYPE_EXACT_AMOUNT_OUT
+            }
+            Self::SwapExactOut(_) => Self::TAG_SWAP_EXACT_OUT,
+            Self::SetFees(_) => Self::TAG_SET_FEES,
+            Self::Pause => Self::TAG_PAUSE,
+            Self::Unpause => Self::TAG_UNPAUSE,
+            Self::WithdrawFees(_) => Self::TAG_WITHDRAW_FEES,
+            Self::TransferStateOwnership { .. } => Self::TAG_TRANSFER_STATE_OWNERSHIP,
+            Self::AcceptStateOwnership => Self::TAG_ACCEPT_STATE_OWNERSHIP,
+            Self::ClosePool => Self::TAG_CLOSE_POOL,
+            Self::InitializeProgramState { .. } => Self::TAG_INITIALIZE_PROGRAM_STATE,
+            Self::UpdateProgramState { .. } => Self::TAG_UPDATE_PROGRAM_STATE,
+            Self::Initialize2(_) => Self::TAG_INITIALIZE2,
+            Self::Swap2(_) => Self::TAG_SWAP2,
+        }
+    }
+
+    /// Upper bound of the compute units consumed by the instruction, from
+    /// the token program invocations it performs and whether it runs the
+    /// curve calculation, see [COMPUTE_UNITS_BASE]
+    pub const fn compute_unit_estimate(&self) -> u32 {
+        let (token_invocations, curve) = match self {
+            Self::Initialize(_) | Self::Initialize2(_) => (1, false),
+            // transfers in and out, owner and host fee mints, SOL fee transfer
+            Self::Swap(_) | Self::Swap2(_) | Self::SwapExactOut(_) => (5, true),
+            Self::DepositAllTokenTypes(_) => (3, false),
+            Self::WithdrawAllTokenTypes(_) => (4, false),
+            Self::DepositSingleTokenTypeExactAmountIn(_) => (2, true),
+            Self::WithdrawSingleTokenTypeExactAmountOut(_) => (3, true),
+            Self::WithdrawFees(_) => (1, false),
+            Self::ClosePool => (2, false),
+            Self::SetFees(_)
+            | Self::Pause
+            | Self::Unpause
+            | Self::TransferStateOwnership { .. }
+            | Self::AcceptStateOwnership
+            | Self::InitializeProgramState { .. }
+            | Self::UpdateProgramState { .. } => (0, false),
+        };
+        let curve_units = if curve { COMPUTE_UNITS_CURVE } else { 0 };
+        COMPUTE_UNITS_BASE + token_invocations * COMPUTE_UNITS_PER_TOKEN_CPI + curve_units
+    }
+
+    /// Size of the packed instruction, tag included
+    pub const fn packed_len(&self) -> usize {
+        1 + match self {
+            Self::Initialize(InitializeInstruction {
+                swap_curve: Some(_),
+                ..
+            }) => 1 + SwapCurve::LEN,
+            Self::Initialize(InitializeInstruction {
+                swap_curve: None, ..
+            }) => 1,
+            Self::Swap(_)
+            | Self::DepositSingleTokenTypeExactAmountIn(_)
+            | Self::WithdrawSingleTokenTypeExactAmountOut(_)
+            | Self::SwapExactOut(_) => 16,
+            Self::DepositAllTokenTypes(_) | Self::WithdrawAllTokenTypes(_) => 24,
+            Self::SetFees(_) => Fees::LEN,
+            Self::Pause | Self::Unpause | Self::AcceptStateOwnership | Self::ClosePool => 0,
+            Self::WithdrawFees(_) => 8,
+            Self::TransferStateOwnership { .. } => 32,
+            Self::InitializeProgramState { .. } | Self::UpdateProgramState { .. } => {
+                8 + Fees::LEN + SwapCurve::LEN
+            }
+            Self::Initialize2(_) => 1 + 24,
+            Self::Swap2(SwapInstruction2 { direction, .. }) => {
+                if direction.is_some() {
+                    33
+                } else {
+                    32
+                }
+            }
+        }
+    }
+
+    /// Unpacks a byte buffer into a [AmmInstruction](enum.AmmInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = unpack_u8(input)?;
+        Ok(match tag {
+            Self::TAG_INITIALIZE => {
+                let (nonce, rest) = unpack_u8(rest)?;
+                let swap_curve = if rest.is_empty() {
+                    None
+                } else if rest.len() == SwapCurve::LEN {
+                    Some(SwapCurve::unpack_from_slice(rest)?)
+                } else {
+                    return Err(AmmError::InvalidInstruction.into());
+                };
+                Self::Initialize(InitializeInstruction {
+                    nonce,
+                    swap_curve,
+                })
+            }
+            Self::TAG_SWAP => {
+                let (amount_in, rest) = unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = unpack_u64(rest)?;
+                Self::Swap(SwapInstruction {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            Self::TAG_DEPOSIT_ALL_TOKEN_TYPES => {
+                let (pool_token_amount, rest) = unpack_u64(rest)?;
+                let (maximum_token_a_amount, rest) = unpack_u64(rest)?;
+                let (maximum_token_b_amount, _rest) = unpack_u64(rest)?;
+                Self::DepositAllTokenTypes(DepositInstruction {
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                })
+            }
+            Self::TAG_WITHDRAW_ALL_TOKEN_TYPES => {
+                let (pool_token_amount, rest) = unpack_u64(rest)?;
+                let (minimum_token_a_amount, rest) = unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = unpack_u64(rest)?;
+                Self::WithdrawAllTokenTypes(WithdrawInstruction {
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            Self::TAG_DEPOSIT_SINGLE_TOKEN_TYPE_EXACT_AMOUNT_IN => {
+                let (source_token_amount, rest) = unpack_u64(rest)?;
+                let (minimum_pool_token_amount, _rest) = unpack_u64(rest)?;
+                Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                })
+            }
+            Self::TAG_WITHDRAW_SINGLE_TOKEN_TYPE_EXACT_AMOUNT_OUT => {
+                let (destination_token_amount, rest) = unpack_u64(rest)?;
+                let (maximum_pool_token_amount, _rest) = unpack_u64(rest)?;
+                Self::WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                })
+            }
+            Self::TAG_SWAP_EXACT_OUT => {
+                let (amount_out, rest) = unpack_u64(rest)?;
+                let (maximum_amount_in, _rest) = unpack_u64(rest)?;
+                Self::SwapExactOut(SwapExactOutInstruction {
+                    amount_out,
+                    maximum_amount_in,
+                })
+            }
+            Self::TAG_SET_FEES => {
+                if rest.len() < Fees::LEN {
+                    return Err(AmmError::InvalidInstruction.into());
+                }
+                let (fees, _rest) = rest.split_at(Fees::LEN);
+                let fees = Fees::unpack_from_slice(fees)?;
+                fees.validate()?;
+                Self::SetFees(fees)
+            }
+            Self::TAG_PAUSE => Self::Pause,
+            Self::TAG_UNPAUSE => Self::Unpause,
+            Self::TAG_WITHDRAW_FEES => {
+                let (amount, _rest) = unpack_u64(rest)?;
+                Self::WithdrawFees(amount)
+            }
+            Self::TAG_TRANSFER_STATE_OWNERSHIP => {
+                let (new_owner, _rest) = unpack_pubkey(rest)?;
+                Self::TransferStateOwnership { new_owner }
+            }
+            Self::TAG_ACCEPT_STATE_OWNERSHIP => Self::AcceptStateOwnership,
+            Self::TAG_CLOSE_POOL => Self::ClosePool,
+            Self::TAG_INITIALIZE_PROGRAM_STATE => {
+                let (initial_supply, fees, curve) = Self::unpack_program_state(rest)?;
+                Self::InitializeProgramState {
+                    initial_supply,
+                    fees,
+                    curve,
+                }
+            }
+            Self::TAG_UPDATE_PROGRAM_STATE => {
+                let (initial_supply, fees, curve) = Self::unpack_program_state(rest)?;
+                Self::UpdateProgramState {
+                    initial_supply,
+                    fees,
+                    curve,
+                }
+            }
+            Self::TAG_INITIALIZE2 => {
+                let (nonce, rest) = unpack_u8(rest)?;
+                let (initial_token_a, rest) = unpack_u64(rest)?;
+                let (initial_token_b, rest) = unpack_u64(rest)?;
+                let (min_pool_tokens, _rest) = unpack_u64(rest)?;
+                Self::Initialize2(Initialize2Instruction {
+                    nonce,
+                    initial_token_a,
+                    initial_token_b,
+                    min_pool_tokens,
+                })
+            }
+            Self::TAG_SWAP2 => {
+                let (amount_in, rest) = unpack_u128(rest)?;
+                let (minimum_amount_out, rest) = unpack_u128(rest)?;
+                let direction = match rest.first() {
+                    None => None,
+                    Some(0) => Some(TradeDirection::AtoB),
+                    Some(1) => Some(TradeDirection::BtoA),
+                    Some(_) => return Err(AmmError::InvalidInstruction.into()),
+                };
+                Self::Swap2(SwapInstruction2 {
+                    amount_in,
+                    minimum_amount_out,
+                    direction,
+                })
+            }
+            _ => return Err(AmmError::InvalidInstruction.into()),
+        })
+    }
+
+    /// Unpacks the initial supply, fees and curve of the program state
+    /// instructions, laid out as in the program state account
+    fn unpack_program_state(input: &[u8]) -> Result<(u64, Fees, SwapCurve), ProgramError> {
+        let (initial_supply, rest) = unpack_u64(input)?;
+        if rest.len() < Fees::LEN + SwapCurve::LEN {
+            return Err(AmmError::InvalidInstruction.into());
+        }
+        let (fees, rest) = rest.split_at(Fees::LEN);
+        let fees = Fees::unpack_from_slice(fees)?;
+        fees.validate()?;
+        let curve = SwapCurve::unpack_from_slice(&rest[..SwapCurve::LEN])?;
+        Ok((initial_supply, fees, curve))
+    }
+
+    /// Same as [unpack](Self::unpack), also rejecting with
+    /// `ZeroTradingTokens` the instructions the builders refuse to create:
+    /// swaps, withdrawals of both tokens and single token deposits and
+    /// withdrawals of a zero primary amount.
+    pub fn unpack_strict(input: &[u8]) -> Result<Self, ProgramError> {
+        let instruction = Self::unpack(input)?;
+        let amount = match &instruction {
+            Self::Swap(SwapInstruction { amount_in, .. }) => u128::from(*amount_in),
+            Self::Swap2(SwapInstruction2 { amount_in, .. }) => *amount_in,
+            Self::WithdrawAllTokenTypes(WithdrawInstruction {
+                pool_token_amount, ..
+            }) => u128::from(*pool_token_amount),
+            Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                source_token_amount,
+                ..
+            }) => u128::from(*source_token_amount),
+            Self::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    ..
+                },
+            ) => u128::from(*destination_token_amount),
+            _ => return Ok(instruction),
+        };
+        if amount == 0 {
+            return Err(AmmError::ZeroTradingTokens.into());
+        }
+        Ok(instruction)
+    }
+
+    /// Packs a [AmmInstruction](enum.AmmInstruction.html) into a byte buffer.
+    /// Use [pack_into](Self::pack_into) to encode without allocating.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; self.packed_len()];
+        self.pack_into_exact(&mut buf);
+        buf
+    }
+
+    /// Packs a [AmmInstruction](enum.AmmInstruction.html) at the start of
+    /// `buf` without allocating, returning the number of bytes written, its
+    /// [packed_len](Self::packed_len). Fails with `BufferTooSmall` when `buf`
+    /// is shorter.
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<usize, AmmError> {
+        let len = self.packed_len();
+        let buf = buf.get_mut(..len).ok_or(AmmError::BufferTooSmall)?;
+        self.pack_into_exact(buf);
+        Ok(len)
+    }
+
+    /// Packs into `buf`, which must be exactly [packed_len](Self::packed_len)
+    /// bytes long
+    fn pack_into_exact(&self, buf: &mut [u8]) {
+        let mut offset = 0;
+        let mut write = |bytes: &[u8]| {
+            buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+            offset += bytes.len();
+        };
+        write(&[self.tag()]);
+        match self {
+            Self::Initialize(InitializeInstruction {
+                nonce,
+                swap_curve,
+            }) => {
+                write(&[*nonce]);
+                if let Some(swap_curve) = swap_curve {
+                    let mut swap_curve_slice = [0u8; SwapCurve::LEN];
+                    Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
+                    write(&swap_curve_slice);
+                }
+            }
+            Self::Swap(SwapInstruction {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                write(&amount_in.to_le_bytes());
+                write(&minimum_amount_out.to_le_bytes());
+            }
+            Self::DepositAllTokenTypes(DepositInstruction {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                write(&pool_token_amount.to_le_bytes());
+                write(&maximum_token_a_amount.to_le_bytes());
+                write(&maximum_token_b_amount.to_le_bytes());
+            }
+            Self::WithdrawAllTokenTypes(WithdrawInstruction {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                write(&pool_token_amount.to_le_bytes());
+                write(&minimum_token_a_amount.to_le_bytes());
+                write(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                source_token_amount,
+                minimum_pool_token_amount,
+            }) => {
+                write(&source_token_amount.to_le_bytes());
+                write(&minimum_pool_token_amount.to_le_bytes());
+            }
+            Self::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                write(&destination_token_amount.to_le_bytes());
+                write(&maximum_pool_token_amount.to_le_bytes());
+            }
+            Self::SwapExactOut(SwapExactOutInstruction {
+                amount_out,
+                maximum_amount_in,
+            }) => {
+                write(&amount_out.to_le_bytes());
+                write(&maximum_amount_in.to_le_bytes());
+            }
+            Self::SetFees(fees) => {
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                write(&fees_slice);
+            }
+            Self::Pause | Self::Unpause | Self::AcceptStateOwnership | Self::ClosePool => {}
+            Self::WithdrawFees(amount) => {
+                write(&amount.to_le_bytes());
+            }
+            Self::TransferStateOwnership { new_owner } => {
+                write(new_owner.as_ref());
+            }
+            Self::InitializeProgramState {
+                initial_supply,
+                fees,
+                curve,
+            }
+            | Self::UpdateProgramState {
+                initial_supply,
+                fees,
+                curve,
+            } => {
+                write(&initial_supply.to_le_bytes());
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                write(&fees_slice);
+                let mut curve_slice = [0u8; SwapCurve::LEN];
+                Pack::pack_into_slice(curve, &mut curve_slice[..]);
+                write(&curve_slice);
+            }
+            Self::Initialize2(Initialize2Instruction {
+                nonce,
+                initial_token_a,
+                initial_token_b,
+                min_pool_tokens,
+            }) => {
+                write(&[*nonce]);
+                write(&initial_token_a.to_le_bytes());
+                write(&initial_token_b.to_le_bytes());
+                write(&min_pool_tokens.to_le_bytes());
+            }
+            Self::Swap2(SwapInstruction2 {
+                amount_in,
+                minimum_amount_out,
+                direction,
+            }) => {
+                write(&amount_in.to_le_bytes());
+                write(&minimum_amount_out.to_le_bytes());
+                match direction {
+                    Some(TradeDirection::AtoB) => write(&[0]),
+                    Some(TradeDirection::BtoA) => write(&[1]),
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for AmmInstruction {
+    type Error = ProgramError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::unpack(input)
+    }
+}
+
+impl From<AmmInstruction> for Vec<u8> {
+    fn from(instruction: AmmInstruction) -> Self {
+        instruction.pack()
+    }
+}
+
+/// One-line summary of the instruction and its amounts, e.g.
+/// `Swap { in: 1500000, min_out: 1430000 }`. Amounts are raw token units.
+/// The format is stable, log parsers may rely on it
+impl fmt::Display for AmmInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Initialize(InitializeInstruction { nonce, swap_curve }) => match swap_curve {
+                Some(swap_curve) => write!(
+                    f,
+                    "Initialize {{ nonce: {}, curve: {:?} }}",
+                    nonce, swap_curve.curve_type
+                ),
+                None => write!(f, "Initialize {{ nonce: {} }}", nonce),
+            },
+            Self::Swap(SwapInstruction {
+                amount_in,
+                minimum_amount_out,
+            }) => write!(
+                f,
+                "Swap {{ in: {}, min_out: {} }}",
+                amount_in, minimum_amount_out
+            ),
+            Self::DepositAllTokenTypes(DepositInstruction {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => write!(
+                f,
+                "DepositAllTokenTypes {{ pool_tokens: {}, max_a: {}, max_b: {} }}",
+                pool_token_amount, maximum_token_a_amount, maximum_token_b_amount
+            ),
+            Self::WithdrawAllTokenTypes(WithdrawInstruction {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => write!(
+                f,
+                "WithdrawAllTokenTypes {{ pool_tokens: {}, min_a: {}, min_b: {} }}",
+                pool_token_amount, minimum_token_a_amount, minimum_token_b_amount
+            ),
+            Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                source_token_amount,
+                minimum_pool_token_amount,
+            }) => write!(
+                f,
+                "DepositSingleTokenTypeExactAmountIn {{ in: {}, min_pool_tokens: {} }}",
+                source_token_amount, minimum_pool_token_amount
+            ),
+            Self::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => write!(
+                f,
+                "WithdrawSingleTokenTypeExactAmountOut {{ out: {}, max_pool_tokens: {} }}",
+                destination_token_amount, maximum_pool_token_amount
+            ),
+            Self::SwapExactOut(SwapExactOutInstruction {
+                amount_out,
+                maximum_amount_in,
+            }) => write!(
+                f,
+                "SwapExactOut {{ out: {}, max_in: {} }}",
+                amount_out, maximum_amount_in
+            ),
+            Self::SetFees(fees) => write!(f, "SetFees {{ {} }}", fees),
+            Self::Pause => write!(f, "Pause"),
+            Self::Unpause => write!(f, "Unpause"),
+            Self::WithdrawFees(amount) => write!(f, "WithdrawFees {{ amount: {} }}", amount),
+            Self::TransferStateOwnership { new_owner } => {
+                write!(f, "TransferStateOwnership {{ new_owner: {} }}", new_owner)
+            }
+            Self::AcceptStateOwnership => write!(f, "AcceptStateOwnership"),
+            Self::ClosePool => write!(f, "ClosePool"),
+            Self::InitializeProgramState {
+                initial_supply,
+                fees,
+                curve,
+            } => write!(
+                f,
+                "InitializeProgramState {{ initial_supply: {}, {}, curve: {:?} }}",
+                initial_supply, fees, curve.curve_type
+            ),
+            Self::UpdateProgramState {
+                initial_supply,
+                fees,
+                curve,
+            } => write!(
+                f,
+                "UpdateProgramState {{ initial_supply: {}, {}, curve: {:?} }}",
+                initial_supply, fees, curve.curve_type
+            ),
+            Self::Initialize2(Initialize2Instruction {
+                nonce,
+                initial_token_a,
+                initial_token_b,
+                min_pool_tokens,
+            }) => write!(
+                f,
+                "Initialize2 {{ nonce: {}, token_a: {}, token_b: {}, min_pool_tokens: {} }}",
+                nonce, initial_token_a, initial_token_b, min_pool_tokens
+            ),
+            Self::Swap2(SwapInstruction2 {
+                amount_in,
+                minimum_amount_out,
+                direction: None,
+            }) => write!(
+                f,
+                "Swap2 {{ in: {}, min_out: {} }}",
+                amount_in, minimum_amount_out
+            ),
+            Self::Swap2(SwapInstruction2 {
+                amount_in,
+                minimum_amount_out,
+                direction: Some(direction),
+            }) => write!(
+                f,
+                "Swap2 {{ in: {}, min_out: {}, direction: {:?} }}",
+                amount_in, minimum_amount_out, direction
+            ),
+        }
+    }
+}
+
+/// Wraps each instruction data struct into its [AmmInstruction] variant,
+/// and packs it stand-alone with its tag
+macro_rules! impl_instruction_data {
+    ($($data:ident => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$data> for AmmInstruction {
+                fn from(data: $data) -> Self {
+                    Self::$variant(data)
+                }
+            }
+
+            impl $data {
+                /// Packs the data into an [AmmInstruction] byte buffer, tag
+                /// included.
+                pub fn to_instruction_data(self) -> Vec<u8> {
+                    AmmInstruction::from(self).pack()
+                }
+            }
+        )*
+    };
+}
+
+impl_instruction_data! {
+    InitializeInstruction => Initialize,
+    SwapInstruction => Swap,
+    DepositInstruction => DepositAllTokenTypes,
+    WithdrawInstruction => WithdrawAllTokenTypes,
+    DepositSingleTokenTypeExactAmountIn => DepositSingleTokenTypeExactAmountIn,
+    WithdrawSingleTokenTypeExactAmountOut => WithdrawSingleTokenTypeExactAmountOut,
+    SwapExactOutInstruction => SwapExactOut,
+    Initialize2Instruction => Initialize2,
+    SwapInstruction2 => Swap2,
+}
+
+/// Creates an 'initialize' instruction. Fails with `InvalidProgramAddress`
+/// when `authority_pubkey` is not the swap authority derived with `nonce`,
+/// which would leave the pool unusable, see [initialize_find_nonce].
+pub fn initialize(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    amm_id: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    token_a_fee_pubkey: &Pubkey,
+    token_b_fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+
+    market_pubkey: &Pubkey,
+    dex_pubkey: &Pubkey,
+
+    nonce: u8,
+    swap_curve: Option<SwapCurve>,
+) -> Result<Instruction, ProgramError> {
+    if swap_authority(program_id, swap_pubkey, nonce)? != *authority_pubkey {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    let init_data = AmmInstruction::Initialize(InitializeInstruction {
+        nonce,
+        swap_curve,
+    });
+    let data = init_data.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, true),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*state_pubkey, false),
+        AccountMeta::new_readonly(*amm_id, false),
+        AccountMeta::new_readonly(*token_a_pubkey, false),
+        AccountMeta::new_readonly(*token_b_pubkey, false),
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*token_a_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_b_fee_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+
+        AccountMeta::new(*market_pubkey, false),
+
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*dex_pubkey, false),
+
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize' instruction, the swap authority and its nonce
+/// being derived with [find_swap_authority].
+pub fn initialize_find_nonce(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    amm_id: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    token_a_fee_pubkey: &Pubkey,
+    token_b_fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    market_pubkey: &Pubkey,
+    dex_pubkey: &Pubkey,
+    swap_curve: Option<SwapCurve>,
+) -> Result<Instruction, ProgramError> {
+    let (authority_pubkey, nonce) = find_swap_authority(program_id, swap_pubkey);
+    initialize(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        &authority_pubkey,
+        state_pubkey,
+        amm_id,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        token_a_fee_pubkey,
+        token_b_fee_pubkey,
+        destination_pubkey,
+        market_pubkey,
+        dex_pubkey,
+        nonce,
+        swap_curve,
+    )
+}
+
+/// Creates an 'initialize2' instruction, taking the accounts of
+/// [initialize].
+pub fn initialize2(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    amm_id: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    token_a_fee_pubkey: &Pubkey,
+    token_b_fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    market_pubkey: &Pubkey,
+    dex_pubkey: &Pubkey,
+    instruction: Initialize2Instruction,
+) -> Result<Instruction, ProgramError> {
+    let mut initialize_ix = initialize(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        state_pubkey,
+        amm_id,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        token_a_fee_pubkey,
+        token_b_fee_pubkey,
+        destination_pubkey,
+        market_pubkey,
+        dex_pubkey,
+        instruction.nonce,
+        None,
+    )?;
+    initialize_ix.data = AmmInstruction::Initialize2(instruction).pack();
+    Ok(initialize_ix)
+}
+
+/// Accounts of a pool, shared by every instruction trading against it.
+///
+/// Instructions only use the accounts listed in their documentation, the
+/// others are ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapPoolKeys {
+    /// Amm program id
+    pub program_id: Pubkey,
+    /// Token program id
+    pub token_program_id: Pubkey,
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// token_a Base Account of the pool
+    pub token_a_vault: Pubkey,
+    /// token_b Base Account of the pool
+    pub token_b_vault: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Fee token account, to receive trading and withdrawal fees
+    pub fee_account: Pubkey,
+    /// Fee wallet, to receive fees when swapping from SOL. Only set for pools
+    /// trading SOL
+    pub fee_wallet: Option<Pubkey>,
+}
+
+/// User accounts of a swap or a single-sided deposit or withdrawal
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserSwapKeys {
+    /// user transfer authority
+    pub transfer_authority: Pubkey,
+    /// SOURCE Account, amount is transferable by user transfer authority
+    pub source: Pubkey,
+    /// DESTINATION Account assigned to USER as the owner
+    pub destination: Pubkey,
+}
+
+/// User accounts of a deposit or withdrawal of both token types
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserLiquidityKeys {
+    /// user transfer authority
+    pub transfer_authority: Pubkey,
+    /// token_a user Account
+    pub token_a: Pubkey,
+    /// token_b user Account
+    pub token_b: Pubkey,
+    /// Pool token user Account
+    pub pool_token: Pubkey,
+}
+
+/// Side of the pool a single token deposit adds to, see
+/// [SwapPoolKeys::deposit_single_side_ix]
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DepositSide {
+    /// Deposit of token A
+    A,
+    /// Deposit of token B
+    B,
+}
+
+impl SwapPoolKeys {
+    /// The same pool with token A and B swapped, used to trade B for A
+    pub fn reversed(&self) -> Self {
+        Self {
+            token_a_vault: self.token_b_vault,
+            token_b_vault: self.token_a_vault,
+            ..self.clone()
+        }
+    }
+
+    /// Creates a 'swap' instruction trading token A for token B. Fails with
+    /// `ZeroTradingTokens` when `amount_in` is zero, and with
+    /// `UnprotectedSwap` when `minimum_amount_out` is zero, see
+    /// [SwapPoolKeys::swap_unprotected_ix].
+    pub fn swap_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: SwapInstruction,
+    ) -> Result<Instruction, ProgramError> {
+        check_swap_protection(&AmmInstruction::Swap(instruction))?;
+        self.swap_unprotected_ix(user, instruction)
+    }
+
+    /// Creates a 'swap' instruction like [SwapPoolKeys::swap_ix], accepting
+    /// a zero `minimum_amount_out`
+    pub fn swap_unprotected_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: SwapInstruction,
+    ) -> Result<Instruction, ProgramError> {
+        check_nonzero_amount(instruction.amount_in)?;
+        let data = AmmInstruction::Swap(instruction).pack();
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.swap_accounts(user),
+            data,
+        })
+    }
+
+    /// Creates a 'swap2' instruction trading token A for token B. Fails with
+    /// `ZeroTradingTokens` when `amount_in` is zero, and with
+    /// `UnprotectedSwap` when `minimum_amount_out` is zero, see
+    /// [SwapPoolKeys::swap2_unprotected_ix].
+    pub fn swap2_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: SwapInstruction2,
+    ) -> Result<Instruction, ProgramError> {
+        check_swap_protection(&AmmInstruction::Swap2(instruction))?;
+        self.swap2_unprotected_ix(user, instruction)
+    }
+
+    /// Creates a 'swap2' instruction like [SwapPoolKeys::swap2_ix], accepting
+    /// a zero `minimum_amount_out`
+    pub fn swap2_unprotected_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: SwapInstruction2,
+    ) -> Result<Instruction, ProgramError> {
+        if instruction.amount_in == 0 {
+            return Err(AmmError::ZeroTradingTokens.into());
+        }
+        let data = AmmInstruction::Swap2(instruction).pack();
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.swap_accounts(user),
+            data,
+        })
+    }
+
+    /// Creates a 'swap2' instruction trading in the given direction, the
+    /// vaults being ordered accordingly and the direction packed in the
+    /// instruction data. `user` holds the source and destination accounts of
+    /// that direction.
+    pub fn swap2_directed_ix(
+        &self,
+        user: &UserSwapKeys,
+        direction: TradeDirection,
+        amount_in: u128,
+        minimum_amount_out: u128,
+    ) -> Result<Instruction, ProgramError> {
+        let instruction = SwapInstruction2 {
+            amount_in,
+            minimum_amount_out,
+            direction: Some(direction),
+        };
+        match direction {
+            TradeDirection::AtoB => self.swap2_ix(user, instruction),
+            TradeDirection::BtoA => self.reversed().swap2_ix(user, instruction),
+        }
+    }
+
+    /// Creates a 'swap_exact_out' instruction trading token A for token B.
+    /// Fails with `UnprotectedSwap` when `maximum_amount_in` is `u64::MAX`,
+    /// see [SwapPoolKeys::swap_exact_out_unprotected_ix].
+    pub fn swap_exact_out_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: SwapExactOutInstruction,
+    ) -> Result<Instruction, ProgramError> {
+        check_swap_protection(&AmmInstruction::SwapExactOut(instruction))?;
+        self.swap_exact_out_unprotected_ix(user, instruction)
+    }
+
+    /// Creates a 'swap_exact_out' instruction like
+    /// [SwapPoolKeys::swap_exact_out_ix], accepting a `maximum_amount_in` of
+    /// `u64::MAX`
+    pub fn swap_exact_out_unprotected_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: SwapExactOutInstruction,
+    ) -> Result<Instruction, ProgramError> {
+        let data = AmmInstruction::SwapExactOut(instruction).pack();
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.swap_accounts(user),
+            data,
+        })
+    }
+
+    fn swap_accounts(&self, user: &UserSwapKeys) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.swap, false),
+
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(user.transfer_authority, true),
+            AccountMeta::new_readonly(self.state, false),
+
+            AccountMeta::new(user.source, false),
+            AccountMeta::new(self.token_a_vault, false),
+            AccountMeta::new(self.token_b_vault, false),
+            AccountMeta::new(user.destination, false),
+
+            AccountMeta::new(self.pool_mint, false),
+
+            AccountMeta::new(self.fee_account, false),
+        ];
+        match self.fee_wallet {
+            Some(fee_wallet) => {
+                accounts.push(AccountMeta::new(fee_wallet, false));
+                accounts.push(AccountMeta::new_readonly(self.token_program_id, false));
+                accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+            }
+            None => accounts.push(AccountMeta::new_readonly(self.token_program_id, false)),
+        }
+        accounts
+    }
+
+    /// Creates a 'deposit_all_token_types' instruction.
+    pub fn deposit_all_token_types_ix(
+        &self,
+        user: &UserLiquidityKeys,
+        instruction: DepositInstruction,
+    ) -> Result<Instruction, ProgramError> {
+        let data = AmmInstruction::DepositAllTokenTypes(instruction).pack();
+
+        let accounts = vec![
+            AccountMeta::new_readonly(self.swap, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(user.transfer_authority, true),
+            AccountMeta::new_readonly(self.state, false),
+            AccountMeta::new(user.token_a, false),
+            AccountMeta::new(user.token_b, false),
+            AccountMeta::new(self.token_a_vault, false),
+            AccountMeta::new(self.token_b_vault, false),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(user.pool_token, false),
+            AccountMeta::new_readonly(self.token_program_id, false),
+        ];
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+
+    /// Creates a 'withdraw_all_token_types' instruction. Fails with
+    /// `ZeroTradingTokens` when `pool_token_amount` is zero.
+    pub fn withdraw_all_token_types_ix(
+        &self,
+        user: &UserLiquidityKeys,
+        instruction: WithdrawInstruction,
+    ) -> Result<Instruction, ProgramError> {
+        check_nonzero_amount(instruction.pool_token_amount)?;
+        let data = AmmInstruction::WithdrawAllTokenTypes(instruction).pack();
+
+        let accounts = vec![
+            AccountMeta::new_readonly(self.swap, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(user.transfer_authority, true),
+            AccountMeta::new_readonly(self.state, false),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(user.pool_token, false),
+            AccountMeta::new(self.token_a_vault, false),
+            AccountMeta::new(self.token_b_vault, false),
+            AccountMeta::new(user.token_a, false),
+            AccountMeta::new(user.token_b, false),
+            AccountMeta::new(self.fee_account, false),
+            AccountMeta::new_readonly(self.token_program_id, false),
+        ];
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+
+    /// Creates a 'deposit_single_token_type_exact_amount_in' instruction, the
+    /// user's SOURCE being a token account and DESTINATION a pool token account.
+    /// Fails with `ZeroTradingTokens` when `source_token_amount` is zero.
+    pub fn deposit_single_token_type_exact_amount_in_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: DepositSingleTokenTypeExactAmountIn,
+    ) -> Result<Instruction, ProgramError> {
+        check_nonzero_amount(instruction.source_token_amount)?;
+        let data = AmmInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
+
+        let accounts = vec![
+            AccountMeta::new_readonly(self.swap, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(user.transfer_authority, true),
+            AccountMeta::new(user.source, false),
+            AccountMeta::new(self.token_a_vault, false),
+            AccountMeta::new(self.token_b_vault, false),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(user.destination, false),
+            AccountMeta::new_readonly(self.token_program_id, false),
+        ];
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+
+    /// Creates a 'deposit_single_token_type_exact_amount_in' instruction
+    /// depositing the token of `side`, checking that the accounts are
+    /// distinct, see [check_distinct_accounts].
+    ///
+    /// The vaults are ordered A then B as in the `swap` state, whatever their
+    /// order in the pool keys, failing with `IncorrectSwapAccount` when the
+    /// pool keys hold other vaults. `source_mint` is the mint of the user's
+    /// SOURCE account, the deposit failing with `IncorrectDepositSide` when
+    /// it is not the mint of the `side` vault.
+    pub fn deposit_single_side_ix(
+        &self,
+        user: &UserSwapKeys,
+        source_mint: &Pubkey,
+        side: DepositSide,
+        swap: &dyn AmmStatus,
+        instruction: DepositSingleTokenTypeExactAmountIn,
+    ) -> Result<Instruction, ProgramError> {
+        let vaults = (self.token_a_vault, self.token_b_vault);
+        let (token_a, token_b) = (*swap.token_a_account(), *swap.token_b_account());
+        if vaults != (token_a, token_b) && vaults != (token_b, token_a) {
+            return Err(AmmError::IncorrectSwapAccount.into());
+        }
+        let side_mint = match side {
+            DepositSide::A => swap.token_a_mint(),
+            DepositSide::B => swap.token_b_mint(),
+        };
+        if source_mint != side_mint {
+            return Err(AmmError::IncorrectDepositSide.into());
+        }
+        let pool = Self {
+            token_a_vault: token_a,
+            token_b_vault: token_b,
+            ..self.clone()
+        };
+        check_distinct_accounts(
+            &pool.token_a_vault,
+            &pool.token_b_vault,
+            &pool.pool_mint,
+            &[&user.source, &user.destination],
+        )?;
+        pool.deposit_single_token_type_exact_amount_in_ix(user, instruction)
+    }
+
+    /// Creates a 'withdraw_single_token_type_exact_amount_out' instruction,
+    /// the user's SOURCE being a pool token account and DESTINATION a token
+    /// account. Fails with `ZeroTradingTokens` when `destination_token_amount`
+    /// is zero.
+    pub fn withdraw_single_token_type_exact_amount_out_ix(
+        &self,
+        user: &UserSwapKeys,
+        instruction: WithdrawSingleTokenTypeExactAmountOut,
+    ) -> Result<Instruction, ProgramError> {
+        check_nonzero_amount(instruction.destination_token_amount)?;
+        let data = AmmInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+
+        let accounts = vec![
+            AccountMeta::new_readonly(self.swap, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(user.transfer_authority, true),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(user.source, false),
+            AccountMeta::new(self.token_a_vault, false),
+            AccountMeta::new(self.token_b_vault, false),
+            AccountMeta::new(user.destination, false),
+            AccountMeta::new(self.fee_account, false),
+            AccountMeta::new_readonly(self.token_program_id, false),
+        ];
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+}
+
+/// Builder of a 'swap' instruction, for callers setting optional accounts
+/// one at a time rather than through the positional arguments of [swap].
+///
+/// The pool and user accounts are required, `build` failing with
+/// `NotEnoughAccountKeys` when either is missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapIxBuilder {
+    instruction: SwapInstruction,
+    pool: Option<SwapPoolKeys>,
+    user: Option<UserSwapKeys>,
+    token_program_id: Option<Pubkey>,
+    host_fee: Option<Pubkey>,
+    allow_unprotected: bool,
+}
+
+impl SwapIxBuilder {
+    /// Starts a 'swap' instruction with the given amounts
+    pub fn new(instruction: SwapInstruction) -> Self {
+        Self {
+            instruction,
+            pool: None,
+            user: None,
+            token_program_id: None,
+            host_fee: None,
+            allow_unprotected: false,
+        }
+    }
+
+    /// Pool accounts, trading token A for token B
+    pub fn pool(mut self, pool: SwapPoolKeys) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// User accounts
+    pub fn user(mut self, user: UserSwapKeys) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Host fee account, appended after the pool accounts
+    pub fn host_fee(mut self, host_fee: Option<Pubkey>) -> Self {
+        self.host_fee = host_fee;
+        self
+    }
+
+    /// Token program, overriding the one of the pool, e.g. for Token-2022
+    pub fn token_program(mut self, token_program_id: Pubkey) -> Self {
+        self.token_program_id = Some(token_program_id);
+        self
+    }
+
+    /// Accepts a zero `minimum_amount_out`, as [swap_unprotected] does
+    pub fn allow_unprotected(mut self) -> Self {
+        self.allow_unprotected = true;
+        self
+    }
+
+    /// Creates the instruction, checking that the accounts are distinct and
+    /// that the swap is protected as [swap] does
+    pub fn build(self) -> Result<Instruction, ProgramError> {
+        if !self.allow_unprotected {
+            check_swap_protection(&AmmInstruction::Swap(self.instruction))?;
+        }
+        let mut pool = self.pool.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let user = self.user.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if let Some(token_program_id) = self.token_program_id {
+            pool.token_program_id = token_program_id;
+        }
+        check_distinct_accounts(
+            &pool.token_a_vault,
+            &pool.token_b_vault,
+            &pool.pool_mint,
+            &[&user.source, &user.destination],
+        )?;
+        let mut swap_ix = pool.swap_unprotected_ix(&user, self.instruction)?;
+        if let Some(host_fee) = self.host_fee {
+            swap_ix.accounts.push(AccountMeta::new(host_fee, false));
+        }
+        Ok(swap_ix)
+    }
+}
+
+/// Builder of a 'deposit_all_token_types' instruction, see [SwapIxBuilder].
+///
+/// The pool and user accounts are required, `build` failing with
+/// `NotEnoughAccountKeys` when either is missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositIxBuilder {
+    instruction: DepositInstruction,
+    pool: Option<SwapPoolKeys>,
+    user: Option<UserLiquidityKeys>,
+    token_program_id: Option<Pubkey>,
+}
+
+impl DepositIxBuilder {
+    /// Starts a 'deposit_all_token_types' instruction with the given amounts
+    pub fn new(instruction: DepositInstruction) -> Self {
+        Self {
+            instruction,
+            pool: None,
+            user: None,
+            token_program_id: None,
+        }
+    }
+
+    /// Pool accounts
+    pub fn pool(mut self, pool: SwapPoolKeys) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// User accounts, `pool_token` receiving the minted pool tokens
+    pub fn user(mut self, user: UserLiquidityKeys) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Token program, overriding the one of the pool, e.g. for Token-2022
+    pub fn token_program(mut self, token_program_id: Pubkey) -> Self {
+        self.token_program_id = Some(token_program_id);
+        self
+    }
+
+    /// Creates the instruction, checking that the accounts are distinct as
+    /// [deposit_all_token_types] does
+    pub fn build(self) -> Result<Instruction, ProgramError> {
+        let mut pool = self.pool.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let user = self.user.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if let Some(token_program_id) = self.token_program_id {
+            pool.token_program_id = token_program_id;
+        }
+        check_distinct_accounts(
+            &pool.token_a_vault,
+            &pool.token_b_vault,
+            &pool.pool_mint,
+            &[&user.token_a, &user.token_b, &user.pool_token],
+        )?;
+        pool.deposit_all_token_types_ix(&user, self.instruction)
+    }
+}
+
+/// Rejects a zero primary amount, for which an instruction would only waste
+/// a transaction
+fn check_nonzero_amount(amount: u64) -> Result<(), AmmError> {
+    if amount == 0 {
+        return Err(AmmError::ZeroTradingTokens);
+    }
+    Ok(())
+}
+
+/// Rejects a swap accepting any price, open to sandwiching: a zero
+/// `minimum_amount_out`, or a `maximum_amount_in` of `u64::MAX` for a
+/// `SwapExactOut`. Shared by every swap builder but the `*_unprotected` ones
+/// and [swap_unchecked]
+fn check_swap_protection(instruction: &AmmInstruction) -> Result<(), AmmError> {
+    let unprotected = match instruction {
+        AmmInstruction::Swap(swap) => swap.is_unprotected(),
+        AmmInstruction::Swap2(swap) => swap.is_unprotected(),
+        AmmInstruction::SwapExactOut(swap) => swap.is_unprotected(),
+        _ => false,
+    };
+    if unprotected {
+        return Err(AmmError::UnprotectedSwap);
+    }
+    Ok(())
+}
+
+/// Makes `multisig` a non-signer account followed by its `signers`, as
+/// spl-token does. Without signers `multisig` keeps signing itself
+fn append_multisig_signers(instruction: &mut Instruction, multisig: &Pubkey, signers: &[&Pubkey]) {
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == *multisig {
+            account.is_signer = signers.is_empty();
+        }
+    }
+    instruction.accounts.extend(
+        signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(**signer, true)),
+    );
+}
+
+/// Client-side sanity check of the accounts of a pool instruction, whose
+/// mistakes otherwise only surface as opaque on-chain errors.
+///
+/// Fails with `RepeatedMint` when both pool vaults are the same account, and
+/// with `InvalidInput` when the pool mint is one of the vaults or a user
+/// account is one of the vaults or the pool mint.
+pub fn check_distinct_accounts(
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    user_pubkeys: &[&Pubkey],
+) -> Result<(), AmmError> {
+    if swap_token_a_pubkey == swap_token_b_pubkey {
+        return Err(AmmError::RepeatedMint);
+    }
+    let pool_pubkeys = [swap_token_a_pubkey, swap_token_b_pubkey, pool_mint_pubkey];
+    if pool_mint_pubkey == swap_token_a_pubkey
+        || pool_mint_pubkey == swap_token_b_pubkey
+        || user_pubkeys.iter().any(|user| pool_pubkeys.contains(user))
+    {
+        return Err(AmmError::InvalidInput);
+    }
+    Ok(())
+}
+
+/// Creates a 'deposit_all_token_types' instruction, checking that the
+/// accounts are distinct, see [check_distinct_accounts].
+pub fn deposit_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositInstruction,
+) -> Result<Instruction, ProgramError> {
+    check_distinct_accounts(
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        pool_mint_pubkey,
+        &[
+            deposit_token_a_pubkey,
+            deposit_token_b_pubkey,
+            destination_pubkey,
+        ],
+    )?;
+    deposit_all_token_types_unchecked(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        deposit_token_a_pubkey,
+        deposit_token_b_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        pool_mint_pubkey,
+        destination_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'deposit_all_token_types' instruction like
+/// [deposit_all_token_types] for a user transfer authority that is an
+/// spl-token multisig, `signers` being the multisig signers signing the
+/// transaction
+pub fn deposit_all_token_types_multisig(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    signers: &[&Pubkey],
+    state_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositInstruction,
+) -> Result<Instruction, ProgramError> {
+    let mut deposit_ix = deposit_all_token_types(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        deposit_token_a_pubkey,
+        deposit_token_b_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        pool_mint_pubkey,
+        destination_pubkey,
+        instruction,
+    )?;
+    append_multisig_signers(&mut deposit_ix, user_transfer_authority_pubkey, signers);
+    Ok(deposit_ix)
+}
+
+/// Creates a 'deposit_all_token_types' instruction without checking that
+/// the accounts are distinct.
+pub fn deposit_all_token_types_unchecked(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositInstruction,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        state: *state_pubkey,
+        token_a_vault: *swap_token_a_pubkey,
+        token_b_vault: *swap_token_b_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        ..SwapPoolKeys::default()
+    };
+    let user = UserLiquidityKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        token_a: *deposit_token_a_pubkey,
+        token_b: *deposit_token_b_pubkey,
+        pool_token: *destination_pubkey,
+    };
+    pool.deposit_all_token_types_ix(&user, instruction)
+}
+
+/// Creates a 'withdraw_all_token_types' instruction, checking that the
+/// accounts are distinct, see [check_distinct_accounts].
+///
+/// Fails with `UnprotectedWithdraw` when both minimums are zero: use
+/// [withdraw_all_token_types_unprotected] to accept any output. A zero
+/// minimum on a single side still protects the withdrawal through the other
+/// one.
+pub fn withdraw_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawInstruction,
+) -> Result<Instruction, ProgramError> {
+    if instruction.is_unprotected() {
+        return Err(AmmError::UnprotectedWithdraw.into());
+    }
+    withdraw_all_token_types_unprotected(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        pool_mint_pubkey,
+        source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_token_a_pubkey,
+        destination_token_b_pubkey,
+        pool_fee_account_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'withdraw_all_token_types' instruction like
+/// [withdraw_all_token_types], accepting two zero minimums.
+pub fn withdraw_all_token_types_unprotected(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawInstruction,
+) -> Result<Instruction, ProgramError> {
+    check_distinct_accounts(
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        pool_mint_pubkey,
+        &[
+            source_pubkey,
+            destination_token_a_pubkey,
+            destination_token_b_pubkey,
+        ],
+    )?;
+    withdraw_all_token_types_unchecked(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        pool_mint_pubkey,
+        source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_token_a_pubkey,
+        destination_token_b_pubkey,
+        pool_fee_account_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'withdraw_all_token_types' instruction like
+/// [withdraw_all_token_types] for a user transfer authority that is an
+/// spl-token multisig, `signers` being the multisig signers signing the
+/// transaction
+pub fn withdraw_all_token_types_multisig(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    signers: &[&Pubkey],
+    state_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawInstruction,
+) -> Result<Instruction, ProgramError> {
+    let mut withdraw_ix = withdraw_all_token_types(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        pool_mint_pubkey,
+        source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_token_a_pubkey,
+        destination_token_b_pubkey,
+        pool_fee_account_pubkey,
+        instruction,
+    )?;
+    append_multisig_signers(&mut withdraw_ix, user_transfer_authority_pubkey, signers);
+    Ok(withdraw_ix)
+}
+
+/// Creates a 'withdraw_all_token_types' instruction like
+/// [withdraw_all_token_types_multisig], accepting two zero minimums.
+pub fn withdraw_all_token_types_multisig_unprotected(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    signers: &[&Pubkey],
+    state_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawInstruction,
+) -> Result<Instruction, ProgramError> {
+    let mut withdraw_ix = withdraw_all_token_types_unprotected(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        pool_mint_pubkey,
+        source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_token_a_pubkey,
+        destination_token_b_pubkey,
+        pool_fee_account_pubkey,
+        instruction,
+    )?;
+    append_multisig_signers(&mut withdraw_ix, user_transfer_authority_pubkey, signers);
+    Ok(withdraw_ix)
+}
+
+/// Creates a 'withdraw_all_token_types' instruction without checking that
+/// the accounts are distinct nor that the withdrawal is protected.
+pub fn withdraw_all_token_types_unchecked(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawInstruction,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        state: *state_pubkey,
+        token_a_vault: *swap_token_a_pubkey,
+        token_b_vault: *swap_token_b_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        fee_account: *pool_fee_account_pubkey,
+        ..SwapPoolKeys::default()
+    };
+    let user = UserLiquidityKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        token_a: *destination_token_a_pubkey,
+        token_b: *destination_token_b_pubkey,
+        pool_token: *source_pubkey,
+    };
+    pool.withdraw_all_token_types_ix(&user, instruction)
+}
+
+/// Creates a 'deposit_single_token_type_exact_amount_in' instruction.
+pub fn deposit_single_token_type_exact_amount_in(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        token_a_vault: *swap_token_a_pubkey,
+        token_b_vault: *swap_token_b_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        ..SwapPoolKeys::default()
+    };
+    let user = UserSwapKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        source: *source_token_pubkey,
+        destination: *destination_pubkey,
+    };
+    pool.deposit_single_token_type_exact_amount_in_ix(&user, instruction)
+}
+
+/// Creates a 'deposit_single_token_type_exact_amount_in' instruction
+/// depositing the token of `side`, see [SwapPoolKeys::deposit_single_side_ix]
+/// for the checks made against the `swap_state` and `source_mint`.
+pub fn deposit_single_token_type_exact_amount_in_side(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    source_mint_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    side: DepositSide,
+    swap_state: &dyn AmmStatus,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        token_a_vault: *swap_token_a_pubkey,
+        token_b_vault: *swap_token_b_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        ..SwapPoolKeys::default()
+    };
+    let user = UserSwapKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        source: *source_token_pubkey,
+        destination: *destination_pubkey,
+    };
+    pool.deposit_single_side_ix(&user, source_mint_pubkey, side, swap_state, instruction)
+}
+
+/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction.
+///
+/// Fails with `UnprotectedWithdraw` when `maximum_pool_token_amount` is
+/// `u64::MAX`: use [withdraw_single_token_type_exact_amount_out_unprotected]
+/// to burn any amount of pool tokens.
+pub fn withdraw_single_token_type_exact_amount_out(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    if instruction.is_unprotected() {
+        return Err(AmmError::UnprotectedWithdraw.into());
+    }
+    withdraw_single_token_type_exact_amount_out_unprotected(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        pool_mint_pubkey,
+        pool_token_source_pubkey,
+        swap_token_a_pubkey,
+        swap_token_b_pubkey,
+        destination_pubkey,
+        pool_fee_account_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction like
+/// [withdraw_single_token_type_exact_amount_out], accepting a
+/// `maximum_pool_token_amount` of `u64::MAX`.
+pub fn withdraw_single_token_type_exact_amount_out_unprotected(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_fee_account_pubkey: &Pubkey,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        token_a_vault: *swap_token_a_pubkey,
+        token_b_vault: *swap_token_b_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        fee_account: *pool_fee_account_pubkey,
+        ..SwapPoolKeys::default()
+    };
+    let user = UserSwapKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        source: *pool_token_source_pubkey,
+        destination: *destination_pubkey,
+    };
+    pool.withdraw_single_token_type_exact_amount_out_ix(&user, instruction)
+}
+
+/// Creates a 'swap' instruction, checking that the accounts are distinct,
+/// see [check_distinct_accounts].
+///
+/// Fails with `UnprotectedSwap` when `minimum_amount_out` is zero, such a
+/// swap being open to sandwiching: use [swap_unprotected] to accept any
+/// output.
+pub fn swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    fee_wallet_pubkey: Option<&Pubkey>,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: SwapInstruction,
+) -> Result<Instruction, ProgramError> {
+    check_swap_protection(&AmmInstruction::Swap(instruction))?;
+    swap_unprotected(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        source_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        fee_account_pubkey,
+        fee_wallet_pubkey,
+        host_fee_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'swap' instruction like [swap] for a user transfer authority
+/// that is an spl-token multisig, `signers` being the multisig signers
+/// signing the transaction. They follow every other account, the host fee
+/// account included.
+pub fn swap_multisig(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    signers: &[&Pubkey],
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    fee_wallet_pubkey: Option<&Pubkey>,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: SwapInstruction,
+) -> Result<Instruction, ProgramError> {
+    let mut swap_ix = swap(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        source_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        fee_account_pubkey,
+        fee_wallet_pubkey,
+        host_fee_pubkey,
+        instruction,
+    )?;
+    append_multisig_signers(&mut swap_ix, user_transfer_authority_pubkey, signers);
+    Ok(swap_ix)
+}
+
+/// Creates a 'swap' instruction like [swap], accepting a zero
+/// `minimum_amount_out`, for callers taking any output on purpose, e.g. when
+/// cleaning up dust. The accounts are still checked to be distinct.
+pub fn swap_unprotected(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    fee_wallet_pubkey: Option<&Pubkey>,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: SwapInstruction,
+) -> Result<Instruction, ProgramError> {
+    check_distinct_accounts(
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        pool_mint_pubkey,
+        &[source_pubkey, destination_pubkey],
+    )?;
+    swap_unchecked(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        state_pubkey,
+        source_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        fee_account_pubkey,
+        fee_wallet_pubkey,
+        host_fee_pubkey,
+        instruction,
+    )
+}
+
+/// Creates a 'swap' instruction without checking that the accounts are
+/// distinct nor that the swap is protected.
+pub fn swap_unchecked(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    fee_wallet_pubkey: Option<&Pubkey>,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: SwapInstruction,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        state: *state_pubkey,
+        token_a_vault: *swap_source_pubkey,
+        token_b_vault: *swap_destination_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        fee_account: *fee_account_pubkey,
+        fee_wallet: fee_wallet_pubkey.copied(),
+    };
+    let user = UserSwapKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        source: *source_pubkey,
+        destination: *destination_pubkey,
+    };
+    let mut swap_ix = pool.swap_unprotected_ix(&user, instruction)?;
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        swap_ix.accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+    Ok(swap_ix)
+}
+
+/// Creates a 'swap_exact_out' instruction. Fails with `UnprotectedSwap` when
+/// `maximum_amount_in` is `u64::MAX`, as [SwapPoolKeys::swap_exact_out_ix]
+/// does.
+pub fn swap_exact_out(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    fee_wallet_pubkey: Option<&Pubkey>,
+    instruction: SwapExactOutInstruction,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        state: *state_pubkey,
+        token_a_vault: *swap_source_pubkey,
+        token_b_vault: *swap_destination_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        fee_account: *fee_account_pubkey,
+        fee_wallet: fee_wallet_pubkey.copied(),
+    };
+    let user = UserSwapKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        source: *source_pubkey,
+        destination: *destination_pubkey,
+    };
+    pool.swap_exact_out_ix(&user, instruction)
+}
+
+/// Creates a 'swap2' instruction. Fails with `UnprotectedSwap` when
+/// `minimum_amount_out` is zero, as [SwapPoolKeys::swap2_ix] does.
+pub fn swap2(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    fee_wallet_pubkey: Option<&Pubkey>,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: SwapInstruction2,
+) -> Result<Instruction, ProgramError> {
+    let pool = SwapPoolKeys {
+        program_id: *program_id,
+        token_program_id: *token_program_id,
+        swap: *swap_pubkey,
+        authority: *authority_pubkey,
+        state: *state_pubkey,
+        token_a_vault: *swap_source_pubkey,
+        token_b_vault: *swap_destination_pubkey,
+        pool_mint: *pool_mint_pubkey,
+        fee_account: *fee_account_pubkey,
+        fee_wallet: fee_wallet_pubkey.copied(),
+    };
+    let user = UserSwapKeys {
+        transfer_authority: *user_transfer_authority_pubkey,
+        source: *source_pubkey,
+        destination: *destination_pubkey,
+    };
+    let mut swap_ix = pool.swap2_ix(&user, instruction)?;
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        swap_ix.accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+    Ok(swap_ix)
+}
+
+/// Creates the instructions swapping `lamports` of native SOL for token B of
+/// `pool`, token A being wrapped SOL.
+///
+/// The wrapped SOL associated account of `wallet` is created if needed, with
+/// `payer` paying its rent, then funded by `wallet` and synced, used as the
+/// swap source, and finally closed, returning its lamports to `wallet`.
+///
+/// Checks the accounts and the minimum output as [swap] does.
+pub fn swap_with_sol_input(
+    pool: &SwapPoolKeys,
+    payer: &Pubkey,
+    wallet: &Pubkey,
+    destination_pubkey: &Pubkey,
+    lamports: u64,
+    minimum_amount_out: u64,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let instruction = SwapInstruction {
+        amount_in: lamports,
+        minimum_amount_out,
+    };
+    check_swap_protection(&AmmInstruction::Swap(instruction))?;
+    let wrapped_sol = get_associated_token_address(wallet, &native_mint::id());
+    check_distinct_accounts(
+        &pool.token_a_vault,
+        &pool.token_b_vault,
+        &pool.pool_mint,
+        &[&wrapped_sol, destination_pubkey],
+    )?;
+    let user = UserSwapKeys {
+        transfer_authority: *wallet,
+        source: wrapped_sol,
+        destination: *destination_pubkey,
+    };
+    Ok(vec![
+        create_associated_token_account_idempotent(
+            payer,
+            wallet,
+            &native_mint::id(),
+            &pool.token_program_id,
+        ),
+        system_instruction::transfer(wallet, &wrapped_sol, lamports),
+        token_instruction::sync_native(&pool.token_program_id, &wrapped_sol)?,
+        pool.swap_ix(&user, instruction)?,
+        token_instruction::close_account(
+            &pool.token_program_id,
+            &wrapped_sol,
+            wallet,
+            wallet,
+            &[],
+        )?,
+    ])
+}
+
+/// Creates the instructions swapping `amount_in` of token A of `pool` for
+/// native SOL, token B being wrapped SOL.
+///
+/// The wrapped SOL associated account of `wallet` is created if needed, with
+/// `payer` paying its rent, used as the swap destination, then closed,
+/// unwrapping the SOL to `wallet`.
+///
+/// Checks the accounts and the minimum output as [swap] does.
+pub fn swap_with_sol_output(
+    pool: &SwapPoolKeys,
+    payer: &Pubkey,
+    wallet: &Pubkey,
+    source_pubkey: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let instruction = SwapInstruction {
+        amount_in,
+        minimum_amount_out,
+    };
+    check_swap_protection(&AmmInstruction::Swap(instruction))?;
+    let wrapped_sol = get_associated_token_address(wallet, &native_mint::id());
+    check_distinct_accounts(
+        &pool.token_a_vault,
+        &pool.token_b_vault,
+        &pool.pool_mint,
+        &[source_pubkey, &wrapped_sol],
+    )?;
+    let user = UserSwapKeys {
+        transfer_authority: *wallet,
+        source: *source_pubkey,
+        destination: wrapped_sol,
+    };
+    Ok(vec![
+        create_associated_token_account_idempotent(
+            payer,
+            wallet,
+            &native_mint::id(),
+            &pool.token_program_id,
+        ),
+        pool.swap_ix(&user, instruction)?,
+        token_instruction::close_account(
+            &pool.token_program_id,
+            &wrapped_sol,
+            wallet,
+            wallet,
+            &[],
+        )?,
+    ])
+}
+
+/// Creates the instructions swapping from `source_pubkey` of `owner` through
+/// a new ephemeral user transfer authority, approved for exactly the
+/// `amount_in` of `instruction` before the swap and revoked right after it,
+/// so that `owner` never delegates more than the swapped amount.
+///
+/// Checks the minimum output as [swap] does. The returned keypair must sign
+/// the transaction together with `owner`.
+#[cfg(feature = "offchain")]
+pub fn swap_with_ephemeral_authority(
+    pool: &SwapPoolKeys,
+    owner: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: SwapInstruction,
+) -> Result<(Vec<Instruction>, Keypair), ProgramError> {
+    check_nonzero_amount(instruction.amount_in)?;
+    check_swap_protection(&AmmInstruction::Swap(instruction))?;
+    check_distinct_accounts(
+        &pool.token_a_vault,
+        &pool.token_b_vault,
+        &pool.pool_mint,
+        &[source_pubkey, destination_pubkey],
+    )?;
+    let transfer_authority = Keypair::new();
+    let user = UserSwapKeys {
+        transfer_authority: transfer_authority.pubkey(),
+        source: *source_pubkey,
+        destination: *destination_pubkey,
+    };
+    let instructions = vec![
+        token_instruction::approve(
+            &pool.token_program_id,
+            source_pubkey,
+            &user.transfer_authority,
+            owner,
+            &[],
+            instruction.amount_in,
+        )?,
+        pool.swap_ix(&user, instruction)?,
+        token_instruction::revoke(&pool.token_program_id, source_pubkey, owner, &[])?,
+    ];
+    Ok((instructions, transfer_authority))
+}
+
+/// Static accounts of `pool`, in the canonical order of an address lookup
+/// table holding them. The user accounts of a swap stay inline in the
+/// transaction, see [partition_lookup_accounts].
+#[cfg(feature = "offchain")]
+pub fn swap_pool_lut_addresses(pool: &SwapPoolKeys) -> Vec<Pubkey> {
+    let mut addresses = vec![
+        pool.swap,
+        pool.authority,
+        pool.state,
+        pool.token_a_vault,
+        pool.token_b_vault,
+        pool.pool_mint,
+        pool.fee_account,
+        pool.token_program_id,
+    ];
+    if let Some(fee_wallet) = pool.fee_wallet {
+        addresses.push(fee_wallet);
+        addresses.push(system_program::id());
+    }
+    addresses
+}
+
+/// Addresses of an address lookup table serving all of `instructions`, e.g.
+/// a swap followed by the harvest of a farm: their accounts other than
+/// signers, each once in order of first use. Program ids stay static keys of
+/// a v0 message and are left out, unless also passed as an account.
+#[cfg(feature = "offchain")]
+pub fn lookup_table_addresses(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut addresses: Vec<Pubkey> = vec![];
+    let accounts = instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter());
+    for account in accounts.filter(|account| !account.is_signer) {
+        if !addresses.contains(&account.pubkey) {
+            addresses.push(account.pubkey);
+        }
+    }
+    addresses
+}
+
+/// Accounts of an instruction split between an address lookup table and the
+/// transaction, as in the `MessageAddressTableLookup` of a v0 message
+#[cfg(feature = "offchain")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LookupPartition {
+    /// Indexes in the table of the writable accounts it holds
+    pub writable_indexes: Vec<u8>,
+    /// Indexes in the table of the read-only accounts it holds
+    pub readonly_indexes: Vec<u8>,
+    /// Accounts passed inline: signers and accounts missing from the table
+    pub inline_accounts: Vec<AccountMeta>,
+}
+
+/// Splits the accounts of `instruction` between the lookup table whose
+/// addresses are `lut_addresses`, e.g. [swap_pool_lut_addresses], and the
+/// transaction. Signers are always passed inline.
+///
+/// Fails with `InvalidInput` when an account is found past the 256 entries
+/// a lookup table can index.
+#[cfg(feature = "offchain")]
+pub fn partition_lookup_accounts(
+    instruction: &Instruction,
+    lut_addresses: &[Pubkey],
+) -> Result<LookupPartition, ProgramError> {
+    let mut partition = LookupPartition::default();
+    for account in &instruction.accounts {
+        let index = match lut_addresses.iter().position(|key| *key == account.pubkey) {
+            Some(index) if !account.is_signer => index,
+            _ => {
+                partition.inline_accounts.push(account.clone());
+                continue;
+            }
+        };
+        let index = u8::try_from(index).map_err(|_| AmmError::InvalidInput)?;
+        if account.is_writable {
+            partition.writable_indexes.push(index);
+        } else {
+            partition.readonly_indexes.push(index);
+        }
+    }
+    Ok(partition)
+}
+
+/// Prepends to `instructions` the `SetComputeUnitLimit` instruction of the
+/// compute budget program, with the sum of their estimates increased by
+/// `margin_pct` percent and capped at [MAX_COMPUTE_UNIT_LIMIT].
+///
+/// Instructions of `program_id` are estimated with
+/// [AmmInstruction::compute_unit_estimate], other ones with
+/// [COMPUTE_UNITS_OTHER].
+pub fn with_compute_budget(
+    instructions: Vec<Instruction>,
+    program_id: &Pubkey,
+    margin_pct: u8,
+) -> Vec<Instruction> {
+    let units: u64 = instructions
+        .iter()
+        .map(|instruction| {
+            let units = if instruction.program_id == *program_id {
+                AmmInstruction::unpack(&instruction.data).map_or(COMPUTE_UNITS_OTHER, |unpacked| {
+                    unpacked.compute_unit_estimate()
+                })
+            } else {
+                COMPUTE_UNITS_OTHER
+            };
+            u64::from(units)
+        })
+        .sum();
+    let units = units * (100 + u64::from(margin_pct)) / 100;
+    let units = units.min(u64::from(MAX_COMPUTE_UNIT_LIMIT)) as u32;
+    // SetComputeUnitLimit, tag 2 of ComputeBudgetInstruction
+    let mut data = vec![2];
+    data.extend_from_slice(&units.to_le_bytes());
+    let mut with_budget = vec![Instruction {
+        program_id: crate::compute_budget::id(),
+        accounts: vec![],
+        data,
+    }];
+    with_budget.extend(instructions);
+    with_budget
+}
+
+/// Creates a 'set_fees' instruction.
+pub fn set_fees(
+    program_id: &Pubkey,
+    state_pubkey: &Pubkey,
+    state_owner_pubkey: &Pubkey,
+    fees: Fees,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SetFees(fees).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*state_pubkey, false),
+        AccountMeta::new_readonly(*state_owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'pause' instruction.
+pub fn pause(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    state_owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::Pause.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*state_pubkey, false),
+        AccountMeta::new_readonly(*state_owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'unpause' instruction.
+pub fn unpause(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    state_owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::Unpause.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*state_pubkey, false),
+        AccountMeta::new_readonly(*state_owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_fees' instruction.
+pub fn withdraw_fees(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    fee_owner_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::WithdrawFees(amount).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*state_pubkey, false),
+        AccountMeta::new_readonly(*fee_owner_pubkey, true),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'transfer_state_ownership' instruction.
+pub fn transfer_state_ownership(
+    program_id: &Pubkey,
+    state_pubkey: &Pubkey,
+    state_owner_pubkey: &Pubkey,
+    new_owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::TransferStateOwnership {
+        new_owner: *new_owner_pubkey,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*state_pubkey, false),
+        AccountMeta::new_readonly(*state_owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'accept_state_ownership' instruction.
+pub fn accept_state_ownership(
+    program_id: &Pubkey,
+    state_pubkey: &Pubkey,
+    pending_owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::AcceptStateOwnership.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*state_pubkey, false),
+        AccountMeta::new_readonly(*pending_owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_program_state' instruction.
+pub fn initialize_program_state(
+    program_id: &Pubkey,
+    state_pubkey: &Pubkey,
+    state_owner_pubkey: &Pubkey,
+    fee_owner_pubkey: &Pubkey,
+    initial_supply: u64,
+    fees: Fees,
+    curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::InitializeProgramState {
+        initial_supply,
+        fees,
+        curve,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*state_pubkey, false),
+        AccountMeta::new_readonly(*state_owner_pubkey, true),
+        AccountMeta::new_readonly(*fee_owner_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'update_program_state' instruction.
+pub fn update_program_state(
+    program_id: &Pubkey,
+    state_pubkey: &Pubkey,
+    state_owner_pubkey: &Pubkey,
+    fee_owner_pubkey: &Pubkey,
+    initial_supply: u64,
+    fees: Fees,
+    curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::UpdateProgramState {
+        initial_supply,
+        fees,
+        curve,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*state_pubkey, false),
+        AccountMeta::new_readonly(*state_owner_pubkey, true),
+        AccountMeta::new_readonly(*fee_owner_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'close_pool' instruction.
+///
+/// `pool_token_pubkey` is the signer's pool token account holding the whole
+/// pool token supply, and may be omitted when the signer is the state owner.
+pub fn close_pool(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    closer_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_a_pubkey: &Pubkey,
+    destination_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_token_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ClosePool.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*state_pubkey, false),
+        AccountMeta::new(*closer_pubkey, true),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_a_pubkey, false),
+        AccountMeta::new(*destination_b_pubkey, false),
+        AccountMeta::new_readonly(*pool_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(pool_token_pubkey) = pool_token_pubkey {
+        accounts.push(AccountMeta::new_readonly(*pool_token_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Denominator of the slippage tolerances given in basis points
+pub const SLIPPAGE_BPS_DENOMINATOR: u64 = 10_000;
+
+/// Pool accounts and quote of a single hop in a routed swap
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteHop {
+    /// Token-swap of the pool
+    pub swap_pubkey: Pubkey,
+    /// Swap authority of the pool
+    pub authority_pubkey: Pubkey,
+    /// Pool vault receiving the SOURCE token
+    pub swap_source_pubkey: Pubkey,
+    /// Pool vault paying out the DESTINATION token
+    pub swap_destination_pubkey: Pubkey,
+    /// Pool token mint
+    pub pool_mint_pubkey: Pubkey,
+    /// Fee token account of the pool
+    pub fee_account_pubkey: Pubkey,
+    /// Fee wallet of the pool, for pools trading SOL
+    pub fee_wallet_pubkey: Option<Pubkey>,
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+    /// Mint of the DESTINATION token
+    pub destination_mint: Pubkey,
+    /// Quoted output of this hop for the quoted input of the route
+    pub expected_amount_out: u64,
+}
+
+fn apply_slippage(amount: u64, slippage_bps: u16) -> Result<u64, AmmError> {
+    let slippage_bps = u64::from(slippage_bps);
+    if slippage_bps > SLIPPAGE_BPS_DENOMINATOR {
+        return Err(AmmError::InvalidInput);
+    }
+    let amount = u128::from(amount)
+        .checked_mul(u128::from(SLIPPAGE_BPS_DENOMINATOR - slippage_bps))
+        .ok_or(AmmError::CalculationFailure)?
+        / u128::from(SLIPPAGE_BPS_DENOMINATOR);
+    Ok(amount as u64)
+}
+
+/// Raises `amount` by `slippage_bps`, rounding up, for maximum amounts
+fn apply_slippage_up(amount: u64, slippage_bps: u16) -> Result<u64, AmmError> {
+    let slippage_bps = u64::from(slippage_bps);
+    if slippage_bps > SLIPPAGE_BPS_DENOMINATOR {
+        return Err(AmmError::InvalidInput);
+    }
+    let denominator = u128::from(SLIPPAGE_BPS_DENOMINATOR);
+    let amount = u128::from(amount)
+        .checked_mul(u128::from(SLIPPAGE_BPS_DENOMINATOR + slippage_bps))
+        .and_then(|amount| amount.checked_add(denominator - 1))
+        .ok_or(AmmError::CalculationFailure)?
+        / denominator;
+    u64::try_from(amount).map_err(|_| AmmError::ConversionFailure)
+}
+
+impl SwapInstruction {
+    /// Swap of `amount_in` accepting an output down to `slippage_bps` below
+    /// `quoted_out`, rounded down
+    pub fn with_slippage_bps(
+        amount_in: u64,
+        quoted_out: u64,
+        slippage_bps: u16,
+    ) -> Result<Self, AmmError> {
+        Ok(Self {
+            amount_in,
+            minimum_amount_out: apply_slippage(quoted_out, slippage_bps)?,
+        })
+    }
+
+    /// Whether the minimum output is zero, accepting any output
+    pub fn is_unprotected(&self) -> bool {
+        self.minimum_amount_out == 0
+    }
+}
+
+impl SwapInstruction2 {
+    /// Whether the minimum output is zero, accepting any output
+    pub fn is_unprotected(&self) -> bool {
+        self.minimum_amount_out == 0
+    }
+}
+
+impl SwapExactOutInstruction {
+    /// Whether the maximum input is `u64::MAX`, paying any price
+    pub fn is_unprotected(&self) -> bool {
+        self.maximum_amount_in == u64::MAX
+    }
+}
+
+impl DepositInstruction {
+    /// Deposit for `pool_token_amount` of at most `maximum_token_a_amount`
+    /// and `maximum_token_b_amount`
+    pub fn new(
+        pool_token_amount: PoolTokenAmount,
+        maximum_token_a_amount: TradingTokenAmount,
+        maximum_token_b_amount: TradingTokenAmount,
+    ) -> Self {
+        Self {
+            pool_token_amount: pool_token_amount.0,
+            maximum_token_a_amount: maximum_token_a_amount.0,
+            maximum_token_b_amount: maximum_token_b_amount.0,
+        }
+    }
+
+    /// Deposit for `pool_token_amount` accepting token amounts up to
+    /// `slippage_bps` above the quoted ones, rounded up
+    pub fn with_slippage_bps(
+        pool_token_amount: u64,
+        quoted_token_a_amount: u64,
+        quoted_token_b_amount: u64,
+        slippage_bps: u16,
+    ) -> Result<Self, AmmError> {
+        Ok(Self {
+            pool_token_amount,
+            maximum_token_a_amount: apply_slippage_up(quoted_token_a_amount, slippage_bps)?,
+            maximum_token_b_amount: apply_slippage_up(quoted_token_b_amount, slippage_bps)?,
+        })
+    }
+}
+
+impl WithdrawInstruction {
+    /// Withdrawal of `pool_token_amount` for at least `minimum_token_a_amount`
+    /// and `minimum_token_b_amount`
+    pub fn new(
+        pool_token_amount: PoolTokenAmount,
+        minimum_token_a_amount: TradingTokenAmount,
+        minimum_token_b_amount: TradingTokenAmount,
+    ) -> Self {
+        Self {
+            pool_token_amount: pool_token_amount.0,
+            minimum_token_a_amount: minimum_token_a_amount.0,
+            minimum_token_b_amount: minimum_token_b_amount.0,
+        }
+    }
+
+    /// Withdrawal of `pool_token_amount` accepting token amounts down to
+    /// `slippage_bps` below the quoted ones, rounded down
+    pub fn with_slippage_bps(
+        pool_token_amount: u64,
+        quoted_token_a_amount: u64,
+        quoted_token_b_amount: u64,
+        slippage_bps: u16,
+    ) -> Result<Self, AmmError> {
+        Ok(Self {
+            pool_token_amount,
+            minimum_token_a_amount: apply_slippage(quoted_token_a_amount, slippage_bps)?,
+            minimum_token_b_amount: apply_slippage(quoted_token_b_amount, slippage_bps)?,
+        })
+    }
+
+    /// Withdrawal of `pool_tokens` protected on both sides, the expected
+    /// amounts being the pool share of each reserve, e.g. from
+    /// [tokens_for_withdraw](crate::curve::liquidity::tokens_for_withdraw).
+    /// Same as [WithdrawInstruction::with_slippage_bps]
+    pub fn proportional(
+        pool_tokens: u64,
+        expected_a: u64,
+        expected_b: u64,
+        slippage_bps: u16,
+    ) -> Result<Self, AmmError> {
+        Self::with_slippage_bps(pool_tokens, expected_a, expected_b, slippage_bps)
+    }
+
+    /// Withdrawal of `pool_tokens` accepting any output on both sides. Only
+    /// built by [withdraw_all_token_types_unprotected]
+    pub fn no_slippage_protection(pool_tokens: u64) -> Self {
+        Self {
+            pool_token_amount: pool_tokens,
+            minimum_token_a_amount: 0,
+            minimum_token_b_amount: 0,
+        }
+    }
+
+    /// Whether both minimums are zero, accepting any output
+    pub fn is_unprotected(&self) -> bool {
+        self.minimum_token_a_amount == 0 && self.minimum_token_b_amount == 0
+    }
+}
+
+impl DepositSingleTokenTypeExactAmountIn {
+    /// Deposit of `source_token_amount` for at least
+    /// `minimum_pool_token_amount`
+    pub fn new(
+        source_token_amount: TradingTokenAmount,
+        minimum_pool_token_amount: PoolTokenAmount,
+    ) -> Self {
+        Self {
+            source_token_amount: source_token_amount.0,
+            minimum_pool_token_amount: minimum_pool_token_amount.0,
+        }
+    }
+}
+
+impl WithdrawSingleTokenTypeExactAmountOut {
+    /// Withdrawal of `destination_token_amount` for at most
+    /// `maximum_pool_token_amount`
+    pub fn new(
+        destination_token_amount: TradingTokenAmount,
+        maximum_pool_token_amount: PoolTokenAmount,
+    ) -> Self {
+        Self {
+            destination_token_amount: destination_token_amount.0,
+            maximum_pool_token_amount: maximum_pool_token_amount.0,
+        }
+    }
+
+    /// Whether the maximum of pool tokens is `u64::MAX`, burning as many as
+    /// the withdrawal takes
+    pub fn is_unprotected(&self) -> bool {
+        self.maximum_pool_token_amount == u64::MAX
+    }
+}
+
+/// Creates the two 'swap' instructions of a route from the SOURCE token to
+/// the DESTINATION token through an intermediate token.
+///
+/// The first hop must output `slippage_bps` less than its quote at worst,
+/// and only that guaranteed amount is swapped by the second hop.  The second
+/// hop's quote is scaled down to that input before the same tolerance is
+/// applied again.  Any intermediate tokens received above the minimum are
+/// left in `intermediate_pubkey`.
+pub fn swap_route(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    state_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    intermediate_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    first_hop: &RouteHop,
+    second_hop: &RouteHop,
+    amount_in: u64,
+    slippage_bps: u16,
+) -> Result<Vec<Instruction>, ProgramError> {
+    if first_hop.destination_mint != second_hop.source_mint {
+        return Err(AmmError::IncorrectRouteMint.into());
+    }
+    if first_hop.expected_amount_out == 0 {
+        return Err(AmmError::ZeroTradingTokens.into());
+    }
+
+    let intermediate_amount = apply_slippage(first_hop.expected_amount_out, slippage_bps)?;
+    let second_hop_expected_amount_out = u128::from(second_hop.expected_amount_out)
+        .checked_mul(u128::from(intermediate_amount))
+        .ok_or(AmmError::CalculationFailure)?
+        / u128::from(first_hop.expected_amount_out);
+    let second_hop_expected_amount_out = u64::try_from(second_hop_expected_amount_out)
+        .map_err(|_| AmmError::ConversionFailure)?;
+    let minimum_amount_out = apply_slippage(second_hop_expected_amount_out, slippage_bps)?;
+
+    Ok(vec![
+        swap(
+            program_id,
+            token_program_id,
+            &first_hop.swap_pubkey,
+            &first_hop.authority_pubkey,
+            user_transfer_authority_pubkey,
+            state_pubkey,
+            source_pubkey,
+            &first_hop.swap_source_pubkey,
+            &first_hop.swap_destination_pubkey,
+            intermediate_pubkey,
+            &first_hop.pool_mint_pubkey,
+            &first_hop.fee_account_pubkey,
+            first_hop.fee_wallet_pubkey.as_ref(),
+            None,
+            SwapInstruction {
+                amount_in,
+                minimum_amount_out: intermediate_amount,
+            },
+        )?,
+        swap(
+            program_id,
+            token_program_id,
+            &second_hop.swap_pubkey,
+            &second_hop.authority_pubkey,
+            user_transfer_authority_pubkey,
+            state_pubkey,
+            intermediate_pubkey,
+            &second_hop.swap_source_pubkey,
+            &second_hop.swap_destination_pubkey,
+            destination_pubkey,
+            &second_hop.pool_mint_pubkey,
+            &second_hop.fee_account_pubkey,
+            second_hop.fee_wallet_pubkey.as_ref(),
+            None,
+            SwapInstruction {
+                amount_in: intermediate_amount,
+                minimum_amount_out,
+            },
+        )?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{base::CurveType, constant_price::ConstantPriceCurve};
+    use spl_associated_token_account::instruction::AssociatedTokenAccountInstruction;
+    use std::sync::Arc;
+
+    fn pool_keys() -> SwapPoolKeys {
+        SwapPoolKeys {
+            program_id: crate::id(),
+            token_program_id: spl_token::id(),
+            swap: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            state: Pubkey::new_unique(),
+            token_a_vault: Pubkey::new_unique(),
+            token_b_vault: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            fee_account: Pubkey::new_unique(),
+            fee_wallet: None,
+        }
+    }
+
+    fn route_hop(
+        source_mint: Pubkey,
+        destination_mint: Pubkey,
+        expected_amount_out: u64,
+    ) -> RouteHop {
+        RouteHop {
+            swap_pubkey: Pubkey::new_unique(),
+            authority_pubkey: Pubkey::new_unique(),
+            swap_source_pubkey: Pubkey::new_unique(),
+            swap_destination_pubkey: Pubkey::new_unique(),
+            pool_mint_pubkey: Pubkey::new_unique(),
+            fee_account_pubkey: Pubkey::new_unique(),
+            fee_wallet_pubkey: None,
+            source_mint,
+            destination_mint,
+            expected_amount_out,
+        }
+    }
+
+    /// Instruction data of a swap of `amount_in` for `minimum_amount_out`
+    fn swap_data(amount_in: u64, minimum_amount_out: u64) -> Vec<u8> {
+        AmmInstruction::Swap(SwapInstruction {
+            amount_in,
+            minimum_amount_out,
+        })
+        .pack()
+    }
+
+    #[test]
+    fn test_initialize_nonce() {
+        let k = Pubkey::new_unique;
+        let program_id = crate::id();
+        let swap_account = k();
+        let (authority, nonce) = find_swap_authority(&program_id, &swap_account);
+        let (state, amm_id, token_a, token_b, pool, fee_a, fee_b, destination, market, dex) =
+            (k(), k(), k(), k(), k(), k(), k(), k(), k(), k());
+        let build = |authority: &Pubkey, nonce| {
+            initialize(
+                &program_id,
+                &spl_token::id(),
+                &swap_account,
+                authority,
+                &state,
+                &amm_id,
+                &token_a,
+                &token_b,
+                &pool,
+                &fee_a,
+                &fee_b,
+                &destination,
+                &market,
+                &dex,
+                nonce,
+                None,
+            )
+        };
+
+        let instruction = build(&authority, nonce).unwrap();
+        assert_eq!(instruction.accounts[1].pubkey, authority);
+        assert_eq!(
+            AmmInstruction::unpack(&instruction.data).unwrap(),
+            AmmInstruction::Initialize(InitializeInstruction {
+                nonce,
+                swap_curve: None,
+            })
+        );
+        assert_eq!(
+            initialize_find_nonce(
+                &program_id,
+                &spl_token::id(),
+                &swap_account,
+                &state,
+                &amm_id,
+                &token_a,
+                &token_b,
+                &pool,
+                &fee_a,
+                &fee_b,
+                &destination,
+                &market,
+                &dex,
+                None,
+            )
+            .unwrap(),
+            instruction
+        );
+
+        // the nonce of another authority, or of no authority
+        for wrong_nonce in [nonce.wrapping_sub(1), nonce.wrapping_add(1)] {
+            assert_eq!(
+                build(&authority, wrong_nonce),
+                Err(AmmError::InvalidProgramAddress.into())
+            );
+        }
+        // an authority not derived from the swap
+        assert_eq!(build(&k(), nonce), Err(AmmError::InvalidProgramAddress.into()));
+        let (other_authority, other_nonce) = find_swap_authority(&program_id, &k());
+        assert_eq!(
+            build(&other_authority, other_nonce),
+            Err(AmmError::InvalidProgramAddress.into())
+        );
+    }
+
+    #[cfg(feature = "offchain")]
+    #[test]
+    fn test_lookup_table_addresses() {
+        let k = Pubkey::new_unique;
+        let pool = pool_keys();
+        let user = UserSwapKeys {
+            transfer_authority: k(),
+            source: k(),
+            destination: k(),
+        };
+        let swap_ix = pool
+            .swap_ix(
+                &user,
+                SwapInstruction {
+                    amount_in: 1_000,
+                    minimum_amount_out: 900,
+                },
+            )
+            .unwrap();
+        // `Deposit(0)` of the farm program, harvesting the farm of the pool
+        // mint and paying the reward to the swap destination
+        let (farm_id, farm_authority, user_info, user_lp, pool_lp) = (k(), k(), k(), k(), k());
+        let (pool_reward, fee_reward_ata, program_data) = (k(), k(), k());
+        let harvest_ix = Instruction {
+            program_id: k(),
+            accounts: vec![
+                AccountMeta::new(farm_id, false),
+                AccountMeta::new_readonly(farm_authority, false),
+                AccountMeta::new_readonly(user.transfer_authority, true),
+                AccountMeta::new(user_info, false),
+                AccountMeta::new(user_lp, false),
+                AccountMeta::new(pool_lp, false),
+                AccountMeta::new(user.destination, false),
+                AccountMeta::new(pool_reward, false),
+                AccountMeta::new_readonly(pool.pool_mint, false),
+                AccountMeta::new(fee_reward_ata, false),
+                AccountMeta::new_readonly(program_data, false),
+                AccountMeta::new_readonly(pool.token_program_id, false),
+                AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
+            ],
+            data: vec![2, 0, 0, 0, 0, 0, 0, 0, 0],
+        };
+        let instructions = vec![swap_ix, harvest_ix];
+        let addresses = lookup_table_addresses(&instructions);
+
+        // the accounts shared by both instructions are held once
+        assert_eq!(
+            addresses,
+            vec![
+                pool.swap,
+                pool.authority,
+                pool.state,
+                user.source,
+                pool.token_a_vault,
+                pool.token_b_vault,
+                user.destination,
+                pool.pool_mint,
+                pool.fee_account,
+                pool.token_program_id,
+                farm_id,
+                farm_authority,
+                user_info,
+                user_lp,
+                pool_lp,
+                pool_reward,
+                fee_reward_ata,
+                program_data,
+                solana_program::sysvar::clock::id(),
+            ]
+        );
+        for address in swap_pool_lut_addresses(&pool) {
+            assert!(addresses.contains(&address), "{}", address);
+        }
+        // only the signer is left inline
+        for instruction in &instructions {
+            let partition = partition_lookup_accounts(instruction, &addresses).unwrap();
+            assert_eq!(
+                partition.inline_accounts,
+                vec![AccountMeta::new_readonly(user.transfer_authority, true)]
+            );
+            assert_eq!(
+                partition.writable_indexes.len() + partition.readonly_indexes.len(),
+                instruction.accounts.len() - 1
+            );
+        }
+        assert_eq!(lookup_table_addresses(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_typed_amounts() {
+        assert_eq!(u64::from(PoolTokenAmount::from(7)), 7);
+        assert_eq!(u64::from(TradingTokenAmount::from(u64::MAX)), u64::MAX);
+        assert_eq!(PoolTokenAmount::from(7), PoolTokenAmount(7));
+        assert_eq!(TradingTokenAmount::default(), TradingTokenAmount(0));
+
+        // the typed constructors keep the raw fields, and so the layout
+        let le = |amounts: &[u64]| -> Vec<u8> {
+            amounts
+                .iter()
+                .flat_map(|amount| amount.to_le_bytes())
+                .collect()
+        };
+        let deposit = DepositInstruction::new(
+            PoolTokenAmount(1),
+            TradingTokenAmount(2),
+            TradingTokenAmount(3),
+        );
+        assert_eq!(
+            deposit,
+            DepositInstruction {
+                pool_token_amount: 1,
+                maximum_token_a_amount: 2,
+                maximum_token_b_amount: 3,
+            }
+        );
+        let withdraw = WithdrawInstruction::new(
+            PoolTokenAmount(1),
+            TradingTokenAmount(2),
+            TradingTokenAmount(3),
+        );
+        assert_eq!(
+            withdraw,
+            WithdrawInstruction {
+                pool_token_amount: 1,
+                minimum_token_a_amount: 2,
+                minimum_token_b_amount: 3,
+            }
+        );
+        let deposit_single =
+            DepositSingleTokenTypeExactAmountIn::new(TradingTokenAmount(4), PoolTokenAmount(5));
+        assert_eq!(
+            deposit_single,
+            DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 4,
+                minimum_pool_token_amount: 5,
+            }
+        );
+        let withdraw_single =
+            WithdrawSingleTokenTypeExactAmountOut::new(TradingTokenAmount(4), PoolTokenAmount(5));
+        assert_eq!(
+            withdraw_single,
+            WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount: 4,
+                maximum_pool_token_amount: 5,
+            }
+        );
+
+        let packed = [
+            (AmmInstruction::from(deposit), 2, le(&[1, 2, 3])),
+            (AmmInstruction::from(withdraw), 3, le(&[1, 2, 3])),
+            (AmmInstruction::from(deposit_single), 4, le(&[4, 5])),
+            (AmmInstruction::from(withdraw_single), 5, le(&[4, 5])),
+        ];
+        for (instruction, tag, amounts) in packed.iter() {
+            let data = instruction.pack();
+            assert_eq!(data[0], *tag);
+            assert_eq!(&data[1..], &amounts[..], "{:?}", instruction);
+            assert_eq!(AmmInstruction::unpack(&data).as_ref(), Ok(instruction));
+        }
+    }
+
+    #[test]
+    fn test_swap_route() {
+        let (mint_a, mint_b, mint_c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let first_hop = route_hop(mint_a, mint_b, 1_000_000);
+        let second_hop = route_hop(mint_b, mint_c, 2_000_000);
+        let (authority, state, source, intermediate, destination) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let route = |first_hop: &RouteHop, second_hop: &RouteHop, slippage_bps| {
+            swap_route(
+                &crate::id(),
+                &spl_token::id(),
+                &authority,
+                &state,
+                &source,
+                &intermediate,
+                &destination,
+                first_hop,
+                second_hop,
+                500_000,
+                slippage_bps,
+            )
+        };
+
+        // the first hop guarantees 990_000 intermediate tokens, the second
+        // hop swaps them for its quote scaled to 1_980_000, less 1%
+        let instructions = route(&first_hop, &second_hop, 100).unwrap();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].data, swap_data(500_000, 990_000));
+        assert_eq!(instructions[1].data, swap_data(990_000, 1_960_200));
+        let hop = |hop: &RouteHop, source: &Pubkey, destination: &Pubkey, data: Vec<u8>| {
+            Instruction {
+                data,
+                ..swap(
+                    &crate::id(),
+                    &spl_token::id(),
+                    &hop.swap_pubkey,
+                    &hop.authority_pubkey,
+                    &authority,
+                    &state,
+                    source,
+                    &hop.swap_source_pubkey,
+                    &hop.swap_destination_pubkey,
+                    destination,
+                    &hop.pool_mint_pubkey,
+                    &hop.fee_account_pubkey,
+                    None,
+                    None,
+                    SwapInstruction {
+                        amount_in: 1,
+                        minimum_amount_out: 1,
+                    },
+                )
+                .unwrap()
+            }
+        };
+        // through the intermediate token account
+        assert_eq!(
+            instructions,
+            vec![
+                hop(&first_hop, &source, &intermediate, swap_data(500_000, 990_000)),
+                hop(&second_hop, &intermediate, &destination, swap_data(990_000, 1_960_200)),
+            ]
+        );
+
+        // without slippage, the second hop gets exactly its quote
+        let instructions = route(&first_hop, &second_hop, 0).unwrap();
+        assert_eq!(instructions[0].data, swap_data(500_000, 1_000_000));
+        assert_eq!(instructions[1].data, swap_data(1_000_000, 2_000_000));
+
+        // the intermediate minimum and the scaled quote are rounded down
+        let first_hop = route_hop(mint_a, mint_b, 3);
+        let second_hop = route_hop(mint_b, mint_c, 7);
+        let instructions = route(&first_hop, &second_hop, 5_000).unwrap();
+        // 3 * 0.5 = 1, 7 * 1 / 3 = 2, 2 * 0.5 = 1
+        assert_eq!(instructions[0].data, swap_data(500_000, 1));
+        assert_eq!(instructions[1].data, swap_data(1, 1));
+    }
+
+    #[test]
+    fn test_swap_route_final_hop_slippage() {
+        let (mint_a, mint_b, mint_c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let first_hop = route_hop(mint_a, mint_b, 10_000);
+        let second_hop = route_hop(mint_b, mint_c, 1_000_000);
+        let route = |slippage_bps| {
+            swap_route(
+                &crate::id(),
+                &spl_token::id(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &first_hop,
+                &second_hop,
+                1_000,
+                slippage_bps,
+            )
+        };
+        // the tolerance applies to each hop, the final minimum being below
+        // the quote by about twice the slippage
+        for (slippage_bps, intermediate, minimum_amount_out) in [
+            (0, 10_000, 1_000_000),
+            (1, 9_999, 999_800),
+            (100, 9_900, 980_100),
+            (5_000, 5_000, 250_000),
+        ] {
+            let instructions = route(slippage_bps).unwrap();
+            assert_eq!(instructions[0].data, swap_data(1_000, intermediate));
+            assert_eq!(
+                instructions[1].data,
+                swap_data(intermediate, minimum_amount_out),
+                "{}",
+                slippage_bps
+            );
+        }
+        // a full tolerance leaves the swaps without any minimum
+        assert_eq!(route(10_000), Err(AmmError::UnprotectedSwap.into()));
+        assert_eq!(route(10_001), Err(AmmError::InvalidInput.into()));
+    }
+
+    #[test]
+    fn test_swap_route_mismatched_mint() {
+        let (mint_a, mint_b, mint_c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let route = |first_hop: &RouteHop, second_hop: &RouteHop| {
+            swap_route(
+                &crate::id(),
+                &spl_token::id(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                first_hop,
+                second_hop,
+                1_000,
+                100,
+            )
+        };
+        // the second pool does not trade the intermediate token
+        assert_eq!(
+            route(&route_hop(mint_a, mint_b, 1_000), &route_hop(mint_c, mint_a, 1_000)),
+            Err(AmmError::IncorrectRouteMint.into())
+        );
+        // nor the reversed first pool
+        assert_eq!(
+            route(&route_hop(mint_b, mint_a, 1_000), &route_hop(mint_b, mint_c, 1_000)),
+            Err(AmmError::IncorrectRouteMint.into())
+        );
+        // a first hop quoting nothing
+        assert_eq!(
+            route(&route_hop(mint_a, mint_b, 0), &route_hop(mint_b, mint_c, 1_000)),
+            Err(AmmError::ZeroTradingTokens.into())
+        );
+        assert!(route(
+            &route_hop(mint_a, mint_b, 1_000),
+            &route_hop(mint_b, mint_c, 1_000)
+        )
+        .is_ok());
+    }
+
+    fn assert_create_idempotent(instruction: &Instruction, payer: &Pubkey, wallet: &Pubkey) {
+        assert_eq!(instruction.program_id, spl_associated_token_account::id());
+        assert_eq!(
+            instruction.data,
+            vec![AssociatedTokenAccountInstruction::CreateIdempotent as u8]
+        );
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(
+            keys,
+            vec![
+                *payer,
+                get_associated_token_address(wallet, &native_mint::id()),
+                *wallet,
+                native_mint::id(),
+                system_program::id(),
+                spl_token::id(),
+            ]
+        );
+    }
+
+    /// (is_signer, is_writable) of an account
+    type Flags = (bool, bool);
+    const R: Flags = (false, false);
+    const W: Flags = (false, true);
+    const S: Flags = (true, false);
+    const WS: Flags = (true, true);
+
+    fn flags(instruction: &Instruction) -> Vec<Flags> {
+        instruction
+            .accounts
+            .iter()
+            .map(|account| (account.is_signer, account.is_writable))
+            .collect()
+    }
+
+    /// Flags of the accounts passed by each builder, as documented on
+    /// [AmmInstruction]
+    #[test]
+    fn test_account_flags() {
+        let k = Pubkey::new_unique;
+        let program_id = crate::id();
+        let token_program_id = spl_token::id();
+        let fees = Fees::default();
+        let curve = SwapCurve::default();
+        let swap_account = k();
+        let (authority, nonce) = find_swap_authority(&program_id, &swap_account);
+
+        #[rustfmt::skip]
+        let golden: Vec<(&str, Instruction, Vec<Flags>)> = vec![
+            (
+                "initialize",
+                initialize(
+                    &program_id, &token_program_id, &swap_account, &authority, &k(), &k(),
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), nonce, None,
+                )
+                .unwrap(),
+                vec![WS, R, R, R, R, R, W, R, R, W, W, R, R],
+            ),
+            (
+                "initialize2",
+                initialize2(
+                    &program_id, &token_program_id, &swap_account, &authority, &k(), &k(),
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    Initialize2Instruction {
+                        nonce,
+                        initial_token_a: 1,
+                        initial_token_b: 1,
+                        min_pool_tokens: 1,
+                    },
+                )
+                .unwrap(),
+                vec![WS, R, R, R, R, R, W, R, R, W, W, R, R],
+            ),
+            (
+                "swap",
+                swap(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), &k(), None, None,
+                    SwapInstruction {
+                        amount_in: 10,
+                        minimum_amount_out: 1,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, R, W, W, W, W, W, W, R],
+            ),
+            (
+                "swap with fee wallet and host fee",
+                swap(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), &k(), Some(&k()), Some(&k()),
+                    SwapInstruction {
+                        amount_in: 10,
+                        minimum_amount_out: 1,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, R, W, W, W, W, W, W, W, R, R, W],
+            ),
+            (
+                "swap_exact_out",
+                swap_exact_out(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), &k(), None,
+                    SwapExactOutInstruction {
+                        amount_out: 10,
+                        maximum_amount_in: 20,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, R, W, W, W, W, W, W, R],
+            ),
+            (
+                "swap2",
+                swap2(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), &k(), None, None,
+                    SwapInstruction2 {
+                        amount_in: 10,
+                        minimum_amount_out: 1,
+                        direction: None,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, R, W, W, W, W, W, W, R],
+            ),
+            (
+                "deposit_all_token_types",
+                deposit_all_token_types(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), &k(),
+                    DepositInstruction {
+                        pool_token_amount: 10,
+                        maximum_token_a_amount: 10,
+                        maximum_token_b_amount: 10,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, R, W, W, W, W, W, W, R],
+            ),
+            (
+                "withdraw_all_token_types",
+                withdraw_all_token_types(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), &k(), &k(),
+                    WithdrawInstruction {
+                        pool_token_amount: 10,
+                        minimum_token_a_amount: 1,
+                        minimum_token_b_amount: 1,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, R, W, W, W, W, W, W, W, R],
+            ),
+            (
+                "deposit_single_token_type_exact_amount_in",
+                deposit_single_token_type_exact_amount_in(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(),
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount: 10,
+                        minimum_pool_token_amount: 1,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, W, W, W, W, W, R],
+            ),
+            (
+                "withdraw_single_token_type_exact_amount_out",
+                withdraw_single_token_type_exact_amount_out(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(),
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount: 10,
+                        maximum_pool_token_amount: 20,
+                    },
+                )
+                .unwrap(),
+                vec![R, R, S, W, W, W, W, W, W, R],
+            ),
+            (
+                "set_fees",
+                set_fees(&program_id, &k(), &k(), fees.clone()).unwrap(),
+                vec![W, S],
+            ),
+            ("pause", pause(&program_id, &k(), &k(), &k()).unwrap(), vec![W, R, S]),
+            ("unpause", unpause(&program_id, &k(), &k(), &k()).unwrap(), vec![W, R, S]),
+            (
+                "withdraw_fees",
+                withdraw_fees(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), 0,
+                )
+                .unwrap(),
+                vec![R, R, R, S, W, W, R],
+            ),
+            (
+                "transfer_state_ownership",
+                transfer_state_ownership(&program_id, &k(), &k(), &k()).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "accept_state_ownership",
+                accept_state_ownership(&program_id, &k(), &k()).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "close_pool",
+                close_pool(
+                    &program_id, &token_program_id, &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &k(), &k(), Some(&k()),
+                )
+                .unwrap(),
+                vec![W, R, R, WS, W, W, W, W, R, R, R],
+            ),
+            (
+                "initialize_program_state",
+                initialize_program_state(
+                    &program_id, &k(), &k(), &k(), 1, fees.clone(), curve.clone(),
+                )
+                .unwrap(),
+                vec![W, S, R],
+            ),
+            (
+                "update_program_state",
+                update_program_state(&program_id, &k(), &k(), &k(), 1, fees, curve).unwrap(),
+                vec![W, S, R],
+            ),
+        ];
+
+        for (name, instruction, expected) in golden {
+            assert_eq!(flags(&instruction), expected, "{}", name);
+        }
+    }
+
+    /// Pools of either token program invoke it last, after the pool mint,
+    /// the only mint passed
+    #[test]
+    fn test_token_programs() {
+        for token_program_id in [spl_token::id(), crate::spl_token_2022::id()] {
+            let pool = SwapPoolKeys {
+                token_program_id,
+                ..pool_keys()
+            };
+            let swap_user = UserSwapKeys {
+                transfer_authority: Pubkey::new_unique(),
+                source: Pubkey::new_unique(),
+                destination: Pubkey::new_unique(),
+            };
+            let liquidity_user = UserLiquidityKeys {
+                transfer_authority: Pubkey::new_unique(),
+                token_a: Pubkey::new_unique(),
+                token_b: Pubkey::new_unique(),
+                pool_token: Pubkey::new_unique(),
+            };
+            let instructions = [
+                pool.swap_ix(
+                    &swap_user,
+                    SwapInstruction {
+                        amount_in: 10,
+                        minimum_amount_out: 1,
+                    },
+                )
+                .unwrap(),
+                pool.deposit_all_token_types_ix(
+                    &liquidity_user,
+                    DepositInstruction {
+                        pool_token_amount: 10,
+                        maximum_token_a_amount: 10,
+                        maximum_token_b_amount: 10,
+                    },
+                )
+                .unwrap(),
+                pool.withdraw_all_token_types_ix(
+                    &liquidity_user,
+                    WithdrawInstruction {
+                        pool_token_amount: 10,
+                        minimum_token_a_amount: 1,
+                        minimum_token_b_amount: 1,
+                    },
+                )
+                .unwrap(),
+            ];
+            for instruction in instructions.iter() {
+                let keys: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+                assert_eq!(keys.last(), Some(&token_program_id));
+                assert_eq!(keys.iter().filter(|k| **k == token_program_id).count(), 1);
+                assert_eq!(keys.iter().filter(|k| **k == pool.pool_mint).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_withdraw_instruction_constructors() {
+        assert_eq!(
+            WithdrawInstruction::proportional(1_000, 500, 2_000, 100).unwrap(),
+            WithdrawInstruction {
+                pool_token_amount: 1_000,
+                minimum_token_a_amount: 495,
+                minimum_token_b_amount: 1_980,
+            }
+        );
+        let unprotected = WithdrawInstruction::no_slippage_protection(1_000);
+        assert_eq!(unprotected.pool_token_amount, 1_000);
+        assert!(unprotected.is_unprotected());
+        assert!(!WithdrawInstruction::proportional(1_000, 0, 2_000, 100)
+            .unwrap()
+            .is_unprotected());
+    }
+
+    #[test]
+    fn test_withdraw_all_token_types_protection() {
+        let pool = pool_keys();
+        let (authority, source, destination_a, destination_b, signer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let withdraw = |instruction| {
+            withdraw_all_token_types(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &authority,
+                &pool.state,
+                &pool.pool_mint,
+                &source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &destination_a,
+                &destination_b,
+                &pool.fee_account,
+                instruction,
+            )
+        };
+        let withdraw_unprotected = |instruction| {
+            withdraw_all_token_types_unprotected(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &authority,
+                &pool.state,
+                &pool.pool_mint,
+                &source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &destination_a,
+                &destination_b,
+                &pool.fee_account,
+                instruction,
+            )
+        };
+        let withdraw_multisig = |instruction| {
+            withdraw_all_token_types_multisig(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &authority,
+                &[&signer],
+                &pool.state,
+                &pool.pool_mint,
+                &source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &destination_a,
+                &destination_b,
+                &pool.fee_account,
+                instruction,
+            )
+        };
+        let withdraw_multisig_unprotected = |instruction| {
+            withdraw_all_token_types_multisig_unprotected(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &authority,
+                &[&signer],
+                &pool.state,
+                &pool.pool_mint,
+                &source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &destination_a,
+                &destination_b,
+                &pool.fee_account,
+                instruction,
+            )
+        };
+
+        let unprotected = WithdrawInstruction::no_slippage_protection(1_000);
+        assert_eq!(
+            withdraw(unprotected),
+            Err(AmmError::UnprotectedWithdraw.into())
+        );
+        assert_eq!(
+            withdraw_multisig(unprotected),
+            Err(AmmError::UnprotectedWithdraw.into())
+        );
+        let instruction = withdraw_unprotected(unprotected).unwrap();
+        assert_eq!(
+            AmmInstruction::unpack(&instruction.data).unwrap(),
+            AmmInstruction::WithdrawAllTokenTypes(unprotected)
+        );
+        let mut expected = instruction;
+        expected.accounts[2].is_signer = false;
+        expected
+            .accounts
+            .push(AccountMeta::new_readonly(signer, true));
+        assert_eq!(
+            withdraw_multisig_unprotected(unprotected).unwrap(),
+            expected
+        );
+
+        // a single protected side is enough
+        let one_side = WithdrawInstruction {
+            pool_token_amount: 1_000,
+            minimum_token_a_amount: 0,
+            minimum_token_b_amount: 1,
+        };
+        assert_eq!(
+            withdraw(one_side).unwrap(),
+            withdraw_unprotected(one_side).unwrap()
+        );
+        assert!(withdraw_multisig(one_side).is_ok());
+
+        // the unprotected variants still check the accounts
+        assert_eq!(
+            withdraw_all_token_types_unprotected(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &authority,
+                &pool.state,
+                &pool.pool_mint,
+                &source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &pool.token_a_vault,
+                &destination_b,
+                &pool.fee_account,
+                unprotected,
+            ),
+            Err(AmmError::InvalidInput.into())
+        );
+    }
+
+    #[test]
+    fn test_swap_protection() {
+        let pool = pool_keys();
+        let user = UserSwapKeys {
+            transfer_authority: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+        };
+        let unprotected: Result<Instruction, ProgramError> = Err(AmmError::UnprotectedSwap.into());
+        let swap_instruction = SwapInstruction {
+            amount_in: 1_000,
+            minimum_amount_out: 0,
+        };
+        let swap2_instruction = SwapInstruction2 {
+            amount_in: 1_000,
+            minimum_amount_out: 0,
+            direction: None,
+        };
+        let exact_out_instruction = SwapExactOutInstruction {
+            amount_out: 1_000,
+            maximum_amount_in: u64::MAX,
+        };
+        let positional_swap = |instruction| {
+            swap(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &user.transfer_authority,
+                &pool.state,
+                &user.source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &user.destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction,
+            )
+        };
+        let positional_swap2 = |instruction| {
+            swap2(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &user.transfer_authority,
+                &pool.state,
+                &user.source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &user.destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction,
+            )
+        };
+        let positional_swap_exact_out = |instruction| {
+            swap_exact_out(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &user.transfer_authority,
+                &pool.state,
+                &user.source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &user.destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                instruction,
+            )
+        };
+
+        // every builder rejects an unprotected swap
+        assert_eq!(positional_swap(swap_instruction), unprotected);
+        assert_eq!(
+            SwapIxBuilder::new(swap_instruction)
+                .pool(pool.clone())
+                .user(user.clone())
+                .build(),
+            unprotected
+        );
+        assert_eq!(pool.swap_ix(&user, swap_instruction), unprotected);
+        assert_eq!(positional_swap2(swap2_instruction), unprotected);
+        assert_eq!(pool.swap2_ix(&user, swap2_instruction), unprotected);
+        for direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+            assert_eq!(
+                pool.swap2_directed_ix(&user, direction, 1_000, 0),
+                unprotected
+            );
+        }
+        assert_eq!(
+            positional_swap_exact_out(exact_out_instruction),
+            unprotected
+        );
+        assert_eq!(
+            pool.swap_exact_out_ix(&user, exact_out_instruction),
+            unprotected
+        );
+        #[cfg(feature = "offchain")]
+        assert_eq!(
+            swap_with_ephemeral_authority(
+                &pool,
+                &Pubkey::new_unique(),
+                &user.source,
+                &user.destination,
+                swap_instruction,
+            )
+            .unwrap_err(),
+            AmmError::UnprotectedSwap.into()
+        );
+
+        // the opt-outs build the same instruction data
+        let unprotected_swap = pool.swap_unprotected_ix(&user, swap_instruction).unwrap();
+        assert_eq!(
+            AmmInstruction::unpack(&unprotected_swap.data).unwrap(),
+            AmmInstruction::Swap(swap_instruction)
+        );
+        assert_eq!(
+            SwapIxBuilder::new(swap_instruction)
+                .pool(pool.clone())
+                .user(user.clone())
+                .allow_unprotected()
+                .build()
+                .unwrap(),
+            unprotected_swap
+        );
+        assert_eq!(
+            swap_unprotected(
+                &pool.program_id,
+                &pool.token_program_id,
+                &pool.swap,
+                &pool.authority,
+                &user.transfer_authority,
+                &pool.state,
+                &user.source,
+                &pool.token_a_vault,
+                &pool.token_b_vault,
+                &user.destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                swap_instruction,
+            )
+            .unwrap(),
+            unprotected_swap
+        );
+        assert_eq!(
+            AmmInstruction::unpack(
+                &pool
+                    .swap2_unprotected_ix(&user, swap2_instruction)
+                    .unwrap()
+                    .data
+            )
+            .unwrap(),
+            AmmInstruction::Swap2(swap2_instruction)
+        );
+        assert_eq!(
+            AmmInstruction::unpack(
+                &pool
+                    .swap_exact_out_unprotected_ix(&user, exact_out_instruction)
+                    .unwrap()
+                    .data
+            )
+            .unwrap(),
+            AmmInstruction::SwapExactOut(exact_out_instruction)
+        );
+
+        // a minimum of one unit, or a bounded input, is enough
+        let protected = SwapInstruction {
+            minimum_amount_out: 1,
+            ..swap_instruction
+        };
+        assert!(positional_swap(protected).is_ok());
+        assert!(pool.swap_ix(&user, protected).is_ok());
+        assert!(positional_swap2(SwapInstruction2 {
+            minimum_amount_out: 1,
+            ..swap2_instruction
+        })
+        .is_ok());
+        assert!(pool
+            .swap2_directed_ix(&user, TradeDirection::BtoA, 1_000, 1)
+            .is_ok());
+        assert!(positional_swap_exact_out(SwapExactOutInstruction {
+            maximum_amount_in: u64::MAX - 1,
+            ..exact_out_instruction
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn test_swap_with_sol_input() {
+        let pool = pool_keys();
+        let (payer, wallet, destination) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let wrapped_sol = get_associated_token_address(&wallet, &native_mint::id());
+        let instructions =
+            swap_with_sol_input(&pool, &payer, &wallet, &destination, 1_000, 990).unwrap();
+        assert_eq!(instructions.len(), 5);
+        assert_create_idempotent(&instructions[0], &payer, &wallet);
+        assert_eq!(
+            instructions[1],
+            system_instruction::transfer(&wallet, &wrapped_sol, 1_000)
+        );
+        assert_eq!(
+            instructions[2],
+            token_instruction::sync_native(&spl_token::id(), &wrapped_sol).unwrap()
+        );
+        assert_eq!(instructions[3].program_id, crate::id());
+        assert_eq!(
+            AmmInstruction::unpack(&instructions[3].data).unwrap(),
+            AmmInstruction::Swap(SwapInstruction {
+                amount_in: 1_000,
+                minimum_amount_out: 990,
+            })
+        );
+        assert_eq!(
+            instructions[4],
+            token_instruction::close_account(&spl_token::id(), &wrapped_sol, &wallet, &wallet, &[])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_swap_with_sol_output() {
+        let pool = pool_keys();
+        let (payer, wallet, source) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let wrapped_sol = get_associated_token_address(&wallet, &native_mint::id());
+        let instructions =
+            swap_with_sol_output(&pool, &payer, &wallet, &source, 1_000, 990).unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_create_idempotent(&instructions[0], &payer, &wallet);
+        assert_eq!(instructions[1].program_id, crate::id());
+        // the close destination is the wallet, unwrapping the output
+        assert_eq!(
+            instructions[2],
+            token_instruction::close_account(&spl_token::id(), &wrapped_sol, &wallet, &wallet, &[])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_swap_with_sol_checks() {
+        let pool = pool_keys();
+        let (payer, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(
+            swap_with_sol_input(&pool, &payer, &wallet, &Pubkey::new_unique(), 1_000, 0),
+            Err(AmmError::UnprotectedSwap.into())
+        );
+        assert_eq!(
+            swap_with_sol_output(&pool, &payer, &wallet, &Pubkey::new_unique(), 1_000, 0),
+            Err(AmmError::UnprotectedSwap.into())
+        );
+        assert_eq!(
+            swap_with_sol_input(&pool, &payer, &wallet, &pool.token_b_vault, 1_000, 990),
+            Err(AmmError::InvalidInput.into())
+        );
+        assert_eq!(
+            swap_with_sol_output(&pool, &payer, &wallet, &pool.pool_mint, 1_000, 990),
+            Err(AmmError::InvalidInput.into())
+        );
+    }
+
+    #[test]
+    fn test_deposit_single_side() {
+        let pool = pool_keys();
+        let swap = crate::state::SwapV1 {
+            is_initialized: true,
+            token_a: pool.token_a_vault,
+            token_b: pool.token_b_vault,
+            pool_mint: pool.pool_mint,
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+            ..Default::default()
+        };
+        let user = UserSwapKeys {
+            transfer_authority: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+        };
+        let deposit = DepositSingleTokenTypeExactAmountIn {
+            source_token_amount: 1_000,
+            minimum_pool_token_amount: 1,
+        };
+        let vaults = |ix: &Instruction| (ix.accounts[4].pubkey, ix.accounts[5].pubkey);
+
+        for (side, mint) in [
+            (DepositSide::A, swap.token_a_mint),
+            (DepositSide::B, swap.token_b_mint),
+        ] {
+            let ix = pool
+                .deposit_single_side_ix(&user, &mint, side, &swap, deposit)
+                .unwrap();
+            assert_eq!(vaults(&ix), (swap.token_a, swap.token_b));
+            // the vaults are ordered from the state, not the pool keys
+            let ix = pool
+                .reversed()
+                .deposit_single_side_ix(&user, &mint, side, &swap, deposit)
+                .unwrap();
+            assert_eq!(vaults(&ix), (swap.token_a, swap.token_b));
+        }
+
+        for (side, mint) in [
+            (DepositSide::A, swap.token_b_mint),
+            (DepositSide::B, swap.token_a_mint),
+            (DepositSide::A, Pubkey::new_unique()),
+        ] {
+            assert_eq!(
+                pool.deposit_single_side_ix(&user, &mint, side, &swap, deposit),
+                Err(AmmError::IncorrectDepositSide.into())
+            );
+        }
+
+        let other_pool = SwapPoolKeys {
+            token_b_vault: Pubkey::new_unique(),
+            ..pool.clone()
+        };
+        assert_eq!(
+            other_pool.deposit_single_side_ix(
+                &user,
+                &swap.token_a_mint,
+                DepositSide::A,
+                &swap,
+                deposit
+            ),
+            Err(AmmError::IncorrectSwapAccount.into())
+        );
+    }
+
+    #[test]
+    fn test_decode_primitives() {
+        use decode::*;
+
+        let input: Vec<u8> = (1..=40).collect();
+        // each primitive takes its own width and leaves the rest
+        assert_eq!(unpack_u8(&input), Ok((1, &input[1..])));
+        assert_eq!(unpack_u16(&input), Ok((0x0201, &input[2..])));
+        assert_eq!(unpack_u64(&input), Ok((0x0807_0605_0403_0201, &input[8..])));
+        assert_eq!(
+            unpack_u128(&input),
+            Ok((0x100f_0e0d_0c0b_0a09_0807_0605_0403_0201, &input[16..]))
+        );
+        assert_eq!(
+            unpack_i64(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 7]),
+            Ok((-2, &[7][..]))
+        );
+        let (pubkey, rest) = unpack_pubkey(&input).unwrap();
+        assert_eq!(pubkey.as_ref(), &input[..32]);
+        assert_eq!(rest, &input[32..]);
+        assert_eq!(unpack_bool(&[0, 9]), Ok((false, &[9][..])));
+        assert_eq!(unpack_bool(&[1]), Ok((true, &[][..])));
+        assert_eq!(unpack_bool(&[2]), Err(AmmError::InvalidInstruction));
+
+        // exactly the width, then one byte short
+        type Decodes = fn(&[u8]) -> bool;
+        #[rustfmt::skip]
+        let widths: Vec<(usize, Decodes)> = vec![
+            (1, |input| unpack_u8(input).is_ok()),
+            (2, |input| unpack_u16(input).is_ok()),
+            (8, |input| unpack_u64(input).is_ok()),
+            (16, |input| unpack_u128(input).is_ok()),
+            (8, |input| unpack_i64(input).is_ok()),
+            (32, |input| unpack_pubkey(input).is_ok()),
+            (1, |input| unpack_bool(input).is_ok()),
+        ];
+        for (width, decodes) in widths {
+            let input = vec![0; width];
+            assert!(decodes(&input), "{}", width);
+            assert!(!decodes(&input[..width - 1]), "{}", width);
+            assert!(!decodes(&[]), "{}", width);
+        }
+        assert_eq!(unpack_u64(&[1; 7]), Err(AmmError::InvalidInstruction));
+        assert_eq!(unpack_pubkey(&[1; 31]), Err(AmmError::InvalidInstruction));
+    }
+
+    #[test]
+    fn test_unpack_truncated_and_over_long() {
+        let instructions = [
+            AmmInstruction::Swap(SwapInstruction {
+                amount_in: 1,
+                minimum_amount_out: 1,
+            }),
+            AmmInstruction::DepositAllTokenTypes(DepositInstruction {
+                pool_token_amount: 1,
+                maximum_token_a_amount: 1,
+                maximum_token_b_amount: 1,
+            }),
+            AmmInstruction::WithdrawFees(1),
+            AmmInstruction::TransferStateOwnership {
+                new_owner: Pubkey::new_unique(),
+            },
+            AmmInstruction::Initialize2(Initialize2Instruction {
+                nonce: 255,
+                initial_token_a: 1,
+                initial_token_b: 1,
+                min_pool_tokens: 1,
+            }),
+            AmmInstruction::Swap2(SwapInstruction2 {
+                amount_in: 1,
+                minimum_amount_out: 1,
+                direction: None,
+            }),
+        ];
+        for instruction in instructions.iter() {
+            let data = instruction.pack();
+            for len in 0..data.len() {
+                assert_eq!(
+                    AmmInstruction::unpack(&data[..len]),
+                    Err(AmmError::InvalidInstruction.into()),
+                    "{:?} truncated to {}",
+                    instruction,
+                    len
+                );
+            }
+        }
+
+        // trailing bytes after the fixed size fields are ignored
+        for instruction in instructions[..5].iter() {
+            let mut data = instruction.pack();
+            data.extend_from_slice(&[7; 8]);
+            assert_eq!(
+                AmmInstruction::unpack(&data).as_ref(),
+                Ok(instruction),
+                "{:?}",
+                instruction
+            );
+        }
+        // unlike after the optional direction or curve
+        let mut data = instructions[5].pack();
+        data.push(2);
+        assert_eq!(
+            AmmInstruction::unpack(&data),
+            Err(AmmError::InvalidInstruction.into())
+        );
+        assert_eq!(
+            AmmInstruction::unpack(&[AmmInstruction::TAG_INITIALIZE, 255, 0]),
+            Err(AmmError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            owner_trade_fee_numerator: 5,
+            owner_withdraw_fee_numerator: 1,
+            host_fee_numerator: 20,
+            fee_denominator: 10_000,
+        };
+        let curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve { token_b_price: 2 }),
+        };
+        let owner = Pubkey::new_from_array([1; 32]);
+        #[rustfmt::skip]
+        let snapshots = vec![
+            (
+                AmmInstruction::Initialize(InitializeInstruction { nonce: 254, swap_curve: None }),
+                "Initialize { nonce: 254 }",
+            ),
+            (
+                AmmInstruction::Initialize(InitializeInstruction {
+                    nonce: 254,
+                    swap_curve: Some(SwapCurve::default()),
+                }),
+                "Initialize { nonce: 254, curve: ConstantProduct }",
+            ),
+            (
+                AmmInstruction::Swap(SwapInstruction { amount_in: 1_500_000, minimum_amount_out: 1_430_000 }),
+                "Swap { in: 1500000, min_out: 1430000 }",
+            ),
+            (
+                AmmInstruction::DepositAllTokenTypes(DepositInstruction {
+                    pool_token_amount: 10,
+                    maximum_token_a_amount: 20,
+                    maximum_token_b_amount: 30,
+                }),
+                "DepositAllTokenTypes { pool_tokens: 10, max_a: 20, max_b: 30 }",
+            ),
+            (
+                AmmInstruction::WithdrawAllTokenTypes(WithdrawInstruction {
+                    pool_token_amount: 10,
+                    minimum_token_a_amount: 20,
+                    minimum_token_b_amount: 30,
+                }),
+                "WithdrawAllTokenTypes { pool_tokens: 10, min_a: 20, min_b: 30 }",
+            ),
+            (
+                AmmInstruction::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount: 10,
+                    minimum_pool_token_amount: 5,
+                }),
+                "DepositSingleTokenTypeExactAmountIn { in: 10, min_pool_tokens: 5 }",
+            ),
+            (
+                AmmInstruction::WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount: 10,
+                    maximum_pool_token_amount: 15,
+                }),
+                "WithdrawSingleTokenTypeExactAmountOut { out: 10, max_pool_tokens: 15 }",
+            ),
+            (
+                AmmInstruction::SwapExactOut(SwapExactOutInstruction { amount_out: 100, maximum_amount_in: 110 }),
+                "SwapExactOut { out: 100, max_in: 110 }",
+            ),
+            (
+                AmmInstruction::SetFees(fees.clone()),
+                "SetFees { trade: 25/10000, owner_trade: 5/10000, owner_withdraw: 1/10000, host: 20/10000 }",
+            ),
+            (AmmInstruction::Pause, "Pause"),
+            (AmmInstruction::Unpause, "Unpause"),
+            (AmmInstruction::WithdrawFees(7), "WithdrawFees { amount: 7 }"),
+            (
+                AmmInstruction::TransferStateOwnership { new_owner: owner },
+                "TransferStateOwnership { new_owner: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi }",
+            ),
+            (AmmInstruction::AcceptStateOwnership, "AcceptStateOwnership"),
+            (AmmInstruction::ClosePool, "ClosePool"),
+            (
+                AmmInstruction::InitializeProgramState {
+                    initial_supply: 1_000,
+                    fees: fees.clone(),
+                    curve: curve.clone(),
+                },
+                "InitializeProgramState { initial_supply: 1000, trade: 25/10000, owner_trade: 5/10000, \
+                 owner_withdraw: 1/10000, host: 20/10000, curve: ConstantPrice }",
+            ),
+            (
+                AmmInstruction::UpdateProgramState { initial_supply: 1_000, fees, curve },
+                "UpdateProgramState { initial_supply: 1000, trade: 25/10000, owner_trade: 5/10000, \
+                 owner_withdraw: 1/10000, host: 20/10000, curve: ConstantPrice }",
+            ),
+            (
+                AmmInstruction::Initialize2(Initialize2Instruction {
+                    nonce: 254,
+                    initial_token_a: 1,
+                    initial_token_b: 2,
+                    min_pool_tokens: 3,
+                }),
+                "Initialize2 { nonce: 254, token_a: 1, token_b: 2, min_pool_tokens: 3 }",
+            ),
+            (
+                AmmInstruction::Swap2(SwapInstruction2 { amount_in: 10, minimum_amount_out: 9, direction: None }),
+                "Swap2 { in: 10, min_out: 9 }",
+            ),
+            (
+                AmmInstruction::Swap2(SwapInstruction2 {
+                    amount_in: 10,
+                    minimum_amount_out: 9,
+                    direction: Some(TradeDirection::BtoA),
+                }),
+                "Swap2 { in: 10, min_out: 9, direction: BtoA }",
+            ),
+        ];
+        for (instruction, snapshot) in snapshots {
+            assert_eq!(instruction.to_string(), snapshot);
+        }
+    }
+
+    /// Reordering or inserting a variant changes the first byte every
+    /// deployed client sends
+    #[test]
+    fn test_stable_discriminants() {
+        let pinned = [
+            (
+                0,
+                AmmInstruction::Initialize(InitializeInstruction {
+                    nonce: 255,
+                    swap_curve: None,
+                }),
+            ),
+            (
+                1,
+                AmmInstruction::Swap(SwapInstruction {
+                    amount_in: 1,
+                    minimum_amount_out: 1,
+                }),
+            ),
+            (
+                2,
+                AmmInstruction::DepositAllTokenTypes(DepositInstruction {
+                    pool_token_amount: 1,
+                    maximum_token_a_amount: 1,
+                    maximum_token_b_amount: 1,
+                }),
+            ),
+            (
+                3,
+                AmmInstruction::WithdrawAllTokenTypes(WithdrawInstruction {
+                    pool_token_amount: 1,
+                    minimum_token_a_amount: 1,
+                    minimum_token_b_amount: 1,
+                }),
+            ),
+            (
+                4,
+                AmmInstruction::DepositSingleTokenTypeExactAmountIn(
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount: 1,
+                        minimum_pool_token_amount: 1,
+                    },
+                ),
+            ),
+            (
+                5,
+                AmmInstruction::WithdrawSingleTokenTypeExactAmountOut(
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount: 1,
+                        maximum_pool_token_amount: 1,
+                    },
+                ),
+            ),
+            (
+                6,
+                AmmInstruction::SwapExactOut(SwapExactOutInstruction {
+                    amount_out: 1,
+                    maximum_amount_in: 1,
+                }),
+            ),
+            (7, AmmInstruction::SetFees(Fees::default())),
+            (8, AmmInstruction::Pause),
+            (9, AmmInstruction::Unpause),
+            (10, AmmInstruction::WithdrawFees(1)),
+            (
+                11,
+                AmmInstruction::TransferStateOwnership {
+                    new_owner: Pubkey::new_unique(),
+                },
+            ),
+            (12, AmmInstruction::AcceptStateOwnership),
+            (13, AmmInstruction::ClosePool),
+            (
+                14,
+                AmmInstruction::InitializeProgramState {
+                    initial_supply: 1,
+                    fees: Fees::default(),
+                    curve: SwapCurve::default(),
+                },
+            ),
+            (
+                15,
+                AmmInstruction::UpdateProgramState {
+                    initial_supply: 1,
+                    fees: Fees::default(),
+                    curve: SwapCurve::default(),
+                },
+            ),
+            (
+                16,
+                AmmInstruction::Initialize2(Initialize2Instruction {
+                    nonce: 255,
+                    initial_token_a: 1,
+                    initial_token_b: 1,
+                    min_pool_tokens: 1,
+                }),
+            ),
+            (
+                17,
+                AmmInstruction::Swap2(SwapInstruction2 {
+                    amount_in: 1,
+                    minimum_amount_out: 1,
+                    direction: None,
+                }),
+            ),
+        ];
+
+        for (tag, instruction) in pinned.iter() {
+            assert_eq!(instruction.pack()[0], *tag, "{:?}", instruction);
+            assert_eq!(instruction.tag(), *tag, "{:?}", instruction);
+        }
+        assert_eq!(
+            AmmInstruction::unpack(&[18]),
+            Err(AmmError::InvalidInstruction.into())
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! Instructions and state of the Cropper AMM program
+//!
+//! The crate does not define an entrypoint, so it can be linked into other
+//! on-chain programs. The `no-entrypoint` feature is kept for dependents to
+//! enable it like for the farm crate, and guards any entrypoint added here.
+//!
+//! There is no `std` feature and the crate is not `no_std`: solana-program,
+//! which provides `Pubkey`, `Pack` and `ProgramError` to every module,
+//! spl-token and thiserror all require std, and the curves share their
+//! calculator through an `Arc`. Embedders avoiding heap allocation encode
+//! with [instruction::AmmInstruction::pack_into], and
+//! [instruction::AmmInstruction::unpack] only allocates the calculator of an
+//! `Initialize` carrying a curve.
+//!
+//! The builders take the token program of the pool, spl-token or
+//! [spl_token_2022], but pass no token mints: mints with extensions requiring
+//! `TransferChecked`, a transfer fee or a transfer hook, are not supported,
+//! and [curve::fees::amount_after_transfer_fee] only quotes their swaps.
+
+pub mod curve;
+pub mod error;
+pub mod instruction;
+pub mod logs;
+pub mod parsed;
+#[cfg(feature = "offchain")]
+pub mod pool_analysis;
+pub mod state;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Size of a swap account in the latest layout, see [state::SwapVersion::LATEST_LEN]
+pub const SWAP_ACCOUNT_LEN: usize = state::SwapVersion::LATEST_LEN;
+
+/// Size of a program state account of the original layout, see
+/// [state::ProgramState]
+pub const PROGRAM_STATE_LEN: usize =
+    <state::ProgramState as solana_program::program_pack::Pack>::LEN;
+
+/// Size of a program state account able to hold a pending owner, see
+/// [state::ProgramStateV2]
+pub const PROGRAM_STATE_V2_LEN: usize =
+    <state::ProgramStateV2 as solana_program::program_pack::Pack>::LEN;
+
+solana_program::declare_id!("CTMAxxk34HjKWxQ3QLZK1HpaLXmBveao3ESePXbiyfzh");
+
+/// Compute budget program, setting the compute unit limit of a transaction
+pub mod compute_budget {
+    solana_program::declare_id!("ComputeBudget111111111111111111111111111111");
+}
+
+/// Token-2022 program, accepted next to spl-token as the token program of a
+/// pool whose mints need no `TransferChecked`
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
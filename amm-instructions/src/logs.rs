@@ -0,0 +1,404 @@
+//! Structured log lines of swaps, deposits and withdrawals, and of farm
+//! harvests, so that indexers do not have to infer them from token balance
+//! changes.
+//!
+//! A line is a prefix, an event name and `key=value` fields, e.g.
+//! `cropper-amm: swap in=100 out=98 fee=1 pool=<base58>`. New fields may be
+//! appended to an event, [parse_log] ignoring the fields it does not know.
+//!
+//! Any program can log such a line: the events of a transaction are read
+//! from its log messages with [parse_program_logs], which only takes the
+//! lines logged by the AMM and the farm programs themselves.
+
+use solana_program::{msg, pubkey::Pubkey};
+use std::{fmt, str::FromStr};
+
+/// Prefix of the log lines of the AMM program
+pub const AMM_LOG_PREFIX: &str = "cropper-amm:";
+
+/// Prefix of the log lines of the farm program, written by `emit_harvest_log`
+/// of the farm crate
+pub const FARM_LOG_PREFIX: &str = "cropper-farm:";
+
+/// Prefix added by the runtime to the lines logged with `msg!`
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+/// Prefix of the lines logged by the runtime, `Program <id> invoke [<depth>]`,
+/// `Program <id> success` and `Program <id> failed: <error>` framing the
+/// lines logged by a program
+const PROGRAM_PREFIX: &str = "Program ";
+
+/// Event logged by the AMM or the farm program
+#[derive(Clone, Debug, PartialEq)]
+pub enum CropperEvent {
+    /// Swap of `amount_in` source tokens for `amount_out` destination tokens,
+    /// `fee` source tokens being taken as trading fees
+    Swap {
+        /// Source tokens transferred to the pool
+        amount_in: u64,
+        /// Destination tokens transferred to the user
+        amount_out: u64,
+        /// Trading fees, in source tokens
+        fee: u64,
+        /// Token-swap
+        pool: Pubkey,
+    },
+    /// Deposit of token A and B for pool tokens
+    Deposit {
+        /// Token A transferred to the pool
+        token_a_amount: u64,
+        /// Token B transferred to the pool
+        token_b_amount: u64,
+        /// Pool tokens minted to the user
+        pool_token_amount: u64,
+        /// Token-swap
+        pool: Pubkey,
+    },
+    /// Withdrawal of token A and B for pool tokens
+    Withdraw {
+        /// Token A transferred to the user
+        token_a_amount: u64,
+        /// Token B transferred to the user
+        token_b_amount: u64,
+        /// Pool tokens burnt
+        pool_token_amount: u64,
+        /// Token-swap
+        pool: Pubkey,
+    },
+    /// Harvest of the rewards of a farm
+    Harvest {
+        /// Rewards transferred to the user, net of the harvest fee
+        reward: u64,
+        /// Farm account
+        farm: Pubkey,
+    },
+}
+
+/// Log line of the event, without the runtime prefix
+impl fmt::Display for CropperEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Swap {
+                amount_in,
+                amount_out,
+                fee,
+                pool,
+            } => write!(
+                f,
+                "{} swap in={} out={} fee={} pool={}",
+                AMM_LOG_PREFIX, amount_in, amount_out, fee, pool
+            ),
+            Self::Deposit {
+                token_a_amount,
+                token_b_amount,
+                pool_token_amount,
+                pool,
+            } => write!(
+                f,
+                "{} deposit a={} b={} pool_tokens={} pool={}",
+                AMM_LOG_PREFIX, token_a_amount, token_b_amount, pool_token_amount, pool
+            ),
+            Self::Withdraw {
+                token_a_amount,
+                token_b_amount,
+                pool_token_amount,
+                pool,
+            } => write!(
+                f,
+                "{} withdraw a={} b={} pool_tokens={} pool={}",
+                AMM_LOG_PREFIX, token_a_amount, token_b_amount, pool_token_amount, pool
+            ),
+            Self::Harvest { reward, farm } => write!(
+                f,
+                "{} harvest reward={} farm={}",
+                FARM_LOG_PREFIX, reward, farm
+            ),
+        }
+    }
+}
+
+/// Logs a [CropperEvent::Swap]
+pub fn emit_swap_log(amount_in: u64, amount_out: u64, fee: u64, pool: &Pubkey) {
+    msg!(
+        "{}",
+        CropperEvent::Swap {
+            amount_in,
+            amount_out,
+            fee,
+            pool: *pool,
+        }
+    );
+}
+
+/// Logs a [CropperEvent::Deposit]
+pub fn emit_deposit_log(
+    token_a_amount: u64,
+    token_b_amount: u64,
+    pool_token_amount: u64,
+    pool: &Pubkey,
+) {
+    msg!(
+        "{}",
+        CropperEvent::Deposit {
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+            pool: *pool,
+        }
+    );
+}
+
+/// Logs a [CropperEvent::Withdraw]
+pub fn emit_withdraw_log(
+    token_a_amount: u64,
+    token_b_amount: u64,
+    pool_token_amount: u64,
+    pool: &Pubkey,
+) {
+    msg!(
+        "{}",
+        CropperEvent::Withdraw {
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+            pool: *pool,
+        }
+    );
+}
+
+/// `key=value` fields of a log line
+struct LogFields<'a>(Vec<(&'a str, &'a str)>);
+
+impl<'a> LogFields<'a> {
+    fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        let (_, value) = self.0.iter().find(|(name, _)| *name == key)?;
+        value.parse().ok()
+    }
+}
+
+/// Parses a log line of the AMM or the farm program, with or without the
+/// `Program log: ` prefix of the runtime. Returns `None` for other lines,
+/// unknown events and events missing a field.
+///
+/// The line is not known to come from a Cropper program, see
+/// [parse_program_logs] for the log messages of a transaction.
+pub fn parse_log(line: &str) -> Option<CropperEvent> {
+    let line = line.trim();
+    let line = line.strip_prefix(PROGRAM_LOG_PREFIX).unwrap_or(line);
+    let (prefix, line) = if let Some(line) = line.strip_prefix(AMM_LOG_PREFIX) {
+        (AMM_LOG_PREFIX, line)
+    } else {
+        (FARM_LOG_PREFIX, line.strip_prefix(FARM_LOG_PREFIX)?)
+    };
+    let mut words = line.split_whitespace();
+    let event = words.next()?;
+    let fields = LogFields(words.filter_map(|word| word.split_once('=')).collect());
+    match (prefix, event) {
+        (AMM_LOG_PREFIX, "swap") => Some(CropperEvent::Swap {
+            amount_in: fields.parse("in")?,
+            amount_out: fields.parse("out")?,
+            fee: fields.parse("fee")?,
+            pool: fields.parse("pool")?,
+        }),
+        (AMM_LOG_PREFIX, "deposit") => Some(CropperEvent::Deposit {
+            token_a_amount: fields.parse("a")?,
+            token_b_amount: fields.parse("b")?,
+            pool_token_amount: fields.parse("pool_tokens")?,
+            pool: fields.parse("pool")?,
+        }),
+        (AMM_LOG_PREFIX, "withdraw") => Some(CropperEvent::Withdraw {
+            token_a_amount: fields.parse("a")?,
+            token_b_amount: fields.parse("b")?,
+            pool_token_amount: fields.parse("pool_tokens")?,
+            pool: fields.parse("pool")?,
+        }),
+        (FARM_LOG_PREFIX, "harvest") => Some(CropperEvent::Harvest {
+            reward: fields.parse("reward")?,
+            farm: fields.parse("farm")?,
+        }),
+        _ => None,
+    }
+}
+
+/// Parses the log messages of a transaction, returning the events logged by
+/// the AMM program `amm_program_id` and the farm program `farm_program_id`,
+/// including from inner instructions, in order.
+///
+/// The `invoke` and `success` or `failed` lines of the runtime are followed
+/// to know the program logging each line: AMM events are only taken from the
+/// AMM program, farm events from the farm program, and the lines of other
+/// programs are ignored. Parsing stops at a truncated log.
+pub fn parse_program_logs<S: AsRef<str>>(
+    logs: &[S],
+    amm_program_id: &Pubkey,
+    farm_program_id: &Pubkey,
+) -> Vec<CropperEvent> {
+    let mut invoked: Vec<Option<Pubkey>> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let line = line.as_ref();
+        if line.starts_with(PROGRAM_LOG_PREFIX) {
+            let (event, program_id) = match parse_log(line) {
+                Some(event @ CropperEvent::Harvest { .. }) => (event, farm_program_id),
+                Some(event) => (event, amm_program_id),
+                None => continue,
+            };
+            if invoked.last() == Some(&Some(*program_id)) {
+                events.push(event);
+            }
+        } else if let Some(frame) = line.strip_prefix(PROGRAM_PREFIX) {
+            let mut words = frame.split_whitespace();
+            let program_id = words.next().and_then(|id| id.parse().ok());
+            match words.next() {
+                Some("invoke") => invoked.push(program_id),
+                Some("success") | Some("failed:") => {
+                    invoked.pop();
+                }
+                _ => {}
+            }
+        } else if line == "Log truncated" {
+            break;
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events() -> Vec<CropperEvent> {
+        let (pool, farm) = (Pubkey::new_unique(), Pubkey::new_unique());
+        vec![
+            CropperEvent::Swap {
+                amount_in: 100,
+                amount_out: 98,
+                fee: 1,
+                pool,
+            },
+            CropperEvent::Deposit {
+                token_a_amount: u64::MAX,
+                token_b_amount: 0,
+                pool_token_amount: 7,
+                pool,
+            },
+            CropperEvent::Withdraw {
+                token_a_amount: 3,
+                token_b_amount: 4,
+                pool_token_amount: 5,
+                pool,
+            },
+            CropperEvent::Harvest {
+                reward: 99_000,
+                farm,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_parse_log() {
+        for event in events() {
+            let line = event.to_string();
+            assert_eq!(parse_log(&line), Some(event.clone()));
+            assert_eq!(
+                parse_log(&format!("{}{}", PROGRAM_LOG_PREFIX, line)),
+                Some(event.clone())
+            );
+            // fields appended by later versions are ignored, wherever they are
+            assert_eq!(
+                parse_log(&format!("{} slot=12 memo", line)),
+                Some(event.clone())
+            );
+            let (name, fields) = line.split_at(line.find('=').unwrap());
+            let (name, first) = name.rsplit_once(' ').unwrap();
+            assert_eq!(
+                parse_log(&format!("{} version=2 {}{}", name, first, fields)),
+                Some(event.clone())
+            );
+            // a missing field
+            let (truncated, _) = line.rsplit_once(' ').unwrap();
+            assert_eq!(parse_log(truncated), None);
+        }
+        let pool = Pubkey::new_unique();
+        assert_eq!(
+            parse_log(&format!("cropper-amm: swap in=1 out=x fee=0 pool={}", pool)),
+            None
+        );
+        assert_eq!(parse_log("cropper-amm: mint amount=1"), None);
+        assert_eq!(
+            parse_log("cropper-farm: swap in=1 out=1 fee=0 pool=1"),
+            None
+        );
+        assert_eq!(parse_log("Program log: Instruction: Swap"), None);
+    }
+
+    #[test]
+    fn test_log_lines() {
+        let events = events();
+        let (pool, farm) = match (&events[0], &events[3]) {
+            (CropperEvent::Swap { pool, .. }, CropperEvent::Harvest { farm, .. }) => (pool, farm),
+            _ => unreachable!(),
+        };
+        // the harvest line is the one of `emit_harvest_log` of the farm crate
+        let lines = [
+            format!("cropper-amm: swap in=100 out=98 fee=1 pool={}", pool),
+            format!(
+                "cropper-amm: deposit a={} b=0 pool_tokens=7 pool={}",
+                u64::MAX,
+                pool
+            ),
+            format!("cropper-amm: withdraw a=3 b=4 pool_tokens=5 pool={}", pool),
+            format!("cropper-farm: harvest reward=99000 farm={}", farm),
+        ];
+        for (event, line) in events.iter().zip(&lines) {
+            assert_eq!(event.to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn test_parse_program_logs() {
+        let (amm, farm, other) = (crate::id(), Pubkey::new_unique(), Pubkey::new_unique());
+        let events = events();
+        let log = |event: &CropperEvent| format!("{}{}", PROGRAM_LOG_PREFIX, event);
+        let logs = vec![
+            // a spoofed swap of another program
+            format!("Program {} invoke [1]", other),
+            log(&events[0]),
+            // the AMM invoked by another program
+            format!("Program {} invoke [2]", amm),
+            "Program log: Instruction: Swap".to_string(),
+            log(&events[0]),
+            // a harvest log is not the AMM's
+            log(&events[3]),
+            format!("Program {} consumed 2000 of 200000 compute units", amm),
+            format!("Program {} success", amm),
+            // back in the frame of the other program
+            log(&events[1]),
+            format!("Program {} success", other),
+            format!("Program {} invoke [1]", farm),
+            log(&events[3]),
+            // nor is a withdraw log the farm's
+            log(&events[2]),
+            format!("Program {} invoke [2]", other),
+            log(&events[3]),
+            format!("Program {} success", other),
+            format!("Program {} success", farm),
+            format!("Program {} invoke [1]", amm),
+            log(&events[2]),
+            format!("Program {} failed: custom program error: 0x1", amm),
+            log(&events[1]),
+            "Log truncated".to_string(),
+            format!("Program {} invoke [1]", amm),
+            log(&events[1]),
+        ];
+        assert_eq!(
+            parse_program_logs(&logs, &amm, &farm),
+            vec![events[0].clone(), events[3].clone(), events[2].clone()]
+        );
+        assert_eq!(
+            parse_program_logs(&logs, &other, &farm),
+            vec![events[0].clone(), events[1].clone(), events[3].clone()]
+        );
+        assert!(parse_program_logs::<String>(&[], &amm, &farm).is_empty());
+    }
+}
@@ -0,0 +1,1007 @@
+//! Instructions decoded together with the named accounts they operate on
+
+use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::instruction::{
+    AmmInstruction, DepositInstruction, DepositSingleTokenTypeExactAmountIn,
+    Initialize2Instruction, InitializeInstruction, SwapExactOutInstruction, SwapInstruction,
+    SwapInstruction2, WithdrawInstruction, WithdrawSingleTokenTypeExactAmountOut,
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Accounts and data of an `Initialize` or `Initialize2` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedInitialize<T = InitializeInstruction> {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// AMMID of the swap
+    pub amm_id: Pubkey,
+    /// token_a Account of the pool
+    pub token_a: Pubkey,
+    /// token_b Account of the pool
+    pub token_b: Pubkey,
+    /// Pool Token Mint
+    pub pool_mint: Pubkey,
+    /// Token A Account to transfer fees when swap
+    pub token_a_fee: Pubkey,
+    /// Token B Account to transfer fees when swap
+    pub token_b_fee: Pubkey,
+    /// Pool Token Account receiving the initial supply
+    pub destination: Pubkey,
+    /// Market ID
+    pub market: Pubkey,
+    /// Dex Program ID
+    pub dex_program_id: Pubkey,
+    /// Decoded instruction data
+    pub instruction: T,
+}
+
+/// Accounts and data of a `Swap`, `Swap2` or `SwapExactOut` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedSwap<T> {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// user transfer authority
+    pub user_transfer_authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// SOURCE Account of the user
+    pub source: Pubkey,
+    /// Base Account to swap INTO
+    pub swap_source: Pubkey,
+    /// Base Account to swap FROM
+    pub swap_destination: Pubkey,
+    /// DESTINATION Account of the user
+    pub destination: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Fee token account
+    pub fee_account: Pubkey,
+    /// Fee wallet, only passed for pools trading SOL
+    pub fee_wallet: Option<Pubkey>,
+    /// Host fee account, only passed by frontends earning a share of the fees
+    pub host_fee_account: Option<Pubkey>,
+    /// Signers of a user transfer authority that is an spl-token multisig,
+    /// see [AmmInstruction::unpack_with_signers]
+    pub signers: Vec<Pubkey>,
+    /// Decoded instruction data
+    pub instruction: T,
+}
+
+/// Accounts and data of a `DepositAllTokenTypes` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedDeposit {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// user transfer authority
+    pub user_transfer_authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// token_a user Account
+    pub user_token_a: Pubkey,
+    /// token_b user Account
+    pub user_token_b: Pubkey,
+    /// token_a Base Account to deposit into
+    pub swap_token_a: Pubkey,
+    /// token_b Base Account to deposit into
+    pub swap_token_b: Pubkey,
+    /// Pool MINT account
+    pub pool_mint: Pubkey,
+    /// Pool Account receiving the generated tokens
+    pub user_pool_token: Pubkey,
+    /// Signers of a user transfer authority that is an spl-token multisig,
+    /// see [AmmInstruction::unpack_with_signers]
+    pub signers: Vec<Pubkey>,
+    /// Decoded instruction data
+    pub instruction: DepositInstruction,
+}
+
+/// Accounts and data of a `WithdrawAllTokenTypes` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedWithdraw {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// user transfer authority
+    pub user_transfer_authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// Pool mint account
+    pub pool_mint: Pubkey,
+    /// SOURCE Pool account of the user
+    pub user_pool_token: Pubkey,
+    /// token_a Swap Account to withdraw FROM
+    pub swap_token_a: Pubkey,
+    /// token_b Swap Account to withdraw FROM
+    pub swap_token_b: Pubkey,
+    /// token_a user Account to credit
+    pub user_token_a: Pubkey,
+    /// token_b user Account to credit
+    pub user_token_b: Pubkey,
+    /// Fee account, to receive withdrawal fees
+    pub pool_fee_account: Pubkey,
+    /// Signers of a user transfer authority that is an spl-token multisig,
+    /// see [AmmInstruction::unpack_with_signers]
+    pub signers: Vec<Pubkey>,
+    /// Decoded instruction data
+    pub instruction: WithdrawInstruction,
+}
+
+/// Accounts and data of a `DepositSingleTokenTypeExactAmountIn` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedDepositSingle {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// user transfer authority
+    pub user_transfer_authority: Pubkey,
+    /// SOURCE Account of the user
+    pub source: Pubkey,
+    /// token_a Swap Account
+    pub swap_token_a: Pubkey,
+    /// token_b Swap Account
+    pub swap_token_b: Pubkey,
+    /// Pool MINT account
+    pub pool_mint: Pubkey,
+    /// Pool Account receiving the generated tokens
+    pub user_pool_token: Pubkey,
+    /// Decoded instruction data
+    pub instruction: DepositSingleTokenTypeExactAmountIn,
+}
+
+/// Accounts and data of a `WithdrawSingleTokenTypeExactAmountOut` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedWithdrawSingle {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// user transfer authority
+    pub user_transfer_authority: Pubkey,
+    /// Pool mint account
+    pub pool_mint: Pubkey,
+    /// SOURCE Pool account of the user
+    pub user_pool_token: Pubkey,
+    /// token_a Swap Account
+    pub swap_token_a: Pubkey,
+    /// token_b Swap Account
+    pub swap_token_b: Pubkey,
+    /// User Account to credit
+    pub destination: Pubkey,
+    /// Fee account, to receive withdrawal fees
+    pub pool_fee_account: Pubkey,
+    /// Decoded instruction data
+    pub instruction: WithdrawSingleTokenTypeExactAmountOut,
+}
+
+/// Accounts and data of a `SetFees` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedSetFees {
+    /// Program state account
+    pub state: Pubkey,
+    /// State owner
+    pub state_owner: Pubkey,
+    /// New fees
+    pub fees: Fees,
+}
+
+/// Accounts of a `Pause` or `Unpause` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedPause {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// State owner
+    pub state_owner: Pubkey,
+}
+
+/// Accounts and amount of a `WithdrawFees` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedWithdrawFees {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// Fee owner
+    pub fee_owner: Pubkey,
+    /// Fee token account of the pool
+    pub fee_account: Pubkey,
+    /// Destination token account
+    pub destination: Pubkey,
+    /// Amount to withdraw, 0 meaning the whole balance
+    pub amount: u64,
+}
+
+/// Accounts and data of a `TransferStateOwnership` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedTransferStateOwnership {
+    /// Program state account
+    pub state: Pubkey,
+    /// State owner
+    pub state_owner: Pubkey,
+    /// Proposed state owner
+    pub new_owner: Pubkey,
+}
+
+/// Accounts of an `AcceptStateOwnership` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedAcceptStateOwnership {
+    /// Program state account
+    pub state: Pubkey,
+    /// Pending state owner
+    pub pending_owner: Pubkey,
+}
+
+/// Accounts of a `ClosePool` instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedClosePool {
+    /// Token-swap
+    pub swap: Pubkey,
+    /// swap authority
+    pub authority: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// State owner or last liquidity provider
+    pub closer: Pubkey,
+    /// token_a Swap Account
+    pub swap_token_a: Pubkey,
+    /// token_b Swap Account
+    pub swap_token_b: Pubkey,
+    /// token_a destination
+    pub destination_a: Pubkey,
+    /// token_b destination
+    pub destination_b: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Pool token account of the closer, absent when closed by the state owner
+    pub pool_token: Option<Pubkey>,
+}
+
+/// Accounts and data of an `InitializeProgramState` or `UpdateProgramState`
+/// instruction
+#[derive(Debug, PartialEq)]
+pub struct ParsedSetProgramState {
+    /// Program state account
+    pub state: Pubkey,
+    /// State owner
+    pub state_owner: Pubkey,
+    /// Fee owner
+    pub fee_owner: Pubkey,
+    /// Pool tokens minted to the creator of a new pool
+    pub initial_supply: u64,
+    /// Fees of the pools
+    pub fees: Fees,
+    /// Curve of the pools not carrying their own
+    pub curve: SwapCurve,
+}
+
+/// An [AmmInstruction] with its accounts mapped to their roles
+#[derive(Debug, PartialEq)]
+pub enum ParsedAmmInstruction {
+    /// See [AmmInstruction::Initialize]
+    Initialize(ParsedInitialize),
+    /// See [AmmInstruction::Swap]
+    Swap(ParsedSwap<SwapInstruction>),
+    /// See [AmmInstruction::DepositAllTokenTypes]
+    DepositAllTokenTypes(ParsedDeposit),
+    /// See [AmmInstruction::WithdrawAllTokenTypes]
+    WithdrawAllTokenTypes(ParsedWithdraw),
+    /// See [AmmInstruction::DepositSingleTokenTypeExactAmountIn]
+    DepositSingleTokenTypeExactAmountIn(ParsedDepositSingle),
+    /// See [AmmInstruction::WithdrawSingleTokenTypeExactAmountOut]
+    WithdrawSingleTokenTypeExactAmountOut(ParsedWithdrawSingle),
+    /// See [AmmInstruction::SwapExactOut]
+    SwapExactOut(ParsedSwap<SwapExactOutInstruction>),
+    /// See [AmmInstruction::SetFees]
+    SetFees(ParsedSetFees),
+    /// See [AmmInstruction::Pause]
+    Pause(ParsedPause),
+    /// See [AmmInstruction::Unpause]
+    Unpause(ParsedPause),
+    /// See [AmmInstruction::WithdrawFees]
+    WithdrawFees(ParsedWithdrawFees),
+    /// See [AmmInstruction::TransferStateOwnership]
+    TransferStateOwnership(ParsedTransferStateOwnership),
+    /// See [AmmInstruction::AcceptStateOwnership]
+    AcceptStateOwnership(ParsedAcceptStateOwnership),
+    /// See [AmmInstruction::ClosePool]
+    ClosePool(ParsedClosePool),
+    /// See [AmmInstruction::InitializeProgramState]
+    InitializeProgramState(ParsedSetProgramState),
+    /// See [AmmInstruction::UpdateProgramState]
+    UpdateProgramState(ParsedSetProgramState),
+    /// See [AmmInstruction::Initialize2]
+    Initialize2(ParsedInitialize<Initialize2Instruction>),
+    /// See [AmmInstruction::Swap2]
+    Swap2(ParsedSwap<SwapInstruction2>),
+}
+
+/// Checks the number of accounts of an instruction, returning them
+fn expect_accounts(account_keys: &[Pubkey], len: usize) -> Result<&[Pubkey], ProgramError> {
+    if account_keys.len() != len {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    Ok(account_keys)
+}
+
+/// Splits the signers of an spl-token multisig user transfer authority off
+/// the end of `account_keys`, the accounts flagged in `is_signer` following
+/// the `len` accounts an instruction takes at least. None of the optional
+/// accounts signs, so that they are told apart from the signers
+fn split_signers<'a>(
+    account_keys: &'a [Pubkey],
+    is_signer: &[bool],
+    len: usize,
+) -> Result<(&'a [Pubkey], Vec<Pubkey>), ProgramError> {
+    if is_signer.len() != account_keys.len() {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let signers = is_signer
+        .iter()
+        .skip(len)
+        .rev()
+        .take_while(|is_signer| **is_signer)
+        .count();
+    let (accounts, signers) = account_keys.split_at(account_keys.len() - signers);
+    Ok((accounts, signers.to_vec()))
+}
+
+fn parse_initialize<T>(
+    account_keys: &[Pubkey],
+    instruction: T,
+) -> Result<ParsedInitialize<T>, ProgramError> {
+    let k = expect_accounts(account_keys, 13)?;
+    Ok(ParsedInitialize {
+        swap: k[0],
+        authority: k[1],
+        state: k[2],
+        amm_id: k[3],
+        token_a: k[4],
+        token_b: k[5],
+        pool_mint: k[6],
+        token_a_fee: k[7],
+        token_b_fee: k[8],
+        destination: k[9],
+        market: k[10],
+        dex_program_id: k[12],
+        instruction,
+    })
+}
+
+fn parse_swap<T>(
+    account_keys: &[Pubkey],
+    is_signer: &[bool],
+    instruction: T,
+) -> Result<ParsedSwap<T>, ProgramError> {
+    let (account_keys, signers) = split_signers(account_keys, is_signer, 11)?;
+    // pools trading SOL also pass the fee wallet and the system program,
+    // and a host fee account may follow either layout
+    let k = match account_keys.len() {
+        12..=14 => account_keys,
+        _ => expect_accounts(account_keys, 11)?,
+    };
+    let with_sol = k.len() >= 13;
+    Ok(ParsedSwap {
+        swap: k[0],
+        authority: k[1],
+        user_transfer_authority: k[2],
+        state: k[3],
+        source: k[4],
+        swap_source: k[5],
+        swap_destination: k[6],
+        destination: k[7],
+        pool_mint: k[8],
+        fee_account: k[9],
+        fee_wallet: if with_sol { Some(k[10]) } else { None },
+        host_fee_account: match k.len() {
+            12 | 14 => k.last().copied(),
+            _ => None,
+        },
+        signers,
+        instruction,
+    })
+}
+
+/// Account roles of the instructions with a fixed account list, named as
+/// the fields of their parsed structs
+const INITIALIZE_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "state",
+    "amm_id",
+    "token_a",
+    "token_b",
+    "pool_mint",
+    "token_a_fee",
+    "token_b_fee",
+    "destination",
+    "market",
+    "token_program",
+    "dex_program_id",
+];
+const DEPOSIT_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "user_transfer_authority",
+    "state",
+    "user_token_a",
+    "user_token_b",
+    "swap_token_a",
+    "swap_token_b",
+    "pool_mint",
+    "user_pool_token",
+    "token_program",
+];
+const WITHDRAW_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "user_transfer_authority",
+    "state",
+    "pool_mint",
+    "user_pool_token",
+    "swap_token_a",
+    "swap_token_b",
+    "user_token_a",
+    "user_token_b",
+    "pool_fee_account",
+    "token_program",
+];
+const DEPOSIT_SINGLE_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "user_transfer_authority",
+    "source",
+    "swap_token_a",
+    "swap_token_b",
+    "pool_mint",
+    "user_pool_token",
+    "token_program",
+];
+const WITHDRAW_SINGLE_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "user_transfer_authority",
+    "pool_mint",
+    "user_pool_token",
+    "swap_token_a",
+    "swap_token_b",
+    "destination",
+    "pool_fee_account",
+    "token_program",
+];
+const PAUSE_ROLES: &[&str] = &["swap", "state", "state_owner"];
+const WITHDRAW_FEES_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "state",
+    "fee_owner",
+    "fee_account",
+    "destination",
+    "token_program",
+];
+const CLOSE_POOL_ROLES: &[&str] = &[
+    "swap",
+    "authority",
+    "state",
+    "closer",
+    "swap_token_a",
+    "swap_token_b",
+    "destination_a",
+    "destination_b",
+    "pool_mint",
+    "token_program",
+    "pool_token",
+];
+const PROGRAM_STATE_ROLES: &[&str] = &["state", "state_owner", "fee_owner"];
+
+/// Account roles of a swap of `len` accounts, see [parse_swap]
+fn swap_roles(len: usize) -> Vec<&'static str> {
+    let with_sol = len >= 13;
+    let mut roles = vec![
+        "swap",
+        "authority",
+        "user_transfer_authority",
+        "state",
+        "source",
+        "swap_source",
+        "swap_destination",
+        "destination",
+        "pool_mint",
+        "fee_account",
+    ];
+    if with_sol {
+        roles.push("fee_wallet");
+    }
+    roles.push("token_program");
+    if with_sol {
+        roles.push("system_program");
+    }
+    if len == 12 || len == 14 {
+        roles.push("host_fee_account");
+    }
+    roles
+}
+
+fn parse_set_program_state(
+    account_keys: &[Pubkey],
+    initial_supply: u64,
+    fees: Fees,
+    curve: SwapCurve,
+) -> Result<ParsedSetProgramState, ProgramError> {
+    let k = expect_accounts(account_keys, 3)?;
+    Ok(ParsedSetProgramState {
+        state: k[0],
+        state_owner: k[1],
+        fee_owner: k[2],
+        initial_supply,
+        fees,
+        curve,
+    })
+}
+
+impl AmmInstruction {
+    /// Unpacks a byte buffer into a [ParsedAmmInstruction], naming the
+    /// accounts of the instruction, given in the order of the instruction.
+    /// The signers of a multisig user transfer authority are only told apart
+    /// from the optional accounts by [AmmInstruction::unpack_with_signers]
+    pub fn unpack_with_accounts(
+        data: &[u8],
+        account_keys: &[Pubkey],
+    ) -> Result<ParsedAmmInstruction, ProgramError> {
+        Self::unpack_with_signers(data, account_keys, &vec![false; account_keys.len()])
+    }
+
+    /// Same as [AmmInstruction::unpack_with_accounts], `is_signer` flagging
+    /// the accounts signing the transaction. The signers following every
+    /// other account of a swap, deposit or withdrawal are then those of a
+    /// user transfer authority that is an spl-token multisig, e.g. as passed
+    /// by [swap_multisig](crate::instruction::swap_multisig), and are
+    /// returned apart
+    pub fn unpack_with_signers(
+        data: &[u8],
+        account_keys: &[Pubkey],
+        is_signer: &[bool],
+    ) -> Result<ParsedAmmInstruction, ProgramError> {
+        Ok(match Self::unpack(data)? {
+            Self::Initialize(instruction) => {
+                ParsedAmmInstruction::Initialize(parse_initialize(account_keys, instruction)?)
+            }
+            Self::Swap(instruction) => {
+                ParsedAmmInstruction::Swap(parse_swap(account_keys, is_signer, instruction)?)
+            }
+            Self::DepositAllTokenTypes(instruction) => {
+                let (account_keys, signers) = split_signers(account_keys, is_signer, 11)?;
+                let k = expect_accounts(account_keys, 11)?;
+                ParsedAmmInstruction::DepositAllTokenTypes(ParsedDeposit {
+                    swap: k[0],
+                    authority: k[1],
+                    user_transfer_authority: k[2],
+                    state: k[3],
+                    user_token_a: k[4],
+                    user_token_b: k[5],
+                    swap_token_a: k[6],
+                    swap_token_b: k[7],
+                    pool_mint: k[8],
+                    user_pool_token: k[9],
+                    signers,
+                    instruction,
+                })
+            }
+            Self::WithdrawAllTokenTypes(instruction) => {
+                let (account_keys, signers) = split_signers(account_keys, is_signer, 12)?;
+                let k = expect_accounts(account_keys, 12)?;
+                ParsedAmmInstruction::WithdrawAllTokenTypes(ParsedWithdraw {
+                    swap: k[0],
+                    authority: k[1],
+                    user_transfer_authority: k[2],
+                    state: k[3],
+                    pool_mint: k[4],
+                    user_pool_token: k[5],
+                    swap_token_a: k[6],
+                    swap_token_b: k[7],
+                    user_token_a: k[8],
+                    user_token_b: k[9],
+                    pool_fee_account: k[10],
+                    signers,
+                    instruction,
+                })
+            }
+            Self::DepositSingleTokenTypeExactAmountIn(instruction) => {
+                let k = expect_accounts(account_keys, 9)?;
+                ParsedAmmInstruction::DepositSingleTokenTypeExactAmountIn(ParsedDepositSingle {
+                    swap: k[0],
+                    authority: k[1],
+                    user_transfer_authority: k[2],
+                    source: k[3],
+                    swap_token_a: k[4],
+                    swap_token_b: k[5],
+                    pool_mint: k[6],
+                    user_pool_token: k[7],
+                    instruction,
+                })
+            }
+            Self::WithdrawSingleTokenTypeExactAmountOut(instruction) => {
+                let k = expect_accounts(account_keys, 10)?;
+                ParsedAmmInstruction::WithdrawSingleTokenTypeExactAmountOut(ParsedWithdrawSingle {
+                    swap: k[0],
+                    authority: k[1],
+                    user_transfer_authority: k[2],
+                    pool_mint: k[3],
+                    user_pool_token: k[4],
+                    swap_token_a: k[5],
+                    swap_token_b: k[6],
+                    destination: k[7],
+                    pool_fee_account: k[8],
+                    instruction,
+                })
+            }
+            Self::SwapExactOut(instruction) => {
+                let swap = parse_swap(account_keys, is_signer, instruction)?;
+                ParsedAmmInstruction::SwapExactOut(swap)
+            }
+            Self::SetFees(fees) => {
+                let k = expect_accounts(account_keys, 2)?;
+                ParsedAmmInstruction::SetFees(ParsedSetFees {
+                    state: k[0],
+                    state_owner: k[1],
+                    fees,
+                })
+            }
+            Self::Pause => {
+                let k = expect_accounts(account_keys, 3)?;
+                ParsedAmmInstruction::Pause(ParsedPause {
+                    swap: k[0],
+                    state: k[1],
+                    state_owner: k[2],
+                })
+            }
+            Self::Unpause => {
+                let k = expect_accounts(account_keys, 3)?;
+                ParsedAmmInstruction::Unpause(ParsedPause {
+                    swap: k[0],
+                    state: k[1],
+                    state_owner: k[2],
+                })
+            }
+            Self::WithdrawFees(amount) => {
+                let k = expect_accounts(account_keys, 7)?;
+                ParsedAmmInstruction::WithdrawFees(ParsedWithdrawFees {
+                    swap: k[0],
+                    authority: k[1],
+                    state: k[2],
+                    fee_owner: k[3],
+                    fee_account: k[4],
+                    destination: k[5],
+                    amount,
+                })
+            }
+            Self::TransferStateOwnership { new_owner } => {
+                let k = expect_accounts(account_keys, 2)?;
+                ParsedAmmInstruction::TransferStateOwnership(ParsedTransferStateOwnership {
+                    state: k[0],
+                    state_owner: k[1],
+                    new_owner,
+                })
+            }
+            Self::AcceptStateOwnership => {
+                let k = expect_accounts(account_keys, 2)?;
+                ParsedAmmInstruction::AcceptStateOwnership(ParsedAcceptStateOwnership {
+                    state: k[0],
+                    pending_owner: k[1],
+                })
+            }
+            Self::ClosePool => {
+                let k = match account_keys.len() {
+                    11 => account_keys,
+                    _ => expect_accounts(account_keys, 10)?,
+                };
+                ParsedAmmInstruction::ClosePool(ParsedClosePool {
+                    swap: k[0],
+                    authority: k[1],
+                    state: k[2],
+                    closer: k[3],
+                    swap_token_a: k[4],
+                    swap_token_b: k[5],
+                    destination_a: k[6],
+                    destination_b: k[7],
+                    pool_mint: k[8],
+                    pool_token: k.get(10).copied(),
+                })
+            }
+            Self::InitializeProgramState {
+                initial_supply,
+                fees,
+                curve,
+            } => ParsedAmmInstruction::InitializeProgramState(parse_set_program_state(
+                account_keys,
+                initial_supply,
+                fees,
+                curve,
+            )?),
+            Self::UpdateProgramState {
+                initial_supply,
+                fees,
+                curve,
+            } => ParsedAmmInstruction::UpdateProgramState(parse_set_program_state(
+                account_keys,
+                initial_supply,
+                fees,
+                curve,
+            )?),
+            Self::Initialize2(instruction) => {
+                ParsedAmmInstruction::Initialize2(parse_initialize(account_keys, instruction)?)
+            }
+            Self::Swap2(instruction) => {
+                ParsedAmmInstruction::Swap2(parse_swap(account_keys, is_signer, instruction)?)
+            }
+        })
+    }
+
+    /// Roles of the accounts of the instruction, in order, when it is given
+    /// `len` accounts. Names follow the fields of [ParsedAmmInstruction],
+    /// optional trailing accounts being always named
+    pub fn account_roles(&self, len: usize) -> Vec<&'static str> {
+        match self {
+            Self::Initialize(_) | Self::Initialize2(_) => INITIALIZE_ROLES.to_vec(),
+            Self::Swap(_) | Self::SwapExactOut(_) | Self::Swap2(_) => swap_roles(len),
+            Self::DepositAllTokenTypes(_) => DEPOSIT_ROLES.to_vec(),
+            Self::WithdrawAllTokenTypes(_) => WITHDRAW_ROLES.to_vec(),
+            Self::DepositSingleTokenTypeExactAmountIn(_) => DEPOSIT_SINGLE_ROLES.to_vec(),
+            Self::WithdrawSingleTokenTypeExactAmountOut(_) => WITHDRAW_SINGLE_ROLES.to_vec(),
+            Self::SetFees(_) | Self::TransferStateOwnership { .. } => vec!["state", "state_owner"],
+            Self::AcceptStateOwnership => vec!["state", "pending_owner"],
+            Self::Pause | Self::Unpause => PAUSE_ROLES.to_vec(),
+            Self::WithdrawFees(_) => WITHDRAW_FEES_ROLES.to_vec(),
+            Self::ClosePool => CLOSE_POOL_ROLES.to_vec(),
+            Self::InitializeProgramState { .. } | Self::UpdateProgramState { .. } => {
+                PROGRAM_STATE_ROLES.to_vec()
+            }
+        }
+    }
+
+    /// Summary of the instruction followed by one line per account with its
+    /// role, e.g.
+    ///
+    /// ```text
+    /// Swap { in: 1500000, min_out: 1430000 }
+    ///   0. swap: <pubkey>
+    ///   1. authority: <pubkey>
+    /// ```
+    ///
+    /// Accounts beyond the documented ones are listed as `unknown`. Unlike
+    /// [AmmInstruction::unpack_with_accounts] it never fails, to be usable on
+    /// malformed transactions
+    pub fn explain(&self, accounts: &[Pubkey]) -> String {
+        let roles = self.account_roles(accounts.len());
+        let mut explanation = self.to_string();
+        for (index, account) in accounts.iter().enumerate() {
+            let role = roles.get(index).copied().unwrap_or("unknown");
+            explanation.push_str(&format!("\n  {}. {}: {}", index, role, account));
+        }
+        explanation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{
+        deposit_all_token_types_multisig, swap, swap_multisig, withdraw_all_token_types_multisig,
+    };
+    use solana_program::instruction::Instruction;
+
+    /// Parses `instruction` with the keys and signer flags of its accounts, as
+    /// given by a transaction message
+    fn parse(instruction: &Instruction) -> Result<ParsedAmmInstruction, ProgramError> {
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+        let is_signer: Vec<bool> = instruction.accounts.iter().map(|a| a.is_signer).collect();
+        AmmInstruction::unpack_with_signers(&instruction.data, &keys, &is_signer)
+    }
+
+    #[test]
+    fn test_parse_swap_multisig() {
+        let k = Pubkey::new_unique;
+        let (multisig, signer_1, signer_2) = (k(), k(), k());
+        let (fee_wallet, host_fee) = (k(), k());
+        let instruction = SwapInstruction {
+            amount_in: 10,
+            minimum_amount_out: 1,
+        };
+        for (fee_wallet, host_fee) in [
+            (None, None),
+            (None, Some(host_fee)),
+            (Some(fee_wallet), None),
+            (Some(fee_wallet), Some(host_fee)),
+        ] {
+            let swap_ix = swap_multisig(
+                &crate::id(),
+                &spl_token::id(),
+                &k(),
+                &k(),
+                &multisig,
+                &[&signer_1, &signer_2],
+                &k(),
+                &k(),
+                &k(),
+                &k(),
+                &k(),
+                &k(),
+                &k(),
+                fee_wallet.as_ref(),
+                host_fee.as_ref(),
+                instruction,
+            )
+            .unwrap();
+            match parse(&swap_ix).unwrap() {
+                ParsedAmmInstruction::Swap(parsed) => {
+                    assert_eq!(parsed.user_transfer_authority, multisig);
+                    assert_eq!(parsed.fee_wallet, fee_wallet);
+                    assert_eq!(parsed.host_fee_account, host_fee);
+                    assert_eq!(parsed.signers, vec![signer_1, signer_2]);
+                    assert_eq!(parsed.instruction, instruction);
+                }
+                parsed => panic!("not a swap: {:?}", parsed),
+            }
+        }
+
+        // a signing user transfer authority, the host fee account last
+        let swap_ix = swap(
+            &crate::id(),
+            &spl_token::id(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            None,
+            Some(&host_fee),
+            instruction,
+        )
+        .unwrap();
+        match parse(&swap_ix).unwrap() {
+            ParsedAmmInstruction::Swap(parsed) => {
+                assert_eq!(parsed.host_fee_account, Some(host_fee));
+                assert!(parsed.signers.is_empty());
+            }
+            parsed => panic!("not a swap: {:?}", parsed),
+        }
+
+        // the flags are those of every account
+        let keys: Vec<Pubkey> = swap_ix.accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(
+            AmmInstruction::unpack_with_signers(&swap_ix.data, &keys, &[false; 3]),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_parse_deposit_withdraw_multisig() {
+        let k = Pubkey::new_unique;
+        let (multisig, signer_1, signer_2) = (k(), k(), k());
+        let deposit_instruction = DepositInstruction {
+            pool_token_amount: 10,
+            maximum_token_a_amount: 20,
+            maximum_token_b_amount: 20,
+        };
+        let deposit_ix = deposit_all_token_types_multisig(
+            &crate::id(),
+            &spl_token::id(),
+            &k(),
+            &k(),
+            &multisig,
+            &[&signer_1, &signer_2],
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            deposit_instruction,
+        )
+        .unwrap();
+        match parse(&deposit_ix).unwrap() {
+            ParsedAmmInstruction::DepositAllTokenTypes(parsed) => {
+                assert_eq!(parsed.user_transfer_authority, multisig);
+                assert_eq!(parsed.user_pool_token, deposit_ix.accounts[9].pubkey);
+                assert_eq!(parsed.signers, vec![signer_1, signer_2]);
+                assert_eq!(parsed.instruction, deposit_instruction);
+            }
+            parsed => panic!("not a deposit: {:?}", parsed),
+        }
+
+        let withdraw_instruction = WithdrawInstruction {
+            pool_token_amount: 10,
+            minimum_token_a_amount: 1,
+            minimum_token_b_amount: 1,
+        };
+        let withdraw_ix = withdraw_all_token_types_multisig(
+            &crate::id(),
+            &spl_token::id(),
+            &k(),
+            &k(),
+            &multisig,
+            &[&signer_1],
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            &k(),
+            withdraw_instruction,
+        )
+        .unwrap();
+        match parse(&withdraw_ix).unwrap() {
+            ParsedAmmInstruction::WithdrawAllTokenTypes(parsed) => {
+                assert_eq!(parsed.user_transfer_authority, multisig);
+                assert_eq!(parsed.pool_fee_account, withdraw_ix.accounts[10].pubkey);
+                assert_eq!(parsed.signers, vec![signer_1]);
+                assert_eq!(parsed.instruction, withdraw_instruction);
+            }
+            parsed => panic!("not a withdrawal: {:?}", parsed),
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let keys: Vec<Pubkey> = (1..=12).map(|i| Pubkey::new_from_array([i; 32])).collect();
+        let swap = AmmInstruction::Swap(SwapInstruction {
+            amount_in: 1_500_000,
+            minimum_amount_out: 1_430_000,
+        });
+        assert_eq!(
+            swap.explain(&keys[..11]),
+            "Swap { in: 1500000, min_out: 1430000 }
+  0. swap: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi
+  1. authority: 8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR
+  2. user_transfer_authority: CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8
+  3. state: GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq
+  4. source: LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY
+  5. swap_source: QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF
+  6. swap_destination: US517G5965aydkZ46HS38QLi7UQiSojurfbQfKCELFx
+  7. destination: YMN9Qj5jPNp7j14VPcML1B6xGgcPWVZUGLFU3Mnyfaf
+  8. pool_mint: cGfHiC6Kgg3FpFZvgwGcswsCRtp4aBP2fzuXRQPizuN
+  9. fee_account: gBxS1f6uyyGPuW5MzGBukidSb71jdsCb5fZaoSzULE5
+  10. token_program: k7FaK87WHGVXzkaoHb7CdVPgkKDQhZ29VLDeBVbDfYn"
+        );
+        // the twelfth account of a swap is the host fee account
+        assert!(swap
+            .explain(&keys)
+            .ends_with("\n  11. host_fee_account: p2Yicb86aZig616Eav2VWG9vuXR5mEqhtzshZYBxzsV"));
+
+        // accounts beyond the documented ones, or missing ones
+        assert_eq!(
+            AmmInstruction::Pause.explain(&keys[..4]),
+            "Pause
+  0. swap: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi
+  1. state: 8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR
+  2. state_owner: CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8
+  3. unknown: GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq"
+        );
+        assert_eq!(
+            AmmInstruction::AcceptStateOwnership.explain(&keys[..1]),
+            "AcceptStateOwnership
+  0. state: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"
+        );
+        assert_eq!(AmmInstruction::ClosePool.explain(&[]), "ClosePool");
+    }
+}
@@ -0,0 +1,459 @@
+//! Spot price, price impact and value of pools in floating point, for
+//! off-chain analytics only
+
+use crate::{
+    curve::{
+        base::{CurveType, SwapCurve},
+        calculator::TradeDirection,
+        constant_price::ConstantPriceCurve,
+        fees::Fees,
+        offset::OffsetCurve,
+    },
+    error::AmmError,
+    state::{AmmStatus, ProgramState},
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use std::collections::HashMap;
+
+/// Price of one raw unit of token A in raw units of token B.
+///
+/// The calculator is only reachable as a trait object, so its parameters are
+/// recovered from the packed representation.
+fn raw_spot_price(reserve_a: u64, reserve_b: u64, curve: &SwapCurve) -> Result<f64, AmmError> {
+    let mut packed_curve = [0u8; SwapCurve::LEN];
+    SwapCurve::pack_into_slice(curve, &mut packed_curve);
+    let calculator = &packed_curve[1..];
+    let price = match curve.curve_type {
+        CurveType::ConstantProduct => {
+            if reserve_a == 0 || reserve_b == 0 {
+                return Err(AmmError::EmptySupply);
+            }
+            reserve_b as f64 / reserve_a as f64
+        }
+        CurveType::Offset => {
+            let OffsetCurve { token_b_offset } =
+                OffsetCurve::unpack_from_slice(calculator).map_err(|_| AmmError::InvalidCurve)?;
+            if reserve_a == 0 {
+                return Err(AmmError::EmptySupply);
+            }
+            (reserve_b as f64 + token_b_offset as f64) / reserve_a as f64
+        }
+        CurveType::ConstantPrice => {
+            let ConstantPriceCurve { token_b_price } =
+                ConstantPriceCurve::unpack_from_slice(calculator)
+                    .map_err(|_| AmmError::InvalidCurve)?;
+            if token_b_price == 0 {
+                return Err(AmmError::InvalidCurve);
+            }
+            1.0 / token_b_price as f64
+        }
+        CurveType::Stable => return Err(AmmError::UnsupportedCurveOperation),
+    };
+    if price > 0.0 && price.is_finite() {
+        Ok(price)
+    } else {
+        Err(AmmError::EmptySupply)
+    }
+}
+
+/// Price of one token A in token B, in UI amounts given the decimals of both
+/// mints.
+///
+/// Fails with `EmptySupply` instead of returning NaN or infinity when a
+/// reserve the curve prices with is empty, and with
+/// `UnsupportedCurveOperation` for stable curves.
+pub fn spot_price(
+    reserve_a: u64,
+    reserve_b: u64,
+    decimals_a: u8,
+    decimals_b: u8,
+    curve: &SwapCurve,
+) -> Result<f64, AmmError> {
+    let price = raw_spot_price(reserve_a, reserve_b, curve)?;
+    Ok(price * 10f64.powi(i32::from(decimals_a) - i32::from(decimals_b)))
+}
+
+/// Price impact of swapping `amount_in`, as a fraction between 0 and 1 of
+/// the output the spot price would give once fees are taken out.
+///
+/// Reserves are given in trade order, the source reserve first.
+pub fn price_impact(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    trade_direction: TradeDirection,
+    curve: &SwapCurve,
+    fees: &Fees,
+) -> Result<f64, AmmError> {
+    let out_per_in = match trade_direction {
+        TradeDirection::AtoB => raw_spot_price(reserve_in, reserve_out, curve)?,
+        TradeDirection::BtoA => 1.0 / raw_spot_price(reserve_out, reserve_in, curve)?,
+    };
+    let result = curve
+        .swap(
+            u128::from(amount_in),
+            u128::from(reserve_in),
+            u128::from(reserve_out),
+            trade_direction,
+            fees,
+        )
+        .ok_or(AmmError::CalculationFailure)?;
+    let amount_in_less_fees = u128::from(amount_in)
+        .checked_sub(result.trade_fee)
+        .and_then(|amount| amount.checked_sub(result.owner_fee))
+        .ok_or(AmmError::CalculationFailure)?;
+    let ideal_amount_out = amount_in_less_fees as f64 * out_per_in;
+    if ideal_amount_out == 0.0 {
+        return Ok(0.0);
+    }
+    Ok((1.0 - result.destination_amount_swapped as f64 / ideal_amount_out).max(0.0))
+}
+
+/// Analytics of a pool, combining the addresses of its token accounts with
+/// their balances fetched by the caller. The fees and curve of the
+/// [ProgramState] apply to pools that do not store their own.
+pub trait SwapAnalytics: AmmStatus {
+    /// Balances of the token A and token B accounts of the pool, looked up
+    /// by address in `balances`
+    fn reserves(&self, balances: &HashMap<Pubkey, u64>) -> Result<(u64, u64), AmmError> {
+        let reserve =
+            |account: &Pubkey| balances.get(account).copied().ok_or(AmmError::InvalidInput);
+        Ok((
+            reserve(self.token_a_account())?,
+            reserve(self.token_b_account())?,
+        ))
+    }
+
+    /// Price of one token A in token B, see [spot_price]
+    fn spot_price(
+        &self,
+        balances: &HashMap<Pubkey, u64>,
+        decimals_a: u8,
+        decimals_b: u8,
+        state: &ProgramState,
+    ) -> Result<f64, AmmError> {
+        let (reserve_a, reserve_b) = self.reserves(balances)?;
+        let curve = self.swap_curve().unwrap_or_else(|| state.swap_curve());
+        spot_price(reserve_a, reserve_b, decimals_a, decimals_b, curve)
+    }
+
+    /// Price impact of swapping `amount_in` in `trade_direction`, see
+    /// [price_impact]
+    fn price_impact(
+        &self,
+        amount_in: u64,
+        trade_direction: TradeDirection,
+        balances: &HashMap<Pubkey, u64>,
+        state: &ProgramState,
+    ) -> Result<f64, AmmError> {
+        let (reserve_a, reserve_b) = self.reserves(balances)?;
+        let (reserve_in, reserve_out) = match trade_direction {
+            TradeDirection::AtoB => (reserve_a, reserve_b),
+            TradeDirection::BtoA => (reserve_b, reserve_a),
+        };
+        let curve = self.swap_curve().unwrap_or_else(|| state.swap_curve());
+        let fees = self.fees().unwrap_or_else(|| state.fees());
+        price_impact(
+            amount_in,
+            reserve_in,
+            reserve_out,
+            trade_direction,
+            curve,
+            fees,
+        )
+    }
+
+    /// Value locked in the pool, in UI amount of token B
+    fn pool_value(
+        &self,
+        balances: &HashMap<Pubkey, u64>,
+        decimals_a: u8,
+        decimals_b: u8,
+        state: &ProgramState,
+    ) -> Result<f64, AmmError> {
+        let (reserve_a, reserve_b) = self.reserves(balances)?;
+        let price = self.spot_price(balances, decimals_a, decimals_b, state)?;
+        Ok(reserve_a as f64 / 10f64.powi(i32::from(decimals_a)) * price
+            + reserve_b as f64 / 10f64.powi(i32::from(decimals_b)))
+    }
+}
+
+impl<T: AmmStatus + ?Sized> SwapAnalytics for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::{constant_product::ConstantProductCurve, stable::StableCurve},
+        state::{SwapV1, SwapV2},
+    };
+    use std::sync::Arc;
+
+    fn constant_product() -> SwapCurve {
+        SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve),
+        }
+    }
+
+    fn stable() -> SwapCurve {
+        SwapCurve {
+            curve_type: CurveType::Stable,
+            calculator: Arc::new(StableCurve { amp: 100 }),
+        }
+    }
+
+    fn fees(trade_fee_numerator: u32, owner_trade_fee_numerator: u32) -> Fees {
+        Fees {
+            trade_fee_numerator,
+            owner_trade_fee_numerator,
+            owner_withdraw_fee_numerator: 0,
+            host_fee_numerator: 0,
+            fee_denominator: 10_000,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_spot_price() {
+        let curve = constant_product();
+        assert_close(spot_price(1_000_000, 2_000_000, 6, 6, &curve).unwrap(), 2.0);
+        assert_close(spot_price(2_000_000, 1_000_000, 6, 6, &curve).unwrap(), 0.5);
+        // 1 token A is 10^9 raw units, worth 2 * 10^9 raw units of token B
+        assert_close(
+            spot_price(1_000_000, 2_000_000, 9, 6, &curve).unwrap(),
+            2_000.0,
+        );
+        assert_close(
+            spot_price(1_000_000, 2_000_000, 6, 9, &curve).unwrap(),
+            0.002,
+        );
+
+        let curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve {
+                token_b_offset: 3_000_000,
+            }),
+        };
+        // the offset prices token A while the token B reserve is empty
+        assert_close(spot_price(1_000_000, 0, 6, 6, &curve).unwrap(), 3.0);
+        assert_close(spot_price(1_000_000, 1_000_000, 6, 6, &curve).unwrap(), 4.0);
+
+        let curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve { token_b_price: 4 }),
+        };
+        // whatever the reserves
+        assert_close(spot_price(0, 0, 6, 6, &curve).unwrap(), 0.25);
+        assert_close(spot_price(1, 1_000_000, 6, 6, &curve).unwrap(), 0.25);
+    }
+
+    #[test]
+    fn test_spot_price_empty_reserve() {
+        let curve = constant_product();
+        for (reserve_a, reserve_b) in [(0, 1_000_000), (1_000_000, 0), (0, 0)] {
+            assert_eq!(
+                spot_price(reserve_a, reserve_b, 6, 6, &curve),
+                Err(AmmError::EmptySupply)
+            );
+        }
+        let curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve {
+                token_b_offset: 3_000_000,
+            }),
+        };
+        assert_eq!(
+            spot_price(0, 1_000_000, 6, 6, &curve),
+            Err(AmmError::EmptySupply)
+        );
+        let curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve { token_b_price: 0 }),
+        };
+        assert_eq!(
+            spot_price(1_000_000, 1_000_000, 6, 6, &curve),
+            Err(AmmError::InvalidCurve)
+        );
+        assert_eq!(
+            spot_price(1_000_000, 1_000_000, 6, 6, &stable()),
+            Err(AmmError::UnsupportedCurveOperation)
+        );
+    }
+
+    #[test]
+    fn test_price_impact() {
+        let curve = constant_product();
+        // swapping as much as the source reserve only gets half the
+        // destination reserve, half what the spot price would give
+        assert_close(
+            price_impact(
+                1_000_000,
+                1_000_000,
+                1_000_000,
+                TradeDirection::AtoB,
+                &curve,
+                &fees(0, 0),
+            )
+            .unwrap(),
+            0.5,
+        );
+        // 100_000 B for 2_000_000 * 100_000 / 1_100_000 = 181_818 A instead
+        // of 200_000
+        assert_close(
+            price_impact(
+                100_000,
+                1_000_000,
+                2_000_000,
+                TradeDirection::BtoA,
+                &curve,
+                &fees(0, 0),
+            )
+            .unwrap(),
+            1.0 - 181_818.0 / 200_000.0,
+        );
+
+        // the 0.3% fees are not part of the impact: 9_970 tokens are swapped
+        // for 1_000_000 - ceil(10^12 / 1_009_970) = 9_871
+        let impact = price_impact(
+            10_000,
+            1_000_000,
+            1_000_000,
+            TradeDirection::AtoB,
+            &curve,
+            &fees(25, 5),
+        )
+        .unwrap();
+        assert_close(impact, 1.0 - 9_871.0 / 9_970.0);
+
+        // the curve does not quote a swap of nothing
+        assert_eq!(
+            price_impact(
+                0,
+                1_000_000,
+                1_000_000,
+                TradeDirection::AtoB,
+                &curve,
+                &fees(25, 5)
+            ),
+            Err(AmmError::CalculationFailure)
+        );
+    }
+
+    #[test]
+    fn test_price_impact_unpriced() {
+        let curve = constant_product();
+        for direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+            assert_eq!(
+                price_impact(1_000, 1_000_000, 0, direction, &curve, &fees(25, 5)),
+                Err(AmmError::EmptySupply)
+            );
+            assert_eq!(
+                price_impact(1_000, 0, 1_000_000, direction, &curve, &fees(25, 5)),
+                Err(AmmError::EmptySupply)
+            );
+            assert_eq!(
+                price_impact(
+                    1_000,
+                    1_000_000,
+                    1_000_000,
+                    direction,
+                    &stable(),
+                    &fees(25, 5)
+                ),
+                Err(AmmError::UnsupportedCurveOperation)
+            );
+        }
+    }
+
+    fn swap_v1() -> SwapV1 {
+        SwapV1 {
+            is_initialized: true,
+            nonce: 254,
+            amm_id: Pubkey::new_unique(),
+            dex_program_id: Pubkey::new_unique(),
+            market_id: Pubkey::new_unique(),
+            token_program_id: spl_token::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+        }
+    }
+
+    fn program_state(swap_curve: SwapCurve) -> ProgramState {
+        ProgramState {
+            is_initialized: true,
+            state_owner: Pubkey::new_unique(),
+            fee_owner: Pubkey::new_unique(),
+            initial_supply: 1_000_000_000,
+            fees: fees(0, 0),
+            swap_curve,
+        }
+    }
+
+    #[test]
+    fn test_swap_analytics() {
+        let swap = swap_v1();
+        let balances = HashMap::from([(swap.token_a, 1_000_000), (swap.token_b, 2_000_000)]);
+        let state = program_state(constant_product());
+        assert_eq!(swap.reserves(&balances), Ok((1_000_000, 2_000_000)));
+        assert_close(swap.spot_price(&balances, 9, 6, &state).unwrap(), 2_000.0);
+        // 0.001 token A worth 2 token B, and 2 token B
+        assert_close(swap.pool_value(&balances, 9, 6, &state).unwrap(), 4.0);
+        assert_close(
+            swap.price_impact(1_000_000, TradeDirection::AtoB, &balances, &state)
+                .unwrap(),
+            0.5,
+        );
+
+        // a pool without both balances
+        let missing = HashMap::from([(swap.token_a, 1_000_000)]);
+        assert_eq!(swap.reserves(&missing), Err(AmmError::InvalidInput));
+        assert_eq!(
+            swap.spot_price(&missing, 9, 6, &state),
+            Err(AmmError::InvalidInput)
+        );
+        // an empty pool
+        let empty = HashMap::from([(swap.token_a, 0), (swap.token_b, 0)]);
+        assert_eq!(
+            swap.pool_value(&empty, 9, 6, &state),
+            Err(AmmError::EmptySupply)
+        );
+        // the curve of the program state
+        assert_eq!(
+            swap.spot_price(&balances, 9, 6, &program_state(stable())),
+            Err(AmmError::UnsupportedCurveOperation)
+        );
+    }
+
+    #[test]
+    fn test_swap_analytics_own_curve() {
+        // the curve and fees of the pool apply over those of the program state
+        let swap = SwapV2::from_v1(
+            swap_v1(),
+            fees(25, 5),
+            constant_product(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let balances = HashMap::from([(swap.token_a, 1_000_000), (swap.token_b, 1_000_000)]);
+        let state = program_state(stable());
+        assert_close(swap.spot_price(&balances, 6, 6, &state).unwrap(), 1.0);
+        assert_close(
+            swap.price_impact(10_000, TradeDirection::AtoB, &balances, &state)
+                .unwrap(),
+            1.0 - 9_871.0 / 9_970.0,
+        );
+    }
+}
@@ -0,0 +1,1692 @@
+//! State transition types
+use crate::error::AmmError;
+use crate::curve::{
+    base::{SwapCurve, SwapResult},
+    calculator::TradeDirection,
+    fees::Fees,
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use enum_dispatch::enum_dispatch;
+use solana_program::{
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+#[cfg(feature = "borsh")]
+use borsh::BorshSchema;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Checks that an account buffer holds at least `len` bytes, trailing bytes
+/// being ignored by the unpacking functions
+pub(crate) fn check_account_len(input: &[u8], len: usize) -> Result<(), ProgramError> {
+    if input.len() < len {
+        msg!("Account data too short: expected {} bytes, got {}", len, input.len());
+        return Err(AmmError::InvalidAccountLength.into());
+    }
+    Ok(())
+}
+
+/// Checks that an output buffer can hold `len` bytes before packing into it,
+/// `array_mut_ref!` panicking on short buffers
+fn check_pack_len(output: &[u8], len: usize) -> Result<(), ProgramError> {
+    if output.len() < len {
+        msg!("Account data too small: expected {} bytes, got {}", len, output.len());
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    Ok(())
+}
+
+/// Trait representing access to program state across all versions
+#[enum_dispatch]
+pub trait AmmStatus {
+    /// Is the swap initialized, with data written to it
+    fn is_initialized(&self) -> bool;
+    /// Bump seed used to generate the program address / authority
+    fn nonce(&self) -> u8;
+    /// Token program ID associated with the swap
+    fn token_program_id(&self) -> &Pubkey;
+    /// Address of token A liquidity account
+    fn token_a_account(&self) -> &Pubkey;
+    /// Address of token B liquidity account
+    fn token_b_account(&self) -> &Pubkey;
+    /// Address of pool token mint
+    fn pool_mint(&self) -> &Pubkey;
+
+    /// Address of token A mint
+    fn token_a_mint(&self) -> &Pubkey;
+    /// Address of token B mint
+    fn token_b_mint(&self) -> &Pubkey;
+
+    /// Address of the amm account
+    fn amm_id(&self) -> &Pubkey;
+    /// Program ID of the Serum dex
+    fn dex_program_id(&self) -> &Pubkey;
+    /// Address of the Serum market
+    fn market_id(&self) -> &Pubkey;
+
+    /// Is the swap paused, rejecting trades and liquidity changes
+    fn is_paused(&self) -> bool {
+        false
+    }
+
+    /// Fees of this pool, `None` when the pool uses the fees of the
+    /// [ProgramState]
+    fn fees(&self) -> Option<&Fees> {
+        None
+    }
+    /// Curve of this pool, `None` when the pool uses the curve of the
+    /// [ProgramState]
+    fn swap_curve(&self) -> Option<&SwapCurve> {
+        None
+    }
+
+    /// Pool token account receiving the owner fees, `None` for the original
+    /// version
+    fn pool_fee_account(&self) -> Option<&Pubkey> {
+        None
+    }
+    /// Token A account receiving the owner fees, `None` for the original
+    /// version
+    fn token_a_fee_account(&self) -> Option<&Pubkey> {
+        None
+    }
+    /// Token B account receiving the owner fees, `None` for the original
+    /// version
+    fn token_b_fee_account(&self) -> Option<&Pubkey> {
+        None
+    }
+}
+
+/// All versions of AmmStatus
+#[enum_dispatch(AmmStatus)]
+#[derive(Debug, PartialEq)]
+pub enum SwapVersion {
+    /// Original version, without pause support
+    SwapV1,
+    /// Latest version, used for all new swaps, with pause support, per-pool
+    /// fees and curve, and fee accounts
+    SwapV2,
+}
+
+/// SwapVersion does not implement program_pack::Pack because there are size
+/// checks on pack and unpack that would break backwards compatibility, so
+/// special implementations are provided here
+impl SwapVersion {
+    /// Size of the latest version of the AmmStatus
+    pub const LATEST_LEN: usize = 1 + SwapV2::LEN; // add one for the version enum
+
+    /// Pack a swap into a byte array, based on its version. Fails with
+    /// `AccountDataTooSmall` if `dst` cannot hold that version, trailing
+    /// bytes being left untouched
+    pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        match src {
+            Self::SwapV1(swap_info) => {
+                check_pack_len(dst, 1 + SwapV1::LEN)?;
+                dst[0] = 1;
+                SwapV1::pack(swap_info, &mut dst[1..1 + SwapV1::LEN])
+            }
+            Self::SwapV2(swap_info) => {
+                check_pack_len(dst, 1 + SwapV2::LEN)?;
+                dst[0] = 2;
+                SwapV2::pack(swap_info, &mut dst[1..1 + SwapV2::LEN])
+            }
+        }
+    }
+
+    /// Unpack the swap account based on its version, returning the result as a
+    /// AmmStatus trait object
+    pub fn unpack(input: &[u8]) -> Result<Box<dyn AmmStatus>, ProgramError> {
+        match Self::unpack_concrete(input)? {
+            Self::SwapV1(swap_info) => Ok(Box::new(swap_info)),
+            Self::SwapV2(swap_info) => Ok(Box::new(swap_info)),
+        }
+    }
+
+    /// Unpack the swap account based on its version without allocating,
+    /// AmmStatus being implemented by the enum itself
+    pub fn unpack_concrete(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            1 => Ok(Self::SwapV1(SwapV1::unpack(rest)?)),
+            2 => Ok(Self::SwapV2(SwapV2::unpack(rest)?)),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Unpack a swap account holding the original version, failing with
+    /// `InvalidAccountData` for any other version
+    pub fn unpack_v1(input: &[u8]) -> Result<SwapV1, ProgramError> {
+        match Self::unpack_concrete(input)? {
+            Self::SwapV1(swap_info) => Ok(swap_info),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// View the swap account in place, whatever its version, without copying
+    /// it
+    pub fn unpack_ref(input: &[u8]) -> Result<SwapV1Ref<'_>, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            1..=2 => SwapV1Ref::new(version, rest),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Special check to be done before any instruction processing, works for
+    /// all versions. Only the version, the length and the initialized flag
+    /// are read, without unpacking or allocating
+    pub fn is_initialized(input: &[u8]) -> bool {
+        match Self::unpack_ref(input) {
+            Ok(swap) => swap.is_initialized(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Byte offsets of the fields of a swap account, the one-byte version prefix
+/// written by [SwapVersion::pack] included, for `memcmp` RPC filters
+pub mod swap_v1_offsets {
+    use solana_program::pubkey::Pubkey;
+
+    /// Version of the swap layout
+    pub const VERSION: usize = 0;
+    /// Initialized state
+    pub const IS_INITIALIZED: usize = 1;
+    /// Nonce used in program address
+    pub const NONCE: usize = 2;
+    /// ID of the amm account
+    pub const AMM_ID: usize = 3;
+    /// Program ID of Serum Market
+    pub const DEX_PROGRAM_ID: usize = 35;
+    /// Market ID of Serum
+    pub const MARKET_ID: usize = 67;
+    /// Program ID of the tokens being exchanged
+    pub const TOKEN_PROGRAM_ID: usize = 99;
+    /// Token A account of the pool
+    pub const TOKEN_A: usize = 131;
+    /// Token B account of the pool
+    pub const TOKEN_B: usize = 163;
+    /// Pool token mint
+    pub const POOL_MINT: usize = 195;
+    /// Mint of token A
+    pub const TOKEN_A_MINT: usize = 227;
+    /// Mint of token B
+    pub const TOKEN_B_MINT: usize = 259;
+    /// Paused state, only present from the second version
+    pub const IS_PAUSED: usize = 291;
+    /// Packed fees, only present from the second version
+    pub const FEES: usize = 292;
+    /// Packed swap curve, only present from the second version
+    pub const SWAP_CURVE: usize = 316;
+    /// Pool token account receiving the owner fees, only present from the
+    /// second version
+    pub const POOL_FEE_ACCOUNT: usize = 349;
+    /// Token A account receiving the owner fees, only present from the
+    /// second version
+    pub const TOKEN_A_FEE_ACCOUNT: usize = 381;
+    /// Token B account receiving the owner fees, only present from the
+    /// second version
+    pub const TOKEN_B_FEE_ACCOUNT: usize = 413;
+
+    /// Offset and bytes matching the swaps trading `mint` as token A
+    pub fn memcmp_filter_for_token_a_mint(mint: &Pubkey) -> (usize, [u8; 32]) {
+        (TOKEN_A_MINT, mint.to_bytes())
+    }
+
+    /// Offset and bytes matching the swaps trading `mint` as token B
+    pub fn memcmp_filter_for_token_b_mint(mint: &Pubkey) -> (usize, [u8; 32]) {
+        (TOKEN_B_MINT, mint.to_bytes())
+    }
+
+    /// Offset and bytes matching the swap issuing `mint` as pool token
+    pub fn memcmp_filter_for_pool_mint(mint: &Pubkey) -> (usize, [u8; 32]) {
+        (POOL_MINT, mint.to_bytes())
+    }
+}
+
+/// Byte offsets of the fields of the program state account, for `memcmp`
+/// RPC filters
+pub mod program_state_offsets {
+    use solana_program::pubkey::Pubkey;
+
+    /// Initialized state
+    pub const IS_INITIALIZED: usize = 0;
+    /// Owner allowed to update the program state
+    pub const STATE_OWNER: usize = 1;
+    /// Owner receiving the fees
+    pub const FEE_OWNER: usize = 33;
+    /// Initial supply of new pools
+    pub const INITIAL_SUPPLY: usize = 65;
+    /// Packed fees
+    pub const FEES: usize = 73;
+    /// Packed swap curve
+    pub const SWAP_CURVE: usize = 97;
+    /// Pending state owner, only present in a [super::ProgramStateV2]
+    pub const PENDING_OWNER: usize = 130;
+
+    /// Offset and bytes matching the program states owned by `owner`
+    pub fn memcmp_filter_for_state_owner(owner: &Pubkey) -> (usize, [u8; 32]) {
+        (STATE_OWNER, owner.to_bytes())
+    }
+}
+
+/// Bytes reserved for future fields at the end of a swap account: none.
+///
+/// The layout was ported from spl-token-swap but does not match it: the
+/// spl-token-swap `SwapV1` is 323 bytes, with a pool fee account, packed fees
+/// and curve, where the Cropper [SwapV1] is exactly its 290 bytes of fields.
+/// New fields get a new [SwapVersion] rather than using padding, so a swap
+/// account is [SwapVersion::LATEST_LEN] bytes, the version prefix included.
+pub const SWAP_ACCOUNT_RESERVED: usize = 0;
+
+// The `array_refs!` segments of each layout are checked against its `LEN` by
+// the compiler, the offsets above are checked here against the same lengths
+const _: () = assert!(swap_v1_offsets::TOKEN_B_MINT + 32 == 1 + SwapV1::LEN);
+const _: () = assert!(swap_v1_offsets::IS_PAUSED == 1 + SwapV1::LEN);
+const _: () = assert!(swap_v1_offsets::FEES == swap_v1_offsets::IS_PAUSED + 1);
+const _: () = assert!(swap_v1_offsets::FEES + Fees::LEN == swap_v1_offsets::SWAP_CURVE);
+const _: () =
+    assert!(swap_v1_offsets::SWAP_CURVE + SwapCurve::LEN == swap_v1_offsets::POOL_FEE_ACCOUNT);
+const _: () = assert!(swap_v1_offsets::TOKEN_B_FEE_ACCOUNT + 32 == 1 + SwapV2::LEN);
+const _: () = assert!(SwapVersion::LATEST_LEN == 1 + SwapV2::LEN + SWAP_ACCOUNT_RESERVED);
+const _: () = assert!(program_state_offsets::FEES + Fees::LEN == program_state_offsets::SWAP_CURVE);
+const _: () = assert!(program_state_offsets::SWAP_CURVE + SwapCurve::LEN == ProgramState::LEN);
+const _: () = assert!(program_state_offsets::PENDING_OWNER == ProgramState::LEN);
+const _: () = assert!(program_state_offsets::PENDING_OWNER + 32 == ProgramStateV2::LEN);
+
+/// Serializes pubkeys as base58 strings rather than byte arrays
+#[cfg(feature = "serde")]
+pub(crate) mod pubkey_base58 {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use solana_program::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(pubkey)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        Pubkey::from_str(&encoded).map_err(D::Error::custom)
+    }
+}
+
+/// Basis points in a whole, used to express price impact
+const BPS_DENOMINATOR: u16 = 10_000;
+
+fn to_u64(val: u128) -> Result<u64, AmmError> {
+    u64::try_from(val).map_err(|_| AmmError::ConversionFailure)
+}
+
+/// Expected outcome of a swap, see [ProgramState::quote_swap]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapQuote {
+    /// Amount of destination token received
+    pub amount_out: u64,
+    /// Source tokens kept by the pool as trade fee
+    pub fee_amount: u64,
+    /// Source tokens paid to the owner as owner trade fee
+    pub owner_fee_amount: u64,
+    /// Shortfall of `amount_out` against the reserve ratio, in basis points
+    pub price_impact_bps: u16,
+}
+
+/// Expected outcome of a swap with 16-byte amounts, see
+/// [ProgramState::quote_swap_u128]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapQuoteU128 {
+    /// Amount of destination token received
+    pub amount_out: u128,
+    /// Source tokens kept by the pool as trade fee
+    pub fee_amount: u128,
+    /// Source tokens paid to the owner as owner trade fee
+    pub owner_fee_amount: u128,
+    /// Shortfall of `amount_out` against the reserve ratio, in basis points
+    pub price_impact_bps: u16,
+}
+
+///Program State
+///
+/// Original layout of the program state account, held by every account
+/// created before ownership transfers were introduced. See [ProgramStateV2]
+/// for the layout with a pending owner.
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct ProgramState {
+    /// Initialized state.
+    pub is_initialized:bool,
+
+    /// owner address to update the program state
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub state_owner: Pubkey,
+
+    /// Fee owner address to redistribute
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub fee_owner: Pubkey,
+
+    /// owner address to update the program state
+    pub initial_supply: u64,
+
+    ///Fee ratio to redistribute
+    pub fees: Fees,
+
+    ///Curve Type to swap
+    pub swap_curve: SwapCurve,
+}
+impl Sealed for ProgramState {}
+impl Pack for ProgramState{
+    /// Size of the Program State
+    const LEN:usize = 130;
+
+    /// Packs the program state, panicking if `output` is shorter than
+    /// `LEN`: use `Pack::pack`, which checks the length first
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, ProgramState::LEN];
+        let (
+            is_initialized,
+            state_owner,
+            fee_owner,
+            initial_supply,
+            fees,
+            swap_curve,
+        ) = mut_array_refs![output, 1, 32, 32, 8, 24, 33];
+        is_initialized[0] = self.is_initialized as u8;
+        state_owner.copy_from_slice(self.state_owner.as_ref());
+        fee_owner.copy_from_slice(self.fee_owner.as_ref());
+        *initial_supply = self.initial_supply.to_le_bytes();
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+    }
+
+    /// Unpacks a byte buffer into a [ProgramState], ignoring trailing bytes
+    /// such as the pending owner of a [ProgramStateV2]
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let input = array_ref![input, 0, ProgramState::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            state_owner,
+            fee_owner,
+            initial_supply,
+            fees,
+            swap_curve,
+        ) = array_refs![input, 1, 32, 32, 8,  24, 33];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            state_owner: Pubkey::new_from_array(*state_owner),
+            fee_owner: Pubkey::new_from_array(*fee_owner),
+            initial_supply:u64::from_le_bytes(*initial_supply),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+        })
+    }
+}
+
+
+impl ProgramState{
+    /// Offset of the packed fees, see [Fees::unpack_at]
+    pub const FEES_OFFSET: usize = program_state_offsets::FEES;
+
+    /// Offset of the packed swap curve, see [SwapCurve::unpack_at]
+    pub const SWAP_CURVE_OFFSET: usize = program_state_offsets::SWAP_CURVE;
+
+    /// is program account initialized
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+    /// state owner to change current program state
+    pub fn state_owner(&self) -> &Pubkey {
+        &self.state_owner
+    }
+
+    /// fee owner to recevie when swap
+    pub fn fee_owner(&self) -> &Pubkey {
+        &self.fee_owner
+    }
+
+    /// initial supply to create pool
+    pub fn initial_supply(&self) -> u64 {
+        self.initial_supply
+    }
+    
+    /// fees redistributed
+    pub fn fees(&self) -> &Fees {
+        &self.fees
+    }
+    
+    /// fee calculators
+    pub fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    /// Expected result of swapping `amount_in` against a pool holding
+    /// `reserve_in` of the source token and `reserve_out` of the destination
+    /// token, using the current fees and curve. Reserves are given in trade
+    /// order, from token A to token B for curves that are not symmetric.
+    pub fn quote_swap(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+    ) -> Result<SwapQuote, AmmError> {
+        let quote = self.quote_swap_u128(
+            u128::from(amount_in),
+            u128::from(reserve_in),
+            u128::from(reserve_out),
+        )?;
+        Ok(SwapQuote {
+            amount_out: to_u64(quote.amount_out)?,
+            fee_amount: to_u64(quote.fee_amount)?,
+            owner_fee_amount: to_u64(quote.owner_fee_amount)?,
+            price_impact_bps: quote.price_impact_bps,
+        })
+    }
+
+    /// Same as [ProgramState::quote_swap] with 16-byte amounts, for the
+    /// `Swap2` instruction. Fails with `CalculationFailure` when the curve
+    /// math overflows, e.g. a constant product above `u128::MAX`
+    pub fn quote_swap_u128(
+        &self,
+        amount_in: u128,
+        reserve_in: u128,
+        reserve_out: u128,
+    ) -> Result<SwapQuoteU128, AmmError> {
+        let SwapResult {
+            destination_amount_swapped,
+            trade_fee,
+            owner_fee,
+            ..
+        } = self
+            .swap_curve
+            .swap(
+                amount_in,
+                reserve_in,
+                reserve_out,
+                TradeDirection::AtoB,
+                &self.fees,
+            )
+            .ok_or(AmmError::CalculationFailure)?;
+
+        // price impact is measured against the output at the reserve ratio,
+        // once fees are taken out
+        let amount_in_less_fees = amount_in
+            .checked_sub(trade_fee)
+            .and_then(|amount| amount.checked_sub(owner_fee))
+            .ok_or(AmmError::CalculationFailure)?;
+        let ideal_amount_out = amount_in_less_fees
+            .checked_mul(reserve_out)
+            .ok_or(AmmError::CalculationFailure)?
+            .checked_div(reserve_in)
+            .unwrap_or(0);
+        let price_impact_bps = ideal_amount_out
+            .saturating_sub(destination_amount_swapped)
+            .checked_mul(u128::from(BPS_DENOMINATOR))
+            .ok_or(AmmError::CalculationFailure)?
+            .checked_div(ideal_amount_out)
+            .unwrap_or(0);
+
+        Ok(SwapQuoteU128 {
+            amount_out: destination_amount_swapped,
+            fee_amount: trade_fee,
+            owner_fee_amount: owner_fee,
+            price_impact_bps: u16::try_from(price_impact_bps)
+                .map_err(|_| AmmError::ConversionFailure)?,
+        })
+    }
+
+}
+
+/// Program state with a pending owner, for two-step state ownership transfers
+///
+/// The fields of the original [ProgramState] layout are followed by the owner
+/// proposed by `TransferStateOwnership`. Accounts of the original
+/// [ProgramState::LEN] bytes are read by [ProgramStateV2::unpack_versioned]
+/// with no pending owner and keep taking every instruction but
+/// `TransferStateOwnership`, which fails with `AccountDataTooSmall`.
+///
+/// solana-program 1.7 cannot resize an account, so an original account is
+/// migrated by replacing it: the state owner allocates a new account of
+/// [ProgramStateV2::LEN] bytes owned by the program, runs
+/// `InitializeProgramState` on it with the fee owner, initial supply, fees
+/// and curve of the original account, then points the program and its clients
+/// at the new account. The original account can be closed afterwards.
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, PartialEq)]
+pub struct ProgramStateV2 {
+    /// Fields of the original layout
+    pub state: ProgramState,
+
+    /// owner proposed by the state owner, taking over once accepted.
+    /// `Pubkey::default()` when no transfer is pending
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58", default))]
+    pub pending_owner: Pubkey,
+}
+impl Sealed for ProgramStateV2 {}
+impl Pack for ProgramStateV2 {
+    /// Size of the Program State with a pending owner
+    const LEN: usize = ProgramState::LEN + 32;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, ProgramStateV2::LEN];
+        let (state, pending_owner) = mut_array_refs![output, ProgramState::LEN, 32];
+        self.state.pack_into_slice(&mut state[..]);
+        pending_owner.copy_from_slice(self.pending_owner.as_ref());
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let input = array_ref![input, 0, ProgramStateV2::LEN];
+        let (state, pending_owner) = array_refs![input, ProgramState::LEN, 32];
+        Ok(Self {
+            state: ProgramState::unpack_from_slice(state)?,
+            pending_owner: Pubkey::new_from_array(*pending_owner),
+        })
+    }
+}
+
+impl ProgramStateV2 {
+    /// Program state of the original layout, with no pending owner
+    pub fn from_v1(state: ProgramState) -> Self {
+        Self {
+            state,
+            pending_owner: Pubkey::default(),
+        }
+    }
+
+    /// Unpacks program state account data of either layout, checking it is
+    /// initialized. `Pack::unpack_unchecked` only accepts accounts of exactly
+    /// `LEN` bytes.
+    pub fn unpack_versioned(input: &[u8]) -> Result<Self, ProgramError> {
+        let state = if input.len() >= Self::LEN {
+            Self::unpack_from_slice(input)?
+        } else {
+            Self::from_v1(ProgramState::unpack_from_slice(input)?)
+        };
+        if !state.state.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(state)
+    }
+
+    /// Packs into program state account data of either layout, checking its
+    /// length first. Accounts of the original layout cannot hold a pending
+    /// owner, failing with `AccountDataTooSmall` when one is set.
+    pub fn pack_versioned(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() >= Self::LEN || self.pending_owner != Pubkey::default() {
+            check_pack_len(dst, Self::LEN)?;
+            self.pack_into_slice(dst);
+        } else {
+            check_pack_len(dst, ProgramState::LEN)?;
+            self.state.pack_into_slice(dst);
+        }
+        Ok(())
+    }
+
+    /// owner proposed by the state owner, if a transfer is pending
+    pub fn pending_owner(&self) -> Option<&Pubkey> {
+        if self.pending_owner == Pubkey::default() {
+            None
+        } else {
+            Some(&self.pending_owner)
+        }
+    }
+}
+
+/// Borrowed view over a packed [ProgramState], reading its fields from the
+/// account data
+#[derive(Clone, Copy, Debug)]
+pub struct ProgramStateRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProgramStateRef<'a> {
+    /// View over packed program state data, of either layout
+    pub fn new(data: &'a [u8]) -> Result<Self, ProgramError> {
+        check_account_len(data, ProgramState::LEN)?;
+        Ok(Self { data })
+    }
+
+    /// is program account initialized
+    pub fn is_initialized(&self) -> bool {
+        self.data[program_state_offsets::IS_INITIALIZED] == 1
+    }
+
+    /// state owner to change current program state
+    pub fn state_owner(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![self.data, program_state_offsets::STATE_OWNER, 32])
+    }
+
+    /// fee owner to recevie when swap
+    pub fn fee_owner(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![self.data, program_state_offsets::FEE_OWNER, 32])
+    }
+
+    /// initial supply to create pool
+    pub fn initial_supply(&self) -> u64 {
+        u64::from_le_bytes(*array_ref![self.data, program_state_offsets::INITIAL_SUPPLY, 8])
+    }
+
+    /// fees redistributed
+    pub fn fees(&self) -> Result<Fees, ProgramError> {
+        Fees::unpack_from_slice(&self.data[program_state_offsets::FEES..][..Fees::LEN])
+    }
+
+    /// owner proposed by the state owner, if a transfer is pending
+    pub fn pending_owner(&self) -> Option<Pubkey> {
+        self.data
+            .get(program_state_offsets::PENDING_OWNER..ProgramStateV2::LEN)
+            .map(|data| Pubkey::new_from_array(*array_ref![data, 0, 32]))
+            .filter(|pending_owner| *pending_owner != Pubkey::default())
+    }
+}
+
+/// Pool states.
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV1 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub nonce: u8,
+    
+    ///ID of current amm account 
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub amm_id: Pubkey,
+
+    ///Program ID of Serum Market
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub dex_program_id: Pubkey,
+
+    ///Market ID of Serum
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub market_id: Pubkey,
+
+    /// Program ID of the tokens being exchanged.
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_a: Pubkey,
+    /// Token B
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_b_mint: Pubkey,
+}
+
+impl AmmStatus for SwapV1 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn amm_id(&self) -> &Pubkey {
+        &self.amm_id
+    }
+
+    fn dex_program_id(&self) -> &Pubkey {
+        &self.dex_program_id
+    }
+
+    fn market_id(&self) -> &Pubkey {
+        &self.market_id
+    }
+}
+
+impl Sealed for SwapV1 {}
+impl IsInitialized for SwapV1 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV1 {
+    const LEN: usize = 290;
+
+    /// Packs the swap, panicking if `output` is shorter than `LEN`: use
+    /// `Pack::pack` or [SwapVersion::pack], which check the length first
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, SwapV1::LEN];
+        let (
+            is_initialized,
+            nonce,
+            amm_id,
+            dex_program_id,
+            market_id,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        amm_id.copy_from_slice(self.amm_id.as_ref());
+        dex_program_id.copy_from_slice(self.dex_program_id.as_ref());
+        market_id.copy_from_slice(self.market_id.as_ref());
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [SwapV1](struct.SwapV1.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let input = array_ref![input, 0, SwapV1::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            amm_id,
+            dex_program_id,
+            market_id,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            amm_id: Pubkey::new_from_array(*amm_id),
+            dex_program_id: Pubkey::new_from_array(*dex_program_id),
+            market_id: Pubkey::new_from_array(*market_id),
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+        })
+    }
+}
+
+// In memory, `SwapV1` has its packed layout: `repr(C)` lays its fields out in
+// declaration order, which is the packing order, and fields of alignment 1
+// leave no padding
+#[cfg(feature = "zero-copy")]
+const _: () = assert!(
+    std::mem::size_of::<SwapV1>() == <SwapV1 as Pack>::LEN && std::mem::align_of::<SwapV1>() == 1
+);
+
+#[cfg(feature = "zero-copy")]
+impl SwapV1 {
+    /// Borrows the swap packed at the start of `input`, without copying its
+    /// keys as [Pack::unpack_from_slice] does, and failing the same way.
+    /// Every swap version starts with a [SwapV1], after the version prefix.
+    pub fn load(input: &[u8]) -> Result<&SwapV1, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        if input[0] > 1 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // SAFETY: the layout is the packed one, see the assertion above, any
+        // pointer is aligned for an alignment of 1, and `is_initialized` is
+        // the only field with invalid bit patterns
+        Ok(unsafe { &*(input.as_ptr() as *const SwapV1) })
+    }
+}
+
+/// Borrowed view over the fields every swap version shares, reading them
+/// from the account data instead of unpacking a [SwapV1]
+#[derive(Clone, Copy, Debug)]
+pub struct SwapV1Ref<'a> {
+    version: u8,
+    data: &'a [u8],
+}
+
+impl<'a> SwapV1Ref<'a> {
+    /// View over packed swap data of the given version, without the version
+    /// prefix, failing with `InvalidAccountData` for an unknown version
+    pub fn new(version: u8, data: &'a [u8]) -> Result<Self, ProgramError> {
+        let len = match version {
+            1 => SwapV1::LEN,
+            2 => SwapV2::LEN,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        check_account_len(data, len)?;
+        Ok(Self { version, data })
+    }
+
+    fn pubkey_at(&self, offset: usize) -> Pubkey {
+        // offsets are given for the account data, version prefix included
+        Pubkey::new_from_array(*array_ref![self.data, offset - 1, 32])
+    }
+
+    /// Version of the swap layout
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Is the swap initialized, with data written to it
+    pub fn is_initialized(&self) -> bool {
+        self.data[swap_v1_offsets::IS_INITIALIZED - 1] == 1
+    }
+
+    /// Bump seed used to generate the program address / authority
+    pub fn nonce(&self) -> u8 {
+        self.data[swap_v1_offsets::NONCE - 1]
+    }
+
+    /// Address of the amm account
+    pub fn amm_id(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::AMM_ID)
+    }
+
+    /// Program ID of the Serum dex
+    pub fn dex_program_id(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::DEX_PROGRAM_ID)
+    }
+
+    /// Address of the Serum market
+    pub fn market_id(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::MARKET_ID)
+    }
+
+    /// Token program ID associated with the swap
+    pub fn token_program_id(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::TOKEN_PROGRAM_ID)
+    }
+
+    /// Address of token A liquidity account
+    pub fn token_a_account(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::TOKEN_A)
+    }
+
+    /// Address of token B liquidity account
+    pub fn token_b_account(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::TOKEN_B)
+    }
+
+    /// Address of pool token mint
+    pub fn pool_mint(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::POOL_MINT)
+    }
+
+    /// Address of token A mint
+    pub fn token_a_mint(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::TOKEN_A_MINT)
+    }
+
+    /// Address of token B mint
+    pub fn token_b_mint(&self) -> Pubkey {
+        self.pubkey_at(swap_v1_offsets::TOKEN_B_MINT)
+    }
+
+    /// Is the swap paused, always false before the second version
+    pub fn is_paused(&self) -> bool {
+        self.version >= 2 && self.data[swap_v1_offsets::IS_PAUSED - 1] == 1
+    }
+
+    /// Pool token account receiving the owner fees, `None` before the second
+    /// version
+    pub fn pool_fee_account(&self) -> Option<Pubkey> {
+        self.fee_account_at(swap_v1_offsets::POOL_FEE_ACCOUNT)
+    }
+
+    /// Token A account receiving the owner fees, `None` before the second
+    /// version
+    pub fn token_a_fee_account(&self) -> Option<Pubkey> {
+        self.fee_account_at(swap_v1_offsets::TOKEN_A_FEE_ACCOUNT)
+    }
+
+    /// Token B account receiving the owner fees, `None` before the second
+    /// version
+    pub fn token_b_fee_account(&self) -> Option<Pubkey> {
+        self.fee_account_at(swap_v1_offsets::TOKEN_B_FEE_ACCOUNT)
+    }
+
+    fn fee_account_at(&self, offset: usize) -> Option<Pubkey> {
+        if self.version >= 2 {
+            Some(self.pubkey_at(offset))
+        } else {
+            None
+        }
+    }
+}
+
+/// Pool states of new pools, with pause support, fees and curve overriding
+/// the ones of the [ProgramState], and the accounts receiving the owner fees.
+#[cfg_attr(feature = "borsh", derive(BorshSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV2 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub nonce: u8,
+
+    ///ID of current amm account
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub amm_id: Pubkey,
+
+    ///Program ID of Serum Market
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub dex_program_id: Pubkey,
+
+    ///Market ID of Serum
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub market_id: Pubkey,
+
+    /// Program ID of the tokens being exchanged.
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_a: Pubkey,
+    /// Token B
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_b_mint: Pubkey,
+
+    /// Paused state, set by the state owner to halt the pool
+    pub is_paused: bool,
+
+    /// Fees of this pool
+    pub fees: Fees,
+
+    /// Curve of this pool
+    pub swap_curve: SwapCurve,
+
+    /// Pool token account receiving the owner trade and withdraw fees
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub pool_fee_account: Pubkey,
+    /// Token A account receiving the owner fees taken in token A
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_a_fee_account: Pubkey,
+    /// Token B account receiving the owner fees taken in token B
+    #[cfg_attr(feature = "serde", serde(with = "pubkey_base58"))]
+    pub token_b_fee_account: Pubkey,
+}
+
+impl SwapV2 {
+    /// Migrates a V1 pool to the latest layout, unpaused, with its own fees
+    /// and curve, usually those of the [ProgramState] it was using, and its
+    /// fee accounts
+    pub fn from_v1(
+        swap_v1: SwapV1,
+        fees: Fees,
+        swap_curve: SwapCurve,
+        pool_fee_account: Pubkey,
+        token_a_fee_account: Pubkey,
+        token_b_fee_account: Pubkey,
+    ) -> Self {
+        Self {
+            is_initialized: swap_v1.is_initialized,
+            nonce: swap_v1.nonce,
+            amm_id: swap_v1.amm_id,
+            dex_program_id: swap_v1.dex_program_id,
+            market_id: swap_v1.market_id,
+            token_program_id: swap_v1.token_program_id,
+            token_a: swap_v1.token_a,
+            token_b: swap_v1.token_b,
+            pool_mint: swap_v1.pool_mint,
+            token_a_mint: swap_v1.token_a_mint,
+            token_b_mint: swap_v1.token_b_mint,
+            is_paused: false,
+            fees,
+            swap_curve,
+            pool_fee_account,
+            token_a_fee_account,
+            token_b_fee_account,
+        }
+    }
+}
+
+impl AmmStatus for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn amm_id(&self) -> &Pubkey {
+        &self.amm_id
+    }
+
+    fn dex_program_id(&self) -> &Pubkey {
+        &self.dex_program_id
+    }
+
+    fn market_id(&self) -> &Pubkey {
+        &self.market_id
+    }
+
+    fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    fn fees(&self) -> Option<&Fees> {
+        Some(&self.fees)
+    }
+
+    fn swap_curve(&self) -> Option<&SwapCurve> {
+        Some(&self.swap_curve)
+    }
+
+    fn pool_fee_account(&self) -> Option<&Pubkey> {
+        Some(&self.pool_fee_account)
+    }
+
+    fn token_a_fee_account(&self) -> Option<&Pubkey> {
+        Some(&self.token_a_fee_account)
+    }
+
+    fn token_b_fee_account(&self) -> Option<&Pubkey> {
+        Some(&self.token_b_fee_account)
+    }
+}
+
+impl Sealed for SwapV2 {}
+impl IsInitialized for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV2 {
+    const LEN: usize = 444;
+
+    /// Packs the swap, panicking if `output` is shorter than `LEN`: use
+    /// `Pack::pack` or [SwapVersion::pack], which check the length first
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, SwapV2::LEN];
+        let (
+            is_initialized,
+            nonce,
+            amm_id,
+            dex_program_id,
+            market_id,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            is_paused,
+            fees,
+            swap_curve,
+            pool_fee_account,
+            token_a_fee_account,
+            token_b_fee_account,
+        ) = mut_array_refs![
+            output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32, 1, 24, 33, 32, 32, 32
+        ];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        amm_id.copy_from_slice(self.amm_id.as_ref());
+        dex_program_id.copy_from_slice(self.dex_program_id.as_ref());
+        market_id.copy_from_slice(self.market_id.as_ref());
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        is_paused[0] = self.is_paused as u8;
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        token_a_fee_account.copy_from_slice(self.token_a_fee_account.as_ref());
+        token_b_fee_account.copy_from_slice(self.token_b_fee_account.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [SwapV2](struct.SwapV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        check_account_len(input, Self::LEN)?;
+        let input = array_ref![input, 0, SwapV2::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            amm_id,
+            dex_program_id,
+            market_id,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            is_paused,
+            fees,
+            swap_curve,
+            pool_fee_account,
+            token_a_fee_account,
+            token_b_fee_account,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32, 1, 24, 33, 32, 32, 32];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            amm_id: Pubkey::new_from_array(*amm_id),
+            dex_program_id: Pubkey::new_from_array(*dex_program_id),
+            market_id: Pubkey::new_from_array(*market_id),
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            is_paused: match is_paused {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            token_a_fee_account: Pubkey::new_from_array(*token_a_fee_account),
+            token_b_fee_account: Pubkey::new_from_array(*token_b_fee_account),
+        })
+    }
+}
+
+/// Authority of a swap, owning its token accounts and the pool mint, derived
+/// from `[swap, nonce]` with the nonce stored in the swap
+pub fn swap_authority(program_id: &Pubkey, swap: &Pubkey, nonce: u8) -> Result<Pubkey, AmmError> {
+    Pubkey::create_program_address(&[swap.as_ref(), &[nonce]], program_id)
+        .map_err(|_| AmmError::InvalidProgramAddress)
+}
+
+/// Authority of a swap with the canonical nonce, the one to pass when
+/// initializing a new pool
+pub fn find_swap_authority(program_id: &Pubkey, swap: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[swap.as_ref()], program_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{base::CurveType, stable::StableCurve};
+    use std::sync::Arc;
+
+    fn test_swap_v1() -> SwapV1 {
+        SwapV1 {
+            is_initialized: true,
+            nonce: 254,
+            amm_id: Pubkey::new_unique(),
+            dex_program_id: Pubkey::new_unique(),
+            market_id: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+        }
+    }
+
+    fn test_swap_v2() -> SwapV2 {
+        let mut swap_v2 = SwapV2::from_v1(
+            test_swap_v1(),
+            Fees {
+                trade_fee_numerator: 25,
+                owner_trade_fee_numerator: 5,
+                owner_withdraw_fee_numerator: 1,
+                host_fee_numerator: 20,
+                fee_denominator: 10_000,
+            },
+            SwapCurve {
+                curve_type: CurveType::Stable,
+                calculator: Arc::new(StableCurve { amp: 100 }),
+            },
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        swap_v2.is_paused = true;
+        swap_v2
+    }
+
+    #[test]
+    fn test_swap_v2_fee_accounts() {
+        let mut data = vec![0; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(SwapVersion::SwapV2(test_swap_v2()), &mut data).unwrap();
+        let swap_v2 = SwapV2::unpack(&data[1..]).unwrap();
+        assert_eq!(data[0], 2);
+        assert_eq!(
+            &data[swap_v1_offsets::POOL_FEE_ACCOUNT..][..32],
+            swap_v2.pool_fee_account.as_ref()
+        );
+        assert_eq!(
+            &data[swap_v1_offsets::TOKEN_B_FEE_ACCOUNT..],
+            swap_v2.token_b_fee_account.as_ref()
+        );
+
+        let swap = SwapVersion::unpack(&data).unwrap();
+        assert!(swap.is_paused());
+        assert_eq!(swap.fees(), Some(&swap_v2.fees));
+        assert_eq!(swap.swap_curve(), Some(&swap_v2.swap_curve));
+        assert_eq!(swap.pool_fee_account(), Some(&swap_v2.pool_fee_account));
+        assert_eq!(
+            swap.token_a_fee_account(),
+            Some(&swap_v2.token_a_fee_account)
+        );
+        assert_eq!(
+            swap.token_b_fee_account(),
+            Some(&swap_v2.token_b_fee_account)
+        );
+        let swap = SwapVersion::unpack_ref(&data).unwrap();
+        assert_eq!(swap.version(), 2);
+        assert!(swap.is_paused());
+        assert_eq!(swap.pool_fee_account(), Some(swap_v2.pool_fee_account));
+        assert_eq!(
+            swap.token_a_fee_account(),
+            Some(swap_v2.token_a_fee_account)
+        );
+        assert_eq!(
+            swap.token_b_fee_account(),
+            Some(swap_v2.token_b_fee_account)
+        );
+
+        // the original version has no fee accounts
+        let mut data = vec![0; 1 + SwapV1::LEN];
+        SwapVersion::pack(SwapVersion::SwapV1(test_swap_v1()), &mut data).unwrap();
+        let swap = SwapVersion::unpack(&data).unwrap();
+        assert!(!swap.is_paused());
+        assert_eq!(swap.fees(), None);
+        assert_eq!(swap.pool_fee_account(), None);
+        assert_eq!(swap.token_a_fee_account(), None);
+        assert_eq!(swap.token_b_fee_account(), None);
+        let swap = SwapVersion::unpack_ref(&data).unwrap();
+        assert!(!swap.is_paused());
+        assert_eq!(swap.pool_fee_account(), None);
+    }
+
+    #[test]
+    fn test_swap_unknown_version() {
+        let data = [0; SwapVersion::LATEST_LEN];
+        for version in [0, 3, 4, u8::MAX] {
+            assert_eq!(
+                SwapV1Ref::new(version, &data[1..]).unwrap_err(),
+                ProgramError::InvalidAccountData
+            );
+            let mut data = data;
+            data[0] = version;
+            assert_eq!(
+                SwapVersion::unpack_ref(&data).unwrap_err(),
+                ProgramError::UninitializedAccount
+            );
+            assert_eq!(
+                SwapVersion::unpack_concrete(&data).unwrap_err(),
+                ProgramError::UninitializedAccount
+            );
+            assert!(!SwapVersion::is_initialized(&data));
+        }
+    }
+
+    #[test]
+    fn test_swap_version_round_trip() {
+        let mut data = vec![0; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(SwapVersion::SwapV2(test_swap_v2()), &mut data).unwrap();
+        assert_eq!(data[swap_v1_offsets::VERSION], 2);
+        let swap_v2 = SwapV2::unpack(&data[1..]).unwrap();
+        assert!(swap_v2.is_paused);
+        assert_eq!(swap_v2.fees.trade_fee_numerator, 25);
+        assert_eq!(swap_v2.swap_curve.curve_type, CurveType::Stable);
+        let mut repacked = vec![0; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(SwapVersion::SwapV2(swap_v2), &mut repacked).unwrap();
+        assert_eq!(repacked, data);
+        assert_eq!(
+            SwapVersion::unpack_concrete(&data).unwrap(),
+            SwapVersion::SwapV2(SwapV2::unpack(&data[1..]).unwrap())
+        );
+
+        // both versions share the fields of the original one
+        let swap_v1 = test_swap_v1();
+        let amm_id = swap_v1.amm_id;
+        let mut data_v1 = vec![0; 1 + SwapV1::LEN];
+        SwapVersion::pack(SwapVersion::SwapV1(swap_v1), &mut data_v1).unwrap();
+        assert_eq!(data_v1[swap_v1_offsets::VERSION], 1);
+        assert_eq!(*SwapVersion::unpack(&data_v1).unwrap().amm_id(), amm_id);
+        assert_eq!(
+            SwapV1::unpack(&data[1..1 + SwapV1::LEN]).unwrap().pool_mint,
+            *SwapVersion::unpack(&data).unwrap().pool_mint()
+        );
+        assert_eq!(
+            SwapVersion::unpack_v1(&data).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(SwapVersion::unpack_v1(&data_v1).unwrap().amm_id, amm_id);
+
+        // the original version uses the fees and curve of the program state
+        let swap = SwapVersion::unpack(&data_v1).unwrap();
+        assert_eq!(swap.fees(), None);
+        assert_eq!(swap.swap_curve(), None);
+
+        // the account must hold the whole layout
+        assert_eq!(
+            SwapVersion::unpack_concrete(&data[..SwapVersion::LATEST_LEN - 1]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(
+            SwapVersion::pack(
+                SwapVersion::SwapV2(test_swap_v2()),
+                &mut data[..SwapVersion::LATEST_LEN - 1]
+            )
+            .unwrap_err(),
+            ProgramError::AccountDataTooSmall
+        );
+    }
+
+    fn test_program_state() -> ProgramState {
+        ProgramState {
+            is_initialized: true,
+            state_owner: Pubkey::new_unique(),
+            fee_owner: Pubkey::new_unique(),
+            initial_supply: 1_000_000_000,
+            fees: test_swap_v2().fees,
+            swap_curve: SwapCurve {
+                curve_type: CurveType::Stable,
+                calculator: Arc::new(StableCurve { amp: 100 }),
+            },
+        }
+    }
+
+    #[test]
+    fn test_program_state_original_layout() {
+        assert_eq!(ProgramState::LEN, 130);
+        assert_eq!(crate::PROGRAM_STATE_LEN, 130);
+        let mut data = vec![0; ProgramState::LEN];
+        ProgramState::pack(test_program_state(), &mut data).unwrap();
+        let state = ProgramState::unpack_unchecked(&data).unwrap();
+        assert_eq!(state.initial_supply, 1_000_000_000);
+
+        let mut state_v2 = ProgramStateV2::unpack_versioned(&data).unwrap();
+        assert_eq!(state_v2.state, state);
+        assert_eq!(state_v2.pending_owner(), None);
+        assert_eq!(ProgramStateRef::new(&data).unwrap().pending_owner(), None);
+
+        // the original layout is kept while no transfer is pending
+        let mut packed = vec![0; ProgramState::LEN];
+        state_v2.pack_versioned(&mut packed).unwrap();
+        assert_eq!(packed, data);
+
+        state_v2.pending_owner = Pubkey::new_unique();
+        assert_eq!(
+            state_v2.pack_versioned(&mut packed).unwrap_err(),
+            ProgramError::AccountDataTooSmall
+        );
+        assert_eq!(packed, data);
+    }
+
+    #[test]
+    fn test_program_state_v2() {
+        assert_eq!(ProgramStateV2::LEN, 162);
+        assert_eq!(crate::PROGRAM_STATE_V2_LEN, 162);
+        let pending_owner = Pubkey::new_unique();
+        let mut state_v2 = ProgramStateV2::from_v1(test_program_state());
+        state_v2.pending_owner = pending_owner;
+        let mut data = vec![0; ProgramStateV2::LEN];
+        state_v2.pack_versioned(&mut data).unwrap();
+
+        let unpacked = ProgramStateV2::unpack_unchecked(&data).unwrap();
+        assert_eq!(unpacked, ProgramStateV2::unpack_versioned(&data).unwrap());
+        assert_eq!(unpacked.pending_owner(), Some(&pending_owner));
+        assert_eq!(
+            ProgramStateRef::new(&data).unwrap().pending_owner(),
+            Some(pending_owner)
+        );
+
+        // the original fields are read from the prefix, while the exact
+        // length check of `Pack::unpack_unchecked` rejects the longer account
+        assert_eq!(
+            ProgramState::unpack_from_slice(&data).unwrap(),
+            unpacked.state
+        );
+        assert_eq!(
+            ProgramState::unpack_unchecked(&data).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+
+        // accepting the transfer clears the pending owner in place
+        let mut accepted = unpacked;
+        accepted.state.state_owner = pending_owner;
+        accepted.pending_owner = Pubkey::default();
+        accepted.pack_versioned(&mut data).unwrap();
+        let accepted = ProgramStateV2::unpack_unchecked(&data).unwrap();
+        assert_eq!(accepted.state.state_owner, pending_owner);
+        assert_eq!(accepted.pending_owner(), None);
+
+        assert_eq!(
+            ProgramStateV2::unpack_versioned(&[0; ProgramStateV2::LEN]).unwrap_err(),
+            ProgramError::UninitializedAccount
+        );
+    }
+
+    #[test]
+    fn test_unpack_at_offset() {
+        let mut data = vec![0; ProgramStateV2::LEN];
+        ProgramStateV2::from_v1(test_program_state())
+            .pack_versioned(&mut data)
+            .unwrap();
+        let state = test_program_state();
+        assert_eq!(
+            Fees::unpack_at(&data, ProgramState::FEES_OFFSET).unwrap(),
+            state.fees
+        );
+        assert_eq!(
+            SwapCurve::unpack_at(&data, ProgramState::SWAP_CURVE_OFFSET).unwrap(),
+            state.swap_curve
+        );
+        // the field may end the data
+        let curve_end = ProgramState::SWAP_CURVE_OFFSET + SwapCurve::LEN;
+        assert_eq!(
+            SwapCurve::unpack_at(&data[..curve_end], ProgramState::SWAP_CURVE_OFFSET).unwrap(),
+            state.swap_curve
+        );
+
+        // the field running past the data, or the offset past the data
+        for offset in [
+            curve_end - SwapCurve::LEN + 1,
+            data.len(),
+            data.len() + 1,
+            usize::MAX,
+        ] {
+            assert_eq!(
+                SwapCurve::unpack_at(&data[..curve_end], offset).unwrap_err(),
+                AmmError::InvalidAccountLength.into(),
+                "{}",
+                offset
+            );
+        }
+        for offset in [data.len() - Fees::LEN + 1, data.len() + 1, usize::MAX] {
+            assert_eq!(
+                Fees::unpack_at(&data, offset).unwrap_err(),
+                AmmError::InvalidAccountLength.into(),
+                "{}",
+                offset
+            );
+        }
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_swap_borsh_schema() {
+        use borsh::schema::{Definition, Fields};
+
+        fn field_names<T: BorshSchema>() -> Vec<String> {
+            let container = T::schema_container();
+            match &container.definitions[&container.declaration] {
+                Definition::Struct {
+                    fields: Fields::NamedFields(fields),
+                } => fields.iter().map(|(name, _)| name.clone()).collect(),
+                definition => panic!("unexpected definition {:?}", definition),
+            }
+        }
+
+        let swap_v1_fields = [
+            "is_initialized",
+            "nonce",
+            "amm_id",
+            "dex_program_id",
+            "market_id",
+            "token_program_id",
+            "token_a",
+            "token_b",
+            "pool_mint",
+            "token_a_mint",
+            "token_b_mint",
+        ];
+        assert_eq!(field_names::<SwapV1>(), swap_v1_fields);
+        let swap_v2_fields = swap_v1_fields
+            .iter()
+            .chain(&[
+                "is_paused",
+                "fees",
+                "swap_curve",
+                "pool_fee_account",
+                "token_a_fee_account",
+                "token_b_fee_account",
+            ])
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(field_names::<SwapV2>(), swap_v2_fields);
+        let definitions = SwapV2::schema_container().definitions;
+        assert!(definitions.contains_key("Fees"));
+        assert!(definitions.contains_key("SwapCurve"));
+    }
+
+    #[test]
+    fn test_swap_authority() {
+        let program_id = crate::id();
+        let swap = Pubkey::new_unique();
+        let (authority, nonce) = find_swap_authority(&program_id, &swap);
+        assert_eq!(swap_authority(&program_id, &swap, nonce), Ok(authority));
+
+        // any other nonce derives another address, or none at all
+        let mut off_curve = 0;
+        for other_nonce in (0..=u8::MAX).filter(|other_nonce| *other_nonce != nonce) {
+            match swap_authority(&program_id, &swap, other_nonce) {
+                Ok(other_authority) => assert_ne!(other_authority, authority),
+                Err(error) => {
+                    assert_eq!(error, AmmError::InvalidProgramAddress);
+                    off_curve += 1;
+                }
+            }
+        }
+        assert!(off_curve > 0);
+        // nor is the authority of another swap
+        assert_ne!(
+            swap_authority(&program_id, &Pubkey::new_unique(), nonce),
+            Ok(authority)
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Quotes and instruction data for the browser, exported with `wasm-bindgen`
+//!
+//! Amounts are `u64`, i.e. `BigInt` on the JavaScript side. Fees are passed
+//! as the JSON form of [Fees], e.g.
+//! `{"trade_fee_numerator":25,"owner_trade_fee_numerator":5,...}`, and
+//! errors are thrown as the message of the [AmmError].
+
+use crate::{
+    curve::{calculator::TradingTokenResult, fees::Fees, liquidity},
+    error::AmmError,
+    instruction::{AmmInstruction, DepositInstruction, SwapInstruction, WithdrawInstruction},
+    state::ProgramState,
+};
+use serde::Serialize;
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+/// Expected outcome of a withdrawal of both tokens, see [quote_withdraw]
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct WithdrawQuote {
+    /// Amount of token A received
+    pub token_a_amount: u64,
+    /// Amount of token B received
+    pub token_b_amount: u64,
+}
+
+fn js_error(error: AmmError) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+fn parse_fees(fees_json: &str) -> Result<Fees, JsValue> {
+    let fees: Fees = serde_json::from_str(fees_json).map_err(|_| js_error(AmmError::InvalidFee))?;
+    fees.validate().map_err(js_error)?;
+    Ok(fees)
+}
+
+fn to_u64(amount: u128) -> Result<u64, JsValue> {
+    u64::try_from(amount).map_err(|_| js_error(AmmError::ConversionFailure))
+}
+
+/// Quote of swapping `amount_in` against a constant product pool, returned
+/// as a `SwapQuote` object, see [ProgramState::quote_swap]
+#[wasm_bindgen]
+pub fn quote_swap(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fees_json: &str,
+) -> Result<JsValue, JsValue> {
+    let state = ProgramState {
+        fees: parse_fees(fees_json)?,
+        ..ProgramState::default()
+    };
+    let quote = state
+        .quote_swap(amount_in, reserve_in, reserve_out)
+        .map_err(js_error)?;
+    serde_wasm_bindgen::to_value(&quote).map_err(JsValue::from)
+}
+
+/// Pool tokens minted for depositing up to `amount_a` and `amount_b`
+#[wasm_bindgen]
+pub fn quote_deposit(
+    amount_a: u64,
+    amount_b: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    pool_supply: u64,
+) -> Result<u64, JsValue> {
+    let pool_tokens = liquidity::pool_tokens_for_deposit(
+        u128::from(pool_supply),
+        u128::from(reserve_a),
+        u128::from(reserve_b),
+        u128::from(amount_a),
+        u128::from(amount_b),
+    )
+    .map_err(js_error)?;
+    to_u64(pool_tokens)
+}
+
+/// Tokens received for burning `pool_tokens`, once the withdraw fee is
+/// taken, returned as a [WithdrawQuote] object
+#[wasm_bindgen]
+pub fn quote_withdraw(
+    pool_tokens: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    pool_supply: u64,
+    fees_json: &str,
+) -> Result<JsValue, JsValue> {
+    let fees = parse_fees(fees_json)?;
+    let TradingTokenResult {
+        token_a_amount,
+        token_b_amount,
+    } = liquidity::tokens_for_withdraw(
+        u128::from(pool_supply),
+        u128::from(reserve_a),
+        u128::from(reserve_b),
+        u128::from(pool_tokens),
+        &fees,
+        false,
+    )
+    .map_err(js_error)?;
+    let quote = WithdrawQuote {
+        token_a_amount: to_u64(token_a_amount)?,
+        token_b_amount: to_u64(token_b_amount)?,
+    };
+    serde_wasm_bindgen::to_value(&quote).map_err(JsValue::from)
+}
+
+/// Instruction data of a `Swap`
+#[wasm_bindgen]
+pub fn encode_swap_instruction(amount_in: u64, minimum_amount_out: u64) -> Vec<u8> {
+    AmmInstruction::Swap(SwapInstruction {
+        amount_in,
+        minimum_amount_out,
+    })
+    .pack()
+}
+
+/// Instruction data of a `DepositAllTokenTypes`
+#[wasm_bindgen]
+pub fn encode_deposit_instruction(
+    pool_token_amount: u64,
+    maximum_token_a_amount: u64,
+    maximum_token_b_amount: u64,
+) -> Vec<u8> {
+    AmmInstruction::DepositAllTokenTypes(DepositInstruction {
+        pool_token_amount,
+        maximum_token_a_amount,
+        maximum_token_b_amount,
+    })
+    .pack()
+}
+
+/// Instruction data of a `WithdrawAllTokenTypes`
+#[wasm_bindgen]
+pub fn encode_withdraw_instruction(
+    pool_token_amount: u64,
+    minimum_token_a_amount: u64,
+    minimum_token_b_amount: u64,
+) -> Vec<u8> {
+    AmmInstruction::WithdrawAllTokenTypes(WithdrawInstruction {
+        pool_token_amount,
+        minimum_token_a_amount,
+        minimum_token_b_amount,
+    })
+    .pack()
+}
+
+#[cfg(test)]
+mod tests {
+    //! Run natively with `cargo test --features wasm`, the quotes returning
+    //! objects only with `wasm-pack test --node -- --features wasm`
+
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    const FEES_JSON: &str = r#"{"trade_fee_numerator":25,"owner_trade_fee_numerator":5,"owner_withdraw_fee_numerator":0,"host_fee_numerator":20,"fee_denominator":10000}"#;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_roundtrip() {
+        assert_eq!(
+            AmmInstruction::unpack(&encode_swap_instruction(1_000, 990)).unwrap(),
+            AmmInstruction::Swap(SwapInstruction {
+                amount_in: 1_000,
+                minimum_amount_out: 990,
+            })
+        );
+        assert_eq!(
+            AmmInstruction::unpack(&encode_deposit_instruction(10, 200, 300)).unwrap(),
+            AmmInstruction::DepositAllTokenTypes(DepositInstruction {
+                pool_token_amount: 10,
+                maximum_token_a_amount: 200,
+                maximum_token_b_amount: 300,
+            })
+        );
+        assert_eq!(
+            AmmInstruction::unpack(&encode_withdraw_instruction(10, 200, 300)).unwrap(),
+            AmmInstruction::WithdrawAllTokenTypes(WithdrawInstruction {
+                pool_token_amount: 10,
+                minimum_token_a_amount: 200,
+                minimum_token_b_amount: 300,
+            })
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_quote_deposit() {
+        assert_eq!(quote_deposit(100, 300, 1_000, 2_000, 500), Ok(50));
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    fn test_quote_swap() {
+        let quote = quote_swap(1_000, 1_000_000, 2_000_000, FEES_JSON).unwrap();
+        let expected = ProgramState {
+            fees: serde_json::from_str(FEES_JSON).unwrap(),
+            ..ProgramState::default()
+        }
+        .quote_swap(1_000, 1_000_000, 2_000_000)
+        .unwrap();
+        assert_eq!(
+            serde_wasm_bindgen::from_value::<serde_json::Value>(quote).unwrap(),
+            serde_json::to_value(expected).unwrap()
+        );
+        assert_eq!(
+            quote_swap(1_000, 1_000_000, 2_000_000, "{}").unwrap_err(),
+            js_error(AmmError::InvalidFee)
+        );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    fn test_quote_withdraw() {
+        let quote = quote_withdraw(50, 1_000, 2_000, 500, FEES_JSON).unwrap();
+        let quote: serde_json::Value = serde_wasm_bindgen::from_value(quote).unwrap();
+        assert_eq!(
+            quote,
+            serde_json::json!({"token_a_amount": 100, "token_b_amount": 200})
+        );
+    }
+}
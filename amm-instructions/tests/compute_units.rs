@@ -0,0 +1,534 @@
+//! Measures the compute units consumed by the amm instructions, from which
+//! the `COMPUTE_UNITS_*` constants of the instruction module are derived.
+//!
+//! `measure_token_invocations` measures the token instructions invoked by
+//! the program, and those sent next to its instructions, with the spl-token
+//! and Token-2022 programs of the bank, to derive
+//! [COMPUTE_UNITS_PER_TOKEN_CPI] and [COMPUTE_UNITS_OTHER]:
+//!
+//! ```text
+//! cargo test --test compute_units -- --ignored --nocapture measure_token_invocations
+//! ```
+//!
+//! `measure_compute_units` measures the amm instructions against the
+//! deployed program, dumped as `cropper_amm_v1.so` in `SBF_OUT_DIR`, to
+//! derive [COMPUTE_UNITS_BASE] and [COMPUTE_UNITS_CURVE]:
+//!
+//! ```text
+//! solana program dump CTMAxxk34HjKWxQ3QLZK1HpaLXmBveao3ESePXbiyfzh target/deploy/cropper_amm_v1.so
+//! SBF_OUT_DIR=target/deploy cargo test --test compute_units -- --ignored --nocapture measure_compute_units
+//! ```
+//!
+//! Both print the derived constants after the measurements, and fail when
+//! an estimate or a constant is below its measurement.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use cropper_amm_v1::{
+    curve::{
+        base::SwapCurve,
+        fees::{Fees, FeesBps},
+    },
+    instruction::{
+        update_program_state, AmmInstruction, DepositInstruction,
+        DepositSingleTokenTypeExactAmountIn, SwapInstruction, SwapPoolKeys, UserLiquidityKeys,
+        UserSwapKeys, WithdrawInstruction, WithdrawSingleTokenTypeExactAmountOut,
+        COMPUTE_UNITS_BASE, COMPUTE_UNITS_CURVE, COMPUTE_UNITS_OTHER, COMPUTE_UNITS_PER_TOKEN_CPI,
+    },
+    state::{find_swap_authority, ProgramState, SwapV1, SwapVersion},
+};
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+/// Token-2022 program, loaded in the bank by `ProgramTest`
+mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+/// Tokens of each side of the pool and of the user accounts
+const LIQUIDITY: u64 = 1_000_000_000;
+
+/// Units charged by the runtime for each cross-program invocation, on top
+/// of those consumed by the invoked program
+const INVOKE_UNITS: u64 = 1_000;
+
+/// Account of `owner` holding `data`, rent exempt for any size used here
+fn program_account(data: Vec<u8>, owner: &Pubkey) -> Account {
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Adds a mint of `authority` with `supply` to the bank
+fn add_mint(
+    program_test: &mut ProgramTest,
+    token_program_id: &Pubkey,
+    authority: &Pubkey,
+    supply: u64,
+) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(*authority),
+            supply,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(mint, program_account(data, token_program_id));
+    mint
+}
+
+/// Adds a token account of `owner` holding `amount` of `mint` to the bank
+fn add_token_account(
+    program_test: &mut ProgramTest,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let account = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(account, program_account(data, token_program_id));
+    account
+}
+
+/// Units consumed by `instruction` alone in a transaction, processed once
+/// measured
+async fn measure(
+    context: &mut ProgramTestContext,
+    instruction: Instruction,
+    signers: &[&Keypair],
+) -> u64 {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction.clone())
+        .await
+        .unwrap();
+    simulation.result.unwrap().unwrap();
+    let units = simulation.simulation_details.unwrap().units_consumed;
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    units
+}
+
+#[tokio::test]
+#[ignore]
+async fn measure_token_invocations() {
+    let wallet = Keypair::new();
+    let mut program_test = ProgramTest::default();
+    let token_programs = [spl_token::id(), spl_token_2022::id()];
+    let mut accounts = vec![];
+    for token_program_id in token_programs.iter() {
+        let mint = add_mint(
+            &mut program_test,
+            token_program_id,
+            &wallet.pubkey(),
+            LIQUIDITY,
+        );
+        let source = add_token_account(
+            &mut program_test,
+            token_program_id,
+            &mint,
+            &wallet.pubkey(),
+            LIQUIDITY,
+        );
+        let destination = add_token_account(
+            &mut program_test,
+            token_program_id,
+            &mint,
+            &wallet.pubkey(),
+            0,
+        );
+        accounts.push((*token_program_id, mint, source, destination));
+    }
+    program_test.add_account(
+        wallet.pubkey(),
+        Account::new(1_000_000_000, 0, &solana_program::system_program::id()),
+    );
+    let mut context = program_test.start_with_context().await;
+
+    // the instructions are built for spl-token, whose layout Token-2022
+    // shares, before being sent to their program. The approval and the
+    // associated token account creation are instructions of the transaction
+    // next to the amm ones, the others are invoked by the amm program
+    let wallet_key = wallet.pubkey();
+    let (mut invoked, mut other) = (0, 0);
+    println!("{:<24}{:<48}{:>8}", "instruction", "program", "units");
+    for (token_program_id, mint, source, destination) in accounts {
+        let instructions = [
+            (
+                "Transfer",
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &source,
+                    &destination,
+                    &wallet_key,
+                    &[],
+                    1_000,
+                ),
+            ),
+            (
+                "MintTo",
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    &mint,
+                    &destination,
+                    &wallet_key,
+                    &[],
+                    1_000,
+                ),
+            ),
+            (
+                "Burn",
+                spl_token::instruction::burn(
+                    &spl_token::id(),
+                    &source,
+                    &mint,
+                    &wallet_key,
+                    &[],
+                    1_000,
+                ),
+            ),
+            (
+                "Approve",
+                spl_token::instruction::approve(
+                    &spl_token::id(),
+                    &source,
+                    &Pubkey::new_unique(),
+                    &wallet_key,
+                    &[],
+                    1_000,
+                ),
+            ),
+        ];
+        for (name, instruction) in instructions.iter() {
+            let instruction = Instruction {
+                program_id: token_program_id,
+                ..instruction.clone().unwrap()
+            };
+            let units = measure(&mut context, instruction, &[&wallet]).await;
+            println!(
+                "{:<24}{:<48}{:>8}",
+                name,
+                token_program_id.to_string(),
+                units
+            );
+            if *name == "Approve" {
+                other = other.max(units);
+            } else {
+                invoked = invoked.max(units);
+            }
+        }
+        let instruction = create_associated_token_account_idempotent(
+            &wallet_key,
+            &Pubkey::new_unique(),
+            &mint,
+            &token_program_id,
+        );
+        let units = measure(&mut context, instruction, &[&wallet]).await;
+        println!(
+            "{:<24}{:<48}{:>8}",
+            "CreateIdempotent",
+            token_program_id.to_string(),
+            units
+        );
+        other = other.max(units);
+    }
+
+    let per_cpi = invoked + INVOKE_UNITS;
+    println!("COMPUTE_UNITS_PER_TOKEN_CPI >= {}", per_cpi);
+    println!("COMPUTE_UNITS_OTHER >= {}", other);
+    assert!(u64::from(COMPUTE_UNITS_PER_TOKEN_CPI) >= per_cpi);
+    assert!(u64::from(COMPUTE_UNITS_OTHER) >= other);
+}
+
+#[tokio::test]
+#[ignore]
+async fn measure_compute_units() {
+    let program_id = cropper_amm_v1::id();
+    let mut program_test = ProgramTest::default();
+    program_test.add_program("cropper_amm_v1", program_id, None);
+    program_test.prefer_bpf(true);
+
+    let state_owner = Keypair::new();
+    let wallet = Keypair::new();
+    let fee_owner = Pubkey::new_unique();
+    let fees = Fees::from_bps(FeesBps {
+        trade_bps: 25,
+        owner_trade_bps: 5,
+        owner_withdraw_bps: 0,
+        host_bps: 0,
+    })
+    .unwrap();
+    let state = Pubkey::new_unique();
+    let mut data = vec![0; ProgramState::LEN];
+    ProgramState {
+        is_initialized: true,
+        state_owner: state_owner.pubkey(),
+        fee_owner,
+        initial_supply: LIQUIDITY,
+        fees: fees.clone(),
+        swap_curve: SwapCurve::default(),
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(state, program_account(data, &program_id));
+
+    // a constant product pool holding LIQUIDITY of each token, the wallet
+    // holding as much of each token and of the pool tokens
+    let swap = Pubkey::new_unique();
+    let (authority, nonce) = find_swap_authority(&program_id, &swap);
+    let token_program_id = spl_token::id();
+    let wallet_key = wallet.pubkey();
+    let token_a_mint = add_mint(
+        &mut program_test,
+        &token_program_id,
+        &wallet_key,
+        2 * LIQUIDITY,
+    );
+    let token_b_mint = add_mint(
+        &mut program_test,
+        &token_program_id,
+        &wallet_key,
+        2 * LIQUIDITY,
+    );
+    let pool_mint = add_mint(
+        &mut program_test,
+        &token_program_id,
+        &authority,
+        2 * LIQUIDITY,
+    );
+    let mut add_account = |mint: &Pubkey, owner: &Pubkey| {
+        add_token_account(&mut program_test, &token_program_id, mint, owner, LIQUIDITY)
+    };
+    let pool = SwapPoolKeys {
+        program_id,
+        token_program_id,
+        swap,
+        authority,
+        state,
+        token_a_vault: add_account(&token_a_mint, &authority),
+        token_b_vault: add_account(&token_b_mint, &authority),
+        pool_mint,
+        fee_account: add_account(&pool_mint, &fee_owner),
+        fee_wallet: None,
+    };
+    let user = UserLiquidityKeys {
+        transfer_authority: wallet_key,
+        token_a: add_account(&token_a_mint, &wallet_key),
+        token_b: add_account(&token_b_mint, &wallet_key),
+        pool_token: add_account(&pool_mint, &wallet_key),
+    };
+    let mut data = vec![0; 1 + SwapV1::LEN];
+    SwapVersion::pack(
+        SwapVersion::SwapV1(SwapV1 {
+            is_initialized: true,
+            nonce,
+            token_program_id,
+            token_a: pool.token_a_vault,
+            token_b: pool.token_b_vault,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            ..SwapV1::default()
+        }),
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(swap, program_account(data, &program_id));
+    let mut context = program_test.start_with_context().await;
+
+    let a_to_b = UserSwapKeys {
+        transfer_authority: wallet_key,
+        source: user.token_a,
+        destination: user.token_b,
+    };
+    let b_to_a = UserSwapKeys {
+        source: user.token_b,
+        destination: user.token_a,
+        ..a_to_b.clone()
+    };
+    let swap = SwapInstruction {
+        amount_in: 1_000_000,
+        minimum_amount_out: 1,
+    };
+    let instructions = [
+        pool.swap_ix(&a_to_b, swap).unwrap(),
+        pool.reversed().swap_ix(&b_to_a, swap).unwrap(),
+        pool.deposit_all_token_types_ix(
+            &user,
+            DepositInstruction {
+                pool_token_amount: 1_000_000,
+                maximum_token_a_amount: 2_000_000,
+                maximum_token_b_amount: 2_000_000,
+            },
+        )
+        .unwrap(),
+        pool.withdraw_all_token_types_ix(
+            &user,
+            WithdrawInstruction {
+                pool_token_amount: 1_000_000,
+                minimum_token_a_amount: 1,
+                minimum_token_b_amount: 1,
+            },
+        )
+        .unwrap(),
+        pool.deposit_single_token_type_exact_amount_in_ix(
+            &UserSwapKeys {
+                destination: user.pool_token,
+                ..a_to_b.clone()
+            },
+            DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 1_000_000,
+                minimum_pool_token_amount: 1,
+            },
+        )
+        .unwrap(),
+        pool.withdraw_single_token_type_exact_amount_out_ix(
+            &UserSwapKeys {
+                transfer_authority: wallet_key,
+                source: user.pool_token,
+                destination: user.token_b,
+            },
+            WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount: 1_000_000,
+                maximum_pool_token_amount: 2_000_000,
+            },
+        )
+        .unwrap(),
+    ];
+    let mut measurements = vec![];
+    for instruction in instructions.iter() {
+        let units = measure(&mut context, instruction.clone(), &[&wallet]).await;
+        measurements.push((AmmInstruction::unpack(&instruction.data).unwrap(), units));
+    }
+    let instruction = update_program_state(
+        &program_id,
+        &state,
+        &state_owner.pubkey(),
+        &fee_owner,
+        LIQUIDITY,
+        fees,
+        SwapCurve::default(),
+    )
+    .unwrap();
+    let units = measure(&mut context, instruction.clone(), &[&state_owner]).await;
+    measurements.push((AmmInstruction::unpack(&instruction.data).unwrap(), units));
+
+    report(&measurements);
+}
+
+/// Whether the instruction runs the curve calculation, see
+/// [AmmInstruction::compute_unit_estimate]
+fn runs_curve(instruction: &AmmInstruction) -> bool {
+    matches!(
+        instruction,
+        AmmInstruction::Swap(_)
+            | AmmInstruction::Swap2(_)
+            | AmmInstruction::SwapExactOut(_)
+            | AmmInstruction::DepositSingleTokenTypeExactAmountIn(_)
+            | AmmInstruction::WithdrawSingleTokenTypeExactAmountOut(_)
+    )
+}
+
+/// Prints the measurements and the constants derived from them, failing
+/// when an estimate is below its measurement
+fn report(measurements: &[(AmmInstruction, u64)]) {
+    println!(
+        "{:<40}{:>12}{:>8}{:>10}{:>10}",
+        "instruction", "invocations", "curve", "units", "estimate"
+    );
+    // token program invocations of each instruction, from its estimate
+    let shapes: Vec<(u64, bool)> = measurements
+        .iter()
+        .map(|(instruction, _)| {
+            let curve = runs_curve(instruction);
+            let curve_units = if curve { COMPUTE_UNITS_CURVE } else { 0 };
+            let invocations =
+                (instruction.compute_unit_estimate() - COMPUTE_UNITS_BASE - curve_units)
+                    / COMPUTE_UNITS_PER_TOKEN_CPI;
+            (u64::from(invocations), curve)
+        })
+        .collect();
+    for ((instruction, units), (invocations, curve)) in measurements.iter().zip(shapes.iter()) {
+        println!(
+            "{:<40}{:>12}{:>8}{:>10}{:>10}",
+            format!("{:?}", instruction).split('(').next().unwrap(),
+            invocations,
+            curve,
+            units,
+            instruction.compute_unit_estimate(),
+        );
+    }
+
+    // the base is what the instructions spend over their token program
+    // invocations, the curve what the curve instructions spend over that
+    let per_cpi = u64::from(COMPUTE_UNITS_PER_TOKEN_CPI);
+    let overhead = |(units, invocations): (u64, u64)| units.saturating_sub(invocations * per_cpi);
+    let base = measurements
+        .iter()
+        .zip(shapes.iter())
+        .filter(|(_, (_, curve))| !curve)
+        .map(|((_, units), (invocations, _))| overhead((*units, *invocations)))
+        .max()
+        .unwrap();
+    let curve = measurements
+        .iter()
+        .zip(shapes.iter())
+        .filter(|(_, (_, curve))| *curve)
+        .map(|((_, units), (invocations, _))| overhead((*units, *invocations)).saturating_sub(base))
+        .max()
+        .unwrap();
+    println!("COMPUTE_UNITS_BASE >= {}", base);
+    println!("COMPUTE_UNITS_CURVE >= {}", curve);
+    assert!(u64::from(COMPUTE_UNITS_BASE) >= base);
+    assert!(u64::from(COMPUTE_UNITS_CURVE) >= curve);
+
+    for (instruction, units) in measurements {
+        assert!(
+            u64::from(instruction.compute_unit_estimate()) >= *units,
+            "{:?} estimate is below the {} units measured",
+            instruction,
+            units
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//! Links the crate into a program defining its own entrypoint, as a
+//! dependent on-chain program does. The crate must not export an
+//! `entrypoint` symbol of its own, or this test fails to link.
+
+use cropper_amm_v1::instruction::{AmmInstruction, SwapInstruction};
+use solana_program::{
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
+    program_error::ProgramError, pubkey::Pubkey,
+};
+
+entrypoint!(process_instruction);
+
+fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match AmmInstruction::unpack(instruction_data)? {
+        AmmInstruction::Swap(_) => Ok(()),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+#[test]
+fn test_consumer_links_with_own_entrypoint() {
+    let data = AmmInstruction::Swap(SwapInstruction {
+        amount_in: 1_000,
+        minimum_amount_out: 990,
+    })
+    .pack();
+    assert_eq!(process_instruction(&cropper_amm_v1::id(), &[], &data), Ok(()));
+    assert!(process_instruction(&cropper_amm_v1::id(), &[], &[]).is_err());
+    assert_eq!(
+        cropper_amm_v1::id().to_string(),
+        "CTMAxxk34HjKWxQ3QLZK1HpaLXmBveao3ESePXbiyfzh"
+    );
+}
@@ -0,0 +1,395 @@
+//! Round-trips the instructions and account states through their byte
+//! layouts, and checks that truncated input is rejected without panicking.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use cropper_amm_v1::{
+    curve::{
+        base::{CurveType, SwapCurve},
+        calculator::TradeDirection,
+        constant_price::ConstantPriceCurve,
+        constant_product::ConstantProductCurve,
+        fees::Fees,
+        offset::OffsetCurve,
+        stable::StableCurve,
+    },
+    error::AmmError,
+    instruction::{
+        AmmInstruction, DepositInstruction, DepositSingleTokenTypeExactAmountIn,
+        Initialize2Instruction, InitializeInstruction, SwapExactOutInstruction, SwapInstruction,
+        SwapInstruction2, WithdrawInstruction, WithdrawSingleTokenTypeExactAmountOut,
+    },
+    state::{ProgramState, ProgramStateV2, SwapV1, SwapV2, SwapVersion},
+};
+use proptest::prelude::*;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use std::{convert::TryFrom, sync::Arc};
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+/// Fees passing `Fees::validate`, every numerator at most the denominator
+fn fees() -> impl Strategy<Value = Fees> {
+    (1..=u64::MAX)
+        .prop_flat_map(|fee_denominator| {
+            let max = u32::try_from(fee_denominator).unwrap_or(u32::MAX);
+            (0..=max, 0..=max, 0..=max, 0..=max, Just(fee_denominator))
+        })
+        .prop_map(
+            |(
+                trade_fee_numerator,
+                owner_trade_fee_numerator,
+                owner_withdraw_fee_numerator,
+                host_fee_numerator,
+                fee_denominator,
+            )| Fees {
+                trade_fee_numerator,
+                owner_trade_fee_numerator,
+                owner_withdraw_fee_numerator,
+                host_fee_numerator,
+                fee_denominator,
+            },
+        )
+}
+
+fn swap_curve() -> impl Strategy<Value = SwapCurve> {
+    prop_oneof![
+        Just(SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve),
+        }),
+        any::<u64>().prop_map(|token_b_price| SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve { token_b_price }),
+        }),
+        any::<u64>().prop_map(|amp| SwapCurve {
+            curve_type: CurveType::Stable,
+            calculator: Arc::new(StableCurve { amp }),
+        }),
+        any::<u64>().prop_map(|token_b_offset| SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve { token_b_offset }),
+        }),
+    ]
+}
+
+fn instruction() -> impl Strategy<Value = AmmInstruction> {
+    prop_oneof![
+        (any::<u8>(), proptest::option::of(swap_curve())).prop_map(|(nonce, swap_curve)| {
+            AmmInstruction::Initialize(InitializeInstruction { nonce, swap_curve })
+        }),
+        (any::<u64>(), any::<u64>()).prop_map(|(amount_in, minimum_amount_out)| {
+            AmmInstruction::Swap(SwapInstruction {
+                amount_in,
+                minimum_amount_out,
+            })
+        }),
+        (any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+            |(pool_token_amount, maximum_token_a_amount, maximum_token_b_amount)| {
+                AmmInstruction::DepositAllTokenTypes(DepositInstruction {
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                })
+            }
+        ),
+        (any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+            |(pool_token_amount, minimum_token_a_amount, minimum_token_b_amount)| {
+                AmmInstruction::WithdrawAllTokenTypes(WithdrawInstruction {
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+        ),
+        (any::<u64>(), any::<u64>()).prop_map(
+            |(source_token_amount, minimum_pool_token_amount)| {
+                AmmInstruction::DepositSingleTokenTypeExactAmountIn(
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                )
+            }
+        ),
+        (any::<u64>(), any::<u64>()).prop_map(
+            |(destination_token_amount, maximum_pool_token_amount)| {
+                AmmInstruction::WithdrawSingleTokenTypeExactAmountOut(
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                )
+            }
+        ),
+        (any::<u64>(), any::<u64>()).prop_map(|(amount_out, maximum_amount_in)| {
+            AmmInstruction::SwapExactOut(SwapExactOutInstruction {
+                amount_out,
+                maximum_amount_in,
+            })
+        }),
+        fees().prop_map(AmmInstruction::SetFees),
+        Just(AmmInstruction::Pause),
+        Just(AmmInstruction::Unpause),
+        any::<u64>().prop_map(AmmInstruction::WithdrawFees),
+        pubkey().prop_map(|new_owner| AmmInstruction::TransferStateOwnership { new_owner }),
+        Just(AmmInstruction::AcceptStateOwnership),
+        Just(AmmInstruction::ClosePool),
+        (any::<u64>(), fees(), swap_curve()).prop_map(|(initial_supply, fees, curve)| {
+            AmmInstruction::InitializeProgramState {
+                initial_supply,
+                fees,
+                curve,
+            }
+        }),
+        (any::<u64>(), fees(), swap_curve()).prop_map(|(initial_supply, fees, curve)| {
+            AmmInstruction::UpdateProgramState {
+                initial_supply,
+                fees,
+                curve,
+            }
+        }),
+        (any::<u8>(), any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+            |(nonce, initial_token_a, initial_token_b, min_pool_tokens)| {
+                AmmInstruction::Initialize2(Initialize2Instruction {
+                    nonce,
+                    initial_token_a,
+                    initial_token_b,
+                    min_pool_tokens,
+                })
+            }
+        ),
+        (
+            any::<u128>(),
+            any::<u128>(),
+            proptest::option::of(prop_oneof![
+                Just(TradeDirection::AtoB),
+                Just(TradeDirection::BtoA)
+            ]),
+        )
+            .prop_map(|(amount_in, minimum_amount_out, direction)| {
+                AmmInstruction::Swap2(SwapInstruction2 {
+                    amount_in,
+                    minimum_amount_out,
+                    direction,
+                })
+            }),
+    ]
+}
+
+fn program_state() -> impl Strategy<Value = ProgramState> {
+    (
+        any::<bool>(),
+        pubkey(),
+        pubkey(),
+        any::<u64>(),
+        fees(),
+        swap_curve(),
+    )
+        .prop_map(
+            |(is_initialized, state_owner, fee_owner, initial_supply, fees, swap_curve)| {
+                ProgramState {
+                    is_initialized,
+                    state_owner,
+                    fee_owner,
+                    initial_supply,
+                    fees,
+                    swap_curve,
+                }
+            },
+        )
+}
+
+fn swap_v1() -> impl Strategy<Value = SwapV1> {
+    (
+        any::<bool>(),
+        any::<u8>(),
+        proptest::collection::vec(pubkey(), 9),
+    )
+        .prop_map(|(is_initialized, nonce, keys)| SwapV1 {
+            is_initialized,
+            nonce,
+            amm_id: keys[0],
+            dex_program_id: keys[1],
+            market_id: keys[2],
+            token_program_id: keys[3],
+            token_a: keys[4],
+            token_b: keys[5],
+            pool_mint: keys[6],
+            token_a_mint: keys[7],
+            token_b_mint: keys[8],
+        })
+}
+
+fn swap_v2() -> impl Strategy<Value = SwapV2> {
+    (
+        swap_v1(),
+        any::<bool>(),
+        fees(),
+        swap_curve(),
+        pubkey(),
+        pubkey(),
+        pubkey(),
+    )
+        .prop_map(
+            |(v1, is_paused, fees, swap_curve, pool_fee, token_a_fee, token_b_fee)| SwapV2 {
+                is_paused,
+                ..SwapV2::from_v1(v1, fees, swap_curve, pool_fee, token_a_fee, token_b_fee)
+            },
+        )
+}
+
+/// Initialized swaps of either version, `SwapVersion::unpack_concrete`
+/// rejecting the others
+fn swap_version() -> impl Strategy<Value = SwapVersion> {
+    prop_oneof![
+        swap_v1().prop_map(|swap| SwapVersion::SwapV1(SwapV1 {
+            is_initialized: true,
+            ..swap
+        })),
+        swap_v2().prop_map(|swap| SwapVersion::SwapV2(SwapV2 {
+            is_initialized: true,
+            ..swap
+        })),
+    ]
+}
+
+/// Swap account data of the version of `swap`, packed in place since the
+/// swaps are not `Clone`
+fn swap_data(swap: &SwapVersion) -> Vec<u8> {
+    match swap {
+        SwapVersion::SwapV1(swap) => {
+            let mut data = vec![0; 1 + SwapV1::LEN];
+            data[0] = 1;
+            swap.pack_into_slice(&mut data[1..]);
+            data
+        }
+        SwapVersion::SwapV2(swap) => {
+            let mut data = vec![0; 1 + SwapV2::LEN];
+            data[0] = 2;
+            swap.pack_into_slice(&mut data[1..]);
+            data
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn instruction_roundtrip(instruction in instruction()) {
+        let data = instruction.pack();
+        prop_assert_eq!(data.len(), instruction.packed_len());
+        prop_assert_eq!(AmmInstruction::unpack(&data).unwrap(), instruction);
+    }
+
+    /// Every prefix either fails or decodes to an instruction packing back
+    /// to that prefix, which is how an optional trailing curve or direction
+    /// is left out
+    #[test]
+    fn instruction_truncated(instruction in instruction()) {
+        let data = instruction.pack();
+        for len in 0..data.len() {
+            let prefix = &data[..len];
+            if let Ok(decoded) = AmmInstruction::unpack(prefix) {
+                prop_assert_eq!(decoded.pack(), prefix);
+            }
+        }
+    }
+
+    #[test]
+    fn instruction_unpack_never_panics(data in proptest::collection::vec(any::<u8>(), 0..128)) {
+        let _ = AmmInstruction::unpack(&data);
+    }
+
+    #[test]
+    fn program_state_roundtrip(state in program_state()) {
+        let mut data = vec![0; ProgramState::LEN];
+        state.pack_into_slice(&mut data);
+        prop_assert_eq!(ProgramState::unpack_unchecked(&data).unwrap(), state);
+        for len in 0..data.len() {
+            prop_assert!(ProgramState::unpack_unchecked(&data[..len]).is_err());
+        }
+    }
+
+    /// Prefixes of the original layout's length are read as that layout,
+    /// without the pending owner
+    #[test]
+    fn program_state_v2_truncated(
+        state in program_state(),
+        pending_owner in pubkey(),
+    ) {
+        let state = ProgramStateV2 {
+            state: ProgramState {
+                is_initialized: true,
+                ..state
+            },
+            pending_owner,
+        };
+        let mut data = vec![0; ProgramStateV2::LEN];
+        state.pack_versioned(&mut data).unwrap();
+        prop_assert_eq!(&ProgramStateV2::unpack_versioned(&data).unwrap(), &state);
+        for len in 0..ProgramState::LEN {
+            prop_assert!(ProgramStateV2::unpack_versioned(&data[..len]).is_err());
+        }
+        for len in ProgramState::LEN..data.len() {
+            let decoded = ProgramStateV2::unpack_versioned(&data[..len]).unwrap();
+            prop_assert_eq!(decoded.pending_owner, Pubkey::default());
+            prop_assert_eq!(&decoded.state, &state.state);
+        }
+    }
+
+    #[test]
+    fn swap_roundtrip(swap in swap_version()) {
+        let data = swap_data(&swap);
+        prop_assert_eq!(SwapVersion::unpack_concrete(&data).unwrap(), swap);
+        let mut packed = vec![0; data.len()];
+        SwapVersion::pack(SwapVersion::unpack_concrete(&data).unwrap(), &mut packed).unwrap();
+        prop_assert_eq!(packed, data);
+    }
+
+    #[test]
+    fn swap_truncated(swap in swap_version()) {
+        let data = swap_data(&swap);
+        for len in 0..data.len() {
+            prop_assert!(SwapVersion::unpack_concrete(&data[..len]).is_err());
+        }
+    }
+
+    /// Fees and curves are unpacked from slices of an account or an
+    /// instruction, whose short prefixes fail instead of panicking
+    #[test]
+    fn fees_truncated(fees in fees()) {
+        let mut data = vec![0; Fees::LEN];
+        fees.pack_into_slice(&mut data);
+        prop_assert_eq!(Fees::unpack_from_slice(&data).unwrap(), fees);
+        for len in 0..data.len() {
+            prop_assert_eq!(
+                Fees::unpack_from_slice(&data[..len]).err(),
+                Some(AmmError::InvalidAccountLength.into())
+            );
+        }
+    }
+
+    #[test]
+    fn swap_curve_truncated(curve in swap_curve()) {
+        let mut data = vec![0; SwapCurve::LEN];
+        curve.pack_into_slice(&mut data);
+        prop_assert_eq!(SwapCurve::unpack_from_slice(&data).unwrap(), curve);
+        for len in 0..data.len() {
+            prop_assert_eq!(
+                SwapCurve::unpack_from_slice(&data[..len]).err(),
+                Some(AmmError::InvalidAccountLength.into())
+            );
+        }
+    }
+
+    #[test]
+    fn swap_v1_roundtrip(swap in swap_v1()) {
+        let mut data = vec![0; SwapV1::LEN];
+        swap.pack_into_slice(&mut data);
+        prop_assert_eq!(SwapV1::unpack_unchecked(&data).unwrap(), swap);
+        for len in 0..data.len() {
+            prop_assert!(SwapV1::unpack_unchecked(&data[..len]).is_err());
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Fuzzes the Borsh encoding of the farm instructions: every generated
+//! instruction must round-trip, and arbitrary bytes must decode or fail
+//! without panicking.
+//!
+//! Run with `cargo hfuzz run farm-instructions` from this directory.
+
+use {
+    arbitrary::Arbitrary,
+    borsh::BorshDeserialize,
+    cropper_farm_v1::instruction::FarmInstruction,
+    honggfuzz::fuzz,
+};
+
+#[derive(Debug, Arbitrary)]
+struct FuzzData {
+    instruction: FarmInstruction,
+    raw_data: Vec<u8>,
+}
+
+fn main() {
+    loop {
+        fuzz!(|fuzz_data: FuzzData| { run_fuzz(fuzz_data) });
+    }
+}
+
+fn run_fuzz(fuzz_data: FuzzData) {
+    let packed = fuzz_data.instruction.pack().unwrap();
+    assert_eq!(packed.len(), fuzz_data.instruction.packed_len());
+    assert_eq!(packed[0], fuzz_data.instruction.tag());
+    let unpacked = FarmInstruction::try_from_slice(&packed).unwrap();
+    assert_eq!(unpacked, fuzz_data.instruction);
+
+    let _ = FarmInstruction::try_from_slice(&fuzz_data.raw_data);
+}
@@ -0,0 +1,136 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the Farm program.
+///
+/// The discriminants are part of the program's public interface: clients
+/// match on `ProgramError::Custom(code)`, so variants must never be
+/// reordered and new ones are only appended.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum FarmError {
+    // 0.
+    /// The instruction data could not be serialized or deserialized.
+    #[error("Invalid instruction")]
+    InvalidInstruction = 0,
+    /// The farm has not reached its start timestamp yet.
+    #[error("Farm has not started yet")]
+    FarmNotStarted = 1,
+    /// The farm is past its end timestamp.
+    #[error("Farm has already ended")]
+    FarmEnded = 2,
+    /// The farm fee has not been paid, or the signer may not perform this action.
+    #[error("Not allowed")]
+    NotAllowed = 3,
+    /// The farm does not hold enough reward tokens.
+    #[error("Insufficient reward in the farm")]
+    InsufficientReward = 4,
+
+    // 5.
+    /// The provided fee account is not the one stored in the program data.
+    #[error("Wrong fee account")]
+    WrongFeeAccount = 5,
+    /// The provided authority is not the one derived from the farm and nonce.
+    #[error("Wrong farm authority")]
+    WrongAuthority = 6,
+    /// The amount must be greater than zero.
+    #[error("Amount must not be zero")]
+    ZeroAmount = 7,
+    /// The farm must end after it starts, and a started farm keeps its start.
+    #[error("Invalid farm period")]
+    InvalidFarmPeriod = 8,
+    /// The harvest fee denominator is zero or below the numerator.
+    #[error("Invalid harvest fee")]
+    InvalidHarvestFee = 9,
+
+    // 10.
+    /// A reward calculation overflowed.
+    #[error("Math operation overflow")]
+    MathOverflow = 10,
+    /// The farm still holds staked LP tokens.
+    #[error("Farm still holds staked LP tokens")]
+    FarmNotEmpty = 11,
+    /// The staked LP tokens are locked until the end of the user lockup.
+    #[error("Deposit is still locked")]
+    DepositLocked = 12,
+    /// The boost multiplier has a zero denominator or is below one.
+    #[error("Invalid boost parameters")]
+    InvalidBoostParams = 13,
+    /// The paid farm fee differs from the one set in the program data.
+    #[error("Invalid farm fee")]
+    InvalidFarmFee = 14,
+
+    // 15.
+    /// The added reward is below one token per remaining second of the farm.
+    #[error("Reward below one token per remaining second")]
+    RewardBelowRate = 15,
+    /// The farm has not passed its end timestamp yet.
+    #[error("Farm has not ended yet")]
+    FarmNotEnded = 16,
+}
+
+impl From<FarmError> for ProgramError {
+    fn from(e: FarmError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for FarmError {
+    fn type_of() -> &'static str {
+        "Farm Error"
+    }
+}
+
+impl PrintProgramError for FarmError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        msg!("Error: {}", self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clients match on the codes, which must not change
+    #[test]
+    fn test_error_codes() {
+        let codes = [
+            (FarmError::InvalidInstruction, 0),
+            (FarmError::FarmNotStarted, 1),
+            (FarmError::FarmEnded, 2),
+            (FarmError::NotAllowed, 3),
+            (FarmError::InsufficientReward, 4),
+            (FarmError::WrongFeeAccount, 5),
+            (FarmError::WrongAuthority, 6),
+            (FarmError::ZeroAmount, 7),
+            (FarmError::InvalidFarmPeriod, 8),
+            (FarmError::InvalidHarvestFee, 9),
+            (FarmError::MathOverflow, 10),
+            (FarmError::FarmNotEmpty, 11),
+            (FarmError::DepositLocked, 12),
+            (FarmError::InvalidBoostParams, 13),
+            (FarmError::InvalidFarmFee, 14),
+            (FarmError::RewardBelowRate, 15),
+            (FarmError::FarmNotEnded, 16),
+        ];
+        for (error, code) in codes.iter() {
+            assert_eq!(error.clone() as u32, *code);
+            assert_eq!(
+                ProgramError::from(error.clone()),
+                ProgramError::Custom(*code)
+            );
+            assert_eq!(FarmError::from_u32(*code).as_ref(), Some(error));
+        }
+        assert_eq!(FarmError::from_u32(codes.len() as u32), None);
+    }
+}
@@ -1,349 +1,3560 @@
-//! All instruction types
-//! These instructions represent a function what will be processed by this program
-
-// this allows many arguments for the function parameters
-#![allow(clippy::too_many_arguments)]
-
-use {
-    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
-    solana_program::{
-        instruction::{AccountMeta, Instruction},
-        pubkey::Pubkey,
-        sysvar
-    },
-};
-
-/// Instructions supported by the FarmPool program.
-#[repr(C)]
-#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
-pub enum FarmInstruction {
-    ///   Set program data
-    SetProgramData ,
-
-    ///   Initializes a new FarmPool.
-    ///   These represent the parameters that will be included from client side
-    ///   [w] - writable, [s] - signer
-    /// 
-    ///   0. `[w]` New FarmPool account to create.
-    ///   1. `[]` authority to initialize this farm pool account
-    ///   2. `[s]` Creator/Manager of this farm
-    ///   3. `[w]` LP token account of this farm to store lp token
-    ///   4. `[w]` reward token account of this farm to store rewards for the farmers
-    ///             Creator has to transfer/deposit his reward token to this account.
-    ///             only support spl tokens
-    ///   5. `[]` Pool token mint address
-    ///   6. `[]` Reward token mint address
-    ///   7. `[]` Amm Id
-    ///   8. `[]` farm program data id
-    ///   9. `[]` nonce
-    ///   10.'[]' start timestamp. this reflects that the farm starts at this time
-    ///   11.'[]' end timestamp. this reflects that the farm ends at this time
-    ///   12. `[]` program id
-    InitializeFarm {
-        #[allow(dead_code)]
-        /// nonce
-        nonce: u8,
-
-        #[allow(dead_code)]
-        /// start timestamp
-        start_timestamp: u64,
-
-        #[allow(dead_code)]
-        /// end timestamp
-        end_timestamp: u64,
-    },
-
-    ///   Stake Lp tokens to this farm pool
-    ///   If amount is zero, only performed "harvest"
-    ///   If this farm is not allowed/not started/ended, it fails
-    /// 
-    ///   0. `[w]` FarmPool to deposit to.
-    ///   1. `[]` authority of this farm pool
-    ///   2. `[s]` Depositor
-    ///   3. `[]` User Farming Information Account
-    ///   4. `[]` User LP token account
-    ///   5. `[]` Pool LP token account
-    ///   6. `[]` User reward token account
-    ///   7. `[]` Pool reward token account
-    ///   8. `[]` Pool LP token mint
-    ///   9. `[]` fee reward ata account
-    ///   10. `[]` farm program data id
-    ///   11. `[]` Token program id
-    ///   12. `[]` clock sysvar
-    ///   13. `[]` amount
-    ///   14. `[]` program id
-    Deposit(u64),
-
-    ///   Unstake LP tokens from this farm pool
-    ///   Before unstake lp tokens, "harvest" works
-    /// 
-    ///   0. `[w]` FarmPool to withdraw to.
-    ///   1. `[]` authority of this farm pool
-    ///   2. `[s]` Withdrawer
-    ///   3. `[]` User Farming Information Account
-    ///   4. `[]` User LP token account
-    ///   5. `[]` Pool LP token account
-    ///   6. `[]` User reward token account
-    ///   7. `[]` Pool reward token account
-    ///   8. `[]` Pool LP token mint
-    ///   9. `[]` fee reward ata account
-    ///   10. `[]` farm program data id
-    ///   11. `[]` Token program id
-    ///   12. `[]` clock sysvar
-    ///   13. `[]` amount
-    ///   14. `[]` program id
-    Withdraw(u64),
-
-    ///   Creator can add reward to his farm 
-    /// 
-    ///   0. `[w]` FarmPool to add reward to.
-    ///   1. `[]` authority of this farm pool
-    ///   2. `[s]` creator
-    ///   3. `[]` User reward token account
-    ///   4. `[]` Pool reward token account
-    ///   5. `[]` Pool lp token mint
-    ///   6. `[]` farm program data id
-    ///   7. `[]` token program id
-    ///   8. `[]` clock sysvar
-    ///   9. `[]` amount
-    ///   10. `[]` program id
-    AddReward(u64),
-    
-    ///   Creator has to pay farm fee (if not CRP token pairing)
-    ///   So this farm can be allowed to stake/unstake/harvest
-    /// 
-    ///   0. `[w]` FarmPool to pay farm fee.
-    ///   1. `[]` authority of this farm pool
-    ///   2. `[s]` creator
-    ///   3. `[]` User USDC token account
-    ///   4. `[]` fee usdc ata
-    ///   5. `[]` farm program data account
-    ///   6. `[]` token program id
-    ///   7. `[]` amount
-    ///   8. `[]` program id
-    PayFarmFee(u64),
-}
-
-// below functions are used to test above instructions in the rust test side
-// Function's parameters
-
-
-/// Creates an 'SetProgramData' instruction.
-pub fn initialize_program(
-    program_data_account: &Pubkey,
-    super_owner: &Pubkey,
-    new_super_owner: Pubkey,
-    fee_owner: Pubkey,
-    allowed_creator: Pubkey,
-    amm_program_id: Pubkey,
-    farm_fee: u64,
-    harvest_fee_numerator: u64,
-    harvest_fee_denominator: u64,
-    program_id: &Pubkey,
-) -> Instruction {
-    
-    let init_data = FarmInstruction::SetProgramData{
-        super_owner:new_super_owner,
-        fee_owner,
-        allowed_creator,
-        amm_program_id,
-        farm_fee,
-        harvest_fee_numerator,
-        harvest_fee_denominator
-    };
-    
-    let data = init_data.try_to_vec().unwrap();
-    let accounts = vec![
-        AccountMeta::new(*program_data_account, false),
-        AccountMeta::new(*super_owner, true),
-    ];
-    Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    }
-}
-
-
-/// Creates an 'InitializeFarm' instruction.
-pub fn initialize_farm(
-    farm_id: &Pubkey,
-    authority: &Pubkey,
-    owner: &Pubkey,
-    pool_lp_token_account: &Pubkey,
-    pool_reward_token_account: &Pubkey,
-    pool_mint_address: &Pubkey,
-    reward_mint_address: &Pubkey,
-    amm_id: &Pubkey,
-    program_data_account: &Pubkey,
-    nonce: u8,
-    start_timestamp: u64,
-    end_timestamp: u64,
-    program_id: &Pubkey,
-) -> Instruction {
-    
-    let init_data = FarmInstruction::InitializeFarm{
-        nonce,
-        start_timestamp,
-        end_timestamp
-    };
-    
-    let data = init_data.try_to_vec().unwrap();
-    let accounts = vec![
-        AccountMeta::new(*farm_id, false),
-        AccountMeta::new(*authority, false),
-        AccountMeta::new_readonly(*owner, true),
-        AccountMeta::new(*pool_lp_token_account, false),
-        AccountMeta::new(*pool_reward_token_account, false),
-        AccountMeta::new_readonly(*pool_mint_address, false),
-        AccountMeta::new_readonly(*reward_mint_address, false),
-        AccountMeta::new_readonly(*amm_id, false),
-        AccountMeta::new_readonly(*program_data_account, false),
-    ];
-    Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    }
-}
-
-/// Creates instructions required to deposit into a farm pool, given a farm
-/// account owned by the user.
-pub fn deposit(
-    farm_id: &Pubkey,
-    authority: &Pubkey,
-    owner: &Pubkey,
-    user_info_account: &Pubkey,
-    user_lp_token_account: &Pubkey,
-    pool_lp_token_account: &Pubkey,
-    user_reward_token_account: &Pubkey,
-    pool_reward_token_account: &Pubkey,
-    pool_lp_mint: &Pubkey,
-    fee_reward_ata: &Pubkey,
-    program_data_account: &Pubkey,
-    token_program_id: &Pubkey,
-    amount: u64,
-    program_id: &Pubkey,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*farm_id, false),
-        AccountMeta::new_readonly(*authority, false),
-        AccountMeta::new_readonly(*owner, true),
-        AccountMeta::new(*user_info_account, false),
-        AccountMeta::new(*user_lp_token_account, false),
-        AccountMeta::new(*pool_lp_token_account, false),
-        AccountMeta::new(*user_reward_token_account, false),
-        AccountMeta::new(*pool_reward_token_account, false),
-        AccountMeta::new(*pool_lp_mint, false),
-        AccountMeta::new(*fee_reward_ata, false),
-        AccountMeta::new(*program_data_account, false),
-        AccountMeta::new(*token_program_id, false),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-    ];
-    Instruction {
-        program_id: *program_id,
-        accounts,
-        data: FarmInstruction::Deposit(amount).try_to_vec().unwrap(),
-    }
-}
-
-/// Creates a 'withdraw' instruction.
-pub fn withdraw(
-    farm_id: &Pubkey,
-    authority: &Pubkey,
-    owner: &Pubkey,
-    user_info_account: &Pubkey,
-    user_lp_token_account: &Pubkey,
-    pool_lp_token_account: &Pubkey,
-    user_reward_token_account: &Pubkey,
-    pool_reward_token_account: &Pubkey,
-    pool_lp_mint_info: &Pubkey,
-    fee_reward_ata: &Pubkey,
-    program_data_account: &Pubkey,
-    token_program_id: &Pubkey,
-    amount: u64,
-    program_id: &Pubkey,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*farm_id, false),
-        AccountMeta::new_readonly(*authority, false),
-        AccountMeta::new(*owner, true),
-        AccountMeta::new(*user_info_account, false),
-        AccountMeta::new(*user_lp_token_account, false),
-        AccountMeta::new(*pool_lp_token_account, false),
-        AccountMeta::new(*user_reward_token_account, false),
-        AccountMeta::new(*pool_reward_token_account, false),
-        AccountMeta::new(*pool_lp_mint_info, false),
-        AccountMeta::new(*fee_reward_ata, false),
-        AccountMeta::new(*program_data_account, false),
-        AccountMeta::new(*token_program_id, false),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-    ];
-    Instruction {
-        program_id: *program_id,
-        accounts,
-        data: FarmInstruction::Withdraw(amount).try_to_vec().unwrap(),
-    }
-}
-
-
-/// Creates a instruction required to add reward into a farm pool
-pub fn add_reward(
-    farm_id: &Pubkey,
-    authority: &Pubkey,
-    owner: &Pubkey,
-    user_reward_token_account: &Pubkey,
-    pool_reward_token_account: &Pubkey,
-    pool_lp_token_account: &Pubkey,
-    pool_lp_mint_info: &Pubkey,
-    program_data_account: &Pubkey,
-    token_program_id: &Pubkey,
-    amount: u64,
-    program_id: &Pubkey,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*farm_id, false),
-        AccountMeta::new_readonly(*authority, false),
-        AccountMeta::new_readonly(*owner, true),
-        AccountMeta::new(*user_reward_token_account, false),
-        AccountMeta::new(*pool_reward_token_account, false),
-        AccountMeta::new(*pool_lp_token_account, false),
-        AccountMeta::new(*pool_lp_mint_info, false),
-        AccountMeta::new(*program_data_account, false),
-        AccountMeta::new(*token_program_id, false),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-    ];
-    Instruction {
-        program_id: *program_id,
-        accounts,
-        data: FarmInstruction::AddReward(amount).try_to_vec().unwrap(),
-    }
-}
-
-/// Create a instruction required to pay additonal farm fee
-pub fn pay_farm_fee(
-    farm_id: &Pubkey,
-    authority: &Pubkey,
-    owner: &Pubkey,
-    user_usdc_token_account: &Pubkey,
-    fee_usdc_ata: &Pubkey,
-    program_data_account: &Pubkey,
-    token_program_id: &Pubkey,
-    amount: u64,
-    program_id: &Pubkey,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*farm_id, false),
-        AccountMeta::new_readonly(*authority, false),
-        AccountMeta::new_readonly(*owner, true),
-        AccountMeta::new(*user_usdc_token_account, false),
-        AccountMeta::new(*fee_usdc_ata, false),
-        AccountMeta::new(*program_data_account, false),
-        AccountMeta::new(*token_program_id, false),
-    ];
-    Instruction {
-        program_id: *program_id,
-        accounts,
-        data: FarmInstruction::PayFarmFee(amount).try_to_vec().unwrap(),
-    }
-}
\ No newline at end of file
+//! All instruction types
+//! These instructions represent a function what will be processed by this program
+//!
+//! Since 1.1.0 the builders return `Result<Instruction, ProgramError>`,
+//! callers of the 1.0 API only need to add `?` to each call.
+
+// this allows many arguments for the function parameters
+#![allow(clippy::too_many_arguments)]
+
+use {
+    crate::{
+        error::FarmError,
+        state::{
+            farm_authority, find_executable_data_address, find_farm_authority,
+            find_user_info_address, FarmPool, FarmPoolV2, FarmPoolVersion, FarmProgramData,
+            FarmTimestamp,
+        },
+    },
+    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_instruction, system_program, sysvar,
+    },
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    std::{convert::TryFrom, fmt},
+};
+
+#[cfg(feature = "fuzz")]
+use arbitrary::{Arbitrary, Unstructured};
+
+/// Compute units of the processing common to every instruction, unpacking,
+/// account checks and reward calculation.
+///
+/// A guess, not measured yet: the farm program is only metered when the
+/// `compute_units` harness in `tests` runs it as SBF, and the harness then
+/// fails when a measurement exceeds it.
+pub const COMPUTE_UNITS_BASE: u32 = 20_000;
+
+/// Compute units of one token or system program invocation, Token-2022
+/// included.
+///
+/// Derived from the `compute_units` harness in `tests`, measuring at most
+/// 4,690 units per spl-token invocation. A Token-2022 transfer consumes up
+/// to 6,114 units, as measured by the harness of the amm crate, plus the
+/// 1,000 units charged by the runtime for each invocation: 7,114 rounded up.
+pub const COMPUTE_UNITS_PER_CPI: u32 = 7_500;
+
+/// Compute units assumed for an instruction of another program, e.g. an
+/// associated token account creation or a token approval.
+///
+/// Derived from the `compute_units` harness in `tests`, measuring about
+/// 20,400 units for an associated token account creation when the first
+/// bump seed gives its address, and 1,500 more for each bump searched past,
+/// 35,000 covering nine of them.
+pub const COMPUTE_UNITS_OTHER: u32 = 35_000;
+
+/// Maximum compute unit limit of a transaction
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Instructions supported by the FarmPool program.
+///
+/// Borsh encodes the variant position as the first byte, so deployed clients
+/// depend on the declaration order: new variants must only be appended, and
+/// each one gets a matching `TAG_*` constant.
+#[repr(C)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum FarmInstruction {
+    ///   Set program data
+    ///   The super owner cannot be changed here, `super_owner` must be the
+    ///   current one; see `ProposeSuperOwner` and `AcceptSuperOwner`
+    ///   The first call creates the program data account, its signer
+    ///   becoming the super owner. It must be the upgrade authority of the
+    ///   program, so that nobody initializes a newly deployed program first
+    ///
+    ///   0. `[w]` farm program data account, see `find_program_data_address`
+    ///   1. `[w, s]` current super owner, paying for the program data
+    ///      account when creating it
+    ///   2. `[]` System program id, only read when creating the account
+    ///   3. `[]` rent sysvar, only read when creating the account
+    ///   4. `[]` `ProgramData` account of the program in the upgradeable
+    ///      loader, holding its upgrade authority, only read when creating
+    ///      the account
+    SetProgramData {
+        #[allow(dead_code)]
+        /// current super owner
+        super_owner: Pubkey,
+
+        #[allow(dead_code)]
+        /// owner receiving the fees
+        fee_owner: Pubkey,
+
+        #[allow(dead_code)]
+        /// creator allowed to create farms without paying the farm fee
+        allowed_creator: Pubkey,
+
+        #[allow(dead_code)]
+        /// amm program id
+        amm_program_id: Pubkey,
+
+        #[allow(dead_code)]
+        /// farm fee
+        farm_fee: u64,
+
+        #[allow(dead_code)]
+        /// harvest fee numerator
+        harvest_fee_numerator: u64,
+
+        #[allow(dead_code)]
+        /// harvest fee denominator
+        harvest_fee_denominator: u64,
+    },
+
+    ///   Initializes a new FarmPool.
+    ///   These represent the parameters that will be included from client side
+    ///   [w] - writable, [s] - signer
+    /// 
+    ///   0. `[w]` New FarmPool account to create. Signer when the account is
+    ///      created by the same transaction
+    ///   1. `[]` authority to initialize this farm pool account
+    ///   2. `[s]` Creator/Manager of this farm
+    ///   3. `[w]` LP token account of this farm to store lp token
+    ///   4. `[w]` reward token account of this farm to store rewards for the farmers
+    ///      Creator has to transfer/deposit his reward token to this account.
+    ///      only support spl tokens
+    ///   5. `[]` Pool token mint address
+    ///   6. `[]` Reward token mint address
+    ///   7. `[]` Amm Id
+    ///   8. `[]` farm program data id
+    ///   9. `[]` rent sysvar
+    ///   10. `[]` nonce
+    ///   11. `[]` start timestamp. this reflects that the farm starts at this time
+    ///   12. `[]` end timestamp. this reflects that the farm ends at this time
+    ///   13. `[]` program id
+    InitializeFarm {
+        #[allow(dead_code)]
+        /// nonce
+        nonce: u8,
+
+        #[allow(dead_code)]
+        /// start timestamp
+        start_timestamp: u64,
+
+        #[allow(dead_code)]
+        /// end timestamp
+        end_timestamp: u64,
+    },
+
+    ///   Stake Lp tokens to this farm pool
+    ///   If amount is zero, only performed "harvest"
+    ///   If this farm is not allowed/not started/ended, it fails
+    ///   The depositor signs the LP token transfer, as owner or delegate of
+    ///   the user LP token account
+    /// 
+    ///   0. `[w]` FarmPool to deposit to.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` Depositor
+    ///   3. `[w]` User Farming Information Account
+    ///   4. `[w]` User LP token account
+    ///   5. `[w]` Pool LP token account
+    ///   6. `[w]` User reward token account
+    ///   7. `[w]` Pool reward token account
+    ///   8. `[]` Pool LP token mint
+    ///   9. `[w]` fee reward ata account, see `fee_reward_ata`
+    ///   10. `[]` farm program data id
+    ///   11. `[]` Token program id
+    ///   12. `[]` clock sysvar
+    ///   13. `[]` amount
+    ///   14. `[]` program id
+    ///
+    ///   The accounts of a dual-reward farm are followed by `[w]` the user
+    ///   second reward token account, `[w]` the pool second reward token
+    ///   account and `[w]` the fee second reward ata, see
+    ///   `second_reward_accounts`
+    Deposit(u64),
+
+    ///   Unstake LP tokens from this farm pool
+    ///   Before unstake lp tokens, "harvest" works, a reward vault holding
+    ///   less than the pending rewards paying what it holds, the rest staying
+    ///   owed
+    ///   Fails when withdrawing LP tokens still locked, see DepositWithLockup
+    /// 
+    ///   0. `[w]` FarmPool to withdraw to.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` Withdrawer
+    ///   3. `[w]` User Farming Information Account
+    ///   4. `[w]` User LP token account
+    ///   5. `[w]` Pool LP token account
+    ///   6. `[w]` User reward token account
+    ///   7. `[w]` Pool reward token account
+    ///   8. `[]` Pool LP token mint
+    ///   9. `[w]` fee reward ata account, see `fee_reward_ata`
+    ///   10. `[]` farm program data id
+    ///   11. `[]` Token program id
+    ///   12. `[]` clock sysvar
+    ///   13. `[]` amount
+    ///   14. `[]` program id
+    ///
+    ///   The accounts of a dual-reward farm are followed by `[w]` the user
+    ///   second reward token account, `[w]` the pool second reward token
+    ///   account and `[w]` the fee second reward ata, see
+    ///   `second_reward_accounts`
+    Withdraw(u64),
+
+    ///   Creator can add reward to his farm 
+    ///   The amount raises the reward rate over the rest of the farm, only
+    ///   the multiple of the remaining seconds being transferred; fails with
+    ///   `RewardBelowRate` when it is below the remaining seconds
+    /// 
+    ///   0. `[w]` FarmPool to add reward to.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` creator
+    ///   3. `[w]` User reward token account
+    ///   4. `[w]` Pool reward token account
+    ///   5. `[]` Pool LP token account
+    ///   6. `[]` Pool lp token mint
+    ///   7. `[]` farm program data id
+    ///   8. `[]` token program id
+    ///   9. `[]` clock sysvar
+    ///   10. `[]` amount
+    ///   11. `[]` program id
+    ///
+    ///   The creator may be an spl-token multisig, then not a signer and
+    ///   followed by `[s]` signer accounts, see `add_reward_multisig`
+    AddReward(u64),
+    
+    ///   Creator has to pay farm fee (if not CRP token pairing)
+    ///   So this farm can be allowed to stake/unstake/harvest
+    ///   The amount must be the farm fee of the program data, read only from
+    ///   the account at `find_program_data_address`
+    /// 
+    ///   0. `[w]` FarmPool to pay farm fee.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` creator
+    ///   3. `[w]` User USDC token account
+    ///   4. `[w]` fee usdc ata
+    ///   5. `[]` farm program data account
+    ///   6. `[]` token program id
+    ///   7. `[]` amount
+    ///   8. `[]` program id
+    ///
+    ///   The creator may be an spl-token multisig, then not a signer and
+    ///   followed by `[s]` signer accounts, see `pay_farm_fee_multisig`
+    PayFarmFee(u64),
+
+    ///   Unstake all LP tokens from this farm pool, forfeiting pending rewards
+    ///   Works even if the reward vault is drained, the farm is not allowed
+    ///   or the deposit is locked, the lockup being dropped. The rewards
+    ///   accrued until now are distributed first, so that the other stakers
+    ///   keep their pending rewards
+    ///
+    ///   0. `[w]` FarmPool to withdraw from.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` Withdrawer
+    ///   3. `[w]` User Farming Information Account
+    ///   4. `[w]` User LP token account
+    ///   5. `[w]` Pool LP token account
+    ///   6. `[]` Token program id
+    ///   7. `[]` program id
+    EmergencyWithdraw,
+
+    ///   Creator can extend or shorten the farm period
+    ///   The start timestamp of a started farm cannot be moved, and the farm
+    ///   cannot end in the past. Rewards accrued until now are settled with
+    ///   the previous period
+    ///
+    ///   0. `[w]` FarmPool to update.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` creator
+    ///   3. `[]` farm program data id
+    ///   4. `[]` clock sysvar
+    ///   5. `[]` Pool LP token account
+    SetFarmPeriod {
+        #[allow(dead_code)]
+        /// start timestamp
+        start_timestamp: u64,
+
+        #[allow(dead_code)]
+        /// end timestamp
+        end_timestamp: u64,
+    },
+
+    ///   Initializes a new FarmPool, optionally distributing a second reward
+    ///   token. The accounts are the ones of InitializeFarm, followed by the
+    ///   second reward accounts for dual-reward farms
+    ///
+    ///   0-9. Accounts of InitializeFarm
+    ///   10. `[]` (optional) Second reward token mint address
+    ///   11. `[w]` (optional) second reward token account of this farm,
+    ///       owned by the farm authority. The FarmPool must then be in the
+    ///       `FarmPoolV2` layout
+    InitializeFarmV2 {
+        #[allow(dead_code)]
+        /// nonce
+        nonce: u8,
+
+        #[allow(dead_code)]
+        /// start timestamp
+        start_timestamp: u64,
+
+        #[allow(dead_code)]
+        /// end timestamp
+        end_timestamp: u64,
+    },
+
+    ///   Creator can add second reward to his dual-reward farm
+    ///   Accounts are the ones of AddReward, with the second reward token accounts
+    ///   Fails with `InvalidAccountData` for farms with a single reward
+    ///   As with AddReward, the remainder of the amount over the remaining
+    ///   seconds is not transferred
+    ///
+    ///   0. `[w]` FarmPool to add reward to.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` creator
+    ///   3. `[w]` User second reward token account
+    ///   4. `[w]` Pool second reward token account
+    ///   5. `[]` Pool LP token account
+    ///   6. `[]` Pool lp token mint
+    ///   7. `[]` farm program data id
+    ///   8. `[]` token program id
+    ///   9. `[]` clock sysvar
+    AddSecondReward(u64),
+
+    ///   Creates the User Farming Information Account of a wallet, at the
+    ///   address given by `find_user_info_address`, in the `UserInfoV3` layout
+    ///
+    ///   0. `[]` FarmPool the user farms in.
+    ///   1. `[w]` User Farming Information Account to create
+    ///   2. `[w, s]` Wallet of the user, paying for the account
+    ///   3. `[]` System program id
+    ///   4. `[]` rent sysvar
+    CreateUserInfo,
+
+    ///   Proposes a new super owner, who takes over once accepting it
+    ///
+    ///   0. `[w]` farm program data account
+    ///   1. `[s]` current super owner
+    ProposeSuperOwner(Pubkey),
+
+    ///   Accepts a proposed super ownership
+    ///
+    ///   0. `[w]` farm program data account
+    ///   1. `[s]` proposed super owner
+    AcceptSuperOwner,
+
+    ///   Sets the fee owner of the program data
+    ///
+    ///   0. `[w]` farm program data account
+    ///   1. `[s]` super owner
+    SetFeeOwner(Pubkey),
+
+    ///   Sets the creator allowed to create farms without paying the farm fee
+    ///
+    ///   0. `[w]` farm program data account
+    ///   1. `[s]` super owner
+    SetAllowedCreator(Pubkey),
+
+    ///   Sets the farm fee
+    ///
+    ///   0. `[w]` farm program data account
+    ///   1. `[s]` super owner
+    SetFarmFee(u64),
+
+    ///   Sets the harvest fee, `numerator` must not exceed a non-zero
+    ///   `denominator`
+    ///
+    ///   0. `[w]` farm program data account
+    ///   1. `[s]` super owner
+    SetHarvestFee {
+        #[allow(dead_code)]
+        /// harvest fee numerator
+        numerator: u64,
+
+        #[allow(dead_code)]
+        /// harvest fee denominator
+        denominator: u64,
+    },
+
+    ///   Closes a farm once every user withdrew, sending the remaining
+    ///   rewards and the farm account lamports to the creator
+    ///   Fails with `FarmNotEnded` until the clock is past the end timestamp
+    ///   of the farm, and with `FarmNotEmpty` while LP tokens are staked
+    ///
+    ///   0. `[w]` FarmPool to close
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[w, s]` creator, receiving the farm account lamports
+    ///   3. `[w]` Pool reward token account
+    ///   4. `[w]` Reward token account receiving the remaining rewards
+    ///   5. `[]` Pool LP token account, must be empty
+    ///   6. `[]` token program id
+    ///   7. `[w]` (dual-reward farms) Pool second reward token account
+    ///   8. `[w]` (dual-reward farms) Second reward token account receiving
+    ///      the remaining second rewards
+    CloseFarm,
+
+    ///   Same as Deposit, attributing the deposit to a referral, which
+    ///   receives `REFERRAL_FEE_PERCENT` of the harvest fee of the first
+    ///   reward, the fee owner the rest
+    ///
+    ///   0. - 12. Same accounts as Deposit
+    ///   13. `[w]` Referral reward token account, owned by `referral`
+    ///   14. - 16. Second reward accounts of Deposit, for dual-reward farms
+    DepositWithReferral {
+        #[allow(dead_code)]
+        /// amount of LP tokens to stake
+        amount: u64,
+
+        #[allow(dead_code)]
+        /// wallet the deposit is attributed to
+        referral: Pubkey,
+    },
+
+    ///   Creator sets the rewards distributed per second, instead of the
+    ///   rate implied by the rewards in the vault and the farm period
+    ///   Rewards accrued until now are settled at the previous rate
+    ///   Fails with InsufficientReward when the reward vault, less the rewards
+    ///   distributed and not harvested yet, holds less than the rate until the
+    ///   end of the farm. Farms of the original layout, which does not track
+    ///   these rewards, fail with InvalidAccountData unless nothing is staked
+    ///
+    ///   0. `[w]` FarmPool to update.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` creator
+    ///   3. `[]` Pool LP token account
+    ///   4. `[]` farm program data id
+    ///   5. `[]` clock sysvar
+    ///   6. `[]` Pool reward token account
+    SetRewardRate {
+        #[allow(dead_code)]
+        /// rewards distributed per second across all stakers
+        reward_per_second: u64,
+    },
+
+    ///   Same as Deposit, then locks the deposited LP tokens for
+    ///   `lockup_seconds`, preventing their Withdraw until the lockup ends.
+    ///   The tokens of a running lockup stay locked until the later end, the
+    ///   rest of the deposit stays withdrawable, and EmergencyWithdraw
+    ///   ignores the lockup. A lockup of zero behaves as Deposit
+    ///   The deposit weighs its boosted share for `lockup_seconds`, see
+    ///   SetBoostParams
+    ///   The User Farming Information Account must be in the
+    ///   `UserInfoV3` layout to record a lockup
+    ///
+    ///   0. - 12. Same accounts as Deposit
+    DepositWithLockup {
+        #[allow(dead_code)]
+        /// amount of LP tokens to stake
+        amount: u64,
+
+        #[allow(dead_code)]
+        /// seconds the staked LP tokens are locked for
+        lockup_seconds: u64,
+    },
+
+    ///   Creator sets how much the stake of locked deposits is boosted
+    ///   A deposit locked for `max_lock_seconds` or more weighs
+    ///   `multiplier_numerator / multiplier_denominator` times its amount
+    ///   The weight is fixed when the LP tokens are deposited, so that the
+    ///   parameters only apply to later deposits
+    ///   The FarmPool must be in the `FarmPoolV2` layout
+    ///
+    ///   0. `[w]` FarmPool to update.
+    ///   1. `[]` authority of this farm pool
+    ///   2. `[s]` creator
+    ///   3. `[]` farm program data id
+    SetBoostParams {
+        #[allow(dead_code)]
+        /// numerator of the multiplier of the longest locks
+        multiplier_numerator: u64,
+
+        #[allow(dead_code)]
+        /// denominator of the multiplier, at most the numerator
+        multiplier_denominator: u64,
+
+        #[allow(dead_code)]
+        /// lock duration earning the full multiplier
+        max_lock_seconds: u64,
+    },
+}
+
+impl FarmInstruction {
+    /// Borsh discriminant of [FarmInstruction::SetProgramData]
+    pub const TAG_SET_PROGRAM_DATA: u8 = 0;
+    /// Borsh discriminant of [FarmInstruction::InitializeFarm]
+    pub const TAG_INITIALIZE_FARM: u8 = 1;
+    /// Borsh discriminant of [FarmInstruction::Deposit]
+    pub const TAG_DEPOSIT: u8 = 2;
+    /// Borsh discriminant of [FarmInstruction::Withdraw]
+    pub const TAG_WITHDRAW: u8 = 3;
+    /// Borsh discriminant of [FarmInstruction::AddReward]
+    pub const TAG_ADD_REWARD: u8 = 4;
+    /// Borsh discriminant of [FarmInstruction::PayFarmFee]
+    pub const TAG_PAY_FARM_FEE: u8 = 5;
+    /// Borsh discriminant of [FarmInstruction::EmergencyWithdraw]
+    pub const TAG_EMERGENCY_WITHDRAW: u8 = 6;
+    /// Borsh discriminant of [FarmInstruction::SetFarmPeriod]
+    pub const TAG_SET_FARM_PERIOD: u8 = 7;
+    /// Borsh discriminant of [FarmInstruction::InitializeFarmV2]
+    pub const TAG_INITIALIZE_FARM_V2: u8 = 8;
+    /// Borsh discriminant of [FarmInstruction::AddSecondReward]
+    pub const TAG_ADD_SECOND_REWARD: u8 = 9;
+    /// Borsh discriminant of [FarmInstruction::CreateUserInfo]
+    pub const TAG_CREATE_USER_INFO: u8 = 10;
+    /// Borsh discriminant of [FarmInstruction::ProposeSuperOwner]
+    pub const TAG_PROPOSE_SUPER_OWNER: u8 = 11;
+    /// Borsh discriminant of [FarmInstruction::AcceptSuperOwner]
+    pub const TAG_ACCEPT_SUPER_OWNER: u8 = 12;
+    /// Borsh discriminant of [FarmInstruction::SetFeeOwner]
+    pub const TAG_SET_FEE_OWNER: u8 = 13;
+    /// Borsh discriminant of [FarmInstruction::SetAllowedCreator]
+    pub const TAG_SET_ALLOWED_CREATOR: u8 = 14;
+    /// Borsh discriminant of [FarmInstruction::SetFarmFee]
+    pub const TAG_SET_FARM_FEE: u8 = 15;
+    /// Borsh discriminant of [FarmInstruction::SetHarvestFee]
+    pub const TAG_SET_HARVEST_FEE: u8 = 16;
+    /// Borsh discriminant of [FarmInstruction::CloseFarm]
+    pub const TAG_CLOSE_FARM: u8 = 17;
+    /// Borsh discriminant of [FarmInstruction::DepositWithReferral]
+    pub const TAG_DEPOSIT_WITH_REFERRAL: u8 = 18;
+    /// Borsh discriminant of [FarmInstruction::SetRewardRate]
+    pub const TAG_SET_REWARD_RATE: u8 = 19;
+    /// Borsh discriminant of [FarmInstruction::DepositWithLockup]
+    pub const TAG_DEPOSIT_WITH_LOCKUP: u8 = 20;
+    /// Borsh discriminant of [FarmInstruction::SetBoostParams]
+    pub const TAG_SET_BOOST_PARAMS: u8 = 21;
+
+    /// Largest serialized size of any instruction, a `SetProgramData`
+    pub const MAX_PACKED_LEN: usize = 1 + 4 * 32 + 3 * 8;
+
+    /// Borsh discriminant written as the first byte of the instruction
+    pub const fn tag(&self) -> u8 {
+        match self {
+            Self::SetProgramData { .. } => Self::TAG_SET_PROGRAM_DATA,
+            Self::InitializeFarm { .. } => Self::TAG_INITIALIZE_FARM,
+            Self::Deposit(_) => Self::TAG_DEPOSIT,
+            Self::Withdraw(_) => Self::TAG_WITHDRAW,
+            Self::AddReward(_) => Self::TAG_ADD_REWARD,
+            Self::PayFarmFee(_) => Self::TAG_PAY_FARM_FEE,
+            Self::EmergencyWithdraw => Self::TAG_EMERGENCY_WITHDRAW,
+            Self::SetFarmPeriod { .. } => Self::TAG_SET_FARM_PERIOD,
+            Self::InitializeFarmV2 { .. } => Self::TAG_INITIALIZE_FARM_V2,
+            Self::AddSecondReward(_) => Self::TAG_ADD_SECOND_REWARD,
+            Self::CreateUserInfo => Self::TAG_CREATE_USER_INFO,
+            Self::ProposeSuperOwner(_) => Self::TAG_PROPOSE_SUPER_OWNER,
+            Self::AcceptSuperOwner => Self::TAG_ACCEPT_SUPER_OWNER,
+            Self::SetFeeOwner(_) => Self::TAG_SET_FEE_OWNER,
+            Self::SetAllowedCreator(_) => Self::TAG_SET_ALLOWED_CREATOR,
+            Self::SetFarmFee(_) => Self::TAG_SET_FARM_FEE,
+            Self::SetHarvestFee { .. } => Self::TAG_SET_HARVEST_FEE,
+            Self::CloseFarm => Self::TAG_CLOSE_FARM,
+            Self::DepositWithReferral { .. } => Self::TAG_DEPOSIT_WITH_REFERRAL,
+            Self::SetRewardRate { .. } => Self::TAG_SET_REWARD_RATE,
+            Self::DepositWithLockup { .. } => Self::TAG_DEPOSIT_WITH_LOCKUP,
+            Self::SetBoostParams { .. } => Self::TAG_SET_BOOST_PARAMS,
+        }
+    }
+
+    /// Upper bound of the compute units consumed by the instruction, from
+    /// the program invocations it performs, see [COMPUTE_UNITS_BASE]
+    pub const fn compute_unit_estimate(&self) -> u32 {
+        let invocations = match self {
+            // net reward and harvest fee of both rewards, LP transfer
+            Self::Deposit(_) | Self::Withdraw(_) | Self::DepositWithLockup { .. } => 5,
+            // and the referral share of the harvest fee
+            Self::DepositWithReferral { .. } => 6,
+            Self::AddReward(_)
+            | Self::AddSecondReward(_)
+            | Self::PayFarmFee(_)
+            | Self::EmergencyWithdraw
+            | Self::CreateUserInfo
+            | Self::CloseFarm => 1,
+            Self::SetProgramData { .. }
+            | Self::InitializeFarm { .. }
+            | Self::SetFarmPeriod { .. }
+            | Self::InitializeFarmV2 { .. }
+            | Self::ProposeSuperOwner(_)
+            | Self::AcceptSuperOwner
+            | Self::SetFeeOwner(_)
+            | Self::SetAllowedCreator(_)
+            | Self::SetFarmFee(_)
+            | Self::SetHarvestFee { .. }
+            | Self::SetRewardRate { .. }
+            | Self::SetBoostParams { .. } => 0,
+        };
+        COMPUTE_UNITS_BASE + invocations * COMPUTE_UNITS_PER_CPI
+    }
+
+    /// Size of the serialized instruction, discriminant included
+    pub const fn packed_len(&self) -> usize {
+        1 + match self {
+            Self::SetProgramData { .. } => 4 * 32 + 3 * 8,
+            Self::InitializeFarm { .. } | Self::InitializeFarmV2 { .. } => 1 + 8 + 8,
+            Self::SetFarmPeriod { .. } => 8 + 8,
+            Self::Deposit(_)
+            | Self::Withdraw(_)
+            | Self::AddReward(_)
+            | Self::PayFarmFee(_)
+            | Self::AddSecondReward(_) => 8,
+            Self::EmergencyWithdraw
+            | Self::CreateUserInfo
+            | Self::AcceptSuperOwner
+            | Self::CloseFarm => 0,
+            Self::ProposeSuperOwner(_) | Self::SetFeeOwner(_) | Self::SetAllowedCreator(_) => 32,
+            Self::SetFarmFee(_) => 8,
+            Self::SetHarvestFee { .. } => 16,
+            Self::DepositWithReferral { .. } => 8 + 32,
+            Self::SetRewardRate { .. } => 8,
+            Self::DepositWithLockup { .. } => 8 + 8,
+            Self::SetBoostParams { .. } => 3 * 8,
+        }
+    }
+
+    /// Serializes the instruction with Borsh, as expected by the program,
+    /// into a buffer of exactly [packed_len](Self::packed_len) bytes
+    pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut buf = Vec::with_capacity(self.packed_len());
+        self.serialize(&mut buf)
+            .map_err(|_| FarmError::InvalidInstruction)?;
+        Ok(buf)
+    }
+}
+
+impl TryFrom<&[u8]> for FarmInstruction {
+    type Error = ProgramError;
+
+    /// Deserializes the instruction with Borsh, as done by the program
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_slice(input).map_err(|_| FarmError::InvalidInstruction.into())
+    }
+}
+
+/// One-line summary of the instruction and its arguments, e.g.
+/// `Deposit { amount: 1500000 }`. Amounts are raw token units and
+/// timestamps unix seconds. The format is stable, log parsers may rely on it
+impl fmt::Display for FarmInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SetProgramData {
+                super_owner,
+                fee_owner,
+                allowed_creator,
+                amm_program_id,
+                farm_fee,
+                harvest_fee_numerator,
+                harvest_fee_denominator,
+            } => write!(
+                f,
+                "SetProgramData {{ super_owner: {}, fee_owner: {}, allowed_creator: {}, \
+                 amm_program_id: {}, farm_fee: {}, harvest_fee: {}/{} }}",
+                super_owner,
+                fee_owner,
+                allowed_creator,
+                amm_program_id,
+                farm_fee,
+                harvest_fee_numerator,
+                harvest_fee_denominator
+            ),
+            Self::InitializeFarm {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            } => write!(
+                f,
+                "InitializeFarm {{ nonce: {}, start: {}, end: {} }}",
+                nonce, start_timestamp, end_timestamp
+            ),
+            Self::Deposit(amount) => write!(f, "Deposit {{ amount: {} }}", amount),
+            Self::Withdraw(amount) => write!(f, "Withdraw {{ amount: {} }}", amount),
+            Self::AddReward(amount) => write!(f, "AddReward {{ amount: {} }}", amount),
+            Self::PayFarmFee(amount) => write!(f, "PayFarmFee {{ amount: {} }}", amount),
+            Self::EmergencyWithdraw => write!(f, "EmergencyWithdraw"),
+            Self::SetFarmPeriod {
+                start_timestamp,
+                end_timestamp,
+            } => write!(
+                f,
+                "SetFarmPeriod {{ start: {}, end: {} }}",
+                start_timestamp, end_timestamp
+            ),
+            Self::InitializeFarmV2 {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            } => write!(
+                f,
+                "InitializeFarmV2 {{ nonce: {}, start: {}, end: {} }}",
+                nonce, start_timestamp, end_timestamp
+            ),
+            Self::AddSecondReward(amount) => write!(f, "AddSecondReward {{ amount: {} }}", amount),
+            Self::CreateUserInfo => write!(f, "CreateUserInfo"),
+            Self::ProposeSuperOwner(new_super_owner) => write!(
+                f,
+                "ProposeSuperOwner {{ new_super_owner: {} }}",
+                new_super_owner
+            ),
+            Self::AcceptSuperOwner => write!(f, "AcceptSuperOwner"),
+            Self::SetFeeOwner(fee_owner) => write!(f, "SetFeeOwner {{ fee_owner: {} }}", fee_owner),
+            Self::SetAllowedCreator(allowed_creator) => write!(
+                f,
+                "SetAllowedCreator {{ allowed_creator: {} }}",
+                allowed_creator
+            ),
+            Self::SetFarmFee(farm_fee) => write!(f, "SetFarmFee {{ farm_fee: {} }}", farm_fee),
+            Self::SetHarvestFee {
+                numerator,
+                denominator,
+            } => write!(
+                f,
+                "SetHarvestFee {{ harvest_fee: {}/{} }}",
+                numerator, denominator
+            ),
+            Self::CloseFarm => write!(f, "CloseFarm"),
+            Self::DepositWithReferral { amount, referral } => write!(
+                f,
+                "DepositWithReferral {{ amount: {}, referral: {} }}",
+                amount, referral
+            ),
+            Self::SetRewardRate { reward_per_second } => write!(
+                f,
+                "SetRewardRate {{ reward_per_second: {} }}",
+                reward_per_second
+            ),
+            Self::DepositWithLockup {
+                amount,
+                lockup_seconds,
+            } => write!(
+                f,
+                "DepositWithLockup {{ amount: {}, lockup_seconds: {} }}",
+                amount, lockup_seconds
+            ),
+            Self::SetBoostParams {
+                multiplier_numerator,
+                multiplier_denominator,
+                max_lock_seconds,
+            } => write!(
+                f,
+                "SetBoostParams {{ multiplier: {}/{}, max_lock_seconds: {} }}",
+                multiplier_numerator, multiplier_denominator, max_lock_seconds
+            ),
+        }
+    }
+}
+
+/// `Pubkey` has no `Arbitrary` implementation, so the instructions carrying
+/// one are generated by hand from random 32-byte arrays
+#[cfg(feature = "fuzz")]
+impl<'a> Arbitrary<'a> for FarmInstruction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        fn pubkey(u: &mut Unstructured<'_>) -> arbitrary::Result<Pubkey> {
+            Ok(Pubkey::new_from_array(u.arbitrary()?))
+        }
+
+        Ok(match u.int_in_range(0..=Self::TAG_SET_BOOST_PARAMS)? {
+            Self::TAG_SET_PROGRAM_DATA => Self::SetProgramData {
+                super_owner: pubkey(u)?,
+                fee_owner: pubkey(u)?,
+                allowed_creator: pubkey(u)?,
+                amm_program_id: pubkey(u)?,
+                farm_fee: u.arbitrary()?,
+                harvest_fee_numerator: u.arbitrary()?,
+                harvest_fee_denominator: u.arbitrary()?,
+            },
+            Self::TAG_INITIALIZE_FARM => Self::InitializeFarm {
+                nonce: u.arbitrary()?,
+                start_timestamp: u.arbitrary()?,
+                end_timestamp: u.arbitrary()?,
+            },
+            Self::TAG_DEPOSIT => Self::Deposit(u.arbitrary()?),
+            Self::TAG_WITHDRAW => Self::Withdraw(u.arbitrary()?),
+            Self::TAG_ADD_REWARD => Self::AddReward(u.arbitrary()?),
+            Self::TAG_PAY_FARM_FEE => Self::PayFarmFee(u.arbitrary()?),
+            Self::TAG_EMERGENCY_WITHDRAW => Self::EmergencyWithdraw,
+            Self::TAG_SET_FARM_PERIOD => Self::SetFarmPeriod {
+                start_timestamp: u.arbitrary()?,
+                end_timestamp: u.arbitrary()?,
+            },
+            Self::TAG_INITIALIZE_FARM_V2 => Self::InitializeFarmV2 {
+                nonce: u.arbitrary()?,
+                start_timestamp: u.arbitrary()?,
+                end_timestamp: u.arbitrary()?,
+            },
+            Self::TAG_ADD_SECOND_REWARD => Self::AddSecondReward(u.arbitrary()?),
+            Self::TAG_CREATE_USER_INFO => Self::CreateUserInfo,
+            Self::TAG_PROPOSE_SUPER_OWNER => Self::ProposeSuperOwner(pubkey(u)?),
+            Self::TAG_ACCEPT_SUPER_OWNER => Self::AcceptSuperOwner,
+            Self::TAG_SET_FEE_OWNER => Self::SetFeeOwner(pubkey(u)?),
+            Self::TAG_SET_ALLOWED_CREATOR => Self::SetAllowedCreator(pubkey(u)?),
+            Self::TAG_SET_FARM_FEE => Self::SetFarmFee(u.arbitrary()?),
+            Self::TAG_SET_HARVEST_FEE => Self::SetHarvestFee {
+                numerator: u.arbitrary()?,
+                denominator: u.arbitrary()?,
+            },
+            Self::TAG_CLOSE_FARM => Self::CloseFarm,
+            Self::TAG_DEPOSIT_WITH_REFERRAL => Self::DepositWithReferral {
+                amount: u.arbitrary()?,
+                referral: pubkey(u)?,
+            },
+            Self::TAG_SET_REWARD_RATE => Self::SetRewardRate {
+                reward_per_second: u.arbitrary()?,
+            },
+            Self::TAG_DEPOSIT_WITH_LOCKUP => Self::DepositWithLockup {
+                amount: u.arbitrary()?,
+                lockup_seconds: u.arbitrary()?,
+            },
+            _ => Self::SetBoostParams {
+                multiplier_numerator: u.arbitrary()?,
+                multiplier_denominator: u.arbitrary()?,
+                max_lock_seconds: u.arbitrary()?,
+            },
+        })
+    }
+}
+
+// below functions are used to test above instructions in the rust test side
+// Function's parameters
+
+
+/// Creates an 'SetProgramData' instruction. `program_data_account` must be
+/// the address given by [crate::state::find_program_data_address], created
+/// by the first call, which the upgrade authority of the program signs as
+/// `super_owner`.
+pub fn initialize_program(
+    program_data_account: &Pubkey,
+    super_owner: &Pubkey,
+    fee_owner: Pubkey,
+    allowed_creator: Pubkey,
+    amm_program_id: Pubkey,
+    farm_fee: u64,
+    harvest_fee_numerator: u64,
+    harvest_fee_denominator: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    
+    let init_data = FarmInstruction::SetProgramData{
+        super_owner: *super_owner,
+        fee_owner,
+        allowed_creator,
+        amm_program_id,
+        farm_fee,
+        harvest_fee_numerator,
+        harvest_fee_denominator
+    };
+    
+    let data = init_data.pack()?;
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new(*super_owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(find_executable_data_address(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'SetProgramData' instruction, the program data account being
+/// derived with [FarmProgramData::address].
+pub fn initialize_program_with_derived_program_data(
+    super_owner: &Pubkey,
+    fee_owner: Pubkey,
+    allowed_creator: Pubkey,
+    amm_program_id: Pubkey,
+    farm_fee: u64,
+    harvest_fee_numerator: u64,
+    harvest_fee_denominator: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    initialize_program(
+        &FarmProgramData::address(program_id),
+        super_owner,
+        fee_owner,
+        allowed_creator,
+        amm_program_id,
+        farm_fee,
+        harvest_fee_numerator,
+        harvest_fee_denominator,
+        program_id,
+    )
+}
+
+
+/// Creates an 'InitializeFarm' instruction. Fails with `InvalidFarmPeriod`
+/// unless `end_timestamp > start_timestamp > 0`, see [FarmTimestamp].
+pub fn initialize_farm(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_mint_address: &Pubkey,
+    reward_mint_address: &Pubkey,
+    amm_id: &Pubkey,
+    program_data_account: &Pubkey,
+    farm_id_is_signer: bool,
+    nonce: u8,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    FarmTimestamp::period_from_unix_seconds(start_timestamp, end_timestamp)?;
+    let init_data = FarmInstruction::InitializeFarm{
+        nonce,
+        start_timestamp,
+        end_timestamp
+    };
+    
+    let data = init_data.pack()?;
+    let accounts = vec![
+        AccountMeta::new(*farm_id, farm_id_is_signer),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*pool_lp_token_account, false),
+        AccountMeta::new(*pool_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_mint_address, false),
+        AccountMeta::new_readonly(*reward_mint_address, false),
+        AccountMeta::new_readonly(*amm_id, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'InitializeFarm' instruction, the program data account being
+/// derived with [FarmProgramData::address].
+pub fn initialize_farm_with_derived_program_data(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_mint_address: &Pubkey,
+    reward_mint_address: &Pubkey,
+    amm_id: &Pubkey,
+    farm_id_is_signer: bool,
+    nonce: u8,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    initialize_farm(
+        farm_id,
+        authority,
+        owner,
+        pool_lp_token_account,
+        pool_reward_token_account,
+        pool_mint_address,
+        reward_mint_address,
+        amm_id,
+        &FarmProgramData::address(program_id),
+        farm_id_is_signer,
+        nonce,
+        start_timestamp,
+        end_timestamp,
+        program_id,
+    )
+}
+
+/// Creates the instructions creating a new farm account owned by the farm
+/// program, then initializing it. The farm account signs the creation.
+pub fn initialize_farm_with_account_creation(
+    payer: &Pubkey,
+    farm_id: &Pubkey,
+    lamports: u64,
+    space: u64,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_mint_address: &Pubkey,
+    reward_mint_address: &Pubkey,
+    amm_id: &Pubkey,
+    program_data_account: &Pubkey,
+    nonce: u8,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    program_id: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    Ok(vec![
+        system_instruction::create_account(payer, farm_id, lamports, space, program_id),
+        initialize_farm(
+            farm_id,
+            authority,
+            owner,
+            pool_lp_token_account,
+            pool_reward_token_account,
+            pool_mint_address,
+            reward_mint_address,
+            amm_id,
+            program_data_account,
+            true,
+            nonce,
+            start_timestamp,
+            end_timestamp,
+            program_id,
+        )?,
+    ])
+}
+
+/// Creates instructions required to deposit into a farm pool, given a farm
+/// account owned by the user.
+pub fn deposit(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*user_info_account, false),
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new(*pool_lp_token_account, false),
+        AccountMeta::new(*user_reward_token_account, false),
+        AccountMeta::new(*pool_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_mint, false),
+        AccountMeta::new(*fee_reward_ata, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::Deposit(amount).pack()?,
+    })
+}
+
+/// Creates a 'deposit_with_referral' instruction, `referral_reward_token_account`
+/// being a reward token account of `referral`.
+pub fn deposit_with_referral(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    referral: &Pubkey,
+    referral_reward_token_account: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(
+        farm_id,
+        authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint,
+        fee_reward_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )?;
+    instruction
+        .accounts
+        .push(AccountMeta::new(*referral_reward_token_account, false));
+    instruction.data = FarmInstruction::DepositWithReferral {
+        amount,
+        referral: *referral,
+    }
+    .pack()?;
+    Ok(instruction)
+}
+
+/// Creates a 'deposit' instruction, the farm authority being derived with
+/// the canonical nonce of [find_farm_authority].
+pub fn deposit_auto_authority(
+    farm_id: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (authority, _) = find_farm_authority(program_id, farm_id);
+    deposit(
+        farm_id,
+        &authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint,
+        fee_reward_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+/// Creates a 'deposit' instruction, the User Farming Information Account
+/// being derived from the farm and the owner.
+pub fn deposit_with_derived_user_info(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (user_info_account, _) = find_user_info_address(program_id, farm_id, owner);
+    deposit(
+        farm_id,
+        authority,
+        owner,
+        &user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint,
+        fee_reward_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+/// Creates a 'deposit' instruction, the fee reward account being derived from
+/// the fee owner of the program data and the reward mint, see
+/// [fee_reward_ata].
+pub fn deposit_with_fee_owner(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_owner: &Pubkey,
+    reward_mint: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    deposit(
+        farm_id,
+        authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint,
+        &fee_reward_ata(fee_owner, reward_mint, token_program_id),
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+/// Creates a 'withdraw' instruction.
+pub fn withdraw(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*user_info_account, false),
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new(*pool_lp_token_account, false),
+        AccountMeta::new(*user_reward_token_account, false),
+        AccountMeta::new(*pool_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_mint_info, false),
+        AccountMeta::new(*fee_reward_ata, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::Withdraw(amount).pack()?,
+    })
+}
+
+/// Creates a 'withdraw' instruction, the farm authority being derived with
+/// the canonical nonce of [find_farm_authority].
+pub fn withdraw_auto_authority(
+    farm_id: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (authority, _) = find_farm_authority(program_id, farm_id);
+    withdraw(
+        farm_id,
+        &authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint_info,
+        fee_reward_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+
+/// Creates a 'withdraw' instruction, the User Farming Information Account
+/// being derived from the farm and the owner.
+pub fn withdraw_with_derived_user_info(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (user_info_account, _) = find_user_info_address(program_id, farm_id, owner);
+    withdraw(
+        farm_id,
+        authority,
+        owner,
+        &user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint_info,
+        fee_reward_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+/// Creates a 'withdraw' instruction, the fee reward account being derived from
+/// the fee owner of the program data and the reward mint, see
+/// [fee_reward_ata].
+pub fn withdraw_with_fee_owner(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_owner: &Pubkey,
+    reward_mint: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    withdraw(
+        farm_id,
+        authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint,
+        &fee_reward_ata(fee_owner, reward_mint, token_program_id),
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+/// Address of the associated token account of `wallet` for `mint`, whose
+/// token program is `token_program_id`, spl-token or Token-2022
+pub fn get_associated_token_address_with_program_id(
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address_with_program_id(
+        wallet,
+        mint,
+        token_program_id,
+    )
+}
+
+/// Fee reward account of deposits and withdrawals, receiving the harvest
+/// fee: the associated token account of the `fee_owner` of the program data
+/// for the reward mint of the farm, whose token program is
+/// `token_program_id`
+pub fn fee_reward_ata(
+    fee_owner: &Pubkey,
+    reward_mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address_with_program_id(fee_owner, reward_mint, token_program_id)
+}
+
+/// Accounts of a deposit or withdrawal derived from the farm and the wallet
+struct StakeAccounts {
+    authority: Pubkey,
+    user_info_account: Pubkey,
+    user_lp_token_account: Pubkey,
+    user_reward_token_account: Pubkey,
+    fee_reward_ata: Pubkey,
+}
+
+/// Derives the farm authority, the user info account and the associated
+/// token accounts of a deposit or withdrawal, with the instructions creating
+/// those token accounts when `create_atas` is set. The token accounts belong
+/// to the token program of the farm, so that Token-2022 mints are supported
+fn stake_accounts(
+    farm_id: &Pubkey,
+    farm: &FarmPool,
+    wallet: &Pubkey,
+    create_atas: bool,
+    program_id: &Pubkey,
+) -> Result<(StakeAccounts, Vec<Instruction>), ProgramError> {
+    let accounts = StakeAccounts {
+        authority: farm_authority(program_id, farm_id, farm.nonce)?,
+        user_info_account: find_user_info_address(program_id, farm_id, wallet).0,
+        user_lp_token_account: get_associated_token_address_with_program_id(
+            wallet,
+            &farm.pool_mint_address,
+            &farm.token_program_id,
+        ),
+        user_reward_token_account: get_associated_token_address_with_program_id(
+            wallet,
+            &farm.reward_mint_address,
+            &farm.token_program_id,
+        ),
+        fee_reward_ata: fee_reward_ata(
+            &farm.fee_owner,
+            &farm.reward_mint_address,
+            &farm.token_program_id,
+        ),
+    };
+    let mut instructions = Vec::new();
+    if create_atas {
+        instructions.push(create_associated_token_account_idempotent(
+            wallet,
+            wallet,
+            &farm.pool_mint_address,
+            &farm.token_program_id,
+        ));
+        instructions.push(create_associated_token_account_idempotent(
+            wallet,
+            wallet,
+            &farm.reward_mint_address,
+            &farm.token_program_id,
+        ));
+        instructions.push(create_associated_token_account_idempotent(
+            wallet,
+            &farm.fee_owner,
+            &farm.reward_mint_address,
+            &farm.token_program_id,
+        ));
+    }
+    Ok((accounts, instructions))
+}
+
+/// Creates a 'deposit' instruction from the farm state, the token accounts
+/// being the associated token accounts of `wallet`, and of the farm fee
+/// owner for the fee reward account. When `create_atas` is set, they are
+/// first created if missing, paid by `wallet`.
+pub fn deposit_with_atas(
+    farm_id: &Pubkey,
+    farm: &FarmPool,
+    wallet: &Pubkey,
+    program_data_account: &Pubkey,
+    create_atas: bool,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let (accounts, mut instructions) =
+        stake_accounts(farm_id, farm, wallet, create_atas, program_id)?;
+    instructions.push(deposit(
+        farm_id,
+        &accounts.authority,
+        wallet,
+        &accounts.user_info_account,
+        &accounts.user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &accounts.user_reward_token_account,
+        &farm.pool_reward_token_account,
+        &farm.pool_mint_address,
+        &accounts.fee_reward_ata,
+        program_data_account,
+        &farm.token_program_id,
+        amount,
+        program_id,
+    )?);
+    Ok(instructions)
+}
+
+/// Creates a 'withdraw' instruction from the farm state, the token accounts
+/// being the associated token accounts of `wallet`, and of the farm fee
+/// owner for the fee reward account. When `create_atas` is set, they are
+/// first created if missing, paid by `wallet`.
+pub fn withdraw_with_atas(
+    farm_id: &Pubkey,
+    farm: &FarmPool,
+    wallet: &Pubkey,
+    program_data_account: &Pubkey,
+    create_atas: bool,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let (accounts, mut instructions) =
+        stake_accounts(farm_id, farm, wallet, create_atas, program_id)?;
+    instructions.push(withdraw(
+        farm_id,
+        &accounts.authority,
+        wallet,
+        &accounts.user_info_account,
+        &accounts.user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &accounts.user_reward_token_account,
+        &farm.pool_reward_token_account,
+        &farm.pool_mint_address,
+        &accounts.fee_reward_ata,
+        program_data_account,
+        &farm.token_program_id,
+        amount,
+        program_id,
+    )?);
+    Ok(instructions)
+}
+
+/// Same as [deposit_with_atas], the program data account being derived with
+/// [FarmProgramData::address].
+pub fn deposit_with_atas_and_derived_program_data(
+    farm_id: &Pubkey,
+    farm: &FarmPool,
+    wallet: &Pubkey,
+    create_atas: bool,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    deposit_with_atas(
+        farm_id,
+        farm,
+        wallet,
+        &FarmProgramData::address(program_id),
+        create_atas,
+        amount,
+        program_id,
+    )
+}
+
+/// Same as [withdraw_with_atas], the program data account being derived
+/// with [FarmProgramData::address].
+pub fn withdraw_with_atas_and_derived_program_data(
+    farm_id: &Pubkey,
+    farm: &FarmPool,
+    wallet: &Pubkey,
+    create_atas: bool,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    withdraw_with_atas(
+        farm_id,
+        farm,
+        wallet,
+        &FarmProgramData::address(program_id),
+        create_atas,
+        amount,
+        program_id,
+    )
+}
+
+/// Builder of a 'deposit' instruction from the farm state, the token
+/// accounts being the associated token accounts of the user as in
+/// [deposit_with_atas]. Setting a referral makes it a
+/// 'deposit_with_referral' instruction.
+///
+/// The farm, user and program data accounts are required, `build` failing
+/// with `NotEnoughAccountKeys` when any is missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FarmDepositIxBuilder {
+    program_id: Pubkey,
+    amount: u64,
+    farm: Option<(Pubkey, FarmPool)>,
+    wallet: Option<Pubkey>,
+    program_data_account: Option<Pubkey>,
+    token_program_id: Option<Pubkey>,
+    referral: Option<(Pubkey, Pubkey)>,
+}
+
+impl FarmDepositIxBuilder {
+    /// Starts a deposit of `amount` LP tokens
+    pub fn new(amount: u64, program_id: &Pubkey) -> Self {
+        Self {
+            program_id: *program_id,
+            amount,
+            farm: None,
+            wallet: None,
+            program_data_account: None,
+            token_program_id: None,
+            referral: None,
+        }
+    }
+
+    /// Farm account and its state
+    pub fn farm(mut self, farm_id: &Pubkey, farm: &FarmPool) -> Self {
+        self.farm = Some((*farm_id, farm.clone()));
+        self
+    }
+
+    /// Wallet depositing, owner of the User Farming Information Account and
+    /// of the associated token accounts
+    pub fn user(mut self, wallet: &Pubkey) -> Self {
+        self.wallet = Some(*wallet);
+        self
+    }
+
+    /// Farm program data account
+    pub fn program_data(mut self, program_data_account: &Pubkey) -> Self {
+        self.program_data_account = Some(*program_data_account);
+        self
+    }
+
+    /// Token program, overriding the one of the farm, the associated token
+    /// accounts being derived for it
+    pub fn token_program(mut self, token_program_id: &Pubkey) -> Self {
+        self.token_program_id = Some(*token_program_id);
+        self
+    }
+
+    /// Referral credited for the deposit, and its reward token account
+    pub fn referral(mut self, referral: &Pubkey, referral_reward_token_account: &Pubkey) -> Self {
+        self.referral = Some((*referral, *referral_reward_token_account));
+        self
+    }
+
+    /// Creates the instruction
+    pub fn build(self) -> Result<Instruction, ProgramError> {
+        let (farm_id, mut farm) = self.farm.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let wallet = self.wallet.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let program_data_account = self
+            .program_data_account
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if let Some(token_program_id) = self.token_program_id {
+            farm.token_program_id = token_program_id;
+        }
+        let (accounts, _) = stake_accounts(&farm_id, &farm, &wallet, false, &self.program_id)?;
+        match self.referral {
+            Some((referral, referral_reward_token_account)) => deposit_with_referral(
+                &farm_id,
+                &accounts.authority,
+                &wallet,
+                &accounts.user_info_account,
+                &accounts.user_lp_token_account,
+                &farm.pool_lp_token_account,
+                &accounts.user_reward_token_account,
+                &farm.pool_reward_token_account,
+                &farm.pool_mint_address,
+                &accounts.fee_reward_ata,
+                &program_data_account,
+                &farm.token_program_id,
+                &referral,
+                &referral_reward_token_account,
+                self.amount,
+                &self.program_id,
+            ),
+            None => deposit(
+                &farm_id,
+                &accounts.authority,
+                &wallet,
+                &accounts.user_info_account,
+                &accounts.user_lp_token_account,
+                &farm.pool_lp_token_account,
+                &accounts.user_reward_token_account,
+                &farm.pool_reward_token_account,
+                &farm.pool_mint_address,
+                &accounts.fee_reward_ata,
+                &program_data_account,
+                &farm.token_program_id,
+                self.amount,
+                &self.program_id,
+            ),
+        }
+    }
+}
+
+/// Maximum size of a serialized transaction, signatures included, as
+/// `PACKET_DATA_SIZE` of solana-sdk
+pub const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
+
+/// Farm harvested by [build_harvest_all]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FarmHarvestKeys {
+    /// Farm account
+    pub farm_id: Pubkey,
+    /// Farm state, in the layout of the account, the second reward accounts
+    /// of a dual-reward farm being harvested as well
+    pub farm: FarmPoolVersion,
+}
+
+/// Size of a transaction sending `instructions`, paid and signed by `payer`.
+/// Accounts shared between instructions are only counted once.
+fn transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    let num_signatures = usize::from(message.header.num_required_signatures);
+    // signatures are prefixed by their count, a single byte below 128
+    1 + num_signatures * 64 + message.serialize().len()
+}
+
+/// Creates the instructions harvesting every farm of `farms` for `wallet`,
+/// deposits of zero LP tokens from the associated token accounts as in
+/// [deposit_with_atas], which must already exist.
+///
+/// The instructions are split into batches in the order of `farms`, each
+/// fitting a single transaction paid by `wallet` under [PACKET_DATA_SIZE]
+/// once [with_compute_budget] prepends its instruction. A farm given more
+/// than once is harvested once.
+pub fn build_harvest_all(
+    farms: &[FarmHarvestKeys],
+    wallet: &Pubkey,
+    program_data_account: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Vec<Vec<Instruction>>, ProgramError> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<Instruction> = Vec::new();
+    let mut harvested = Vec::new();
+    for keys in farms {
+        if harvested.contains(&keys.farm_id) {
+            continue;
+        }
+        harvested.push(keys.farm_id);
+        let farm = keys.farm.clone().into_latest();
+        let mut harvest = deposit_with_atas(
+            &keys.farm_id,
+            &farm.farm_pool,
+            wallet,
+            program_data_account,
+            false,
+            0,
+            program_id,
+        )?;
+        if farm.has_second_reward() {
+            let deposit = harvest.last_mut().ok_or(ProgramError::InvalidArgument)?;
+            deposit
+                .accounts
+                .extend(second_reward_accounts(&farm, wallet));
+        }
+        batch.append(&mut harvest);
+        let size = transaction_size(&with_compute_budget(batch.clone(), program_id, 0), wallet);
+        if batch.len() > 1 && size > PACKET_DATA_SIZE {
+            let overflow = batch.split_off(batch.len() - 1);
+            batches.push(std::mem::replace(&mut batch, overflow));
+        }
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+/// Prepends to `instructions` the `SetComputeUnitLimit` instruction of the
+/// compute budget program, with the sum of their estimates increased by
+/// `margin_pct` percent and capped at [MAX_COMPUTE_UNIT_LIMIT].
+///
+/// Instructions of `program_id` are estimated with
+/// [FarmInstruction::compute_unit_estimate], other ones with
+/// [COMPUTE_UNITS_OTHER].
+pub fn with_compute_budget(
+    instructions: Vec<Instruction>,
+    program_id: &Pubkey,
+    margin_pct: u8,
+) -> Vec<Instruction> {
+    let units: u64 = instructions
+        .iter()
+        .map(|instruction| {
+            let units = if instruction.program_id == *program_id {
+                FarmInstruction::try_from_slice(&instruction.data)
+                    .map_or(COMPUTE_UNITS_OTHER, |unpacked| {
+                        unpacked.compute_unit_estimate()
+                    })
+            } else {
+                COMPUTE_UNITS_OTHER
+            };
+            u64::from(units)
+        })
+        .sum();
+    let units = units * (100 + u64::from(margin_pct)) / 100;
+    let units = units.min(u64::from(MAX_COMPUTE_UNIT_LIMIT)) as u32;
+    // SetComputeUnitLimit, tag 2 of ComputeBudgetInstruction
+    let mut data = vec![2];
+    data.extend_from_slice(&units.to_le_bytes());
+    let mut with_budget = vec![Instruction {
+        program_id: crate::compute_budget::id(),
+        accounts: vec![],
+        data,
+    }];
+    with_budget.extend(instructions);
+    with_budget
+}
+
+/// Steps of [build_first_deposit] to skip, because they were already
+/// performed
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FirstDepositOptions {
+    /// The User Farming Information Account of the wallet already exists
+    pub assume_user_info_exists: bool,
+    /// The associated token accounts of the deposit already exist
+    pub assume_atas_exist: bool,
+}
+
+/// Instructions of a first deposit into a farm, in execution order
+#[derive(Clone, Debug, PartialEq)]
+pub struct FirstDeposit {
+    /// Instructions to send, in a single transaction
+    pub instructions: Vec<Instruction>,
+    /// Accounts that must sign the transaction
+    pub signers: Vec<Pubkey>,
+}
+
+/// Creates the instructions of the first deposit of `wallet` into a farm:
+/// the User Farming Information Account, the associated token accounts of
+/// [deposit_with_atas], then the deposit itself. Steps are skipped per
+/// `options`, and `wallet` pays for the created accounts.
+pub fn build_first_deposit(
+    farm_id: &Pubkey,
+    farm: &FarmPool,
+    wallet: &Pubkey,
+    program_data_account: &Pubkey,
+    options: FirstDepositOptions,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<FirstDeposit, ProgramError> {
+    let (accounts, ata_instructions) = stake_accounts(
+        farm_id,
+        farm,
+        wallet,
+        !options.assume_atas_exist,
+        program_id,
+    )?;
+    let mut instructions = Vec::new();
+    if !options.assume_user_info_exists {
+        instructions.push(create_user_info_account(farm_id, wallet, program_id)?);
+    }
+    instructions.extend(ata_instructions);
+    instructions.push(deposit(
+        farm_id,
+        &accounts.authority,
+        wallet,
+        &accounts.user_info_account,
+        &accounts.user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &accounts.user_reward_token_account,
+        &farm.pool_reward_token_account,
+        &farm.pool_mint_address,
+        &accounts.fee_reward_ata,
+        program_data_account,
+        &farm.token_program_id,
+        amount,
+        program_id,
+    )?);
+    Ok(FirstDeposit {
+        instructions,
+        signers: vec![*wallet],
+    })
+}
+
+/// Creates a instruction required to add reward into a farm pool
+/// Fails with `ZeroAmount` when `amount` is zero, as the program would
+pub fn add_reward(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    if amount == 0 {
+        return Err(FarmError::ZeroAmount.into());
+    }
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*user_reward_token_account, false),
+        AccountMeta::new(*pool_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_mint_info, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::AddReward(amount).pack()?,
+    })
+}
+
+/// Creates an 'add_reward' instruction, the program data account being
+/// derived with [FarmProgramData::address].
+pub fn add_reward_with_derived_program_data(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    add_reward(
+        farm_id,
+        authority,
+        owner,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_token_account,
+        pool_lp_mint_info,
+        &FarmProgramData::address(program_id),
+        token_program_id,
+        amount,
+        program_id,
+    )
+}
+
+/// Creates an 'add_reward' instruction for a creator `owner` that is an
+/// spl-token multisig, `signers` being the multisig signers signing the
+/// transaction
+pub fn add_reward_multisig(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    signers: &[&Pubkey],
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = add_reward(
+        farm_id,
+        authority,
+        owner,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_token_account,
+        pool_lp_mint_info,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )?;
+    append_multisig_signers(&mut instruction, owner, signers);
+    Ok(instruction)
+}
+
+/// Create a instruction required to pay additonal farm fee
+/// Fails with `ZeroAmount` when `amount` is zero, as the program would
+pub fn pay_farm_fee(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_usdc_token_account: &Pubkey,
+    fee_usdc_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    if amount == 0 {
+        return Err(FarmError::ZeroAmount.into());
+    }
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*user_usdc_token_account, false),
+        AccountMeta::new(*fee_usdc_ata, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::PayFarmFee(amount).pack()?,
+    })
+}
+
+/// Creates a 'pay_farm_fee' instruction for a creator `owner` that is an
+/// spl-token multisig, `signers` being the multisig signers signing the
+/// transaction
+pub fn pay_farm_fee_multisig(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    signers: &[&Pubkey],
+    user_usdc_token_account: &Pubkey,
+    fee_usdc_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = pay_farm_fee(
+        farm_id,
+        authority,
+        owner,
+        user_usdc_token_account,
+        fee_usdc_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )?;
+    append_multisig_signers(&mut instruction, owner, signers);
+    Ok(instruction)
+}
+
+/// Makes `multisig` a non-signer account followed by its `signers`, as
+/// spl-token does. Without signers `multisig` keeps signing itself
+fn append_multisig_signers(instruction: &mut Instruction, multisig: &Pubkey, signers: &[&Pubkey]) {
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == *multisig {
+            account.is_signer = signers.is_empty();
+        }
+    }
+    instruction.accounts.extend(
+        signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(**signer, true)),
+    );
+}
+
+/// Creates a 'pay_farm_fee' instruction paying the farm fee set in
+/// `program_data`, the state of `program_data_account`
+pub fn pay_farm_fee_exact(
+    program_data: &FarmProgramData,
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_usdc_token_account: &Pubkey,
+    fee_usdc_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    pay_farm_fee(
+        farm_id,
+        authority,
+        owner,
+        user_usdc_token_account,
+        fee_usdc_ata,
+        program_data_account,
+        token_program_id,
+        program_data.farm_fee(),
+        program_id,
+    )
+}
+
+/// Creates an 'emergency_withdraw' instruction.
+pub fn emergency_withdraw(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*user_info_account, false),
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new(*pool_lp_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::EmergencyWithdraw.pack()?,
+    })
+}
+
+/// Creates an 'emergency_withdraw' instruction, the farm authority being
+/// derived with the canonical nonce of [find_farm_authority].
+pub fn emergency_withdraw_auto_authority(
+    farm_id: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (authority, _) = find_farm_authority(program_id, farm_id);
+    emergency_withdraw(
+        farm_id,
+        &authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        token_program_id,
+        program_id,
+    )
+}
+
+/// Creates a 'set_farm_period' instruction. Fails with `InvalidFarmPeriod`
+/// unless `end_timestamp > start_timestamp > 0`, see [FarmTimestamp].
+pub fn set_farm_period(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    program_data_account: &Pubkey,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    FarmTimestamp::period_from_unix_seconds(start_timestamp, end_timestamp)?;
+    let data = FarmInstruction::SetFarmPeriod {
+        start_timestamp,
+        end_timestamp,
+    }
+    .pack()?;
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*pool_lp_token_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'InitializeFarmV2' instruction. `second_reward` holds the
+/// second reward token mint and the farm's token account for it, for
+/// dual-reward farms. Fails with `InvalidFarmPeriod` unless
+/// `end_timestamp > start_timestamp > 0`, see [FarmTimestamp].
+pub fn initialize_farm_v2(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_mint_address: &Pubkey,
+    reward_mint_address: &Pubkey,
+    amm_id: &Pubkey,
+    program_data_account: &Pubkey,
+    farm_id_is_signer: bool,
+    second_reward: Option<(&Pubkey, &Pubkey)>,
+    nonce: u8,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    FarmTimestamp::period_from_unix_seconds(start_timestamp, end_timestamp)?;
+    let init_data = FarmInstruction::InitializeFarmV2 {
+        nonce,
+        start_timestamp,
+        end_timestamp,
+    };
+
+    let data = init_data.pack()?;
+    let mut accounts = vec![
+        AccountMeta::new(*farm_id, farm_id_is_signer),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*pool_lp_token_account, false),
+        AccountMeta::new(*pool_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_mint_address, false),
+        AccountMeta::new_readonly(*reward_mint_address, false),
+        AccountMeta::new_readonly(*amm_id, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    if let Some((second_reward_mint_address, pool_second_reward_token_account)) = second_reward {
+        accounts.push(AccountMeta::new_readonly(*second_reward_mint_address, false));
+        accounts.push(AccountMeta::new(*pool_second_reward_token_account, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a instruction required to add second reward into a dual-reward farm pool
+/// Fails with `ZeroAmount` when `amount` is zero, as the program would
+pub fn add_second_reward(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_second_reward_token_account: &Pubkey,
+    pool_second_reward_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_lp_mint_info: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    if amount == 0 {
+        return Err(FarmError::ZeroAmount.into());
+    }
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*user_second_reward_token_account, false),
+        AccountMeta::new(*pool_second_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_mint_info, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::AddSecondReward(amount).pack()?,
+    })
+}
+
+/// Second reward accounts appended to a 'deposit' or 'withdraw' instruction
+/// of a dual-reward farm: the associated token accounts of `wallet`, and of
+/// the farm fee owner for the fee, of the second reward mint, followed by
+/// the farm vault. They are not created, see
+/// `create_associated_token_account_idempotent`.
+pub fn second_reward_accounts(farm: &FarmPoolV2, wallet: &Pubkey) -> Vec<AccountMeta> {
+    let token_program_id = &farm.farm_pool.token_program_id;
+    vec![
+        AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                wallet,
+                &farm.second_reward_mint_address,
+                token_program_id,
+            ),
+            false,
+        ),
+        AccountMeta::new(farm.pool_second_reward_token_account, false),
+        AccountMeta::new(
+            fee_reward_ata(
+                &farm.farm_pool.fee_owner,
+                &farm.second_reward_mint_address,
+                token_program_id,
+            ),
+            false,
+        ),
+    ]
+}
+
+/// Creates a 'create_user_info' instruction, creating the User Farming
+/// Information Account of `owner` in the farm.
+pub fn create_user_info_account(
+    farm_id: &Pubkey,
+    owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (user_info_account, _) = find_user_info_address(program_id, farm_id, owner);
+    let accounts = vec![
+        AccountMeta::new_readonly(*farm_id, false),
+        AccountMeta::new(user_info_account, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::CreateUserInfo.pack()?,
+    })
+}
+
+/// Creates a 'propose_super_owner' instruction.
+pub fn propose_super_owner(
+    program_data_account: &Pubkey,
+    super_owner: &Pubkey,
+    new_super_owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new_readonly(*super_owner, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::ProposeSuperOwner(*new_super_owner).pack()?,
+    })
+}
+
+/// Creates an 'accept_super_owner' instruction.
+pub fn accept_super_owner(
+    program_data_account: &Pubkey,
+    new_super_owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new_readonly(*new_super_owner, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::AcceptSuperOwner.pack()?,
+    })
+}
+
+/// Creates a 'set_fee_owner' instruction.
+pub fn set_fee_owner(
+    program_data_account: &Pubkey,
+    super_owner: &Pubkey,
+    fee_owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new_readonly(*super_owner, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::SetFeeOwner(*fee_owner).pack()?,
+    })
+}
+
+/// Creates a 'set_allowed_creator' instruction.
+pub fn set_allowed_creator(
+    program_data_account: &Pubkey,
+    super_owner: &Pubkey,
+    allowed_creator: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new_readonly(*super_owner, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::SetAllowedCreator(*allowed_creator).pack()?,
+    })
+}
+
+/// Creates a 'set_farm_fee' instruction.
+pub fn set_farm_fee(
+    program_data_account: &Pubkey,
+    super_owner: &Pubkey,
+    farm_fee: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new_readonly(*super_owner, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::SetFarmFee(farm_fee).pack()?,
+    })
+}
+
+/// Creates a 'set_harvest_fee' instruction.
+pub fn set_harvest_fee(
+    program_data_account: &Pubkey,
+    super_owner: &Pubkey,
+    numerator: u64,
+    denominator: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*program_data_account, false),
+        AccountMeta::new_readonly(*super_owner, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::SetHarvestFee {
+            numerator,
+            denominator,
+        }
+        .pack()?,
+    })
+}
+
+/// Creates a 'close_farm' instruction.
+pub fn close_farm(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    creator: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    creator_reward_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(*creator, true),
+        AccountMeta::new(*pool_reward_token_account, false),
+        AccountMeta::new(*creator_reward_token_account, false),
+        AccountMeta::new_readonly(*pool_lp_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::CloseFarm.pack()?,
+    })
+}
+
+/// Creates a 'close_farm' instruction for a dual-reward farm, the remaining
+/// second rewards being sent to `creator_second_reward_token_account`.
+pub fn close_dual_reward_farm(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    creator: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    creator_reward_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_second_reward_token_account: &Pubkey,
+    creator_second_reward_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = close_farm(
+        farm_id,
+        authority,
+        creator,
+        pool_reward_token_account,
+        creator_reward_token_account,
+        pool_lp_token_account,
+        token_program_id,
+        program_id,
+    )?;
+    instruction
+        .accounts
+        .push(AccountMeta::new(*pool_second_reward_token_account, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new(*creator_second_reward_token_account, false));
+    Ok(instruction)
+}
+
+/// Creates a 'set_reward_rate' instruction, see [reward_rate_for](crate::math::reward_rate_for)
+/// to derive the rate from a total reward.
+pub fn set_reward_rate(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    creator: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    program_data_account: &Pubkey,
+    reward_per_second: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new_readonly(*pool_lp_token_account, false),
+        AccountMeta::new_readonly(*program_data_account, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*pool_reward_token_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::SetRewardRate { reward_per_second }.pack()?,
+    })
+}
+
+/// Creates a 'deposit_with_lockup' instruction, the User Farming Information
+/// Account being in the `UserInfoV3` layout created by
+/// [create_user_info_account].
+pub fn deposit_with_lockup(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    user_info_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    pool_lp_token_account: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    pool_reward_token_account: &Pubkey,
+    pool_lp_mint: &Pubkey,
+    fee_reward_ata: &Pubkey,
+    program_data_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    lockup_seconds: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(
+        farm_id,
+        authority,
+        owner,
+        user_info_account,
+        user_lp_token_account,
+        pool_lp_token_account,
+        user_reward_token_account,
+        pool_reward_token_account,
+        pool_lp_mint,
+        fee_reward_ata,
+        program_data_account,
+        token_program_id,
+        amount,
+        program_id,
+    )?;
+    instruction.data = FarmInstruction::DepositWithLockup {
+        amount,
+        lockup_seconds,
+    }
+    .pack()?;
+    Ok(instruction)
+}
+
+/// Creates a 'set_boost_params' instruction, see
+/// [boosted_share](crate::math::boosted_share) for the resulting stake weights.
+pub fn set_boost_params(
+    farm_id: &Pubkey,
+    authority: &Pubkey,
+    creator: &Pubkey,
+    program_data_account: &Pubkey,
+    multiplier_numerator: u64,
+    multiplier_denominator: u64,
+    max_lock_seconds: u64,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new_readonly(*program_data_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: FarmInstruction::SetBoostParams {
+            multiplier_numerator,
+            multiplier_denominator,
+            max_lock_seconds,
+        }
+        .pack()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_associated_token_account::instruction::AssociatedTokenAccountInstruction;
+
+    /// Farm whose authority is derived with its stored nonce
+    fn test_farm(program_id: &Pubkey, farm_id: &Pubkey) -> FarmPool {
+        FarmPool {
+            is_allowed: true,
+            nonce: find_farm_authority(program_id, farm_id).1,
+            pool_lp_token_account: Pubkey::new_unique(),
+            pool_reward_token_account: Pubkey::new_unique(),
+            pool_mint_address: Pubkey::new_unique(),
+            reward_mint_address: Pubkey::new_unique(),
+            token_program_id: spl_token::id(),
+            owner: Pubkey::new_unique(),
+            fee_owner: Pubkey::new_unique(),
+            ..FarmPool::default()
+        }
+    }
+
+    #[test]
+    fn test_deposit_with_atas_creates_idempotent_atas() {
+        let (program_id, farm_id, wallet) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        for token_program_id in [spl_token::id(), crate::spl_token_2022::id()] {
+            let farm = FarmPool {
+                token_program_id,
+                ..test_farm(&program_id, &farm_id)
+            };
+            let instructions = deposit_with_atas(
+                &farm_id,
+                &farm,
+                &wallet,
+                &Pubkey::new_unique(),
+                true,
+                100,
+                &program_id,
+            )
+            .unwrap();
+            assert_eq!(instructions.len(), 4);
+            let owners_and_mints = [
+                (wallet, farm.pool_mint_address),
+                (wallet, farm.reward_mint_address),
+                (farm.fee_owner, farm.reward_mint_address),
+            ];
+            for (instruction, (owner, mint)) in instructions.iter().zip(owners_and_mints.iter()) {
+                assert_eq!(instruction.program_id, spl_associated_token_account::id());
+                assert_eq!(
+                    instruction.data,
+                    vec![AssociatedTokenAccountInstruction::CreateIdempotent as u8]
+                );
+                let keys: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+                assert_eq!(
+                    keys,
+                    vec![
+                        wallet,
+                        get_associated_token_address_with_program_id(
+                            owner,
+                            mint,
+                            &token_program_id
+                        ),
+                        *owner,
+                        *mint,
+                        system_program::id(),
+                        token_program_id,
+                    ]
+                );
+            }
+            assert_eq!(instructions[3].program_id, program_id);
+        }
+    }
+
+    /// Deposits and withdrawals of farms of either token program move the
+    /// associated token accounts of that program, through that program
+    #[test]
+    fn test_stake_with_atas_token_programs() {
+        let (program_id, farm_id, wallet) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let program_data = Pubkey::new_unique();
+        for token_program_id in [spl_token::id(), crate::spl_token_2022::id()] {
+            let farm = FarmPool {
+                token_program_id,
+                ..test_farm(&program_id, &farm_id)
+            };
+            let ata = |owner, mint| {
+                get_associated_token_address_with_program_id(owner, mint, &token_program_id)
+            };
+            let deposit = deposit_with_atas(
+                &farm_id,
+                &farm,
+                &wallet,
+                &program_data,
+                false,
+                100,
+                &program_id,
+            )
+            .unwrap();
+            let withdraw = withdraw_with_atas(
+                &farm_id,
+                &farm,
+                &wallet,
+                &program_data,
+                false,
+                100,
+                &program_id,
+            )
+            .unwrap();
+            for instructions in [deposit, withdraw] {
+                assert_eq!(instructions.len(), 1);
+                let keys: Vec<Pubkey> =
+                    instructions[0].accounts.iter().map(|a| a.pubkey).collect();
+                assert_eq!(keys[4], ata(&wallet, &farm.pool_mint_address));
+                assert_eq!(keys[6], ata(&wallet, &farm.reward_mint_address));
+                assert_eq!(keys[9], ata(&farm.fee_owner, &farm.reward_mint_address));
+                assert_eq!(keys[11], token_program_id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dual_reward_instructions() {
+        let instruction = FarmInstruction::InitializeFarmV2 {
+            nonce: 255,
+            start_timestamp: 1_700_000_000,
+            end_timestamp: 1_700_086_400,
+        };
+        let data = instruction.pack().unwrap();
+        let mut expected = vec![8, 255];
+        expected.extend_from_slice(&1_700_000_000u64.to_le_bytes());
+        expected.extend_from_slice(&1_700_086_400u64.to_le_bytes());
+        assert_eq!(data, expected);
+        assert_eq!(data[0], FarmInstruction::TAG_INITIALIZE_FARM_V2);
+        assert_eq!(FarmInstruction::try_from(&data[..]).unwrap(), instruction);
+        assert!(FarmInstruction::try_from(&data[..data.len() - 1]).is_err());
+
+        let data = FarmInstruction::AddSecondReward(u64::MAX).pack().unwrap();
+        let mut expected = vec![9];
+        expected.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(data, expected);
+        assert_eq!(data[0], FarmInstruction::TAG_ADD_SECOND_REWARD);
+        assert_eq!(
+            FarmInstruction::try_from(&data[..]).unwrap(),
+            FarmInstruction::AddSecondReward(u64::MAX)
+        );
+        assert!(FarmInstruction::try_from(&data[..data.len() - 1]).is_err());
+
+        // the second reward accounts follow the ones of InitializeFarm
+        let program_id = Pubkey::new_unique();
+        let farm_id = Pubkey::new_unique();
+        let keys: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        let second_reward_mint = Pubkey::new_unique();
+        let pool_second_reward_token_account = Pubkey::new_unique();
+        let build = |second_reward| {
+            initialize_farm_v2(
+                &farm_id,
+                &keys[0],
+                &keys[1],
+                &keys[2],
+                &keys[3],
+                &keys[4],
+                &keys[5],
+                &keys[6],
+                &keys[7],
+                false,
+                second_reward,
+                255,
+                1_700_000_000,
+                1_700_086_400,
+                &program_id,
+            )
+            .unwrap()
+        };
+        let single_reward = build(None);
+        assert_eq!(single_reward.accounts.len(), 10);
+        assert_eq!(single_reward.data, instruction.pack().unwrap());
+        let dual_reward = build(Some((&second_reward_mint, &pool_second_reward_token_account)));
+        assert_eq!(dual_reward.accounts[..10], single_reward.accounts[..]);
+        assert_eq!(
+            dual_reward.accounts[10..],
+            [
+                AccountMeta::new_readonly(second_reward_mint, false),
+                AccountMeta::new(pool_second_reward_token_account, false),
+            ]
+        );
+
+        // the harvest accounts of the second reward
+        let farm = FarmPoolV2 {
+            farm_pool: test_farm(&program_id, &farm_id),
+            second_reward_mint_address: second_reward_mint,
+            pool_second_reward_token_account,
+            ..FarmPoolV2::default()
+        };
+        let wallet = Pubkey::new_unique();
+        assert_eq!(
+            second_reward_accounts(&farm, &wallet),
+            vec![
+                AccountMeta::new(
+                    get_associated_token_address_with_program_id(
+                        &wallet,
+                        &second_reward_mint,
+                        &spl_token::id()
+                    ),
+                    false
+                ),
+                AccountMeta::new(pool_second_reward_token_account, false),
+                AccountMeta::new(
+                    fee_reward_ata(
+                        &farm.farm_pool.fee_owner,
+                        &second_reward_mint,
+                        &spl_token::id()
+                    ),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_derived_program_data() {
+        let (program_id, farm_id, wallet) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let program_data = FarmProgramData::address(&program_id);
+        let k: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        assert_eq!(
+            initialize_program_with_derived_program_data(
+                &k[0],
+                k[1],
+                k[2],
+                k[3],
+                100,
+                1,
+                100,
+                &program_id
+            ),
+            initialize_program(
+                &program_data,
+                &k[0],
+                k[1],
+                k[2],
+                k[3],
+                100,
+                1,
+                100,
+                &program_id
+            )
+        );
+        assert_eq!(
+            initialize_farm_with_derived_program_data(
+                &farm_id,
+                &k[0],
+                &k[1],
+                &k[2],
+                &k[3],
+                &k[4],
+                &k[5],
+                &k[6],
+                true,
+                255,
+                1,
+                2,
+                &program_id
+            ),
+            initialize_farm(
+                &farm_id,
+                &k[0],
+                &k[1],
+                &k[2],
+                &k[3],
+                &k[4],
+                &k[5],
+                &k[6],
+                &program_data,
+                true,
+                255,
+                1,
+                2,
+                &program_id
+            )
+        );
+        assert_eq!(
+            add_reward_with_derived_program_data(
+                &farm_id,
+                &k[0],
+                &k[1],
+                &k[2],
+                &k[3],
+                &k[4],
+                &k[5],
+                &k[6],
+                1_000,
+                &program_id
+            ),
+            add_reward(
+                &farm_id,
+                &k[0],
+                &k[1],
+                &k[2],
+                &k[3],
+                &k[4],
+                &k[5],
+                &program_data,
+                &k[6],
+                1_000,
+                &program_id
+            )
+        );
+        let farm = test_farm(&program_id, &farm_id);
+        assert_eq!(
+            deposit_with_atas_and_derived_program_data(
+                &farm_id,
+                &farm,
+                &wallet,
+                true,
+                100,
+                &program_id
+            ),
+            deposit_with_atas(
+                &farm_id,
+                &farm,
+                &wallet,
+                &program_data,
+                true,
+                100,
+                &program_id
+            )
+        );
+        assert_eq!(
+            withdraw_with_atas_and_derived_program_data(
+                &farm_id,
+                &farm,
+                &wallet,
+                false,
+                100,
+                &program_id
+            ),
+            withdraw_with_atas(
+                &farm_id,
+                &farm,
+                &wallet,
+                &program_data,
+                false,
+                100,
+                &program_id
+            )
+        );
+        let deposit = &deposit_with_atas_and_derived_program_data(
+            &farm_id,
+            &farm,
+            &wallet,
+            false,
+            100,
+            &program_id,
+        )
+        .unwrap()[0];
+        assert_eq!(
+            deposit.accounts[10],
+            AccountMeta::new_readonly(program_data, false)
+        );
+    }
+    #[test]
+    fn test_build_harvest_all() {
+        let (program_id, wallet, program_data_account) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let keys = || {
+            let farm_id = Pubkey::new_unique();
+            FarmHarvestKeys {
+                farm_id,
+                farm: FarmPoolVersion::FarmPool(test_farm(&program_id, &farm_id)),
+            }
+        };
+        let build = |farms: &[FarmHarvestKeys]| {
+            build_harvest_all(farms, &wallet, &program_data_account, &program_id).unwrap()
+        };
+        let size = |batch: &[Instruction]| {
+            transaction_size(
+                &with_compute_budget(batch.to_vec(), &program_id, 0),
+                &wallet,
+            )
+        };
+
+        // the largest batch fitting a transaction with its compute budget
+        let mut farms: Vec<FarmHarvestKeys> = Vec::new();
+        loop {
+            farms.push(keys());
+            if build(&farms).len() > 1 {
+                break;
+            }
+        }
+        let fitting = farms.len() - 1;
+        assert!(fitting > 1);
+        let batches = build(&farms[..fitting]);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), fitting);
+        assert!(size(&batches[0]) <= PACKET_DATA_SIZE);
+
+        // one more farm goes to a second batch, in order
+        let batches = build(&farms);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), fitting);
+        assert_eq!(batches[1].len(), 1);
+        for (instruction, keys) in batches.concat().iter().zip(&farms) {
+            assert_eq!(instruction.program_id, program_id);
+            assert_eq!(instruction.accounts[0].pubkey, keys.farm_id);
+            assert_eq!(
+                instruction.data,
+                FarmInstruction::Deposit(0).pack().unwrap()
+            );
+        }
+        for batch in &batches {
+            assert!(size(batch) <= PACKET_DATA_SIZE);
+        }
+        assert!(size(&batches.concat()) > PACKET_DATA_SIZE);
+
+        // a farm given twice is harvested once
+        let duplicated = [farms[0].clone(), farms[1].clone(), farms[0].clone()];
+        assert_eq!(build(&duplicated), build(&farms[..2]));
+        assert!(build(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_build_harvest_all_dual_reward() {
+        let (program_id, farm_id, wallet, program_data_account) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let farm = FarmPoolV2 {
+            farm_pool: test_farm(&program_id, &farm_id),
+            second_reward_mint_address: Pubkey::new_unique(),
+            pool_second_reward_token_account: Pubkey::new_unique(),
+            ..FarmPoolV2::default()
+        };
+        let single_reward = deposit_with_atas(
+            &farm_id,
+            &farm.farm_pool,
+            &wallet,
+            &program_data_account,
+            false,
+            0,
+            &program_id,
+        )
+        .unwrap();
+        let build = |farm: FarmPoolV2| {
+            build_harvest_all(
+                &[FarmHarvestKeys {
+                    farm_id,
+                    farm: FarmPoolVersion::FarmPoolV2(farm),
+                }],
+                &wallet,
+                &program_data_account,
+                &program_id,
+            )
+            .unwrap()
+        };
+
+        let batches = build(farm.clone());
+        assert_eq!(batches.len(), 1);
+        let harvest = &batches[0][0];
+        let accounts = single_reward[0].accounts.len();
+        assert_eq!(harvest.accounts[..accounts], single_reward[0].accounts[..]);
+        assert_eq!(
+            harvest.accounts[accounts..],
+            second_reward_accounts(&farm, &wallet)[..]
+        );
+
+        // no second reward accounts without a second reward
+        let farm = FarmPoolV2 {
+            second_reward_mint_address: Pubkey::default(),
+            ..farm
+        };
+        assert_eq!(build(farm), vec![single_reward]);
+    }
+
+    #[test]
+    fn test_emergency_withdraw() {
+        let data = FarmInstruction::EmergencyWithdraw.pack().unwrap();
+        assert_eq!(data, vec![FarmInstruction::TAG_EMERGENCY_WITHDRAW]);
+        assert_eq!(data, vec![6]);
+        assert_eq!(
+            FarmInstruction::try_from(&data[..]).unwrap(),
+            FarmInstruction::EmergencyWithdraw
+        );
+        // the variant carries no amount
+        assert!(FarmInstruction::try_from(&[6, 0, 0, 0, 0, 0, 0, 0, 0][..]).is_err());
+
+        let program_id = Pubkey::new_unique();
+        let farm_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let user_info_account = Pubkey::new_unique();
+        let user_lp_token_account = Pubkey::new_unique();
+        let pool_lp_token_account = Pubkey::new_unique();
+        let instruction = emergency_withdraw_auto_authority(
+            &farm_id,
+            &owner,
+            &user_info_account,
+            &user_lp_token_account,
+            &pool_lp_token_account,
+            &spl_token::id(),
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.data, data);
+        // no reward account
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(farm_id, false),
+                AccountMeta::new_readonly(find_farm_authority(&program_id, &farm_id).0, false),
+                AccountMeta::new_readonly(owner, true),
+                AccountMeta::new(user_info_account, false),
+                AccountMeta::new(user_lp_token_account, false),
+                AccountMeta::new(pool_lp_token_account, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_super_owner_rotation_tags() {
+        let new_super_owner = Pubkey::new_unique();
+        let data = FarmInstruction::ProposeSuperOwner(new_super_owner)
+            .pack()
+            .unwrap();
+        assert_eq!(data[0], FarmInstruction::TAG_PROPOSE_SUPER_OWNER);
+        assert_eq!(data[0], 11);
+        assert_eq!(&data[1..], new_super_owner.as_ref());
+        assert_eq!(
+            FarmInstruction::try_from(&data[..]).unwrap(),
+            FarmInstruction::ProposeSuperOwner(new_super_owner)
+        );
+
+        let data = FarmInstruction::AcceptSuperOwner.pack().unwrap();
+        assert_eq!(data, vec![FarmInstruction::TAG_ACCEPT_SUPER_OWNER]);
+        assert_eq!(data, vec![12]);
+
+        // the rotation did not shift the original discriminants
+        let data = FarmInstruction::PayFarmFee(1).pack().unwrap();
+        assert_eq!(data[0], 5);
+    }
+
+    #[test]
+    fn test_program_data_update_tags() {
+        let fee_owner = Pubkey::new_unique();
+        let data = FarmInstruction::SetFeeOwner(fee_owner).pack().unwrap();
+        let mut expected = vec![13];
+        expected.extend_from_slice(fee_owner.as_ref());
+        assert_eq!(data, expected);
+        assert_eq!(data[0], FarmInstruction::TAG_SET_FEE_OWNER);
+        assert_eq!(
+            FarmInstruction::try_from(&data[..]).unwrap(),
+            FarmInstruction::SetFeeOwner(fee_owner)
+        );
+
+        let allowed_creator = Pubkey::new_unique();
+        let data = FarmInstruction::SetAllowedCreator(allowed_creator)
+            .pack()
+            .unwrap();
+        let mut expected = vec![14];
+        expected.extend_from_slice(allowed_creator.as_ref());
+        assert_eq!(data, expected);
+        assert_eq!(data[0], FarmInstruction::TAG_SET_ALLOWED_CREATOR);
+        assert_eq!(
+            FarmInstruction::try_from(&data[..]).unwrap(),
+            FarmInstruction::SetAllowedCreator(allowed_creator)
+        );
+
+        let data = FarmInstruction::SetFarmFee(5_000).pack().unwrap();
+        let mut expected = vec![15];
+        expected.extend_from_slice(&5_000u64.to_le_bytes());
+        assert_eq!(data, expected);
+        assert_eq!(data[0], FarmInstruction::TAG_SET_FARM_FEE);
+        assert_eq!(
+            FarmInstruction::try_from(&data[..]).unwrap(),
+            FarmInstruction::SetFarmFee(5_000)
+        );
+
+        let instruction = FarmInstruction::SetHarvestFee {
+            numerator: 3,
+            denominator: 1_000,
+        };
+        let data = instruction.pack().unwrap();
+        let mut expected = vec![16];
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(&1_000u64.to_le_bytes());
+        assert_eq!(data, expected);
+        assert_eq!(data[0], FarmInstruction::TAG_SET_HARVEST_FEE);
+        assert_eq!(FarmInstruction::try_from(&data[..]).unwrap(), instruction);
+
+        // truncated payloads are rejected
+        assert!(FarmInstruction::try_from(&data[..data.len() - 1]).is_err());
+    }
+
+    /// (is_signer, is_writable) of an account
+    type Flags = (bool, bool);
+    const R: Flags = (false, false);
+    const W: Flags = (false, true);
+    const S: Flags = (true, false);
+    const WS: Flags = (true, true);
+
+    fn flags(instruction: &Instruction) -> Vec<Flags> {
+        instruction
+            .accounts
+            .iter()
+            .map(|account| (account.is_signer, account.is_writable))
+            .collect()
+    }
+
+    /// Flags of the accounts passed by each builder, as documented on
+    /// [FarmInstruction]
+    #[test]
+    fn test_account_flags() {
+        let k = Pubkey::new_unique;
+        let program_id = Pubkey::new_unique();
+        let token_program_id = spl_token::id();
+        let stake_flags = vec![W, R, S, W, W, W, W, W, R, W, R, R, R];
+        let farm_id = k();
+        let (authority, nonce) = find_farm_authority(&program_id, &farm_id);
+
+        #[rustfmt::skip]
+        let golden: Vec<(&str, Instruction, Vec<Flags>)> = vec![
+            (
+                "initialize_program",
+                initialize_program(&k(), &k(), k(), k(), k(), 0, 0, 1, &program_id).unwrap(),
+                vec![W, WS, R, R, R],
+            ),
+            (
+                "initialize_farm",
+                initialize_farm(
+                    &farm_id, &authority, &k(), &k(), &k(), &k(), &k(), &k(), &k(), false,
+                    nonce, 1, 2, &program_id,
+                )
+                .unwrap(),
+                vec![W, R, S, W, W, R, R, R, R, R],
+            ),
+            (
+                "initialize_farm_v2",
+                initialize_farm_v2(
+                    &farm_id, &authority, &k(), &k(), &k(), &k(), &k(), &k(), &k(), true,
+                    Some((&k(), &k())), nonce, 1, 2, &program_id,
+                )
+                .unwrap(),
+                vec![WS, R, S, W, W, R, R, R, R, R, R, W],
+            ),
+            (
+                "deposit",
+                deposit(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &token_program_id, 1, &program_id,
+                )
+                .unwrap(),
+                stake_flags.clone(),
+            ),
+            (
+                "deposit_with_referral",
+                deposit_with_referral(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &token_program_id, &k(), &k(), 1, &program_id,
+                )
+                .unwrap(),
+                [stake_flags.clone(), vec![W]].concat(),
+            ),
+            (
+                "deposit_with_lockup",
+                deposit_with_lockup(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &token_program_id, 1, 60, &program_id,
+                )
+                .unwrap(),
+                stake_flags.clone(),
+            ),
+            (
+                "withdraw",
+                withdraw(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(),
+                    &token_program_id, 1, &program_id,
+                )
+                .unwrap(),
+                stake_flags,
+            ),
+            (
+                "add_reward",
+                add_reward(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &token_program_id, 1,
+                    &program_id,
+                )
+                .unwrap(),
+                vec![W, R, S, W, W, R, R, R, R, R],
+            ),
+            (
+                "add_second_reward",
+                add_second_reward(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &k(), &k(), &token_program_id, 1,
+                    &program_id,
+                )
+                .unwrap(),
+                vec![W, R, S, W, W, R, R, R, R, R],
+            ),
+            (
+                "pay_farm_fee",
+                pay_farm_fee(&k(), &k(), &k(), &k(), &k(), &k(), &token_program_id, 1, &program_id)
+                    .unwrap(),
+                vec![W, R, S, W, W, R, R],
+            ),
+            (
+                "emergency_withdraw",
+                emergency_withdraw(
+                    &k(), &k(), &k(), &k(), &k(), &k(), &token_program_id, &program_id,
+                )
+                .unwrap(),
+                vec![W, R, S, W, W, W, R],
+            ),
+            (
+                "set_farm_period",
+                set_farm_period(&k(), &k(), &k(), &k(), &k(), 1, 2, &program_id).unwrap(),
+                vec![W, R, S, R, R, R],
+            ),
+            (
+                "create_user_info_account",
+                create_user_info_account(&k(), &k(), &program_id).unwrap(),
+                vec![R, W, WS, R, R],
+            ),
+            (
+                "propose_super_owner",
+                propose_super_owner(&k(), &k(), &k(), &program_id).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "accept_super_owner",
+                accept_super_owner(&k(), &k(), &program_id).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "set_fee_owner",
+                set_fee_owner(&k(), &k(), &k(), &program_id).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "set_allowed_creator",
+                set_allowed_creator(&k(), &k(), &k(), &program_id).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "set_farm_fee",
+                set_farm_fee(&k(), &k(), 1, &program_id).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "set_harvest_fee",
+                set_harvest_fee(&k(), &k(), 1, 100, &program_id).unwrap(),
+                vec![W, S],
+            ),
+            (
+                "close_farm",
+                close_farm(&k(), &k(), &k(), &k(), &k(), &k(), &token_program_id, &program_id)
+                    .unwrap(),
+                vec![W, R, WS, W, W, R, R],
+            ),
+            (
+                "set_reward_rate",
+                set_reward_rate(&k(), &k(), &k(), &k(), &k(), &k(), 1, &program_id).unwrap(),
+                vec![W, R, S, R, R, R, R],
+            ),
+            (
+                "set_boost_params",
+                set_boost_params(&k(), &k(), &k(), &k(), 2, 1, 60, &program_id).unwrap(),
+                vec![W, R, S, R],
+            ),
+        ];
+
+        for (name, instruction, expected) in golden {
+            assert_eq!(flags(&instruction), expected, "{}", name);
+        }
+    }
+
+    /// Borsh discriminants are positional: reordering or inserting a variant
+    /// changes the first byte every deployed client sends
+    #[test]
+    fn test_stable_discriminants() {
+        let k = Pubkey::new_unique();
+        let pinned = [
+            (
+                0,
+                FarmInstruction::SetProgramData {
+                    super_owner: k,
+                    fee_owner: k,
+                    allowed_creator: k,
+                    amm_program_id: k,
+                    farm_fee: 1,
+                    harvest_fee_numerator: 1,
+                    harvest_fee_denominator: 100,
+                },
+            ),
+            (
+                1,
+                FarmInstruction::InitializeFarm {
+                    nonce: 255,
+                    start_timestamp: 1,
+                    end_timestamp: 2,
+                },
+            ),
+            (2, FarmInstruction::Deposit(1)),
+            (3, FarmInstruction::Withdraw(1)),
+            (4, FarmInstruction::AddReward(1)),
+            (5, FarmInstruction::PayFarmFee(1)),
+            (6, FarmInstruction::EmergencyWithdraw),
+            (
+                7,
+                FarmInstruction::SetFarmPeriod {
+                    start_timestamp: 1,
+                    end_timestamp: 2,
+                },
+            ),
+            (
+                8,
+                FarmInstruction::InitializeFarmV2 {
+                    nonce: 255,
+                    start_timestamp: 1,
+                    end_timestamp: 2,
+                },
+            ),
+            (9, FarmInstruction::AddSecondReward(1)),
+            (10, FarmInstruction::CreateUserInfo),
+            (11, FarmInstruction::ProposeSuperOwner(k)),
+            (12, FarmInstruction::AcceptSuperOwner),
+            (13, FarmInstruction::SetFeeOwner(k)),
+            (14, FarmInstruction::SetAllowedCreator(k)),
+            (15, FarmInstruction::SetFarmFee(1)),
+            (
+                16,
+                FarmInstruction::SetHarvestFee {
+                    numerator: 1,
+                    denominator: 100,
+                },
+            ),
+            (17, FarmInstruction::CloseFarm),
+            (
+                18,
+                FarmInstruction::DepositWithReferral {
+                    amount: 1,
+                    referral: k,
+                },
+            ),
+            (
+                19,
+                FarmInstruction::SetRewardRate {
+                    reward_per_second: 1,
+                },
+            ),
+            (
+                20,
+                FarmInstruction::DepositWithLockup {
+                    amount: 1,
+                    lockup_seconds: 60,
+                },
+            ),
+            (
+                21,
+                FarmInstruction::SetBoostParams {
+                    multiplier_numerator: 2,
+                    multiplier_denominator: 1,
+                    max_lock_seconds: 60,
+                },
+            ),
+        ];
+
+        for (tag, instruction) in pinned.iter() {
+            let data = instruction.pack().unwrap();
+            assert_eq!(data[0], *tag, "{:?}", instruction);
+            assert_eq!(instruction.tag(), *tag, "{:?}", instruction);
+        }
+        assert_eq!(FarmInstruction::try_from(&[22u8][..]).ok(), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let k = |i| Pubkey::new_from_array([i; 32]);
+        #[rustfmt::skip]
+        let snapshots = vec![
+            (
+                FarmInstruction::SetProgramData {
+                    super_owner: k(1),
+                    fee_owner: k(2),
+                    allowed_creator: k(3),
+                    amm_program_id: k(4),
+                    farm_fee: 5_000,
+                    harvest_fee_numerator: 1,
+                    harvest_fee_denominator: 100,
+                },
+                "SetProgramData { super_owner: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi, \
+                 fee_owner: 8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR, \
+                 allowed_creator: CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8, \
+                 amm_program_id: GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq, farm_fee: 5000, \
+                 harvest_fee: 1/100 }",
+            ),
+            (
+                FarmInstruction::InitializeFarm { nonce: 254, start_timestamp: 10, end_timestamp: 20 },
+                "InitializeFarm { nonce: 254, start: 10, end: 20 }",
+            ),
+            (FarmInstruction::Deposit(1_000), "Deposit { amount: 1000 }"),
+            (FarmInstruction::Withdraw(1_000), "Withdraw { amount: 1000 }"),
+            (FarmInstruction::AddReward(1_000), "AddReward { amount: 1000 }"),
+            (FarmInstruction::PayFarmFee(1_000), "PayFarmFee { amount: 1000 }"),
+            (FarmInstruction::EmergencyWithdraw, "EmergencyWithdraw"),
+            (
+                FarmInstruction::SetFarmPeriod { start_timestamp: 10, end_timestamp: 20 },
+                "SetFarmPeriod { start: 10, end: 20 }",
+            ),
+            (
+                FarmInstruction::InitializeFarmV2 { nonce: 254, start_timestamp: 10, end_timestamp: 20 },
+                "InitializeFarmV2 { nonce: 254, start: 10, end: 20 }",
+            ),
+            (FarmInstruction::AddSecondReward(1_000), "AddSecondReward { amount: 1000 }"),
+            (FarmInstruction::CreateUserInfo, "CreateUserInfo"),
+            (
+                FarmInstruction::ProposeSuperOwner(k(1)),
+                "ProposeSuperOwner { new_super_owner: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi }",
+            ),
+            (FarmInstruction::AcceptSuperOwner, "AcceptSuperOwner"),
+            (
+                FarmInstruction::SetFeeOwner(k(1)),
+                "SetFeeOwner { fee_owner: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi }",
+            ),
+            (
+                FarmInstruction::SetAllowedCreator(k(1)),
+                "SetAllowedCreator { allowed_creator: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi }",
+            ),
+            (FarmInstruction::SetFarmFee(5_000), "SetFarmFee { farm_fee: 5000 }"),
+            (
+                FarmInstruction::SetHarvestFee { numerator: 1, denominator: 100 },
+                "SetHarvestFee { harvest_fee: 1/100 }",
+            ),
+            (FarmInstruction::CloseFarm, "CloseFarm"),
+            (
+                FarmInstruction::DepositWithReferral { amount: 1_000, referral: k(1) },
+                "DepositWithReferral { amount: 1000, referral: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi }",
+            ),
+            (
+                FarmInstruction::SetRewardRate { reward_per_second: 7 },
+                "SetRewardRate { reward_per_second: 7 }",
+            ),
+            (
+                FarmInstruction::DepositWithLockup { amount: 1_000, lockup_seconds: 86_400 },
+                "DepositWithLockup { amount: 1000, lockup_seconds: 86400 }",
+            ),
+            (
+                FarmInstruction::SetBoostParams {
+                    multiplier_numerator: 3,
+                    multiplier_denominator: 2,
+                    max_lock_seconds: 86_400,
+                },
+                "SetBoostParams { multiplier: 3/2, max_lock_seconds: 86400 }",
+            ),
+        ];
+        for (instruction, snapshot) in snapshots {
+            assert_eq!(instruction.to_string(), snapshot);
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let keys: Vec<Pubkey> = (1..=14).map(|i| Pubkey::new_from_array([i; 32])).collect();
+        assert_eq!(
+            FarmInstruction::Deposit(1_000).explain(&keys[..13]),
+            "Deposit { amount: 1000 }
+  0. farm_id: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi
+  1. authority: 8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR
+  2. owner: CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8
+  3. user_info_account: GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq
+  4. user_lp_token_account: LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY
+  5. pool_lp_token_account: QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF
+  6. user_reward_token_account: US517G5965aydkZ46HS38QLi7UQiSojurfbQfKCELFx
+  7. pool_reward_token_account: YMN9Qj5jPNp7j14VPcML1B6xGgcPWVZUGLFU3Mnyfaf
+  8. pool_lp_mint: cGfHiC6Kgg3FpFZvgwGcswsCRtp4aBP2fzuXRQPizuN
+  9. fee_reward_ata: gBxS1f6uyyGPuW5MzGBukidSb71jdsCb5fZaoSzULE5
+  10. program_data_account: k7FaK87WHGVXzkaoHb7CdVPgkKDQhZ29VLDeBVbDfYn
+  11. token_program_id: p2Yicb86aZig616Eav2VWG9vuXR5mEqhtzshZYBxzsV
+  12. clock: swqrv48gsrwpBFbftEwnP2vB4jckpvfGJfXkwaniLCC"
+        );
+        // the referral account follows the stake accounts
+        assert!(FarmInstruction::DepositWithReferral {
+            amount: 1_000,
+            referral: keys[13],
+        }
+        .explain(&keys)
+        .ends_with(
+            "\n  13. referral_reward_token_account: ws91DX9HBAAxGW77BZs5FogRDwpRtcUpiLBpKdPTfWu"
+        ));
+
+        // accounts beyond the documented ones, or missing ones
+        assert_eq!(
+            FarmInstruction::SetFarmFee(5_000).explain(&keys[..3]),
+            "SetFarmFee { farm_fee: 5000 }
+  0. program_data_account: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi
+  1. super_owner: 8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR
+  2. unknown: CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8"
+        );
+        assert_eq!(
+            FarmInstruction::AcceptSuperOwner.explain(&keys[..1]),
+            "AcceptSuperOwner
+  0. program_data_account: 4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"
+        );
+        assert_eq!(FarmInstruction::CloseFarm.explain(&[]), "CloseFarm");
+    }
+}
@@ -1,28 +1,65 @@
-/// Main Entrypoint and declaration file
-
-use solana_program::{
-    account_info::{ AccountInfo},
-    entrypoint,
-    entrypoint::ProgramResult,
-    program_error::PrintProgramError,
-    pubkey::Pubkey,
-};
-/// module declaration
-/// 
-/// instruction module
-pub mod instruction;
-
-// Declare and export the program's entrypoint
-#[cfg(not(feature = "no-entrypoint"))]
-entrypoint!(process_instruction);
-
-// Program entrypoint's implementation
-pub fn process_instruction(
-    program_id: &Pubkey, // Public key of the account the Yield Farming program was loaded into
-    accounts: &[AccountInfo], // account informations
-    _instruction_data: &[u8], // Instruction data
-) -> ProgramResult {
-
-    // processed successfully
-    Ok(())
-}
+//! Main Entrypoint and declaration file
+
+use crate::{error::FarmError, processor::Processor};
+use solana_program::{
+    account_info::{ AccountInfo},
+    entrypoint,
+    entrypoint::ProgramResult,
+    program_error::PrintProgramError,
+    pubkey::Pubkey,
+};
+/// module declaration
+/// 
+/// instruction module
+// the BorshSchema derive of FarmInstruction declares a struct per variant
+// whose fields are never read
+#[allow(dead_code)]
+pub mod instruction;
+/// error module
+pub mod error;
+/// processor module
+pub mod processor;
+/// state module
+pub mod state;
+/// parsed instruction module
+pub mod parsed;
+/// math module
+pub mod math;
+/// logs module
+pub mod logs;
+
+/// Token-2022 program, accepted next to spl-token wherever the farm moves tokens.
+///
+/// Only mints whose transfers need no mint account are supported: the farm
+/// issues plain `Transfer`s, and its instructions do not take the mints
+/// `TransferChecked` requires, so that Token-2022 rejects the transfers of
+/// mints with a transfer fee or a transfer hook
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+/// Compute budget program, setting the compute unit limit of a transaction
+pub mod compute_budget {
+    solana_program::declare_id!("ComputeBudget111111111111111111111111111111");
+}
+
+// Declare and export the program's entrypoint
+#[cfg(not(feature = "no-entrypoint"))]
+entrypoint!(process_instruction);
+
+// Program entrypoint's implementation
+pub fn process_instruction(
+    program_id: &Pubkey, // Public key of the account the Yield Farming program was loaded into
+    accounts: &[AccountInfo], // account informations
+    instruction_data: &[u8], // Instruction data
+) -> ProgramResult {
+
+    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
+        // catch the error so we can print it
+        error.print::<FarmError>();
+        return Err(error);
+    }
+
+    // processed successfully
+    Ok(())
+}
@@ -0,0 +1,18 @@
+//! Structured log lines of the farm program, parsed off-chain by `parse_log`
+//! of the AMM crate
+
+use solana_program::{msg, pubkey::Pubkey};
+
+/// Prefix of the log lines of the farm program
+pub const FARM_LOG_PREFIX: &str = "cropper-farm:";
+
+/// Logs the harvest of `reward` tokens, net of the harvest fee, from `farm`:
+/// `cropper-farm: harvest reward=<u64> farm=<base58>`
+pub fn emit_harvest_log(reward: u64, farm: &Pubkey) {
+    msg!(
+        "{} harvest reward={} farm={}",
+        FARM_LOG_PREFIX,
+        reward,
+        farm
+    );
+}
@@ -0,0 +1,295 @@
+//! Fee, reward and boost calculations shared by the program and its clients
+
+use crate::error::FarmError;
+use std::convert::TryFrom;
+
+/// Splits a `pending` reward into the harvest fee, rounded down, and the net
+/// amount paid to the user, so that `fee + net == pending`.
+///
+/// Fails with `InvalidHarvestFee` when `denominator` is zero or the fee
+/// would exceed the reward, i.e. `numerator > denominator`.
+pub fn calculate_harvest_fee(
+    pending: u64,
+    numerator: u64,
+    denominator: u64,
+) -> Result<(u64, u64), FarmError> {
+    if denominator == 0 || numerator > denominator {
+        return Err(FarmError::InvalidHarvestFee);
+    }
+    // cannot overflow: both factors are below 2^64
+    let fee = u128::from(pending) * u128::from(numerator) / u128::from(denominator);
+    let fee = u64::try_from(fee).map_err(|_| FarmError::InvalidHarvestFee)?;
+    Ok((fee, pending - fee))
+}
+
+/// Share of the harvest fee paid to the referral of a `DepositWithReferral`,
+/// in percent
+pub const REFERRAL_FEE_PERCENT: u64 = 50;
+
+/// Splits a harvest `fee` into the part paid to the referral of a
+/// `DepositWithReferral`, [REFERRAL_FEE_PERCENT] of it rounded down, and
+/// the rest paid to the fee owner
+pub fn split_referral_fee(fee: u64) -> (u64, u64) {
+    // cannot overflow: both factors are below 2^64
+    let referral_fee = (u128::from(fee) * u128::from(REFERRAL_FEE_PERCENT) / 100) as u64;
+    (referral_fee, fee - referral_fee)
+}
+
+/// Constant reward rate distributing a total reward over a farm period, see
+/// [reward_rate_for]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RewardRate {
+    /// Rewards distributed per second, rounded down
+    pub reward_per_second: u64,
+    /// Part of the total reward left undistributed by the rounding, below
+    /// the duration of the period
+    pub remainder: u64,
+}
+
+/// Converts a lump sum `total_reward` distributed from `start_timestamp` to
+/// `end_timestamp` into the rate expected by `SetRewardRate`, so that
+/// `reward_per_second * duration + remainder == total_reward`.
+///
+/// Fails with `InvalidFarmPeriod` when the period is empty.
+pub fn reward_rate_for(
+    total_reward: u64,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> Result<RewardRate, FarmError> {
+    if end_timestamp <= start_timestamp {
+        return Err(FarmError::InvalidFarmPeriod);
+    }
+    let duration = end_timestamp - start_timestamp;
+    Ok(RewardRate {
+        reward_per_second: total_reward / duration,
+        remainder: total_reward % duration,
+    })
+}
+
+/// Total reward distributed at `reward_per_second` from `start_timestamp` to
+/// `end_timestamp`, i.e. the rewards the vault must hold for that rate.
+///
+/// Fails with `InvalidFarmPeriod` when the period is empty and with
+/// `MathOverflow` when the total does not fit in a `u64`.
+pub fn total_reward_for(
+    reward_per_second: u64,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> Result<u64, FarmError> {
+    if end_timestamp <= start_timestamp {
+        return Err(FarmError::InvalidFarmPeriod);
+    }
+    reward_per_second
+        .checked_mul(end_timestamp - start_timestamp)
+        .ok_or(FarmError::MathOverflow)
+}
+
+/// Boost of the stake of locked deposits, stored in [FarmPoolV2](crate::state::FarmPoolV2)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoostParams {
+    /// Numerator of the multiplier of a deposit locked for `max_lock_seconds`
+    pub multiplier_numerator: u64,
+    /// Denominator of the multiplier
+    pub multiplier_denominator: u64,
+    /// Lock duration earning the full multiplier, longer locks earn no more
+    pub max_lock_seconds: u64,
+}
+
+impl BoostParams {
+    /// Validate that the multiplier is a fraction of at least one
+    pub fn validate(&self) -> Result<(), FarmError> {
+        if self.multiplier_denominator == 0
+            || self.multiplier_numerator < self.multiplier_denominator
+        {
+            return Err(FarmError::InvalidBoostParams);
+        }
+        Ok(())
+    }
+}
+
+/// Stake weight of `amount` LP tokens locked for `lock_seconds`, growing
+/// linearly from `amount` without lock to `amount` times the multiplier for
+/// a lock of `max_lock_seconds` or more, rounded down.
+///
+/// Default parameters, or a multiplier of one, apply no boost. Fails with
+/// `InvalidBoostParams` when the multiplier has a zero denominator and with
+/// `MathOverflow` when the weight does not fit in a `u64`.
+pub fn boosted_share(
+    amount: u64,
+    lock_seconds: u64,
+    params: &BoostParams,
+) -> Result<u64, FarmError> {
+    if params.multiplier_numerator <= params.multiplier_denominator || params.max_lock_seconds == 0
+    {
+        return Ok(amount);
+    }
+    if params.multiplier_denominator == 0 {
+        return Err(FarmError::InvalidBoostParams);
+    }
+    let lock_seconds = lock_seconds.min(params.max_lock_seconds);
+    // cannot overflow: both factors are below 2^64
+    let bonus = u128::from(amount)
+        * u128::from(params.multiplier_numerator - params.multiplier_denominator);
+    let bonus = bonus
+        .checked_mul(u128::from(lock_seconds))
+        .ok_or(FarmError::MathOverflow)?
+        / (u128::from(params.multiplier_denominator) * u128::from(params.max_lock_seconds));
+    u64::try_from(u128::from(amount) + bonus).map_err(|_| FarmError::MathOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_BOOST: BoostParams = BoostParams {
+        multiplier_numerator: 5,
+        multiplier_denominator: 2,
+        max_lock_seconds: 86_400,
+    };
+
+    #[test]
+    fn test_split_referral_fee() {
+        assert_eq!(split_referral_fee(1_000), (500, 500));
+        // rounded down for the referral
+        assert_eq!(split_referral_fee(1_001), (500, 501));
+        assert_eq!(split_referral_fee(1), (0, 1));
+        assert_eq!(split_referral_fee(0), (0, 0));
+        assert_eq!(
+            split_referral_fee(u64::MAX),
+            (u64::MAX / 2, u64::MAX / 2 + 1)
+        );
+    }
+
+    #[test]
+    fn test_reward_rate_for() {
+        let start = 1_700_000_000;
+        // a day long farm
+        assert_eq!(
+            reward_rate_for(86_400_000, start, start + 86_400),
+            Ok(RewardRate {
+                reward_per_second: 1_000,
+                remainder: 0,
+            })
+        );
+        // rounded down, the remainder below the duration
+        assert_eq!(
+            reward_rate_for(86_400_000 + 86_399, start, start + 86_400),
+            Ok(RewardRate {
+                reward_per_second: 1_000,
+                remainder: 86_399,
+            })
+        );
+        assert_eq!(
+            reward_rate_for(86_399, start, start + 86_400),
+            Ok(RewardRate {
+                reward_per_second: 0,
+                remainder: 86_399,
+            })
+        );
+        assert_eq!(
+            reward_rate_for(u64::MAX, 0, 1),
+            Ok(RewardRate {
+                reward_per_second: u64::MAX,
+                remainder: 0,
+            })
+        );
+        assert_eq!(
+            reward_rate_for(0, start, start + 1),
+            Ok(RewardRate::default())
+        );
+        // empty periods
+        assert_eq!(
+            reward_rate_for(1_000, start, start),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+        assert_eq!(
+            reward_rate_for(1_000, start + 1, start),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+    }
+
+    #[test]
+    fn test_total_reward_for() {
+        let start = 1_700_000_000;
+        assert_eq!(total_reward_for(1_000, start, start + 86_400), Ok(86_400_000));
+        assert_eq!(total_reward_for(0, start, start + 86_400), Ok(0));
+        assert_eq!(total_reward_for(u64::MAX, 0, 1), Ok(u64::MAX));
+        assert_eq!(
+            total_reward_for(u64::MAX, 0, 2),
+            Err(FarmError::MathOverflow)
+        );
+        assert_eq!(
+            total_reward_for(1_000, start, start),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+        assert_eq!(
+            total_reward_for(1_000, start + 1, start),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+
+        // converting back gives the total less the remainder
+        for total_reward in [0, 1, 86_399, 86_400, 86_401, 123_456_789, u64::MAX] {
+            let rate = reward_rate_for(total_reward, start, start + 86_400).unwrap();
+            assert_eq!(
+                total_reward_for(rate.reward_per_second, start, start + 86_400),
+                Ok(total_reward - rate.remainder)
+            );
+        }
+    }
+
+    #[test]
+    fn test_boosted_share_no_boost() {
+        // without lock
+        assert_eq!(boosted_share(1_000, 0, &MAX_BOOST), Ok(1_000));
+        assert_eq!(boosted_share(u64::MAX, 0, &MAX_BOOST), Ok(u64::MAX));
+        // default parameters or a multiplier of one
+        assert_eq!(
+            boosted_share(1_000, 86_400, &BoostParams::default()),
+            Ok(1_000)
+        );
+        let one = BoostParams {
+            multiplier_numerator: 3,
+            multiplier_denominator: 3,
+            ..MAX_BOOST
+        };
+        assert_eq!(boosted_share(1_000, 86_400, &one), Ok(1_000));
+        let no_max_lock = BoostParams {
+            max_lock_seconds: 0,
+            ..MAX_BOOST
+        };
+        assert_eq!(boosted_share(1_000, 86_400, &no_max_lock), Ok(1_000));
+        // the shortest lock earns less than a token of bonus
+        assert_eq!(boosted_share(1_000, 1, &MAX_BOOST), Ok(1_000));
+        assert_eq!(boosted_share(0, 86_400, &MAX_BOOST), Ok(0));
+    }
+
+    #[test]
+    fn test_boosted_share_max_boost() {
+        assert_eq!(boosted_share(1_000, 86_400, &MAX_BOOST), Ok(2_500));
+        // longer locks earn no more
+        assert_eq!(boosted_share(1_000, 86_401, &MAX_BOOST), Ok(2_500));
+        assert_eq!(boosted_share(1_000, u64::MAX, &MAX_BOOST), Ok(2_500));
+        // one second short of the longest lock, rounded down
+        assert_eq!(boosted_share(1_000, 86_399, &MAX_BOOST), Ok(2_499));
+        // linear in between
+        assert_eq!(boosted_share(1_000, 43_200, &MAX_BOOST), Ok(1_750));
+        // the largest amount whose weight fits in a u64
+        let amount = u64::MAX / 5 * 2;
+        assert_eq!(
+            boosted_share(amount, 86_400, &MAX_BOOST),
+            Ok(u64::MAX / 5 * 5)
+        );
+        assert_eq!(
+            boosted_share(amount + 2, 86_400, &MAX_BOOST),
+            Err(FarmError::MathOverflow)
+        );
+        let zero_denominator = BoostParams {
+            multiplier_denominator: 0,
+            ..MAX_BOOST
+        };
+        assert_eq!(
+            boosted_share(1_000, 86_400, &zero_denominator),
+            Err(FarmError::InvalidBoostParams)
+        );
+    }
+}
@@ -0,0 +1,715 @@
+//! Instructions decoded together with the named accounts they operate on
+
+use crate::{error::FarmError, instruction::FarmInstruction};
+use borsh::BorshDeserialize;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// A [FarmInstruction] with its accounts mapped to their roles, in the order
+/// used by the instruction builders
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParsedFarmInstruction {
+    /// See [FarmInstruction::SetProgramData]
+    SetProgramData {
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// current super owner
+        super_owner: Pubkey,
+        /// owner receiving the fees
+        fee_owner: Pubkey,
+        /// creator allowed to create farms without paying the farm fee
+        allowed_creator: Pubkey,
+        /// amm program id
+        amm_program_id: Pubkey,
+        /// farm fee
+        farm_fee: u64,
+        /// harvest fee numerator
+        harvest_fee_numerator: u64,
+        /// harvest fee denominator
+        harvest_fee_denominator: u64,
+    },
+
+    /// See [FarmInstruction::InitializeFarm] and [FarmInstruction::InitializeFarmV2],
+    /// the second reward accounts being only set for the latter
+    InitializeFarm {
+        /// New FarmPool account
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// Creator/Manager of this farm
+        owner: Pubkey,
+        /// LP token account of this farm
+        pool_lp_token_account: Pubkey,
+        /// reward token account of this farm
+        pool_reward_token_account: Pubkey,
+        /// Pool token mint address
+        pool_mint_address: Pubkey,
+        /// Reward token mint address
+        reward_mint_address: Pubkey,
+        /// Amm Id
+        amm_id: Pubkey,
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// Second reward token mint address, for dual-reward farms
+        second_reward_mint_address: Option<Pubkey>,
+        /// second reward token account of this farm, for dual-reward farms
+        pool_second_reward_token_account: Option<Pubkey>,
+        /// nonce
+        nonce: u8,
+        /// start timestamp
+        start_timestamp: u64,
+        /// end timestamp
+        end_timestamp: u64,
+    },
+
+    /// See [FarmInstruction::Deposit]
+    Deposit(ParsedStake),
+
+    /// See [FarmInstruction::DepositWithReferral]
+    DepositWithReferral {
+        /// accounts and amount shared with Deposit
+        stake: ParsedStake,
+        /// wallet the deposit is attributed to
+        referral: Pubkey,
+        /// Referral reward token account
+        referral_reward_token_account: Pubkey,
+    },
+
+    /// See [FarmInstruction::Withdraw]
+    Withdraw(ParsedStake),
+
+    /// See [FarmInstruction::AddReward] and [FarmInstruction::AddSecondReward]
+    AddReward {
+        /// FarmPool to add reward to
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// creator
+        owner: Pubkey,
+        /// User reward token account
+        user_reward_token_account: Pubkey,
+        /// Pool reward token account
+        pool_reward_token_account: Pubkey,
+        /// Pool LP token account
+        pool_lp_token_account: Pubkey,
+        /// Pool lp token mint
+        pool_lp_mint: Pubkey,
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// token program id
+        token_program_id: Pubkey,
+        /// Whether the second reward of a dual-reward farm is added
+        second_reward: bool,
+        /// amount
+        amount: u64,
+    },
+
+    /// See [FarmInstruction::PayFarmFee]
+    PayFarmFee {
+        /// FarmPool to pay farm fee
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// creator
+        owner: Pubkey,
+        /// User USDC token account
+        user_usdc_token_account: Pubkey,
+        /// fee usdc ata
+        fee_usdc_ata: Pubkey,
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// token program id
+        token_program_id: Pubkey,
+        /// amount
+        amount: u64,
+    },
+
+    /// See [FarmInstruction::EmergencyWithdraw]
+    EmergencyWithdraw {
+        /// FarmPool to withdraw from
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// Withdrawer
+        owner: Pubkey,
+        /// User Farming Information Account
+        user_info_account: Pubkey,
+        /// User LP token account
+        user_lp_token_account: Pubkey,
+        /// Pool LP token account
+        pool_lp_token_account: Pubkey,
+        /// Token program id
+        token_program_id: Pubkey,
+    },
+
+    /// See [FarmInstruction::CreateUserInfo]
+    CreateUserInfo {
+        /// FarmPool the user farms in
+        farm_id: Pubkey,
+        /// User Farming Information Account to create
+        user_info_account: Pubkey,
+        /// Wallet of the user
+        wallet: Pubkey,
+    },
+
+    /// See [FarmInstruction::ProposeSuperOwner]
+    ProposeSuperOwner {
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// current super owner
+        super_owner: Pubkey,
+        /// proposed super owner
+        new_super_owner: Pubkey,
+    },
+
+    /// See [FarmInstruction::AcceptSuperOwner]
+    AcceptSuperOwner {
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// proposed super owner
+        new_super_owner: Pubkey,
+    },
+
+    /// See [FarmInstruction::SetFeeOwner], [FarmInstruction::SetAllowedCreator],
+    /// [FarmInstruction::SetFarmFee] and [FarmInstruction::SetHarvestFee]
+    UpdateProgramData {
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// super owner
+        super_owner: Pubkey,
+        /// decoded instruction
+        instruction: FarmInstruction,
+    },
+
+    /// See [FarmInstruction::CloseFarm]
+    CloseFarm {
+        /// FarmPool to close
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// creator
+        owner: Pubkey,
+        /// Pool reward token account
+        pool_reward_token_account: Pubkey,
+        /// Reward token account receiving the remaining rewards
+        creator_reward_token_account: Pubkey,
+        /// Pool LP token account
+        pool_lp_token_account: Pubkey,
+        /// token program id
+        token_program_id: Pubkey,
+    },
+
+    /// See [FarmInstruction::SetFarmPeriod]
+    SetFarmPeriod {
+        /// FarmPool to update
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// creator
+        owner: Pubkey,
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// Pool LP token account
+        pool_lp_token_account: Pubkey,
+        /// start timestamp
+        start_timestamp: u64,
+        /// end timestamp
+        end_timestamp: u64,
+    },
+
+    /// See [FarmInstruction::SetRewardRate]
+    SetRewardRate {
+        /// FarmPool to update
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// creator
+        owner: Pubkey,
+        /// Pool LP token account
+        pool_lp_token_account: Pubkey,
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// Pool reward token account
+        pool_reward_token_account: Pubkey,
+        /// rewards distributed per second
+        reward_per_second: u64,
+    },
+
+    /// See [FarmInstruction::DepositWithLockup]
+    DepositWithLockup {
+        /// accounts and amount shared with Deposit
+        stake: ParsedStake,
+        /// seconds the staked LP tokens are locked for
+        lockup_seconds: u64,
+    },
+
+    /// See [FarmInstruction::SetBoostParams]
+    SetBoostParams {
+        /// FarmPool to update
+        farm_id: Pubkey,
+        /// authority of this farm pool
+        authority: Pubkey,
+        /// creator
+        owner: Pubkey,
+        /// farm program data account
+        program_data_account: Pubkey,
+        /// numerator of the multiplier of the longest locks
+        multiplier_numerator: u64,
+        /// denominator of the multiplier
+        multiplier_denominator: u64,
+        /// lock duration earning the full multiplier
+        max_lock_seconds: u64,
+    },
+}
+
+/// Accounts and amount of a Deposit or Withdraw
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedStake {
+    /// FarmPool to deposit to or withdraw from
+    pub farm_id: Pubkey,
+    /// authority of this farm pool
+    pub authority: Pubkey,
+    /// Depositor or withdrawer
+    pub owner: Pubkey,
+    /// User Farming Information Account
+    pub user_info_account: Pubkey,
+    /// User LP token account
+    pub user_lp_token_account: Pubkey,
+    /// Pool LP token account
+    pub pool_lp_token_account: Pubkey,
+    /// User reward token account
+    pub user_reward_token_account: Pubkey,
+    /// Pool reward token account
+    pub pool_reward_token_account: Pubkey,
+    /// Pool LP token mint
+    pub pool_lp_mint: Pubkey,
+    /// fee reward ata account
+    pub fee_reward_ata: Pubkey,
+    /// farm program data account
+    pub program_data_account: Pubkey,
+    /// Token program id
+    pub token_program_id: Pubkey,
+    /// amount
+    pub amount: u64,
+}
+
+/// Checks the number of accounts of an instruction, returning them
+fn expect_accounts(accounts: &[Pubkey], len: usize) -> Result<&[Pubkey], ProgramError> {
+    if accounts.len() != len {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    Ok(accounts)
+}
+
+fn parse_stake(accounts: &[Pubkey], amount: u64) -> Result<ParsedStake, ProgramError> {
+    let k = expect_accounts(accounts, 13)?;
+    Ok(ParsedStake {
+        farm_id: k[0],
+        authority: k[1],
+        owner: k[2],
+        user_info_account: k[3],
+        user_lp_token_account: k[4],
+        pool_lp_token_account: k[5],
+        user_reward_token_account: k[6],
+        pool_reward_token_account: k[7],
+        pool_lp_mint: k[8],
+        fee_reward_ata: k[9],
+        program_data_account: k[10],
+        token_program_id: k[11],
+        amount,
+    })
+}
+
+fn parse_add_reward(
+    accounts: &[Pubkey],
+    second_reward: bool,
+    amount: u64,
+) -> Result<ParsedFarmInstruction, ProgramError> {
+    let k = expect_accounts(accounts, 10)?;
+    Ok(ParsedFarmInstruction::AddReward {
+        farm_id: k[0],
+        authority: k[1],
+        owner: k[2],
+        user_reward_token_account: k[3],
+        pool_reward_token_account: k[4],
+        pool_lp_token_account: k[5],
+        pool_lp_mint: k[6],
+        program_data_account: k[7],
+        token_program_id: k[8],
+        second_reward,
+        amount,
+    })
+}
+
+/// Account roles of the stake instructions, named as the fields of
+/// [ParsedStake]
+const STAKE_ROLES: &[&str] = &[
+    "farm_id",
+    "authority",
+    "owner",
+    "user_info_account",
+    "user_lp_token_account",
+    "pool_lp_token_account",
+    "user_reward_token_account",
+    "pool_reward_token_account",
+    "pool_lp_mint",
+    "fee_reward_ata",
+    "program_data_account",
+    "token_program_id",
+    "clock",
+];
+const INITIALIZE_FARM_ROLES: &[&str] = &[
+    "farm_id",
+    "authority",
+    "owner",
+    "pool_lp_token_account",
+    "pool_reward_token_account",
+    "pool_mint_address",
+    "reward_mint_address",
+    "amm_id",
+    "program_data_account",
+    "rent",
+    "second_reward_mint_address",
+    "pool_second_reward_token_account",
+];
+const ADD_REWARD_ROLES: &[&str] = &[
+    "farm_id",
+    "authority",
+    "owner",
+    "user_reward_token_account",
+    "pool_reward_token_account",
+    "pool_lp_token_account",
+    "pool_lp_mint",
+    "program_data_account",
+    "token_program_id",
+    "clock",
+];
+
+impl FarmInstruction {
+    /// Roles of the accounts of the instruction, in order. Names follow the
+    /// fields of [ParsedFarmInstruction], optional trailing accounts being
+    /// always named
+    pub fn account_roles(&self) -> Vec<&'static str> {
+        match self {
+            Self::SetProgramData { .. } => vec![
+                "program_data_account",
+                "super_owner",
+                "system_program",
+                "rent",
+                "executable_data_account",
+            ],
+            Self::ProposeSuperOwner(_)
+            | Self::SetFeeOwner(_)
+            | Self::SetAllowedCreator(_)
+            | Self::SetFarmFee(_)
+            | Self::SetHarvestFee { .. } => vec!["program_data_account", "super_owner"],
+            Self::AcceptSuperOwner => vec!["program_data_account", "new_super_owner"],
+            Self::InitializeFarm { .. } => INITIALIZE_FARM_ROLES[..10].to_vec(),
+            Self::InitializeFarmV2 { .. } => INITIALIZE_FARM_ROLES.to_vec(),
+            Self::Deposit(_) | Self::Withdraw(_) | Self::DepositWithLockup { .. } => {
+                STAKE_ROLES.to_vec()
+            }
+            Self::DepositWithReferral { .. } => {
+                let mut roles = STAKE_ROLES.to_vec();
+                roles.push("referral_reward_token_account");
+                roles
+            }
+            Self::AddReward(_) | Self::AddSecondReward(_) => ADD_REWARD_ROLES.to_vec(),
+            Self::PayFarmFee(_) => vec![
+                "farm_id",
+                "authority",
+                "owner",
+                "user_usdc_token_account",
+                "fee_usdc_ata",
+                "program_data_account",
+                "token_program_id",
+            ],
+            Self::EmergencyWithdraw => vec![
+                "farm_id",
+                "authority",
+                "owner",
+                "user_info_account",
+                "user_lp_token_account",
+                "pool_lp_token_account",
+                "token_program_id",
+            ],
+            Self::SetFarmPeriod { .. } => vec![
+                "farm_id",
+                "authority",
+                "owner",
+                "program_data_account",
+                "clock",
+                "pool_lp_token_account",
+            ],
+            Self::CreateUserInfo => vec![
+                "farm_id",
+                "user_info_account",
+                "wallet",
+                "system_program",
+                "rent",
+            ],
+            Self::CloseFarm => vec![
+                "farm_id",
+                "authority",
+                "owner",
+                "pool_reward_token_account",
+                "creator_reward_token_account",
+                "pool_lp_token_account",
+                "token_program_id",
+            ],
+            Self::SetRewardRate { .. } => vec![
+                "farm_id",
+                "authority",
+                "owner",
+                "pool_lp_token_account",
+                "program_data_account",
+                "clock",
+                "pool_reward_token_account",
+            ],
+            Self::SetBoostParams { .. } => {
+                vec!["farm_id", "authority", "owner", "program_data_account"]
+            }
+        }
+    }
+
+    /// Summary of the instruction followed by one line per account with its
+    /// role, e.g.
+    ///
+    /// ```text
+    /// Deposit { amount: 1500000 }
+    ///   0. farm_id: <pubkey>
+    ///   1. authority: <pubkey>
+    /// ```
+    ///
+    /// Accounts beyond the documented ones are listed as `unknown`. Unlike
+    /// [ParsedFarmInstruction::parse] it never fails, to be usable on
+    /// malformed transactions
+    pub fn explain(&self, accounts: &[Pubkey]) -> String {
+        let roles = self.account_roles();
+        let mut explanation = self.to_string();
+        for (index, account) in accounts.iter().enumerate() {
+            let role = roles.get(index).copied().unwrap_or("unknown");
+            explanation.push_str(&format!("\n  {}. {}: {}", index, role, account));
+        }
+        explanation
+    }
+}
+
+impl ParsedFarmInstruction {
+    /// Decodes the instruction data and names its accounts, given in the
+    /// order of the instruction
+    pub fn parse(data: &[u8], accounts: &[Pubkey]) -> Result<Self, ProgramError> {
+        let instruction =
+            FarmInstruction::try_from_slice(data).map_err(|_| FarmError::InvalidInstruction)?;
+        Ok(match instruction {
+            FarmInstruction::SetProgramData {
+                super_owner: _,
+                fee_owner,
+                allowed_creator,
+                amm_program_id,
+                farm_fee,
+                harvest_fee_numerator,
+                harvest_fee_denominator,
+            } => {
+                // the system program, rent sysvar and executable data of the
+                // program create the program data
+                let k = match accounts.len() {
+                    5 => accounts,
+                    _ => expect_accounts(accounts, 2)?,
+                };
+                Self::SetProgramData {
+                    program_data_account: k[0],
+                    super_owner: k[1],
+                    fee_owner,
+                    allowed_creator,
+                    amm_program_id,
+                    farm_fee,
+                    harvest_fee_numerator,
+                    harvest_fee_denominator,
+                }
+            }
+            FarmInstruction::InitializeFarm {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            } => {
+                let k = expect_accounts(accounts, 10)?;
+                Self::InitializeFarm {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    pool_lp_token_account: k[3],
+                    pool_reward_token_account: k[4],
+                    pool_mint_address: k[5],
+                    reward_mint_address: k[6],
+                    amm_id: k[7],
+                    program_data_account: k[8],
+                    second_reward_mint_address: None,
+                    pool_second_reward_token_account: None,
+                    nonce,
+                    start_timestamp,
+                    end_timestamp,
+                }
+            }
+            FarmInstruction::Deposit(amount) => Self::Deposit(parse_stake(accounts, amount)?),
+            FarmInstruction::Withdraw(amount) => Self::Withdraw(parse_stake(accounts, amount)?),
+            FarmInstruction::AddReward(amount) => parse_add_reward(accounts, false, amount)?,
+            FarmInstruction::PayFarmFee(amount) => {
+                let k = expect_accounts(accounts, 7)?;
+                Self::PayFarmFee {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    user_usdc_token_account: k[3],
+                    fee_usdc_ata: k[4],
+                    program_data_account: k[5],
+                    token_program_id: k[6],
+                    amount,
+                }
+            }
+            FarmInstruction::EmergencyWithdraw => {
+                let k = expect_accounts(accounts, 7)?;
+                Self::EmergencyWithdraw {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    user_info_account: k[3],
+                    user_lp_token_account: k[4],
+                    pool_lp_token_account: k[5],
+                    token_program_id: k[6],
+                }
+            }
+            FarmInstruction::SetFarmPeriod {
+                start_timestamp,
+                end_timestamp,
+            } => {
+                let k = expect_accounts(accounts, 6)?;
+                Self::SetFarmPeriod {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    program_data_account: k[3],
+                    pool_lp_token_account: k[5],
+                    start_timestamp,
+                    end_timestamp,
+                }
+            }
+            FarmInstruction::InitializeFarmV2 {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            } => {
+                // dual-reward farms pass the second reward mint and account
+                let k = match accounts.len() {
+                    12 => accounts,
+                    _ => expect_accounts(accounts, 10)?,
+                };
+                Self::InitializeFarm {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    pool_lp_token_account: k[3],
+                    pool_reward_token_account: k[4],
+                    pool_mint_address: k[5],
+                    reward_mint_address: k[6],
+                    amm_id: k[7],
+                    program_data_account: k[8],
+                    second_reward_mint_address: k.get(10).copied(),
+                    pool_second_reward_token_account: k.get(11).copied(),
+                    nonce,
+                    start_timestamp,
+                    end_timestamp,
+                }
+            }
+            FarmInstruction::AddSecondReward(amount) => parse_add_reward(accounts, true, amount)?,
+            FarmInstruction::CreateUserInfo => {
+                let k = expect_accounts(accounts, 5)?;
+                Self::CreateUserInfo {
+                    farm_id: k[0],
+                    user_info_account: k[1],
+                    wallet: k[2],
+                }
+            }
+            FarmInstruction::ProposeSuperOwner(new_super_owner) => {
+                let k = expect_accounts(accounts, 2)?;
+                Self::ProposeSuperOwner {
+                    program_data_account: k[0],
+                    super_owner: k[1],
+                    new_super_owner,
+                }
+            }
+            FarmInstruction::AcceptSuperOwner => {
+                let k = expect_accounts(accounts, 2)?;
+                Self::AcceptSuperOwner {
+                    program_data_account: k[0],
+                    new_super_owner: k[1],
+                }
+            }
+            instruction @ (FarmInstruction::SetFeeOwner(_)
+            | FarmInstruction::SetAllowedCreator(_)
+            | FarmInstruction::SetFarmFee(_)
+            | FarmInstruction::SetHarvestFee { .. }) => {
+                let k = expect_accounts(accounts, 2)?;
+                Self::UpdateProgramData {
+                    program_data_account: k[0],
+                    super_owner: k[1],
+                    instruction,
+                }
+            }
+            FarmInstruction::CloseFarm => {
+                let k = expect_accounts(accounts, 7)?;
+                Self::CloseFarm {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    pool_reward_token_account: k[3],
+                    creator_reward_token_account: k[4],
+                    pool_lp_token_account: k[5],
+                    token_program_id: k[6],
+                }
+            }
+            FarmInstruction::DepositWithReferral { amount, referral } => {
+                let k = expect_accounts(accounts, 14)?;
+                Self::DepositWithReferral {
+                    stake: parse_stake(&k[..13], amount)?,
+                    referral,
+                    referral_reward_token_account: k[13],
+                }
+            }
+            FarmInstruction::SetRewardRate { reward_per_second } => {
+                let k = expect_accounts(accounts, 7)?;
+                Self::SetRewardRate {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    pool_lp_token_account: k[3],
+                    program_data_account: k[4],
+                    pool_reward_token_account: k[6],
+                    reward_per_second,
+                }
+            }
+            FarmInstruction::DepositWithLockup {
+                amount,
+                lockup_seconds,
+            } => Self::DepositWithLockup {
+                stake: parse_stake(accounts, amount)?,
+                lockup_seconds,
+            },
+            FarmInstruction::SetBoostParams {
+                multiplier_numerator,
+                multiplier_denominator,
+                max_lock_seconds,
+            } => {
+                let k = expect_accounts(accounts, 4)?;
+                Self::SetBoostParams {
+                    farm_id: k[0],
+                    authority: k[1],
+                    owner: k[2],
+                    program_data_account: k[3],
+                    multiplier_numerator,
+                    multiplier_denominator,
+                    max_lock_seconds,
+                }
+            }
+        })
+    }
+}
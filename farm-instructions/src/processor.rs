@@ -0,0 +1,2381 @@
+//! Program state processor
+
+use crate::{
+    error::FarmError,
+    instruction::FarmInstruction,
+    logs::emit_harvest_log,
+    math::{
+        boosted_share, calculate_harvest_fee, reward_rate_for, split_referral_fee, BoostParams,
+        RewardRate,
+    },
+    spl_token_2022,
+    state::{
+        farm_authority, find_executable_data_address, find_program_data_address,
+        find_user_info_address, FarmPool, FarmPoolV2, FarmPoolVersion, FarmProgramData,
+        FarmTimestamp, UserInfoV3, UserInfoVersion, PROGRAM_DATA_SEED, REWARD_MULTIPLIER,
+        USER_INFO_SEED,
+    },
+};
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::{self, Sysvar},
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token::{
+    instruction::MAX_SIGNERS,
+    state::{Account as TokenAccount, Multisig},
+};
+use std::slice::Iter;
+
+/// Program state handler.
+pub struct Processor {}
+impl Processor {
+    /// Processes an [Instruction](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        let instruction =
+            FarmInstruction::try_from_slice(input).map_err(|_| FarmError::InvalidInstruction)?;
+        match instruction {
+            FarmInstruction::SetProgramData {
+                super_owner,
+                fee_owner,
+                allowed_creator,
+                amm_program_id,
+                farm_fee,
+                harvest_fee_numerator,
+                harvest_fee_denominator,
+            } => {
+                msg!("Instruction: SetProgramData");
+                Self::process_set_program_data(
+                    program_id,
+                    accounts,
+                    &super_owner,
+                    &fee_owner,
+                    &allowed_creator,
+                    &amm_program_id,
+                    farm_fee,
+                    harvest_fee_numerator,
+                    harvest_fee_denominator,
+                )
+            }
+            FarmInstruction::InitializeFarm {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            } => {
+                msg!("Instruction: InitializeFarm");
+                Self::process_initialize_farm(
+                    program_id,
+                    accounts,
+                    nonce,
+                    start_timestamp,
+                    end_timestamp,
+                )
+            }
+            FarmInstruction::Deposit(amount) => {
+                msg!("Instruction: Deposit");
+                Self::process_deposit(program_id, accounts, amount)
+            }
+            FarmInstruction::Withdraw(amount) => {
+                msg!("Instruction: Withdraw");
+                Self::process_withdraw(program_id, accounts, amount)
+            }
+            FarmInstruction::AddReward(amount) => {
+                msg!("Instruction: AddReward");
+                Self::process_add_reward(program_id, accounts, amount)
+            }
+            FarmInstruction::PayFarmFee(amount) => {
+                msg!("Instruction: PayFarmFee");
+                Self::process_pay_farm_fee(program_id, accounts, amount)
+            }
+            FarmInstruction::EmergencyWithdraw => {
+                msg!("Instruction: EmergencyWithdraw");
+                Self::process_emergency_withdraw(program_id, accounts)
+            }
+            FarmInstruction::SetFarmPeriod {
+                start_timestamp,
+                end_timestamp,
+            } => {
+                msg!("Instruction: SetFarmPeriod");
+                Self::process_set_farm_period(program_id, accounts, start_timestamp, end_timestamp)
+            }
+            FarmInstruction::InitializeFarmV2 {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            } => {
+                msg!("Instruction: InitializeFarmV2");
+                Self::process_initialize_farm_v2(
+                    program_id,
+                    accounts,
+                    nonce,
+                    start_timestamp,
+                    end_timestamp,
+                )
+            }
+            FarmInstruction::AddSecondReward(amount) => {
+                msg!("Instruction: AddSecondReward");
+                Self::process_add_second_reward(program_id, accounts, amount)
+            }
+            FarmInstruction::CreateUserInfo => {
+                msg!("Instruction: CreateUserInfo");
+                Self::process_create_user_info(program_id, accounts)
+            }
+            FarmInstruction::ProposeSuperOwner(new_super_owner) => {
+                msg!("Instruction: ProposeSuperOwner");
+                Self::process_propose_super_owner(program_id, accounts, &new_super_owner)
+            }
+            FarmInstruction::AcceptSuperOwner => {
+                msg!("Instruction: AcceptSuperOwner");
+                Self::process_accept_super_owner(program_id, accounts)
+            }
+            FarmInstruction::SetFeeOwner(fee_owner) => {
+                msg!("Instruction: SetFeeOwner");
+                Self::process_update_program_data(program_id, accounts, |program_data| {
+                    program_data.fee_owner = fee_owner;
+                    Ok(())
+                })
+            }
+            FarmInstruction::SetAllowedCreator(allowed_creator) => {
+                msg!("Instruction: SetAllowedCreator");
+                Self::process_update_program_data(program_id, accounts, |program_data| {
+                    program_data.allowed_creator = allowed_creator;
+                    Ok(())
+                })
+            }
+            FarmInstruction::SetFarmFee(farm_fee) => {
+                msg!("Instruction: SetFarmFee");
+                Self::process_update_program_data(program_id, accounts, |program_data| {
+                    program_data.farm_fee = farm_fee;
+                    Ok(())
+                })
+            }
+            FarmInstruction::SetHarvestFee {
+                numerator,
+                denominator,
+            } => {
+                msg!("Instruction: SetHarvestFee");
+                Self::process_update_program_data(program_id, accounts, |program_data| {
+                    check_harvest_fee(numerator, denominator)?;
+                    program_data.harvest_fee_numerator = numerator;
+                    program_data.harvest_fee_denominator = denominator;
+                    Ok(())
+                })
+            }
+            FarmInstruction::CloseFarm => {
+                msg!("Instruction: CloseFarm");
+                Self::process_close_farm(program_id, accounts)
+            }
+            FarmInstruction::DepositWithReferral { amount, referral } => {
+                msg!("Instruction: DepositWithReferral");
+                Self::process_deposit_with_referral(program_id, accounts, amount, &referral)
+            }
+            FarmInstruction::SetRewardRate { reward_per_second } => {
+                msg!("Instruction: SetRewardRate");
+                Self::process_set_reward_rate(program_id, accounts, reward_per_second)
+            }
+            FarmInstruction::DepositWithLockup {
+                amount,
+                lockup_seconds,
+            } => {
+                msg!("Instruction: DepositWithLockup");
+                Self::process_deposit_with_lockup(program_id, accounts, amount, lockup_seconds)
+            }
+            FarmInstruction::SetBoostParams {
+                multiplier_numerator,
+                multiplier_denominator,
+                max_lock_seconds,
+            } => {
+                msg!("Instruction: SetBoostParams");
+                let params = BoostParams {
+                    multiplier_numerator,
+                    multiplier_denominator,
+                    max_lock_seconds,
+                };
+                Self::process_set_boost_params(program_id, accounts, &params)
+            }
+        }
+    }
+
+    /// Processes a [SetProgramData](enum.FarmInstruction.html).
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_set_program_data(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        super_owner: &Pubkey,
+        fee_owner: &Pubkey,
+        allowed_creator: &Pubkey,
+        amm_program_id: &Pubkey,
+        farm_fee: u64,
+        harvest_fee_numerator: u64,
+        harvest_fee_denominator: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_data_info = next_account_info(account_info_iter)?;
+        let super_owner_info = next_account_info(account_info_iter)?;
+
+        check_writable(program_data_info)?;
+        check_signer(super_owner_info)?;
+        check_harvest_fee(harvest_fee_numerator, harvest_fee_denominator)?;
+        let (program_data_address, bump) = find_program_data_address(program_id);
+        if *program_data_info.key != program_data_address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // the first call, signed by the upgrade authority of the program,
+        // creates the program data and sets the super owner, only the stored
+        // one updates the program data afterwards
+        if program_data_info.data_is_empty() {
+            let system_program_info = next_account_info(account_info_iter)?;
+            let rent_sysvar_info = next_account_info(account_info_iter)?;
+            let executable_data_info = next_account_info(account_info_iter)?;
+            check_writable(super_owner_info)?;
+            check_upgrade_authority(program_id, executable_data_info, super_owner_info)?;
+            let rent = Rent::from_account_info(rent_sysvar_info)?;
+            create_program_data_account(
+                program_id,
+                program_data_info,
+                super_owner_info,
+                system_program_info,
+                &rent,
+                bump,
+            )?;
+        }
+        check_program_account(program_id, program_data_info)?;
+        let program_data = FarmProgramData::unpack_unchecked(&program_data_info.data.borrow())?;
+        if program_data.is_initialized() && program_data.super_owner() != super_owner_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        // the super owner is only rotated by ProposeSuperOwner/AcceptSuperOwner
+        if super_owner != super_owner_info.key {
+            msg!("SetProgramData cannot change the super owner");
+            return Err(FarmError::NotAllowed.into());
+        }
+
+        let program_data = FarmProgramData {
+            is_initialized: true,
+            super_owner: *super_owner,
+            fee_owner: *fee_owner,
+            allowed_creator: *allowed_creator,
+            amm_program_id: *amm_program_id,
+            farm_fee,
+            harvest_fee_numerator,
+            harvest_fee_denominator,
+            pending_super_owner: program_data.pending_super_owner,
+        };
+        FarmProgramData::pack(program_data, &mut program_data_info.data.borrow_mut())
+    }
+
+    /// Processes the single field updates of the program data:
+    /// [SetFeeOwner](enum.FarmInstruction.html),
+    /// [SetAllowedCreator](enum.FarmInstruction.html),
+    /// [SetFarmFee](enum.FarmInstruction.html) and
+    /// [SetHarvestFee](enum.FarmInstruction.html).
+    ///
+    /// `update` applies the field to the stored program data, the other
+    /// fields are kept as they are.
+    pub fn process_update_program_data<F>(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        update: F,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&mut FarmProgramData) -> ProgramResult,
+    {
+        let account_info_iter = &mut accounts.iter();
+        let program_data_info = next_account_info(account_info_iter)?;
+        let super_owner_info = next_account_info(account_info_iter)?;
+
+        check_writable(program_data_info)?;
+        let mut program_data = unpack_program_data(program_id, program_data_info)?;
+        check_super_owner(&program_data, super_owner_info)?;
+
+        update(&mut program_data)?;
+        FarmProgramData::pack(program_data, &mut program_data_info.data.borrow_mut())
+    }
+
+    /// Processes a [ProposeSuperOwner](enum.FarmInstruction.html).
+    pub fn process_propose_super_owner(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_super_owner: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_data_info = next_account_info(account_info_iter)?;
+        let super_owner_info = next_account_info(account_info_iter)?;
+
+        check_writable(program_data_info)?;
+        let mut program_data = unpack_program_data(program_id, program_data_info)?;
+        check_super_owner(&program_data, super_owner_info)?;
+
+        if *new_super_owner == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        program_data.pending_super_owner = *new_super_owner;
+        FarmProgramData::pack(program_data, &mut program_data_info.data.borrow_mut())
+    }
+
+    /// Processes an [AcceptSuperOwner](enum.FarmInstruction.html).
+    pub fn process_accept_super_owner(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_data_info = next_account_info(account_info_iter)?;
+        let new_super_owner_info = next_account_info(account_info_iter)?;
+
+        check_writable(program_data_info)?;
+        check_signer(new_super_owner_info)?;
+        let mut program_data = unpack_program_data(program_id, program_data_info)?;
+
+        // no rotation is pending while the pending super owner is the default
+        if program_data.pending_super_owner == Pubkey::default()
+            || program_data.pending_super_owner != *new_super_owner_info.key
+        {
+            return Err(FarmError::NotAllowed.into());
+        }
+        program_data.super_owner = program_data.pending_super_owner;
+        program_data.pending_super_owner = Pubkey::default();
+        FarmProgramData::pack(program_data, &mut program_data_info.data.borrow_mut())
+    }
+
+    /// Processes an [InitializeFarm](enum.FarmInstruction.html).
+    pub fn process_initialize_farm(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        nonce: u8,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let pool_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let reward_mint_info = next_account_info(account_info_iter)?;
+        let amm_id_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_authority(program_id, farm_id_info.key, nonce, authority_info.key)?;
+        check_signer(creator_info)?;
+        check_token_account(pool_lp_token_account_info)?;
+        check_token_account(pool_reward_token_account_info)?;
+        let program_data = unpack_program_data(program_id, program_data_info)?;
+        if !sysvar::rent::check_id(rent_sysvar_info.key) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        FarmTimestamp::period_from_unix_seconds(start_timestamp, end_timestamp)?;
+
+        // a new farm account of either layout
+        let farm_data_len = farm_id_info.data_len();
+        if farm_data_len != FarmPool::LEN && farm_data_len != FarmPoolV2::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let farm_pool = FarmPool::unpack_unchecked(&farm_id_info.data.borrow()[..FarmPool::LEN])?;
+        if farm_pool.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let rent = Rent::from_account_info(rent_sysvar_info)?;
+        if !rent.is_exempt(farm_id_info.lamports(), farm_data_len) {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        // both vaults belong to the farm authority, under the same token program
+        let token_program_id = *pool_lp_token_account_info.owner;
+        if *pool_reward_token_account_info.owner != token_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_vault(
+            pool_lp_token_account_info,
+            authority_info.key,
+            pool_mint_info.key,
+        )?;
+        check_vault(
+            pool_reward_token_account_info,
+            authority_info.key,
+            reward_mint_info.key,
+        )?;
+        if amm_id_info.owner != program_data.amm_program_id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let farm_pool = FarmPool {
+            // the allowed creator does not pay the farm fee
+            is_allowed: creator_info.key == program_data.allowed_creator()
+                || program_data.farm_fee() == 0,
+            nonce,
+            pool_lp_token_account: *pool_lp_token_account_info.key,
+            pool_reward_token_account: *pool_reward_token_account_info.key,
+            pool_mint_address: *pool_mint_info.key,
+            reward_mint_address: *reward_mint_info.key,
+            token_program_id,
+            owner: *creator_info.key,
+            fee_owner: *program_data.fee_owner(),
+            reward_per_share_net: 0,
+            last_timestamp: start_timestamp,
+            reward_per_timestamp: 0,
+            start_timestamp,
+            end_timestamp,
+        };
+        pack_farm_pool(&farm_pool, farm_id_info);
+        Ok(())
+    }
+
+    /// Processes an [InitializeFarmV2](enum.FarmInstruction.html).
+    pub fn process_initialize_farm_v2(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        nonce: u8,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> ProgramResult {
+        Self::process_initialize_farm(program_id, accounts, nonce, start_timestamp, end_timestamp)?;
+
+        // second reward accounts come in pairs after the InitializeFarm ones
+        let (second_reward_mint_info, pool_second_reward_token_account_info) =
+            match accounts.get(10..) {
+                None | Some([]) => return Ok(()),
+                Some([second_reward_mint_info, pool_second_reward_token_account_info]) => (
+                    second_reward_mint_info,
+                    pool_second_reward_token_account_info,
+                ),
+                Some(_) => return Err(ProgramError::NotEnoughAccountKeys),
+            };
+        let farm_id_info = &accounts[0];
+        let authority_info = &accounts[1];
+        check_token_account(pool_second_reward_token_account_info)?;
+
+        // the original layout has no room for the second reward
+        if farm_id_info.data_len() != FarmPoolV2::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut farm_pool = FarmPoolV2::unpack(&farm_id_info.data.borrow())?;
+        if *pool_second_reward_token_account_info.owner != farm_pool.farm_pool.token_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // each reward is paid from its own vault
+        if *pool_second_reward_token_account_info.key
+            == farm_pool.farm_pool.pool_reward_token_account
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        check_vault(
+            pool_second_reward_token_account_info,
+            authority_info.key,
+            second_reward_mint_info.key,
+        )?;
+
+        farm_pool.second_reward_mint_address = *second_reward_mint_info.key;
+        farm_pool.pool_second_reward_token_account = *pool_second_reward_token_account_info.key;
+        farm_pool.second_last_timestamp = start_timestamp;
+        FarmPoolV2::pack(farm_pool, &mut farm_id_info.data.borrow_mut())
+    }
+
+    /// Processes a [Deposit](enum.FarmInstruction.html).
+    pub fn process_deposit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        Self::process_stake(
+            program_id,
+            accounts,
+            StakeChange::Deposit {
+                amount,
+                lockup_seconds: 0,
+            },
+            None,
+        )
+    }
+
+    /// Processes a [DepositWithReferral](enum.FarmInstruction.html).
+    pub fn process_deposit_with_referral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        referral: &Pubkey,
+    ) -> ProgramResult {
+        let (stake_accounts, referral_accounts) =
+            accounts.split_at(accounts.len().min(STAKE_ACCOUNTS_LEN));
+        let referral_account_info_iter = &mut referral_accounts.iter();
+        let referral_reward_token_account_info = next_account_info(referral_account_info_iter)?;
+        check_writable(referral_reward_token_account_info)?;
+        check_token_account(referral_reward_token_account_info)?;
+        let referral_reward_token_account =
+            unpack_token_account(referral_reward_token_account_info)?;
+        if referral_reward_token_account.owner != *referral {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the second reward accounts of a dual-reward farm come last
+        let mut stake_accounts = stake_accounts.to_vec();
+        stake_accounts.extend(referral_account_info_iter.cloned());
+        Self::process_stake(
+            program_id,
+            &stake_accounts,
+            StakeChange::Deposit {
+                amount,
+                lockup_seconds: 0,
+            },
+            Some(referral_reward_token_account_info),
+        )
+    }
+
+    /// Processes a [DepositWithLockup](enum.FarmInstruction.html).
+    pub fn process_deposit_with_lockup(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        lockup_seconds: u64,
+    ) -> ProgramResult {
+        // older user info layouts have no room for the lockup, failing
+        // with InvalidAccountData when packed
+        Self::process_stake(
+            program_id,
+            accounts,
+            StakeChange::Deposit {
+                amount,
+                lockup_seconds,
+            },
+            None,
+        )
+    }
+
+    /// Processes a [Withdraw](enum.FarmInstruction.html).
+    pub fn process_withdraw(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        Self::process_stake(program_id, accounts, StakeChange::Withdraw(amount), None)
+    }
+
+    /// Harvests the pending rewards of a user, then applies `change` to the
+    /// staked LP tokens. A referral reward token account receives its share
+    /// of the harvest fee of the first reward, see [split_referral_fee]
+    fn process_stake<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        change: StakeChange,
+        referral_reward_token_account_info: Option<&'a AccountInfo<'b>>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mut stake = StakeAccounts::load(program_id, account_info_iter)?;
+        stake.reward.referral_reward_token_account_info = referral_reward_token_account_info;
+
+        let farm_version = FarmPoolVersion::unpack(&stake.farm_id_info.data.borrow())?;
+        let total_staked = farm_version
+            .total_staked(unpack_token_account(stake.pool_lp_token_account_info)?.amount);
+        let mut farm = farm_version.into_latest();
+        stake.check_farm(program_id, &farm.farm_pool)?;
+        // the second reward accounts follow those of a dual-reward farm
+        let second_reward = if farm.has_second_reward() {
+            let second_reward = RewardAccounts::load(account_info_iter)?;
+            second_reward.check_reward(&farm.second_reward_pool())?;
+            Some(second_reward)
+        } else {
+            None
+        };
+        let program_data = unpack_program_data(program_id, stake.program_data_info)?;
+        let mut user_info =
+            UserInfoVersion::unpack(&stake.user_info_account_info.data.borrow())?.into_latest();
+        if user_info.wallet != *stake.owner_info.key || user_info.farm_id != *stake.farm_id_info.key
+        {
+            return Err(FarmError::NotAllowed.into());
+        }
+        let clock = Clock::from_account_info(stake.clock_sysvar_info)?;
+        let now = FarmTimestamp::from(clock.unix_timestamp).unix_seconds();
+        let farm_pool = &mut farm.farm_pool;
+        match change {
+            StakeChange::Deposit { amount, .. } => {
+                if !farm_pool.is_allowed {
+                    return Err(FarmError::NotAllowed.into());
+                }
+                // harvesting stays possible once the farm ended
+                if amount > 0 && now < farm_pool.start_timestamp {
+                    return Err(FarmError::FarmNotStarted.into());
+                }
+                if amount > 0 && now >= farm_pool.end_timestamp {
+                    return Err(FarmError::FarmEnded.into());
+                }
+            }
+            StakeChange::Withdraw(amount) => {
+                // the locked LP tokens stay staked, withdrawing more than the
+                // deposit fails with InsufficientFunds below
+                if amount <= user_info.deposit_balance
+                    && amount > user_info.withdrawable_balance(now)
+                {
+                    return Err(FarmError::DepositLocked.into());
+                }
+            }
+        }
+
+        // distribute the rewards accrued until now, the stake being the
+        // same the whole time
+        distribute_rewards(farm_pool, now, total_staked)?;
+        let pending = farm_pool.pending_reward_of(
+            user_info.boosted_share,
+            user_info.reward_debt,
+            now,
+            total_staked,
+        )?;
+        let nonce = farm_pool.nonce;
+        let unpaid = stake.harvest(nonce, &program_data, &stake.reward, pending)?;
+        let mut unpaid_b = 0;
+        if let Some(second_reward) = &second_reward {
+            let mut second_reward_pool = farm.second_reward_pool();
+            distribute_rewards(&mut second_reward_pool, now, total_staked)?;
+            let pending = second_reward_pool.pending_reward_of(
+                user_info.boosted_share,
+                user_info.reward_debt_b,
+                now,
+                total_staked,
+            )?;
+            unpaid_b = stake.harvest(nonce, &program_data, second_reward, pending)?;
+            farm.set_second_reward_pool(&second_reward_pool);
+        }
+
+        match change {
+            StakeChange::Deposit {
+                amount,
+                lockup_seconds,
+            } => {
+                // signed by the depositor, who must own the LP tokens or be
+                // their delegate
+                owner_transfer(
+                    stake.token_program_info,
+                    stake.user_lp_token_account_info,
+                    stake.pool_lp_token_account_info,
+                    stake.owner_info,
+                    &[],
+                    amount,
+                )?;
+                user_info.deposit_balance = user_info
+                    .deposit_balance
+                    .checked_add(amount)
+                    .ok_or(FarmError::MathOverflow)?;
+                // the boost is fixed for the lockup the deposit is made with
+                let share = boosted_share(amount, lockup_seconds, &farm.boost_params())?;
+                user_info.boosted_share = user_info
+                    .boosted_share
+                    .checked_add(share)
+                    .ok_or(FarmError::MathOverflow)?;
+                farm.total_boosted_stake = farm
+                    .total_boosted_stake
+                    .checked_add(share)
+                    .ok_or(FarmError::MathOverflow)?;
+                user_info.lock(now, amount, lockup_seconds)?;
+            }
+            StakeChange::Withdraw(amount) => {
+                if amount > user_info.deposit_balance {
+                    return Err(ProgramError::InsufficientFunds);
+                }
+                let share = user_info.withdrawn_share(amount)?;
+                user_info.deposit_balance -= amount;
+                user_info.boosted_share -= share;
+                farm.total_boosted_stake = farm.total_boosted_stake.saturating_sub(share);
+                authority_transfer(
+                    stake.token_program_info,
+                    stake.farm_id_info,
+                    nonce,
+                    stake.pool_lp_token_account_info,
+                    stake.user_lp_token_account_info,
+                    stake.authority_info,
+                    amount,
+                )?;
+            }
+        }
+        let reward_debt = owed_reward_debt(
+            user_info.boosted_share,
+            farm.farm_pool.reward_per_share_net,
+            unpaid,
+        )?;
+        farm.total_reward_debt = farm
+            .total_reward_debt
+            .saturating_sub(user_info.reward_debt)
+            .checked_add(reward_debt)
+            .ok_or(FarmError::MathOverflow)?;
+        user_info.reward_debt = reward_debt;
+        if farm.has_second_reward() {
+            user_info.reward_debt_b = owed_reward_debt(
+                user_info.boosted_share,
+                farm.second_reward_per_share_net,
+                unpaid_b,
+            )?;
+        }
+
+        FarmPoolVersion::pack_latest(farm, &mut stake.farm_id_info.data.borrow_mut())?;
+        UserInfoVersion::pack_latest(
+            user_info,
+            &mut stake.user_info_account_info.data.borrow_mut(),
+        )
+    }
+
+    /// Processes an [AddReward](enum.FarmInstruction.html).
+    pub fn process_add_reward(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let user_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_mint_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_sysvar_info = next_account_info(account_info_iter)?;
+        let signer_infos = account_info_iter.as_slice();
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_creator_signature(creator_info, signer_infos)?;
+        check_token_account(user_reward_token_account_info)?;
+        check_token_account(pool_reward_token_account_info)?;
+        check_program_data(program_id, program_data_info)?;
+        check_token_program(token_program_info)?;
+        check_clock(clock_sysvar_info)?;
+
+        let mut farm_pool = unpack_farm_pool(farm_id_info)?;
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        if *pool_reward_token_account_info.key != farm_pool.pool_reward_token_account
+            || *pool_lp_token_account_info.key != farm_pool.pool_lp_token_account
+            || *pool_lp_mint_info.key != farm_pool.pool_mint_address
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *token_program_info.key != farm_pool.token_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if amount == 0 {
+            return Err(FarmError::ZeroAmount.into());
+        }
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+        let now = FarmTimestamp::from(clock.unix_timestamp).unix_seconds();
+        if now >= farm_pool.end_timestamp {
+            return Err(FarmError::FarmEnded.into());
+        }
+
+        let total_staked = unpack_total_staked(farm_id_info, pool_lp_token_account_info)?;
+        distribute_rewards(&mut farm_pool, now, total_staked)?;
+        let spread_amount = spread_reward(&mut farm_pool, now, amount)?;
+        owner_transfer(
+            token_program_info,
+            user_reward_token_account_info,
+            pool_reward_token_account_info,
+            creator_info,
+            signer_infos,
+            spread_amount,
+        )?;
+        pack_farm_pool(&farm_pool, farm_id_info);
+        Ok(())
+    }
+
+    /// Processes an [AddSecondReward](enum.FarmInstruction.html).
+    pub fn process_add_second_reward(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let user_second_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_second_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_mint_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_sysvar_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_signer(creator_info)?;
+        check_token_account(user_second_reward_token_account_info)?;
+        check_token_account(pool_second_reward_token_account_info)?;
+        check_program_data(program_id, program_data_info)?;
+        check_token_program(token_program_info)?;
+        check_clock(clock_sysvar_info)?;
+
+        let mut farm = FarmPoolVersion::unpack(&farm_id_info.data.borrow())?.into_latest();
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm.farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm.farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        // only dual-reward farms hold a second reward vault
+        if !farm.has_second_reward()
+            || *pool_second_reward_token_account_info.key != farm.pool_second_reward_token_account
+            || *pool_lp_token_account_info.key != farm.farm_pool.pool_lp_token_account
+            || *pool_lp_mint_info.key != farm.farm_pool.pool_mint_address
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *token_program_info.key != farm.farm_pool.token_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if amount == 0 {
+            return Err(FarmError::ZeroAmount.into());
+        }
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+        let now = FarmTimestamp::from(clock.unix_timestamp).unix_seconds();
+        if now >= farm.farm_pool.end_timestamp {
+            return Err(FarmError::FarmEnded.into());
+        }
+
+        let total_staked = farm.total_boosted_stake;
+        let mut second_reward_pool = farm.second_reward_pool();
+        distribute_rewards(&mut second_reward_pool, now, total_staked)?;
+        let spread_amount = spread_reward(&mut second_reward_pool, now, amount)?;
+        owner_transfer(
+            token_program_info,
+            user_second_reward_token_account_info,
+            pool_second_reward_token_account_info,
+            creator_info,
+            &[],
+            spread_amount,
+        )?;
+        farm.set_second_reward_pool(&second_reward_pool);
+        FarmPoolV2::pack(farm, &mut farm_id_info.data.borrow_mut())
+    }
+
+    /// Processes a [PayFarmFee](enum.FarmInstruction.html).
+    pub fn process_pay_farm_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let user_usdc_token_account_info = next_account_info(account_info_iter)?;
+        let fee_usdc_ata_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let signer_infos = account_info_iter.as_slice();
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_creator_signature(creator_info, signer_infos)?;
+        check_token_account(user_usdc_token_account_info)?;
+        check_token_account(fee_usdc_ata_info)?;
+        check_token_program(token_program_info)?;
+        let program_data = unpack_program_data(program_id, program_data_info)?;
+
+        let mut farm_pool = unpack_farm_pool(farm_id_info)?;
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        if farm_pool.is_allowed {
+            msg!("The farm is already allowed");
+            return Err(FarmError::NotAllowed.into());
+        }
+        if amount == 0 {
+            return Err(FarmError::ZeroAmount.into());
+        }
+        if amount != program_data.farm_fee() {
+            return Err(FarmError::InvalidFarmFee.into());
+        }
+        if user_usdc_token_account_info.owner != token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let fee_account = unpack_token_account(fee_usdc_ata_info)?;
+        let fee_ata = get_associated_token_address_with_program_id(
+            program_data.fee_owner(),
+            &fee_account.mint,
+            token_program_info.key,
+        );
+        if fee_account.owner != *program_data.fee_owner() || fee_ata != *fee_usdc_ata_info.key {
+            return Err(FarmError::WrongFeeAccount.into());
+        }
+
+        owner_transfer(
+            token_program_info,
+            user_usdc_token_account_info,
+            fee_usdc_ata_info,
+            creator_info,
+            signer_infos,
+            amount,
+        )?;
+        farm_pool.is_allowed = true;
+        pack_farm_pool(&farm_pool, farm_id_info);
+        Ok(())
+    }
+}
+
+impl Processor {
+    /// Processes an [EmergencyWithdraw](enum.FarmInstruction.html).
+    pub fn process_emergency_withdraw(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let user_info_account_info = next_account_info(account_info_iter)?;
+        let user_lp_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_signer(owner_info)?;
+        check_program_account(program_id, user_info_account_info)?;
+        check_writable(user_info_account_info)?;
+        check_token_account(user_lp_token_account_info)?;
+        check_token_account(pool_lp_token_account_info)?;
+        check_token_program(token_program_info)?;
+
+        let farm_version = FarmPoolVersion::unpack(&farm_id_info.data.borrow())?;
+        let total_staked =
+            farm_version.total_staked(unpack_token_account(pool_lp_token_account_info)?.amount);
+        let mut farm = farm_version.into_latest();
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm.farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if *pool_lp_token_account_info.key != farm.farm_pool.pool_lp_token_account {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // the lockup does not hold back an emergency withdrawal
+        let mut user_info =
+            UserInfoVersion::unpack(&user_info_account_info.data.borrow())?.into_latest();
+        if user_info.wallet != *owner_info.key || user_info.farm_id != *farm_id_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+
+        // the stake weight leaves the farm once the rewards accrued with it
+        // are distributed, the original layout weighing the LP tokens the
+        // farm holds before the transfer
+        let now = FarmTimestamp::from(Clock::get()?.unix_timestamp).unix_seconds();
+        distribute_rewards(&mut farm.farm_pool, now, total_staked)?;
+        if farm.has_second_reward() {
+            let mut second_reward_pool = farm.second_reward_pool();
+            distribute_rewards(&mut second_reward_pool, now, total_staked)?;
+            farm.set_second_reward_pool(&second_reward_pool);
+        }
+        farm.total_boosted_stake = farm
+            .total_boosted_stake
+            .saturating_sub(user_info.boosted_share);
+        farm.total_reward_debt = farm.total_reward_debt.saturating_sub(user_info.reward_debt);
+
+        authority_transfer(
+            token_program_info,
+            farm_id_info,
+            farm.farm_pool.nonce,
+            pool_lp_token_account_info,
+            user_lp_token_account_info,
+            authority_info,
+            user_info.deposit_balance,
+        )?;
+        FarmPoolVersion::pack_latest(farm, &mut farm_id_info.data.borrow_mut())?;
+
+        // pending rewards of both reward tokens are forfeited
+        user_info.deposit_balance = 0;
+        user_info.boosted_share = 0;
+        user_info.reward_debt = 0;
+        user_info.reward_debt_b = 0;
+        user_info.lockup_end_timestamp = 0;
+        user_info.locked_balance = 0;
+        UserInfoVersion::pack_latest(user_info, &mut user_info_account_info.data.borrow_mut())
+    }
+
+    /// Processes a [CreateUserInfo](enum.FarmInstruction.html).
+    pub fn process_create_user_info(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let user_info_account_info = next_account_info(account_info_iter)?;
+        let wallet_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(user_info_account_info)?;
+        check_signer(wallet_info)?;
+        check_writable(wallet_info)?;
+
+        let (user_info_address, bump) =
+            find_user_info_address(program_id, farm_id_info.key, wallet_info.key);
+        if user_info_address != *user_info_account_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent = Rent::from_account_info(rent_sysvar_info)?;
+        invoke_signed(
+            &system_instruction::create_account(
+                wallet_info.key,
+                user_info_account_info.key,
+                rent.minimum_balance(UserInfoV3::LEN),
+                UserInfoV3::LEN as u64,
+                program_id,
+            ),
+            &[
+                wallet_info.clone(),
+                user_info_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                USER_INFO_SEED,
+                farm_id_info.key.as_ref(),
+                wallet_info.key.as_ref(),
+                &[bump],
+            ]],
+        )?;
+
+        let user_info = UserInfoV3 {
+            wallet: *wallet_info.key,
+            farm_id: *farm_id_info.key,
+            ..UserInfoV3::default()
+        };
+        UserInfoV3::pack(user_info, &mut user_info_account_info.data.borrow_mut())
+    }
+
+    /// Processes a [SetFarmPeriod](enum.FarmInstruction.html).
+    pub fn process_set_farm_period(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let clock_sysvar_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_signer(creator_info)?;
+        check_program_data(program_id, program_data_info)?;
+        check_clock(clock_sysvar_info)?;
+
+        let farm_version = FarmPoolVersion::unpack(&farm_id_info.data.borrow())?;
+        let total_staked =
+            farm_version.total_staked(unpack_token_account(pool_lp_token_account_info)?.amount);
+        let mut farm = farm_version.into_latest();
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm.farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm.farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        if *pool_lp_token_account_info.key != farm.farm_pool.pool_lp_token_account {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        FarmTimestamp::period_from_unix_seconds(start_timestamp, end_timestamp)?;
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+        let now = FarmTimestamp::from(clock.unix_timestamp).unix_seconds();
+        if end_timestamp <= now {
+            msg!("The farm cannot end in the past");
+            return Err(FarmError::InvalidFarmPeriod.into());
+        }
+        if now >= farm.farm_pool.start_timestamp
+            && start_timestamp != farm.farm_pool.start_timestamp
+        {
+            return Err(FarmError::InvalidFarmPeriod.into());
+        }
+
+        // settle the rewards accrued in the current period, which a shorter
+        // one would otherwise drop
+        distribute_rewards(&mut farm.farm_pool, now, total_staked)?;
+        if farm.has_second_reward() {
+            let mut second_reward_pool = farm.second_reward_pool();
+            distribute_rewards(&mut second_reward_pool, now, total_staked)?;
+            farm.set_second_reward_pool(&second_reward_pool);
+        }
+
+        farm.farm_pool.start_timestamp = start_timestamp;
+        farm.farm_pool.end_timestamp = end_timestamp;
+        FarmPoolVersion::pack_latest(farm, &mut farm_id_info.data.borrow_mut())
+    }
+
+    /// Processes a [SetRewardRate](enum.FarmInstruction.html).
+    pub fn process_set_reward_rate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reward_per_second: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let clock_sysvar_info = next_account_info(account_info_iter)?;
+        let pool_reward_token_account_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_signer(creator_info)?;
+        check_program_data(program_id, program_data_info)?;
+        check_clock(clock_sysvar_info)?;
+
+        let farm_version = FarmPoolVersion::unpack(&farm_id_info.data.borrow())?;
+        let mut farm = farm_version.clone().into_latest();
+        let farm_pool = &mut farm.farm_pool;
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        if *pool_lp_token_account_info.key != farm_pool.pool_lp_token_account
+            || *pool_reward_token_account_info.key != farm_pool.pool_reward_token_account
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // settle the rewards accrued so far at the previous rate
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+        let now = FarmTimestamp::from(clock.unix_timestamp).unix_seconds();
+        let total_staked =
+            farm_version.total_staked(unpack_token_account(pool_lp_token_account_info)?.amount);
+        distribute_rewards(farm_pool, now, total_staked)?;
+
+        // the vault keeps the rewards distributed and not harvested yet, the
+        // rest funds the new rate until the end of the farm. The original
+        // layout does not track them, nothing being owed once nothing is
+        // staked
+        let unharvested = match farm_version {
+            FarmPoolVersion::FarmPoolV2(_) => farm.unharvested_reward()?,
+            FarmPoolVersion::FarmPool(_) if total_staked == 0 => 0,
+            FarmPoolVersion::FarmPool(_) => {
+                msg!("The original layout cannot tell the rewards owed to its stakers");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        let farm_pool = &mut farm.farm_pool;
+        let remaining_seconds = farm_pool
+            .end_timestamp
+            .saturating_sub(now.max(farm_pool.start_timestamp));
+        let vault = unpack_token_account(pool_reward_token_account_info)?.amount;
+        let available = vault.saturating_sub(unharvested);
+        if u128::from(reward_per_second) * u128::from(remaining_seconds) > u128::from(available) {
+            return Err(FarmError::InsufficientReward.into());
+        }
+        farm_pool.reward_per_timestamp = u128::from(reward_per_second);
+        pack_farm_pool(farm_pool, farm_id_info);
+        Ok(())
+    }
+
+    /// Processes a [SetBoostParams](enum.FarmInstruction.html).
+    pub fn process_set_boost_params(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        params: &BoostParams,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_signer(creator_info)?;
+        check_program_data(program_id, program_data_info)?;
+        params.validate()?;
+
+        let mut farm_pool = match FarmPoolVersion::unpack(&farm_id_info.data.borrow())? {
+            FarmPoolVersion::FarmPoolV2(farm_pool) => farm_pool,
+            // the original layout has no room for the boost
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm_pool.farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm_pool.farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+
+        farm_pool.multiplier_numerator = params.multiplier_numerator;
+        farm_pool.multiplier_denominator = params.multiplier_denominator;
+        farm_pool.max_lock_seconds = params.max_lock_seconds;
+        FarmPoolV2::pack(farm_pool, &mut farm_id_info.data.borrow_mut())
+    }
+
+    /// Processes a [CloseFarm](enum.FarmInstruction.html).
+    pub fn process_close_farm(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let pool_reward_token_account_info = next_account_info(account_info_iter)?;
+        let creator_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        check_program_account(program_id, farm_id_info)?;
+        check_writable(farm_id_info)?;
+        check_signer(creator_info)?;
+        check_writable(creator_info)?;
+        check_token_account(pool_reward_token_account_info)?;
+        check_token_account(creator_reward_token_account_info)?;
+        check_token_program(token_program_info)?;
+
+        let farm = FarmPoolVersion::unpack(&farm_id_info.data.borrow())?.into_latest();
+        let farm_pool = &farm.farm_pool;
+        check_authority(
+            program_id,
+            farm_id_info.key,
+            farm_pool.nonce,
+            authority_info.key,
+        )?;
+        if farm_pool.owner != *creator_info.key {
+            return Err(FarmError::NotAllowed.into());
+        }
+        if *pool_lp_token_account_info.key != farm_pool.pool_lp_token_account
+            || *pool_reward_token_account_info.key != farm_pool.pool_reward_token_account
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the second reward vault of a dual-reward farm is emptied as well
+        let second_reward_infos = if farm.has_second_reward() {
+            let pool_second_reward_token_account_info = next_account_info(account_info_iter)?;
+            let creator_second_reward_token_account_info = next_account_info(account_info_iter)?;
+            check_token_account(pool_second_reward_token_account_info)?;
+            check_token_account(creator_second_reward_token_account_info)?;
+            if *pool_second_reward_token_account_info.key != farm.pool_second_reward_token_account {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Some((
+                pool_second_reward_token_account_info,
+                creator_second_reward_token_account_info,
+            ))
+        } else {
+            None
+        };
+
+        let now = FarmTimestamp::from(Clock::get()?.unix_timestamp).unix_seconds();
+        if now <= farm_pool.end_timestamp {
+            return Err(FarmError::FarmNotEnded.into());
+        }
+        let staked = unpack_token_account(pool_lp_token_account_info)?.amount;
+        if staked > 0 {
+            return Err(FarmError::FarmNotEmpty.into());
+        }
+
+        let remaining_reward = unpack_token_account(pool_reward_token_account_info)?.amount;
+        authority_transfer(
+            token_program_info,
+            farm_id_info,
+            farm_pool.nonce,
+            pool_reward_token_account_info,
+            creator_reward_token_account_info,
+            authority_info,
+            remaining_reward,
+        )?;
+        if let Some((
+            pool_second_reward_token_account_info,
+            creator_second_reward_token_account_info,
+        )) = second_reward_infos
+        {
+            let remaining_second_reward =
+                unpack_token_account(pool_second_reward_token_account_info)?.amount;
+            authority_transfer(
+                token_program_info,
+                farm_id_info,
+                farm_pool.nonce,
+                pool_second_reward_token_account_info,
+                creator_second_reward_token_account_info,
+                authority_info,
+                remaining_second_reward,
+            )?;
+        }
+
+        // the farm account is reclaimed once emptied of lamports and data
+        let creator_lamports = creator_info
+            .lamports()
+            .checked_add(farm_id_info.lamports())
+            .ok_or(FarmError::MathOverflow)?;
+        **creator_info.lamports.borrow_mut() = creator_lamports;
+        **farm_id_info.lamports.borrow_mut() = 0;
+        farm_id_info.data.borrow_mut().fill(0);
+        Ok(())
+    }
+}
+
+/// Number of accounts of a Deposit or Withdraw
+const STAKE_ACCOUNTS_LEN: usize = 13;
+
+/// Change of the staked LP tokens of a user, after the harvest
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StakeChange {
+    /// Stakes more LP tokens locked for some seconds, harvesting only when
+    /// zero
+    Deposit { amount: u64, lockup_seconds: u64 },
+    /// Unstakes LP tokens, harvesting only when zero
+    Withdraw(u64),
+}
+
+/// Accounts shared by Deposit and Withdraw, in order
+struct StakeAccounts<'a, 'b> {
+    farm_id_info: &'a AccountInfo<'b>,
+    authority_info: &'a AccountInfo<'b>,
+    owner_info: &'a AccountInfo<'b>,
+    user_info_account_info: &'a AccountInfo<'b>,
+    user_lp_token_account_info: &'a AccountInfo<'b>,
+    pool_lp_token_account_info: &'a AccountInfo<'b>,
+    reward: RewardAccounts<'a, 'b>,
+    pool_lp_mint_info: &'a AccountInfo<'b>,
+    program_data_info: &'a AccountInfo<'b>,
+    token_program_info: &'a AccountInfo<'b>,
+    clock_sysvar_info: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> StakeAccounts<'a, 'b> {
+    /// Takes the accounts of a Deposit or Withdraw, checking their flags and
+    /// owners
+    fn load(
+        program_id: &Pubkey,
+        account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+    ) -> Result<Self, ProgramError> {
+        let farm_id_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let user_info_account_info = next_account_info(account_info_iter)?;
+        let user_lp_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_token_account_info = next_account_info(account_info_iter)?;
+        let user_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_reward_token_account_info = next_account_info(account_info_iter)?;
+        let pool_lp_mint_info = next_account_info(account_info_iter)?;
+        let fee_reward_ata_info = next_account_info(account_info_iter)?;
+        let stake = Self {
+            farm_id_info,
+            authority_info,
+            owner_info,
+            user_info_account_info,
+            user_lp_token_account_info,
+            pool_lp_token_account_info,
+            reward: RewardAccounts {
+                user_reward_token_account_info,
+                pool_reward_token_account_info,
+                fee_reward_ata_info,
+                referral_reward_token_account_info: None,
+            },
+            pool_lp_mint_info,
+            program_data_info: next_account_info(account_info_iter)?,
+            token_program_info: next_account_info(account_info_iter)?,
+            clock_sysvar_info: next_account_info(account_info_iter)?,
+        };
+
+        check_program_account(program_id, stake.farm_id_info)?;
+        check_writable(stake.farm_id_info)?;
+        check_signer(stake.owner_info)?;
+        check_program_account(program_id, stake.user_info_account_info)?;
+        check_writable(stake.user_info_account_info)?;
+        check_token_account(stake.user_lp_token_account_info)?;
+        check_token_account(stake.pool_lp_token_account_info)?;
+        stake.reward.check_token_accounts()?;
+        check_program_data(program_id, stake.program_data_info)?;
+        check_token_program(stake.token_program_info)?;
+        check_clock(stake.clock_sysvar_info)?;
+        Ok(stake)
+    }
+
+    /// Checks the accounts against the state of the farm
+    fn check_farm(&self, program_id: &Pubkey, farm_pool: &FarmPool) -> ProgramResult {
+        check_authority(
+            program_id,
+            self.farm_id_info.key,
+            farm_pool.nonce,
+            self.authority_info.key,
+        )?;
+        if *self.pool_lp_token_account_info.key != farm_pool.pool_lp_token_account
+            || *self.pool_lp_mint_info.key != farm_pool.pool_mint_address
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *self.token_program_info.key != farm_pool.token_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        self.reward.check_reward(farm_pool)
+    }
+
+    /// Pays `pending` rewards out of the vault of `reward` to the user, less
+    /// the harvest fee paid to the fee reward account. A vault holding less
+    /// pays what it holds, and the unpaid rest is returned to stay owed, see
+    /// [owed_reward_debt]
+    fn harvest(
+        &self,
+        nonce: u8,
+        program_data: &FarmProgramData,
+        reward: &RewardAccounts<'a, 'b>,
+        pending: u64,
+    ) -> Result<u64, ProgramError> {
+        if pending == 0 {
+            return Ok(0);
+        }
+        let vault = unpack_token_account(reward.pool_reward_token_account_info)?.amount;
+        let paid = pending.min(vault);
+        if paid < pending {
+            msg!("Reward vault short, {} rewards stay owed", pending - paid);
+        }
+        let (fee, net) = calculate_harvest_fee(
+            paid,
+            program_data.harvest_fee_numerator,
+            program_data.harvest_fee_denominator,
+        )?;
+        let (referral_fee, fee) = match reward.referral_reward_token_account_info {
+            Some(_) => split_referral_fee(fee),
+            None => (0, fee),
+        };
+        authority_transfer(
+            self.token_program_info,
+            self.farm_id_info,
+            nonce,
+            reward.pool_reward_token_account_info,
+            reward.user_reward_token_account_info,
+            self.authority_info,
+            net,
+        )?;
+        authority_transfer(
+            self.token_program_info,
+            self.farm_id_info,
+            nonce,
+            reward.pool_reward_token_account_info,
+            reward.fee_reward_ata_info,
+            self.authority_info,
+            fee,
+        )?;
+        if let Some(referral_reward_token_account_info) = reward.referral_reward_token_account_info
+        {
+            authority_transfer(
+                self.token_program_info,
+                self.farm_id_info,
+                nonce,
+                reward.pool_reward_token_account_info,
+                referral_reward_token_account_info,
+                self.authority_info,
+                referral_fee,
+            )?;
+        }
+        emit_harvest_log(net, self.farm_id_info.key);
+        Ok(pending - paid)
+    }
+}
+
+/// Token accounts a reward is harvested with, those of the first reward
+/// among the accounts of a Deposit or Withdraw, and those of the second
+/// reward following them for dual-reward farms
+struct RewardAccounts<'a, 'b> {
+    user_reward_token_account_info: &'a AccountInfo<'b>,
+    pool_reward_token_account_info: &'a AccountInfo<'b>,
+    fee_reward_ata_info: &'a AccountInfo<'b>,
+    /// Referral of a DepositWithReferral, sharing the harvest fee
+    referral_reward_token_account_info: Option<&'a AccountInfo<'b>>,
+}
+
+impl<'a, 'b> RewardAccounts<'a, 'b> {
+    /// Takes the user, pool and fee token accounts of a reward, in order
+    fn load(account_info_iter: &mut Iter<'a, AccountInfo<'b>>) -> Result<Self, ProgramError> {
+        let reward = Self {
+            user_reward_token_account_info: next_account_info(account_info_iter)?,
+            pool_reward_token_account_info: next_account_info(account_info_iter)?,
+            fee_reward_ata_info: next_account_info(account_info_iter)?,
+            referral_reward_token_account_info: None,
+        };
+        reward.check_token_accounts()?;
+        Ok(reward)
+    }
+
+    fn check_token_accounts(&self) -> ProgramResult {
+        check_token_account(self.user_reward_token_account_info)?;
+        check_token_account(self.pool_reward_token_account_info)?;
+        check_token_account(self.fee_reward_ata_info)
+    }
+
+    /// Checks the accounts against the reward distributed by `farm_pool`,
+    /// see [FarmPoolV2::second_reward_pool] for the second reward
+    fn check_reward(&self, farm_pool: &FarmPool) -> ProgramResult {
+        if *self.pool_reward_token_account_info.key != farm_pool.pool_reward_token_account {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let fee_reward_account = unpack_token_account(self.fee_reward_ata_info)?;
+        if fee_reward_account.owner != farm_pool.fee_owner
+            || fee_reward_account.mint != farm_pool.reward_mint_address
+        {
+            return Err(FarmError::WrongFeeAccount.into());
+        }
+        Ok(())
+    }
+}
+
+/// Distributes the rewards accrued by `farm_pool` until `now` among the
+/// `total_staked` LP tokens
+fn distribute_rewards(farm_pool: &mut FarmPool, now: u64, total_staked: u64) -> ProgramResult {
+    farm_pool.reward_per_share_net = farm_pool.accrued_reward_per_share(now, total_staked)?;
+    farm_pool.last_timestamp = farm_pool.last_timestamp.max(now);
+    Ok(())
+}
+
+/// Spreads `amount` added rewards over the rest of the farm, from `now` or
+/// its start, returning the part of it to transfer to the vault: the
+/// rounding remainder stays with the creator rather than in the vault,
+/// where no rate would distribute it. Fails with `RewardBelowRate` when
+/// `amount` would not raise the rate. The rewards accrued so far must be
+/// distributed first, see [distribute_rewards]
+fn spread_reward(farm_pool: &mut FarmPool, now: u64, amount: u64) -> Result<u64, ProgramError> {
+    let RewardRate {
+        reward_per_second,
+        remainder,
+    } = reward_rate_for(
+        amount,
+        now.max(farm_pool.start_timestamp),
+        farm_pool.end_timestamp,
+    )?;
+    if reward_per_second == 0 {
+        return Err(FarmError::RewardBelowRate.into());
+    }
+    farm_pool.reward_per_timestamp = farm_pool
+        .reward_per_timestamp
+        .checked_add(u128::from(reward_per_second))
+        .ok_or(FarmError::MathOverflow)?;
+    Ok(amount - remainder)
+}
+
+/// Reward debt of a stake of `share` at `reward_per_share_net`, i.e. the
+/// rewards already accounted for it
+fn reward_debt(share: u64, reward_per_share_net: u128) -> Result<u128, ProgramError> {
+    u128::from(share)
+        .checked_mul(reward_per_share_net)
+        .ok_or_else(|| FarmError::MathOverflow.into())
+}
+
+/// Reward debt of `share` keeping `unpaid` rewards pending, those a short
+/// vault could not pay. The share left after a withdrawal carries at most
+/// its own accrued rewards, the rest being forfeited as by EmergencyWithdraw
+fn owed_reward_debt(
+    share: u64,
+    reward_per_share_net: u128,
+    unpaid: u64,
+) -> Result<u128, ProgramError> {
+    let unpaid = u128::from(unpaid)
+        .checked_mul(REWARD_MULTIPLIER)
+        .ok_or(FarmError::MathOverflow)?;
+    Ok(reward_debt(share, reward_per_share_net)?.saturating_sub(unpaid))
+}
+
+/// Unpacks the farm pool state of a farm account in any layout, every layout
+/// starting with the original one
+fn unpack_farm_pool(account_info: &AccountInfo) -> Result<FarmPool, ProgramError> {
+    Ok(FarmPoolVersion::unpack(&account_info.data.borrow())?
+        .into_latest()
+        .farm_pool)
+}
+
+/// Stake weight the rewards of a farm are shared by, see
+/// [FarmPoolVersion::total_staked]
+fn unpack_total_staked(
+    farm_id_info: &AccountInfo,
+    pool_lp_token_account_info: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    let farm = FarmPoolVersion::unpack(&farm_id_info.data.borrow())?;
+    Ok(farm.total_staked(unpack_token_account(pool_lp_token_account_info)?.amount))
+}
+
+/// Packs the farm pool state at the start of a farm account, keeping the
+/// fields of newer layouts
+fn pack_farm_pool(farm_pool: &FarmPool, account_info: &AccountInfo) {
+    farm_pool.pack_into_slice(&mut account_info.data.borrow_mut());
+}
+
+/// Unpacks the program data, which must be set, see [check_program_data]
+fn unpack_program_data(
+    program_id: &Pubkey,
+    program_data_info: &AccountInfo,
+) -> Result<FarmProgramData, ProgramError> {
+    check_program_data(program_id, program_data_info)?;
+    FarmProgramData::unpack(&program_data_info.data.borrow())
+}
+
+/// Creates the program data account at its address, paid by `payer_info`.
+/// Lamports sent to the address beforehand are kept, topped up to the rent
+/// exemption, so they cannot prevent the creation
+fn create_program_data_account<'a>(
+    program_id: &Pubkey,
+    program_data_info: &AccountInfo<'a>,
+    payer_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    rent: &Rent,
+    bump: u8,
+) -> ProgramResult {
+    let seeds: &[&[u8]] = &[PROGRAM_DATA_SEED, &[bump]];
+    let lamports = rent.minimum_balance(FarmProgramData::LEN);
+    let accounts = [
+        payer_info.clone(),
+        program_data_info.clone(),
+        system_program_info.clone(),
+    ];
+    if program_data_info.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer_info.key,
+                program_data_info.key,
+                lamports,
+                FarmProgramData::LEN as u64,
+                program_id,
+            ),
+            &accounts,
+            &[seeds],
+        );
+    }
+    let top_up = lamports.saturating_sub(program_data_info.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, program_data_info.key, top_up),
+            &accounts,
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(program_data_info.key, FarmProgramData::LEN as u64),
+        &accounts,
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(program_data_info.key, program_id),
+        &accounts,
+        &[seeds],
+    )
+}
+
+/// The signer is the upgrade authority of the program, stored in the
+/// `ProgramData` account of the upgradeable loader holding its executable.
+/// A program without upgrade authority cannot create its program data
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    executable_data_info: &AccountInfo,
+    authority_info: &AccountInfo,
+) -> ProgramResult {
+    if *executable_data_info.key != find_executable_data_address(program_id)
+        || *executable_data_info.owner != bpf_loader_upgradeable::id()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    // bincode layout of `UpgradeableLoaderState::ProgramData`: the variant
+    // as a u32, the slot of the deployment as a u64, then the optional
+    // upgrade authority
+    let data = executable_data_info.data.borrow();
+    match data.get(..UpgradeableLoaderState::size_of_programdata_metadata()) {
+        Some([3, 0, 0, 0, _, _, _, _, _, _, _, _, 1, authority @ ..])
+            if authority == authority_info.key.as_ref() =>
+        {
+            Ok(())
+        }
+        _ => {
+            msg!("Only the upgrade authority of the program creates its program data");
+            Err(FarmError::NotAllowed.into())
+        }
+    }
+}
+
+/// The program data account is owned by the program, at the address given
+/// by [find_program_data_address]
+fn check_program_data(program_id: &Pubkey, program_data_info: &AccountInfo) -> ProgramResult {
+    check_program_account(program_id, program_data_info)?;
+    if *program_data_info.key != find_program_data_address(program_id).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+/// The super owner stored in the program data signs
+fn check_super_owner(
+    program_data: &FarmProgramData,
+    super_owner_info: &AccountInfo,
+) -> ProgramResult {
+    check_signer(super_owner_info)?;
+    if program_data.super_owner() != super_owner_info.key {
+        return Err(FarmError::NotAllowed.into());
+    }
+    Ok(())
+}
+
+fn check_signer(account_info: &AccountInfo) -> ProgramResult {
+    if !account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// The creator signs, or is an spl-token multisig with at least `m` of its
+/// signers among the signing `signer_infos`, as spl-token checks owners
+fn check_creator_signature(
+    creator_info: &AccountInfo,
+    signer_infos: &[AccountInfo],
+) -> ProgramResult {
+    if creator_info.is_signer {
+        return Ok(());
+    }
+    if !is_token_program(creator_info.owner) || creator_info.data_len() != Multisig::LEN {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let multisig = Multisig::unpack(&creator_info.data.borrow())?;
+    let mut matched = [false; MAX_SIGNERS];
+    let mut num_signers = 0;
+    for signer_info in signer_infos.iter().filter(|info| info.is_signer) {
+        for (position, key) in multisig.signers[..multisig.n as usize].iter().enumerate() {
+            if key == signer_info.key && !matched[position] {
+                matched[position] = true;
+                num_signers += 1;
+            }
+        }
+    }
+    if num_signers < multisig.m {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+fn check_writable(account_info: &AccountInfo) -> ProgramResult {
+    if !account_info.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Farm, user info and program data accounts are owned by the farm program
+fn check_program_account(program_id: &Pubkey, account_info: &AccountInfo) -> ProgramResult {
+    if account_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// spl-token or Token-2022
+fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || spl_token_2022::check_id(program_id)
+}
+
+/// Token accounts moved by the program are writable and owned by spl-token
+/// or Token-2022
+fn check_token_account(account_info: &AccountInfo) -> ProgramResult {
+    if !is_token_program(account_info.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(account_info)
+}
+
+fn check_token_program(account_info: &AccountInfo) -> ProgramResult {
+    if !is_token_program(account_info.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Base state of a token account. Token-2022 appends its extensions after
+/// the spl-token layout, so only the leading bytes are unpacked
+fn unpack_token_account(account_info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    let data = account_info.data.borrow();
+    let base = data
+        .get(..TokenAccount::LEN)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    TokenAccount::unpack(base)
+}
+
+/// spl-token `Transfer` for the given token program, the instruction layout
+/// being shared with Token-2022. Not `TransferChecked`, the instructions
+/// taking no mint accounts: Token-2022 rejects the transfers of mints with a
+/// transfer fee or a transfer hook, which farms cannot use
+fn token_transfer(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    signers: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = spl_token::instruction::transfer(
+        &spl_token::id(),
+        source,
+        destination,
+        authority,
+        signers,
+        amount,
+    )?;
+    instruction.program_id = *token_program_id;
+    Ok(instruction)
+}
+
+/// Transfers `amount` tokens out of a farm vault, signed by the farm
+/// authority
+fn authority_transfer<'a>(
+    token_program_info: &AccountInfo<'a>,
+    farm_id_info: &AccountInfo<'a>,
+    nonce: u8,
+    source_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+    let instruction = token_transfer(
+        token_program_info.key,
+        source_info.key,
+        destination_info.key,
+        authority_info.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &instruction,
+        &[
+            source_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[&[farm_id_info.key.as_ref(), &[nonce]]],
+    )
+}
+
+/// Transfers `amount` tokens on behalf of their owner, a signer or an
+/// spl-token multisig approved by the signers among `signer_infos`, see
+/// [check_creator_signature]
+fn owner_transfer<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    signer_infos: &[AccountInfo<'a>],
+    amount: u64,
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+    let signer_infos: Vec<AccountInfo<'a>> = if owner_info.is_signer {
+        Vec::new()
+    } else {
+        signer_infos
+            .iter()
+            .filter(|info| info.is_signer)
+            .cloned()
+            .collect()
+    };
+    let signers: Vec<&Pubkey> = signer_infos.iter().map(|info| info.key).collect();
+    let instruction = token_transfer(
+        token_program_info.key,
+        source_info.key,
+        destination_info.key,
+        owner_info.key,
+        &signers,
+        amount,
+    )?;
+    let mut account_infos = vec![
+        source_info.clone(),
+        destination_info.clone(),
+        owner_info.clone(),
+    ];
+    account_infos.extend(signer_infos.iter().cloned());
+    account_infos.push(token_program_info.clone());
+    invoke(&instruction, &account_infos)
+}
+
+/// Farm vaults belong to the farm authority, nobody else may move or close
+/// them
+fn check_vault(account_info: &AccountInfo, authority: &Pubkey, mint: &Pubkey) -> ProgramResult {
+    let vault = unpack_token_account(account_info)?;
+    if vault.owner != *authority
+        || vault.mint != *mint
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn check_clock(account_info: &AccountInfo) -> ProgramResult {
+    if !sysvar::clock::check_id(account_info.key) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+fn check_harvest_fee(numerator: u64, denominator: u64) -> ProgramResult {
+    if denominator == 0 || numerator > denominator {
+        return Err(FarmError::InvalidHarvestFee.into());
+    }
+    Ok(())
+}
+
+/// The farm authority is the program address derived from the farm and nonce
+fn check_authority(
+    program_id: &Pubkey,
+    farm_id: &Pubkey,
+    nonce: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = farm_authority(program_id, farm_id, nonce)?;
+    if expected != *authority {
+        return Err(FarmError::WrongAuthority.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{
+        accept_super_owner, initialize_farm, propose_super_owner, set_allowed_creator,
+        set_farm_fee, set_fee_owner, set_harvest_fee,
+    };
+    use solana_program::instruction::Instruction;
+
+    /// Account of a test, lent to the processor as an [AccountInfo]
+    struct TestAccount {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, data: Vec<u8>, owner: Pubkey) -> Self {
+            Self {
+                key,
+                lamports: 1_000_000,
+                data,
+                owner,
+            }
+        }
+
+        /// Program data owned by `program_id`, at the address derived from it
+        fn program_data(program_id: &Pubkey, program_data: FarmProgramData) -> Self {
+            let mut data = vec![0; FarmProgramData::LEN];
+            FarmProgramData::pack(program_data, &mut data).unwrap();
+            let (address, _) = find_program_data_address(program_id);
+            Self::new(address, data, *program_id)
+        }
+
+        fn wallet(key: Pubkey) -> Self {
+            Self::new(key, vec![], solana_program::system_program::id())
+        }
+    }
+
+    /// Runs `instruction` with `accounts` in the order of its account metas,
+    /// with their signer and writable flags
+    fn process(instruction: &Instruction, accounts: &mut [&mut TestAccount]) -> ProgramResult {
+        let metas = &instruction.accounts;
+        assert_eq!(metas.len(), accounts.len());
+        let account_infos: Vec<AccountInfo> = accounts
+            .iter_mut()
+            .zip(metas.iter())
+            .map(|(account, meta)| {
+                assert_eq!(account.key, meta.pubkey);
+                AccountInfo::new(
+                    &account.key,
+                    meta.is_signer,
+                    meta.is_writable,
+                    &mut account.lamports,
+                    &mut account.data,
+                    &account.owner,
+                    false,
+                    0,
+                )
+            })
+            .collect();
+        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+    }
+
+    fn initialized_program_data(super_owner: &Pubkey) -> FarmProgramData {
+        FarmProgramData {
+            is_initialized: true,
+            super_owner: *super_owner,
+            fee_owner: Pubkey::new_unique(),
+            allowed_creator: Pubkey::new_unique(),
+            amm_program_id: Pubkey::new_unique(),
+            farm_fee: 100,
+            harvest_fee_numerator: 1,
+            harvest_fee_denominator: 100,
+            pending_super_owner: Pubkey::default(),
+        }
+    }
+
+    #[test]
+    fn test_super_owner_rotation() {
+        let program_id = Pubkey::new_unique();
+        let mut super_owner = TestAccount::wallet(Pubkey::new_unique());
+        let mut new_super_owner = TestAccount::wallet(Pubkey::new_unique());
+        let mut program_data =
+            TestAccount::program_data(&program_id, initialized_program_data(&super_owner.key));
+
+        // only the proposed super owner accepts
+        let accept =
+            accept_super_owner(&program_data.key, &new_super_owner.key, &program_id).unwrap();
+        assert_eq!(
+            process(&accept, &mut [&mut program_data, &mut new_super_owner]),
+            Err(FarmError::NotAllowed.into())
+        );
+
+        // only the super owner proposes
+        let propose = propose_super_owner(
+            &program_data.key,
+            &new_super_owner.key,
+            &new_super_owner.key,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            process(&propose, &mut [&mut program_data, &mut new_super_owner]),
+            Err(FarmError::NotAllowed.into())
+        );
+
+        let propose = propose_super_owner(
+            &program_data.key,
+            &super_owner.key,
+            &new_super_owner.key,
+            &program_id,
+        )
+        .unwrap();
+        process(&propose, &mut [&mut program_data, &mut super_owner]).unwrap();
+        let state = FarmProgramData::unpack(&program_data.data).unwrap();
+        assert_eq!(state.super_owner, super_owner.key);
+        assert_eq!(state.pending_super_owner, new_super_owner.key);
+
+        let accept_by_old =
+            accept_super_owner(&program_data.key, &super_owner.key, &program_id).unwrap();
+        assert_eq!(
+            process(&accept_by_old, &mut [&mut program_data, &mut super_owner]),
+            Err(FarmError::NotAllowed.into())
+        );
+
+        process(&accept, &mut [&mut program_data, &mut new_super_owner]).unwrap();
+        let state = FarmProgramData::unpack(&program_data.data).unwrap();
+        assert_eq!(state.super_owner, new_super_owner.key);
+        assert_eq!(state.pending_super_owner, Pubkey::default());
+
+        // the rotation is consumed, and the former super owner lost its rights
+        assert_eq!(
+            process(&accept, &mut [&mut program_data, &mut new_super_owner]),
+            Err(FarmError::NotAllowed.into())
+        );
+        assert_eq!(
+            process(&propose, &mut [&mut program_data, &mut super_owner]),
+            Err(FarmError::NotAllowed.into())
+        );
+    }
+
+    #[test]
+    fn test_super_owner_rotation_requires_program_data() {
+        let program_id = Pubkey::new_unique();
+        let mut super_owner = TestAccount::wallet(Pubkey::new_unique());
+        let new_super_owner = Pubkey::new_unique();
+
+        // never set
+        let mut program_data = TestAccount::new(
+            find_program_data_address(&program_id).0,
+            vec![0; FarmProgramData::LEN],
+            program_id,
+        );
+        let propose = propose_super_owner(
+            &program_data.key,
+            &super_owner.key,
+            &new_super_owner,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            process(&propose, &mut [&mut program_data, &mut super_owner]),
+            Err(ProgramError::UninitializedAccount)
+        );
+
+        // owned by another program
+        let mut program_data = TestAccount::program_data(
+            &Pubkey::new_unique(),
+            initialized_program_data(&super_owner.key),
+        );
+        let propose = propose_super_owner(
+            &program_data.key,
+            &super_owner.key,
+            &new_super_owner,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            process(&propose, &mut [&mut program_data, &mut super_owner]),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        // owned by the program at another address
+        let mut program_data =
+            TestAccount::program_data(&program_id, initialized_program_data(&super_owner.key));
+        program_data.key = Pubkey::new_unique();
+        let propose = propose_super_owner(
+            &program_data.key,
+            &super_owner.key,
+            &new_super_owner,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            process(&propose, &mut [&mut program_data, &mut super_owner]),
+            Err(ProgramError::InvalidSeeds)
+        );
+
+        // the default key cannot be proposed
+        let mut program_data =
+            TestAccount::program_data(&program_id, initialized_program_data(&super_owner.key));
+        let propose = propose_super_owner(
+            &program_data.key,
+            &super_owner.key,
+            &Pubkey::default(),
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            process(&propose, &mut [&mut program_data, &mut super_owner]),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_update_program_data() {
+        let program_id = Pubkey::new_unique();
+        let mut super_owner = TestAccount::wallet(Pubkey::new_unique());
+        let initial = initialized_program_data(&super_owner.key);
+        let mut program_data = TestAccount::program_data(&program_id, initial.clone());
+
+        let fee_owner = Pubkey::new_unique();
+        let instruction =
+            set_fee_owner(&program_data.key, &super_owner.key, &fee_owner, &program_id).unwrap();
+        process(&instruction, &mut [&mut program_data, &mut super_owner]).unwrap();
+        let mut expected = FarmProgramData {
+            fee_owner,
+            ..initial
+        };
+        assert_eq!(
+            FarmProgramData::unpack(&program_data.data).unwrap(),
+            expected
+        );
+
+        let allowed_creator = Pubkey::new_unique();
+        let instruction = set_allowed_creator(
+            &program_data.key,
+            &super_owner.key,
+            &allowed_creator,
+            &program_id,
+        )
+        .unwrap();
+        process(&instruction, &mut [&mut program_data, &mut super_owner]).unwrap();
+        expected.allowed_creator = allowed_creator;
+        assert_eq!(
+            FarmProgramData::unpack(&program_data.data).unwrap(),
+            expected
+        );
+
+        let instruction =
+            set_farm_fee(&program_data.key, &super_owner.key, 5_000, &program_id).unwrap();
+        process(&instruction, &mut [&mut program_data, &mut super_owner]).unwrap();
+        expected.farm_fee = 5_000;
+        assert_eq!(
+            FarmProgramData::unpack(&program_data.data).unwrap(),
+            expected
+        );
+
+        let instruction =
+            set_harvest_fee(&program_data.key, &super_owner.key, 3, 1_000, &program_id).unwrap();
+        process(&instruction, &mut [&mut program_data, &mut super_owner]).unwrap();
+        expected.harvest_fee_numerator = 3;
+        expected.harvest_fee_denominator = 1_000;
+        assert_eq!(
+            FarmProgramData::unpack(&program_data.data).unwrap(),
+            expected
+        );
+
+        // invalid harvest fees leave the program data untouched
+        for (numerator, denominator) in [(1, 0), (1_001, 1_000)] {
+            let instruction = set_harvest_fee(
+                &program_data.key,
+                &super_owner.key,
+                numerator,
+                denominator,
+                &program_id,
+            )
+            .unwrap();
+            assert_eq!(
+                process(&instruction, &mut [&mut program_data, &mut super_owner]),
+                Err(FarmError::InvalidHarvestFee.into())
+            );
+            assert_eq!(
+                FarmProgramData::unpack(&program_data.data).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_program_data_requires_super_owner() {
+        let program_id = Pubkey::new_unique();
+        let super_owner = Pubkey::new_unique();
+        let mut other = TestAccount::wallet(Pubkey::new_unique());
+        let initial = initialized_program_data(&super_owner);
+        let mut program_data = TestAccount::program_data(&program_id, initial.clone());
+
+        let instructions = [
+            set_fee_owner(&program_data.key, &other.key, &other.key, &program_id).unwrap(),
+            set_allowed_creator(&program_data.key, &other.key, &other.key, &program_id).unwrap(),
+            set_farm_fee(&program_data.key, &other.key, 0, &program_id).unwrap(),
+            set_harvest_fee(&program_data.key, &other.key, 0, 1, &program_id).unwrap(),
+        ];
+        for instruction in instructions.iter() {
+            assert_eq!(
+                process(instruction, &mut [&mut program_data, &mut other]),
+                Err(FarmError::NotAllowed.into())
+            );
+        }
+        assert_eq!(
+            FarmProgramData::unpack(&program_data.data).unwrap(),
+            initial
+        );
+
+        // the stored super owner must also sign
+        let mut instruction =
+            set_farm_fee(&program_data.key, &super_owner, 0, &program_id).unwrap();
+        instruction.accounts[1].is_signer = false;
+        let mut super_owner = TestAccount::wallet(super_owner);
+        assert_eq!(
+            process(&instruction, &mut [&mut program_data, &mut super_owner]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_initialize_farm_period() {
+        let program_id = Pubkey::new_unique();
+        let mut farm = TestAccount::new(Pubkey::new_unique(), vec![0; FarmPool::LEN], program_id);
+        let (authority, nonce) = crate::state::find_farm_authority(&program_id, &farm.key);
+        let mut authority = TestAccount::wallet(authority);
+        let mut creator = TestAccount::wallet(Pubkey::new_unique());
+        let mut pool_lp = TestAccount::new(Pubkey::new_unique(), vec![], spl_token::id());
+        let mut pool_reward = TestAccount::new(Pubkey::new_unique(), vec![], spl_token::id());
+        let mut pool_mint = TestAccount::wallet(Pubkey::new_unique());
+        let mut reward_mint = TestAccount::wallet(Pubkey::new_unique());
+        let mut amm_id = TestAccount::wallet(Pubkey::new_unique());
+        let mut program_data =
+            TestAccount::program_data(&program_id, initialized_program_data(&creator.key));
+        let mut rent = TestAccount::new(sysvar::rent::id(), vec![], sysvar::id());
+
+        let mut instruction = initialize_farm(
+            &farm.key,
+            &authority.key,
+            &creator.key,
+            &pool_lp.key,
+            &pool_reward.key,
+            &pool_mint.key,
+            &reward_mint.key,
+            &amm_id.key,
+            &program_data.key,
+            false,
+            nonce,
+            1,
+            2,
+            &program_id,
+        )
+        .unwrap();
+        // the builder rejects the periods the program rejects
+        for (start_timestamp, end_timestamp) in [(0, 10), (10, 10), (10, 5), (u64::MAX, 10)] {
+            instruction.data = FarmInstruction::InitializeFarm {
+                nonce,
+                start_timestamp,
+                end_timestamp,
+            }
+            .pack()
+            .unwrap();
+            assert_eq!(
+                process(
+                    &instruction,
+                    &mut [
+                        &mut farm,
+                        &mut authority,
+                        &mut creator,
+                        &mut pool_lp,
+                        &mut pool_reward,
+                        &mut pool_mint,
+                        &mut reward_mint,
+                        &mut amm_id,
+                        &mut program_data,
+                        &mut rent,
+                    ]
+                ),
+                Err(FarmError::InvalidFarmPeriod.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_owed_reward_debt() {
+        let reward_per_share_net = 10 * REWARD_MULTIPLIER;
+        assert_eq!(
+            owed_reward_debt(1_000, reward_per_share_net, 0),
+            Ok(10_000 * REWARD_MULTIPLIER)
+        );
+        // the unpaid rewards stay pending
+        assert_eq!(
+            owed_reward_debt(1_000, reward_per_share_net, 6_000),
+            Ok(4_000 * REWARD_MULTIPLIER)
+        );
+        // no more than the remaining share accrued
+        assert_eq!(owed_reward_debt(100, reward_per_share_net, 6_000), Ok(0));
+        assert_eq!(owed_reward_debt(0, reward_per_share_net, 6_000), Ok(0));
+    }
+}
@@ -0,0 +1,1914 @@
+//! State transition types
+
+use crate::{
+    error::FarmError,
+    math::{calculate_harvest_fee, BoostParams},
+};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    bpf_loader_upgradeable,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+/// Scale of `reward_per_share_net` and of the user reward debts, keeping
+/// precision when the reward per staked LP token is below one
+pub const REWARD_MULTIPLIER: u128 = 1_000_000_000;
+
+/// Unix timestamp in seconds, signed as the `unix_timestamp` of the clock
+/// sysvar, where the farm state and instructions store `u64` seconds.
+/// Converting through it keeps timestamps before the epoch from wrapping
+/// around to far future ones.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FarmTimestamp(pub i64);
+
+impl FarmTimestamp {
+    /// Seconds as stored in the farm state, timestamps before the epoch
+    /// being clamped to zero, i.e. before any farm starts
+    pub fn unix_seconds(self) -> u64 {
+        u64::try_from(self.0).unwrap_or(0)
+    }
+
+    /// Checks a farm period, which must satisfy `end > start > 0`
+    pub fn validate_period(start: Self, end: Self) -> Result<(), FarmError> {
+        if start.0 <= 0 || end <= start {
+            return Err(FarmError::InvalidFarmPeriod);
+        }
+        Ok(())
+    }
+
+    /// Converts and checks the `u64` period of the farm instructions, failing
+    /// with `InvalidFarmPeriod` when a timestamp does not fit an `i64` or the
+    /// period is invalid, see [FarmTimestamp::validate_period]
+    pub fn period_from_unix_seconds(start: u64, end: u64) -> Result<(Self, Self), FarmError> {
+        let start = Self::try_from(start)?;
+        let end = Self::try_from(end)?;
+        Self::validate_period(start, end)?;
+        Ok((start, end))
+    }
+}
+
+impl From<i64> for FarmTimestamp {
+    fn from(timestamp: i64) -> Self {
+        Self(timestamp)
+    }
+}
+
+impl From<FarmTimestamp> for i64 {
+    fn from(timestamp: FarmTimestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl TryFrom<u64> for FarmTimestamp {
+    type Error = FarmError;
+
+    fn try_from(seconds: u64) -> Result<Self, Self::Error> {
+        i64::try_from(seconds)
+            .map(Self)
+            .map_err(|_| FarmError::InvalidFarmPeriod)
+    }
+}
+
+/// Farm pool state, one account per farm
+///
+/// | offset | size | field                     |
+/// |--------|------|---------------------------|
+/// | 0      | 1    | is_allowed                |
+/// | 1      | 1    | nonce                     |
+/// | 2      | 32   | pool_lp_token_account     |
+/// | 34     | 32   | pool_reward_token_account |
+/// | 66     | 32   | pool_mint_address         |
+/// | 98     | 32   | reward_mint_address       |
+/// | 130    | 32   | token_program_id          |
+/// | 162    | 32   | owner                     |
+/// | 194    | 32   | fee_owner                 |
+/// | 226    | 16   | reward_per_share_net      |
+/// | 242    | 8    | last_timestamp            |
+/// | 250    | 16   | reward_per_timestamp      |
+/// | 266    | 8    | start_timestamp           |
+/// | 274    | 8    | end_timestamp             |
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FarmPool {
+    /// Whether the farm fee was paid (or waived), allowing stake/unstake/harvest
+    pub is_allowed: bool,
+
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// farm program id, and farm account pubkey.  This program address has
+    /// authority over the farm's LP token and reward token accounts.
+    pub nonce: u8,
+
+    /// LP token account of this farm, holding staked LP tokens
+    pub pool_lp_token_account: Pubkey,
+
+    /// Reward token account of this farm, holding rewards for the farmers
+    pub pool_reward_token_account: Pubkey,
+
+    /// LP token mint address
+    pub pool_mint_address: Pubkey,
+
+    /// Reward token mint address
+    pub reward_mint_address: Pubkey,
+
+    /// Program ID of the tokens being staked and rewarded
+    pub token_program_id: Pubkey,
+
+    /// Creator/Manager of this farm
+    pub owner: Pubkey,
+
+    /// Owner receiving the harvest fees
+    pub fee_owner: Pubkey,
+
+    /// Accumulated reward per staked LP token, scaled by the reward multiplier
+    pub reward_per_share_net: u128,
+
+    /// Last time the rewards were distributed
+    pub last_timestamp: u64,
+
+    /// Rewards distributed per second across all stakers
+    pub reward_per_timestamp: u128,
+
+    /// Time the farm starts distributing rewards
+    pub start_timestamp: u64,
+
+    /// Time the farm stops distributing rewards
+    pub end_timestamp: u64,
+}
+
+impl FarmPool {
+    /// Rewards `user` could harvest at `current_timestamp`, `total_staked`
+    /// being the balance of the pool LP token account. Rewards accrue from
+    /// the last distribution, or the start of the farm, until
+    /// `current_timestamp` or the end of the farm, whichever comes first.
+    pub fn pending_reward(
+        &self,
+        user: &UserInfo,
+        current_timestamp: u64,
+        total_staked: u64,
+    ) -> Result<u64, FarmError> {
+        self.pending_reward_of(
+            user.deposit_balance,
+            user.reward_debt,
+            current_timestamp,
+            total_staked,
+        )
+    }
+
+    /// Same as [FarmPool::pending_reward] at a clock timestamp, e.g.
+    /// `FarmTimestamp::from(clock.unix_timestamp)`
+    pub fn pending_reward_at(
+        &self,
+        user: &UserInfo,
+        now: FarmTimestamp,
+        total_staked: u64,
+    ) -> Result<u64, FarmError> {
+        self.pending_reward(user, now.unix_seconds(), total_staked)
+    }
+
+    /// Rewards of a stake of `share` with `reward_debt` already accounted
+    pub(crate) fn pending_reward_of(
+        &self,
+        share: u64,
+        reward_debt: u128,
+        current_timestamp: u64,
+        total_staked: u64,
+    ) -> Result<u64, FarmError> {
+        let reward_per_share_net =
+            self.accrued_reward_per_share(current_timestamp, total_staked)?;
+        let pending = u128::from(share)
+            .checked_mul(reward_per_share_net)
+            .and_then(|reward| reward.checked_sub(reward_debt))
+            .ok_or(FarmError::MathOverflow)?
+            / REWARD_MULTIPLIER;
+        u64::try_from(pending).map_err(|_| FarmError::MathOverflow)
+    }
+
+    /// `reward_per_share_net` once the rewards accrued until
+    /// `current_timestamp` are distributed, see [FarmPool::pending_reward]
+    pub fn accrued_reward_per_share(
+        &self,
+        current_timestamp: u64,
+        total_staked: u64,
+    ) -> Result<u128, FarmError> {
+        let accrual_start = self.last_timestamp.max(self.start_timestamp);
+        let accrual_end = current_timestamp.min(self.end_timestamp);
+        if accrual_end <= accrual_start || total_staked == 0 {
+            return Ok(self.reward_per_share_net);
+        }
+        let accrued_per_share = self
+            .reward_per_timestamp
+            .checked_mul(u128::from(accrual_end - accrual_start))
+            .and_then(|reward| reward.checked_mul(REWARD_MULTIPLIER))
+            .ok_or(FarmError::MathOverflow)?
+            / u128::from(total_staked);
+        self.reward_per_share_net
+            .checked_add(accrued_per_share)
+            .ok_or(FarmError::MathOverflow)
+    }
+}
+
+impl Sealed for FarmPool {}
+impl IsInitialized for FarmPool {
+    fn is_initialized(&self) -> bool {
+        self.owner != Pubkey::default()
+    }
+}
+
+impl Pack for FarmPool {
+    const LEN: usize = 282;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, FarmPool::LEN];
+        let (
+            is_allowed,
+            nonce,
+            pool_lp_token_account,
+            pool_reward_token_account,
+            pool_mint_address,
+            reward_mint_address,
+            token_program_id,
+            owner,
+            fee_owner,
+            reward_per_share_net,
+            last_timestamp,
+            reward_per_timestamp,
+            start_timestamp,
+            end_timestamp,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 16, 8, 16, 8, 8];
+        is_allowed[0] = self.is_allowed as u8;
+        nonce[0] = self.nonce;
+        pool_lp_token_account.copy_from_slice(self.pool_lp_token_account.as_ref());
+        pool_reward_token_account.copy_from_slice(self.pool_reward_token_account.as_ref());
+        pool_mint_address.copy_from_slice(self.pool_mint_address.as_ref());
+        reward_mint_address.copy_from_slice(self.reward_mint_address.as_ref());
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        fee_owner.copy_from_slice(self.fee_owner.as_ref());
+        *reward_per_share_net = self.reward_per_share_net.to_le_bytes();
+        *last_timestamp = self.last_timestamp.to_le_bytes();
+        *reward_per_timestamp = self.reward_per_timestamp.to_le_bytes();
+        *start_timestamp = self.start_timestamp.to_le_bytes();
+        *end_timestamp = self.end_timestamp.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [FarmPool](struct.FarmPool.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, FarmPool::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_allowed,
+            nonce,
+            pool_lp_token_account,
+            pool_reward_token_account,
+            pool_mint_address,
+            reward_mint_address,
+            token_program_id,
+            owner,
+            fee_owner,
+            reward_per_share_net,
+            last_timestamp,
+            reward_per_timestamp,
+            start_timestamp,
+            end_timestamp,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 16, 8, 16, 8, 8];
+        Ok(Self {
+            is_allowed: match is_allowed {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            pool_lp_token_account: Pubkey::new_from_array(*pool_lp_token_account),
+            pool_reward_token_account: Pubkey::new_from_array(*pool_reward_token_account),
+            pool_mint_address: Pubkey::new_from_array(*pool_mint_address),
+            reward_mint_address: Pubkey::new_from_array(*reward_mint_address),
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            owner: Pubkey::new_from_array(*owner),
+            fee_owner: Pubkey::new_from_array(*fee_owner),
+            reward_per_share_net: u128::from_le_bytes(*reward_per_share_net),
+            last_timestamp: u64::from_le_bytes(*last_timestamp),
+            reward_per_timestamp: u128::from_le_bytes(*reward_per_timestamp),
+            start_timestamp: u64::from_le_bytes(*start_timestamp),
+            end_timestamp: u64::from_le_bytes(*end_timestamp),
+        })
+    }
+}
+
+/// Rewards `farm` lacks to pay the stakers until its end, zero when
+/// `reward_vault_balance` covers them. `total_staked` is the balance of the
+/// pool LP token account and `now` the current unix timestamp.
+///
+/// The rewards owed are those accruing from the last distribution, or from
+/// `now` when nothing is staked since an empty farm accrues nothing, until
+/// `end_timestamp`. Rewards distributed before `last_timestamp` and not yet
+/// harvested are not known from the farm account, so the shortfall is a
+/// lower bound.
+#[cfg(feature = "offchain")]
+pub fn farm_reward_shortfall(
+    farm: &FarmPool,
+    reward_vault_balance: u64,
+    total_staked: u64,
+    now: u64,
+) -> Result<u64, FarmError> {
+    let last_timestamp = if total_staked == 0 {
+        farm.last_timestamp.max(now)
+    } else {
+        farm.last_timestamp
+    };
+    let accrual_start = last_timestamp.max(farm.start_timestamp);
+    if farm.end_timestamp <= accrual_start {
+        return Ok(0);
+    }
+    let owed = farm
+        .reward_per_timestamp
+        .checked_mul(u128::from(farm.end_timestamp - accrual_start))
+        .ok_or(FarmError::MathOverflow)?;
+    u64::try_from(owed.saturating_sub(u128::from(reward_vault_balance)))
+        .map_err(|_| FarmError::MathOverflow)
+}
+
+/// Checks that the reward vault of `farm` covers the rewards owed until its
+/// end, so that frontends can warn users before they stake. Fails with
+/// `InsufficientReward` otherwise, the amount missing being given by
+/// [farm_reward_shortfall]: program errors are bare codes and cannot carry
+/// it.
+#[cfg(feature = "offchain")]
+pub fn check_farm_solvency(
+    farm: &FarmPool,
+    reward_vault_balance: u64,
+    total_staked: u64,
+    now: u64,
+) -> Result<(), FarmError> {
+    if farm_reward_shortfall(farm, reward_vault_balance, total_staked, now)? > 0 {
+        return Err(FarmError::InsufficientReward);
+    }
+    Ok(())
+}
+
+/// Farm pool state of a farm boosting locked deposits or distributing a
+/// second reward, the original layout followed by the boost parameters set
+/// by `SetBoostParams`, the second reward set by `InitializeFarmV2`, the
+/// total stake weight the rewards are shared by and the sum of the reward
+/// debts of the stakers
+///
+/// | offset | size | field                            |
+/// |--------|------|----------------------------------|
+/// | 0      | 282  | farm_pool                        |
+/// | 282    | 8    | multiplier_numerator             |
+/// | 290    | 8    | multiplier_denominator           |
+/// | 298    | 8    | max_lock_seconds                 |
+/// | 306    | 32   | second_reward_mint_address       |
+/// | 338    | 32   | pool_second_reward_token_account |
+/// | 370    | 16   | second_reward_per_share_net      |
+/// | 386    | 8    | second_last_timestamp            |
+/// | 394    | 16   | second_reward_per_timestamp      |
+/// | 410    | 8    | total_boosted_stake              |
+/// | 418    | 16   | total_reward_debt                |
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FarmPoolV2 {
+    /// Farm pool state in the original layout
+    pub farm_pool: FarmPool,
+
+    /// Numerator of the multiplier of a deposit locked for `max_lock_seconds`
+    pub multiplier_numerator: u64,
+
+    /// Denominator of the multiplier
+    pub multiplier_denominator: u64,
+
+    /// Lock duration earning the full multiplier
+    pub max_lock_seconds: u64,
+
+    /// Second reward token mint address, `Pubkey::default()` for farms with
+    /// a single reward
+    pub second_reward_mint_address: Pubkey,
+
+    /// Second reward token account of this farm, holding second rewards for
+    /// the farmers
+    pub pool_second_reward_token_account: Pubkey,
+
+    /// Accumulated second reward per staked LP token, scaled by the reward
+    /// multiplier
+    pub second_reward_per_share_net: u128,
+
+    /// Last time the second rewards were distributed
+    pub second_last_timestamp: u64,
+
+    /// Second rewards distributed per second across all stakers
+    pub second_reward_per_timestamp: u128,
+
+    /// Sum of the `boosted_share` of the stakers, which both rewards are
+    /// shared by. Not stored in the original layout, whose rewards are
+    /// shared by the staked LP tokens
+    pub total_boosted_stake: u64,
+
+    /// Sum of the `reward_debt` of the stakers, telling the rewards
+    /// distributed and not harvested yet, see
+    /// [FarmPoolV2::unharvested_reward]. Not stored in the original layout
+    pub total_reward_debt: u128,
+}
+
+impl FarmPoolV2 {
+    /// Boost of the stake of locked deposits
+    pub fn boost_params(&self) -> BoostParams {
+        BoostParams {
+            multiplier_numerator: self.multiplier_numerator,
+            multiplier_denominator: self.multiplier_denominator,
+            max_lock_seconds: self.max_lock_seconds,
+        }
+    }
+
+    /// Whether the farm distributes a second reward
+    pub fn has_second_reward(&self) -> bool {
+        self.second_reward_mint_address != Pubkey::default()
+    }
+
+    /// The farm as distributing its second reward, sharing the stake and the
+    /// period of the first one, so that the reward math of [FarmPool]
+    /// applies to the second reward
+    pub fn second_reward_pool(&self) -> FarmPool {
+        FarmPool {
+            pool_reward_token_account: self.pool_second_reward_token_account,
+            reward_mint_address: self.second_reward_mint_address,
+            reward_per_share_net: self.second_reward_per_share_net,
+            last_timestamp: self.second_last_timestamp,
+            reward_per_timestamp: self.second_reward_per_timestamp,
+            ..self.farm_pool.clone()
+        }
+    }
+
+    /// Keeps the second reward distribution of `second_reward_pool`, as
+    /// returned by [FarmPoolV2::second_reward_pool] then updated
+    pub fn set_second_reward_pool(&mut self, second_reward_pool: &FarmPool) {
+        self.second_reward_per_share_net = second_reward_pool.reward_per_share_net;
+        self.second_last_timestamp = second_reward_pool.last_timestamp;
+        self.second_reward_per_timestamp = second_reward_pool.reward_per_timestamp;
+    }
+
+    /// Rewards distributed to the stakers until the last distribution and
+    /// not harvested yet, which the reward vault must keep for them:
+    /// `reward_per_share_net * total_boosted_stake / REWARD_MULTIPLIER` less
+    /// the sum of their reward debts, rounded up
+    pub fn unharvested_reward(&self) -> Result<u64, FarmError> {
+        let accrued = self
+            .farm_pool
+            .reward_per_share_net
+            .checked_mul(u128::from(self.total_boosted_stake))
+            .ok_or(FarmError::MathOverflow)?;
+        let unharvested = accrued.saturating_sub(self.total_reward_debt);
+        let unharvested = unharvested / REWARD_MULTIPLIER
+            + u128::from(unharvested % REWARD_MULTIPLIER != 0);
+        u64::try_from(unharvested).map_err(|_| FarmError::MathOverflow)
+    }
+
+    /// Rewards `user` could harvest at `current_timestamp`, as
+    /// [FarmPool::pending_reward] with the deposit weighted by its
+    /// `boosted_share`. `total_staked` is then given by
+    /// [FarmPoolVersion::total_staked].
+    pub fn pending_reward(
+        &self,
+        user: &UserInfoV3,
+        current_timestamp: u64,
+        total_staked: u64,
+    ) -> Result<u64, FarmError> {
+        self.farm_pool.pending_reward_of(
+            user.boosted_share,
+            user.reward_debt,
+            current_timestamp,
+            total_staked,
+        )
+    }
+
+    /// Same as [FarmPoolV2::pending_reward] at a clock timestamp
+    pub fn pending_reward_at(
+        &self,
+        user: &UserInfoV3,
+        now: FarmTimestamp,
+        total_staked: u64,
+    ) -> Result<u64, FarmError> {
+        self.pending_reward(user, now.unix_seconds(), total_staked)
+    }
+
+    /// Second rewards `user` could harvest at `current_timestamp`, as
+    /// [FarmPoolV2::pending_reward], zero for farms with a single reward
+    pub fn pending_second_reward(
+        &self,
+        user: &UserInfoV3,
+        current_timestamp: u64,
+        total_staked: u64,
+    ) -> Result<u64, FarmError> {
+        if !self.has_second_reward() {
+            return Ok(0);
+        }
+        self.second_reward_pool().pending_reward_of(
+            user.boosted_share,
+            user.reward_debt_b,
+            current_timestamp,
+            total_staked,
+        )
+    }
+}
+
+impl Sealed for FarmPoolV2 {}
+impl IsInitialized for FarmPoolV2 {
+    fn is_initialized(&self) -> bool {
+        self.farm_pool.is_initialized()
+    }
+}
+
+impl Pack for FarmPoolV2 {
+    const LEN: usize = 434;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, FarmPoolV2::LEN];
+        let (
+            farm_pool,
+            multiplier_numerator,
+            multiplier_denominator,
+            max_lock_seconds,
+            second_reward_mint_address,
+            pool_second_reward_token_account,
+            second_reward_per_share_net,
+            second_last_timestamp,
+            second_reward_per_timestamp,
+            total_boosted_stake,
+            total_reward_debt,
+        ) = mut_array_refs![output, FarmPool::LEN, 8, 8, 8, 32, 32, 16, 8, 16, 8, 16];
+        self.farm_pool.pack_into_slice(farm_pool);
+        *multiplier_numerator = self.multiplier_numerator.to_le_bytes();
+        *multiplier_denominator = self.multiplier_denominator.to_le_bytes();
+        *max_lock_seconds = self.max_lock_seconds.to_le_bytes();
+        second_reward_mint_address.copy_from_slice(self.second_reward_mint_address.as_ref());
+        pool_second_reward_token_account
+            .copy_from_slice(self.pool_second_reward_token_account.as_ref());
+        *second_reward_per_share_net = self.second_reward_per_share_net.to_le_bytes();
+        *second_last_timestamp = self.second_last_timestamp.to_le_bytes();
+        *second_reward_per_timestamp = self.second_reward_per_timestamp.to_le_bytes();
+        *total_boosted_stake = self.total_boosted_stake.to_le_bytes();
+        *total_reward_debt = self.total_reward_debt.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [FarmPoolV2](struct.FarmPoolV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, FarmPoolV2::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            farm_pool,
+            multiplier_numerator,
+            multiplier_denominator,
+            max_lock_seconds,
+            second_reward_mint_address,
+            pool_second_reward_token_account,
+            second_reward_per_share_net,
+            second_last_timestamp,
+            second_reward_per_timestamp,
+            total_boosted_stake,
+            total_reward_debt,
+        ) = array_refs![input, FarmPool::LEN, 8, 8, 8, 32, 32, 16, 8, 16, 8, 16];
+        Ok(Self {
+            farm_pool: FarmPool::unpack_from_slice(farm_pool)?,
+            multiplier_numerator: u64::from_le_bytes(*multiplier_numerator),
+            multiplier_denominator: u64::from_le_bytes(*multiplier_denominator),
+            max_lock_seconds: u64::from_le_bytes(*max_lock_seconds),
+            second_reward_mint_address: Pubkey::new_from_array(*second_reward_mint_address),
+            pool_second_reward_token_account: Pubkey::new_from_array(
+                *pool_second_reward_token_account,
+            ),
+            second_reward_per_share_net: u128::from_le_bytes(*second_reward_per_share_net),
+            second_last_timestamp: u64::from_le_bytes(*second_last_timestamp),
+            second_reward_per_timestamp: u128::from_le_bytes(*second_reward_per_timestamp),
+            total_boosted_stake: u64::from_le_bytes(*total_boosted_stake),
+            total_reward_debt: u128::from_le_bytes(*total_reward_debt),
+        })
+    }
+}
+
+impl From<FarmPool> for FarmPoolV2 {
+    /// Farm without boost
+    fn from(farm_pool: FarmPool) -> Self {
+        Self {
+            farm_pool,
+            ..Self::default()
+        }
+    }
+}
+
+/// All versions of the farm pool state, told apart by account size
+#[derive(Clone, Debug, PartialEq)]
+pub enum FarmPoolVersion {
+    /// Farms without boost
+    FarmPool(FarmPool),
+    /// Farms boosting locked deposits or distributing a second reward
+    FarmPoolV2(FarmPoolV2),
+}
+
+impl FarmPoolVersion {
+    /// Unpack a farm account based on its size
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        match input.len() {
+            FarmPool::LEN => Ok(Self::FarmPool(FarmPool::unpack(input)?)),
+            FarmPoolV2::LEN => Ok(Self::FarmPoolV2(FarmPoolV2::unpack(input)?)),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Farm pool state in the latest layout
+    pub fn into_latest(self) -> FarmPoolV2 {
+        match self {
+            Self::FarmPool(farm_pool) => farm_pool.into(),
+            Self::FarmPoolV2(farm_pool) => farm_pool,
+        }
+    }
+
+    /// Stake weight the rewards of the farm are shared by, given the LP
+    /// tokens it holds: its total boosted stake, or these LP tokens in the
+    /// original layout, which has no boost
+    pub fn total_staked(&self, pool_lp_balance: u64) -> u64 {
+        match self {
+            Self::FarmPool(_) => pool_lp_balance,
+            Self::FarmPoolV2(farm_pool) => farm_pool.total_boosted_stake,
+        }
+    }
+
+    /// Pack farm pool state in the layout given by the size of `dst`,
+    /// failing with `InvalidAccountData` when the layout has no room for a
+    /// field that is set, e.g. a second reward in the original layout. The
+    /// total boosted stake and the total reward debt are dropped from the
+    /// original layout, see [FarmPoolVersion::total_staked]
+    pub fn pack_latest(farm_pool: FarmPoolV2, dst: &mut [u8]) -> Result<(), ProgramError> {
+        match dst.len() {
+            FarmPool::LEN => {
+                let stored = FarmPoolV2 {
+                    total_boosted_stake: 0,
+                    total_reward_debt: 0,
+                    ..farm_pool.clone()
+                };
+                if FarmPoolV2::from(farm_pool.farm_pool.clone()) != stored {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                farm_pool.farm_pool.pack_into_slice(dst);
+            }
+            FarmPoolV2::LEN => farm_pool.pack_into_slice(dst),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+        Ok(())
+    }
+}
+
+/// Global configuration of the farm program, set by `SetProgramData` and
+/// the single field updates. A single account per program, at the address
+/// given by [find_program_data_address]: the program rejects any other
+/// account, even owned by it and holding this layout
+///
+/// | offset | size | field                   |
+/// |--------|------|-------------------------|
+/// | 0      | 1    | is_initialized          |
+/// | 1      | 32   | super_owner             |
+/// | 33     | 32   | fee_owner               |
+/// | 65     | 32   | allowed_creator         |
+/// | 97     | 32   | amm_program_id          |
+/// | 129    | 8    | farm_fee                |
+/// | 137    | 8    | harvest_fee_numerator   |
+/// | 145    | 8    | harvest_fee_denominator |
+/// | 153    | 32   | pending_super_owner     |
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FarmProgramData {
+    /// Whether the program data was set
+    pub is_initialized: bool,
+
+    /// Owner allowed to update the program data
+    pub super_owner: Pubkey,
+
+    /// Owner receiving the farm and harvest fees
+    pub fee_owner: Pubkey,
+
+    /// Creator allowed to create farms without paying the farm fee
+    pub allowed_creator: Pubkey,
+
+    /// Program id of the amm whose LP tokens are farmed
+    pub amm_program_id: Pubkey,
+
+    /// Fee paid to allow a new farm
+    pub farm_fee: u64,
+
+    /// Numerator of the fee taken on harvested rewards
+    pub harvest_fee_numerator: u64,
+
+    /// Denominator of the fee taken on harvested rewards
+    pub harvest_fee_denominator: u64,
+
+    /// Super owner proposed by `ProposeSuperOwner`, `Pubkey::default()` when
+    /// no rotation is pending
+    pub pending_super_owner: Pubkey,
+}
+
+impl FarmProgramData {
+    /// Address of the program data account of the farm program
+    /// `program_id`, see [find_program_data_address]
+    pub fn address(program_id: &Pubkey) -> Pubkey {
+        find_program_data_address(program_id).0
+    }
+
+    /// Owner allowed to update the program data
+    pub fn super_owner(&self) -> &Pubkey {
+        &self.super_owner
+    }
+
+    /// Owner receiving the farm and harvest fees
+    pub fn fee_owner(&self) -> &Pubkey {
+        &self.fee_owner
+    }
+
+    /// Creator allowed to create farms without paying the farm fee
+    pub fn allowed_creator(&self) -> &Pubkey {
+        &self.allowed_creator
+    }
+
+    /// Program id of the amm whose LP tokens are farmed
+    pub fn amm_program_id(&self) -> &Pubkey {
+        &self.amm_program_id
+    }
+
+    /// Fee paid to allow a new farm
+    pub fn farm_fee(&self) -> u64 {
+        self.farm_fee
+    }
+
+    /// Fee taken on harvesting `amount` of rewards, rounded down, see
+    /// [calculate_harvest_fee]
+    pub fn harvest_fee(&self, amount: u64) -> Result<u64, FarmError> {
+        calculate_harvest_fee(
+            amount,
+            self.harvest_fee_numerator,
+            self.harvest_fee_denominator,
+        )
+        .map(|(fee, _net)| fee)
+    }
+}
+
+impl Sealed for FarmProgramData {}
+impl IsInitialized for FarmProgramData {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for FarmProgramData {
+    const LEN: usize = 185;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, FarmProgramData::LEN];
+        let (
+            is_initialized,
+            super_owner,
+            fee_owner,
+            allowed_creator,
+            amm_program_id,
+            farm_fee,
+            harvest_fee_numerator,
+            harvest_fee_denominator,
+            pending_super_owner,
+        ) = mut_array_refs![output, 1, 32, 32, 32, 32, 8, 8, 8, 32];
+        is_initialized[0] = self.is_initialized as u8;
+        super_owner.copy_from_slice(self.super_owner.as_ref());
+        fee_owner.copy_from_slice(self.fee_owner.as_ref());
+        allowed_creator.copy_from_slice(self.allowed_creator.as_ref());
+        amm_program_id.copy_from_slice(self.amm_program_id.as_ref());
+        *farm_fee = self.farm_fee.to_le_bytes();
+        *harvest_fee_numerator = self.harvest_fee_numerator.to_le_bytes();
+        *harvest_fee_denominator = self.harvest_fee_denominator.to_le_bytes();
+        pending_super_owner.copy_from_slice(self.pending_super_owner.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [FarmProgramData](struct.FarmProgramData.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, FarmProgramData::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            super_owner,
+            fee_owner,
+            allowed_creator,
+            amm_program_id,
+            farm_fee,
+            harvest_fee_numerator,
+            harvest_fee_denominator,
+            pending_super_owner,
+        ) = array_refs![input, 1, 32, 32, 32, 32, 8, 8, 8, 32];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            super_owner: Pubkey::new_from_array(*super_owner),
+            fee_owner: Pubkey::new_from_array(*fee_owner),
+            allowed_creator: Pubkey::new_from_array(*allowed_creator),
+            amm_program_id: Pubkey::new_from_array(*amm_program_id),
+            farm_fee: u64::from_le_bytes(*farm_fee),
+            harvest_fee_numerator: u64::from_le_bytes(*harvest_fee_numerator),
+            harvest_fee_denominator: u64::from_le_bytes(*harvest_fee_denominator),
+            pending_super_owner: Pubkey::new_from_array(*pending_super_owner),
+        })
+    }
+}
+
+/// Authority of a farm, owning its token accounts, derived from
+/// `[farm_id, nonce]` with the nonce stored in the FarmPool
+pub fn farm_authority(
+    program_id: &Pubkey,
+    farm_id: &Pubkey,
+    nonce: u8,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[farm_id.as_ref(), &[nonce]], program_id)
+        .map_err(|_| FarmError::WrongAuthority.into())
+}
+
+/// Authority of a farm with the canonical nonce, the one to pass when
+/// initializing a new farm
+pub fn find_farm_authority(program_id: &Pubkey, farm_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[farm_id.as_ref()], program_id)
+}
+
+/// Seed of the farm program data account address
+pub const PROGRAM_DATA_SEED: &[u8] = b"program_data";
+
+/// Address of the farm program data account, derived from
+/// `[PROGRAM_DATA_SEED]`, with its bump seed. The program rejects any other
+/// program data account
+pub fn find_program_data_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROGRAM_DATA_SEED], program_id)
+}
+
+/// Address of the `ProgramData` account of the upgradeable loader, holding
+/// the executable of the farm program and its upgrade authority, which
+/// creates the farm program data
+pub fn find_executable_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// Seed prefix of the User Farming Information Account addresses
+pub const USER_INFO_SEED: &[u8] = b"user_info";
+
+/// Address of the User Farming Information Account of `wallet` in the farm,
+/// derived from `[USER_INFO_SEED, farm_id, wallet]`, with its bump seed
+pub fn find_user_info_address(
+    program_id: &Pubkey,
+    farm_id: &Pubkey,
+    wallet: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USER_INFO_SEED, farm_id.as_ref(), wallet.as_ref()],
+        program_id,
+    )
+}
+
+/// User farming information, one account per user and farm
+///
+/// | offset | size | field           |
+/// |--------|------|-----------------|
+/// | 0      | 32   | wallet          |
+/// | 32     | 32   | farm_id         |
+/// | 64     | 8    | deposit_balance |
+/// | 72     | 16   | reward_debt     |
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserInfo {
+    /// Wallet owning the staked LP tokens
+    pub wallet: Pubkey,
+
+    /// Farm the LP tokens are staked in
+    pub farm_id: Pubkey,
+
+    /// Amount of LP tokens staked
+    pub deposit_balance: u64,
+
+    /// Rewards already accounted for the staked amount, scaled by the reward
+    /// multiplier
+    pub reward_debt: u128,
+}
+
+impl Sealed for UserInfo {}
+impl IsInitialized for UserInfo {
+    fn is_initialized(&self) -> bool {
+        self.wallet != Pubkey::default()
+    }
+}
+
+impl Pack for UserInfo {
+    const LEN: usize = 88;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, UserInfo::LEN];
+        let (wallet, farm_id, deposit_balance, reward_debt) =
+            mut_array_refs![output, 32, 32, 8, 16];
+        wallet.copy_from_slice(self.wallet.as_ref());
+        farm_id.copy_from_slice(self.farm_id.as_ref());
+        *deposit_balance = self.deposit_balance.to_le_bytes();
+        *reward_debt = self.reward_debt.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [UserInfo](struct.UserInfo.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, UserInfo::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (wallet, farm_id, deposit_balance, reward_debt) = array_refs![input, 32, 32, 8, 16];
+        Ok(Self {
+            wallet: Pubkey::new_from_array(*wallet),
+            farm_id: Pubkey::new_from_array(*farm_id),
+            deposit_balance: u64::from_le_bytes(*deposit_balance),
+            reward_debt: u128::from_le_bytes(*reward_debt),
+        })
+    }
+}
+
+/// User farming information of a dual-reward farm, the original layout
+/// followed by the debt of the second reward
+///
+/// | offset | size | field           |
+/// |--------|------|-----------------|
+/// | 0      | 32   | wallet          |
+/// | 32     | 32   | farm_id         |
+/// | 64     | 8    | deposit_balance |
+/// | 72     | 16   | reward_debt     |
+/// | 88     | 16   | reward_debt_b   |
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserInfoV2 {
+    /// Wallet owning the staked LP tokens
+    pub wallet: Pubkey,
+
+    /// Farm the LP tokens are staked in
+    pub farm_id: Pubkey,
+
+    /// Amount of LP tokens staked
+    pub deposit_balance: u64,
+
+    /// Rewards already accounted for the staked amount, scaled by the reward
+    /// multiplier
+    pub reward_debt: u128,
+
+    /// Second rewards already accounted for the staked amount, scaled by the
+    /// reward multiplier
+    pub reward_debt_b: u128,
+}
+
+impl Sealed for UserInfoV2 {}
+impl IsInitialized for UserInfoV2 {
+    fn is_initialized(&self) -> bool {
+        self.wallet != Pubkey::default()
+    }
+}
+
+impl Pack for UserInfoV2 {
+    const LEN: usize = 104;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, UserInfoV2::LEN];
+        let (wallet, farm_id, deposit_balance, reward_debt, reward_debt_b) =
+            mut_array_refs![output, 32, 32, 8, 16, 16];
+        wallet.copy_from_slice(self.wallet.as_ref());
+        farm_id.copy_from_slice(self.farm_id.as_ref());
+        *deposit_balance = self.deposit_balance.to_le_bytes();
+        *reward_debt = self.reward_debt.to_le_bytes();
+        *reward_debt_b = self.reward_debt_b.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [UserInfoV2](struct.UserInfoV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, UserInfoV2::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (wallet, farm_id, deposit_balance, reward_debt, reward_debt_b) =
+            array_refs![input, 32, 32, 8, 16, 16];
+        Ok(Self {
+            wallet: Pubkey::new_from_array(*wallet),
+            farm_id: Pubkey::new_from_array(*farm_id),
+            deposit_balance: u64::from_le_bytes(*deposit_balance),
+            reward_debt: u128::from_le_bytes(*reward_debt),
+            reward_debt_b: u128::from_le_bytes(*reward_debt_b),
+        })
+    }
+}
+
+impl From<UserInfo> for UserInfoV2 {
+    /// Single-reward user information, without second reward debt
+    fn from(user_info: UserInfo) -> Self {
+        Self {
+            wallet: user_info.wallet,
+            farm_id: user_info.farm_id,
+            deposit_balance: user_info.deposit_balance,
+            reward_debt: user_info.reward_debt,
+            reward_debt_b: 0,
+        }
+    }
+}
+
+/// User farming information with a lockup, the dual-reward layout followed
+/// by the end of the lockup set by `DepositWithLockup`, the amount it locks
+/// and the stake weight of the deposit
+///
+/// | offset | size | field                |
+/// |--------|------|----------------------|
+/// | 0      | 32   | wallet               |
+/// | 32     | 32   | farm_id              |
+/// | 64     | 8    | deposit_balance      |
+/// | 72     | 16   | reward_debt          |
+/// | 88     | 16   | reward_debt_b        |
+/// | 104    | 8    | lockup_end_timestamp |
+/// | 112    | 8    | locked_balance       |
+/// | 120    | 8    | boosted_share        |
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserInfoV3 {
+    /// Wallet owning the staked LP tokens
+    pub wallet: Pubkey,
+
+    /// Farm the LP tokens are staked in
+    pub farm_id: Pubkey,
+
+    /// Amount of LP tokens staked
+    pub deposit_balance: u64,
+
+    /// Rewards already accounted for the staked amount, scaled by the reward
+    /// multiplier
+    pub reward_debt: u128,
+
+    /// Second rewards already accounted for the staked amount, scaled by the
+    /// reward multiplier
+    pub reward_debt_b: u128,
+
+    /// Time until which the locked LP tokens cannot be withdrawn, zero when
+    /// they were never locked
+    pub lockup_end_timestamp: u64,
+
+    /// Amount of the staked LP tokens locked until `lockup_end_timestamp`
+    pub locked_balance: u64,
+
+    /// Stake weight of the staked LP tokens, which the rewards are shared
+    /// by. Each deposit adds its [boosted_share](crate::math::boosted_share)
+    /// for the lockup it is made with, and a withdrawal removes the same
+    /// fraction of the weight as of the deposit, so that the boost stays
+    /// fixed at deposit time
+    pub boosted_share: u64,
+}
+
+impl UserInfoV3 {
+    /// Whether every staked LP token can be withdrawn at `now`
+    pub fn is_unlocked(&self, now: u64) -> bool {
+        self.locked_balance_at(now) == 0
+    }
+
+    /// Staked LP tokens still locked at `now`
+    pub fn locked_balance_at(&self, now: u64) -> u64 {
+        if now < self.lockup_end_timestamp {
+            self.locked_balance
+        } else {
+            0
+        }
+    }
+
+    /// Staked LP tokens that can be withdrawn at `now`
+    pub fn withdrawable_balance(&self, now: u64) -> u64 {
+        self.deposit_balance
+            .saturating_sub(self.locked_balance_at(now))
+    }
+
+    /// Locks `amount` newly staked LP tokens for `lockup_seconds` from
+    /// `now`, the rest of the deposit staying withdrawable. The tokens of a
+    /// lockup still running stay locked with them, until the later of both
+    /// ends, so depositing with a shorter lockup keeps the previous end.
+    /// Nothing is locked without amount or lockup.
+    pub fn lock(&mut self, now: u64, amount: u64, lockup_seconds: u64) -> Result<(), FarmError> {
+        if amount == 0 || lockup_seconds == 0 {
+            return Ok(());
+        }
+        let lockup_end_timestamp = now
+            .checked_add(lockup_seconds)
+            .ok_or(FarmError::MathOverflow)?;
+        self.locked_balance = self
+            .locked_balance_at(now)
+            .checked_add(amount)
+            .ok_or(FarmError::MathOverflow)?;
+        self.lockup_end_timestamp = self.lockup_end_timestamp.max(lockup_end_timestamp);
+        Ok(())
+    }
+
+    /// Stake weight removed by withdrawing `amount` of the staked LP tokens,
+    /// the same fraction of `boosted_share` as of the deposit, rounded up so
+    /// that the weight left is never boosted further
+    pub fn withdrawn_share(&self, amount: u64) -> Result<u64, FarmError> {
+        if amount >= self.deposit_balance {
+            return Ok(self.boosted_share);
+        }
+        let left = u128::from(self.boosted_share)
+            .checked_mul(u128::from(self.deposit_balance - amount))
+            .ok_or(FarmError::MathOverflow)?
+            / u128::from(self.deposit_balance);
+        // cannot fail: the weight left is at most boosted_share
+        Ok(self.boosted_share - u64::try_from(left).map_err(|_| FarmError::MathOverflow)?)
+    }
+}
+
+impl Sealed for UserInfoV3 {}
+impl IsInitialized for UserInfoV3 {
+    fn is_initialized(&self) -> bool {
+        self.wallet != Pubkey::default()
+    }
+}
+
+impl Pack for UserInfoV3 {
+    const LEN: usize = 128;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, UserInfoV3::LEN];
+        let (
+            wallet,
+            farm_id,
+            deposit_balance,
+            reward_debt,
+            reward_debt_b,
+            lockup_end_timestamp,
+            locked_balance,
+            boosted_share,
+        ) = mut_array_refs![output, 32, 32, 8, 16, 16, 8, 8, 8];
+        wallet.copy_from_slice(self.wallet.as_ref());
+        farm_id.copy_from_slice(self.farm_id.as_ref());
+        *deposit_balance = self.deposit_balance.to_le_bytes();
+        *reward_debt = self.reward_debt.to_le_bytes();
+        *reward_debt_b = self.reward_debt_b.to_le_bytes();
+        *lockup_end_timestamp = self.lockup_end_timestamp.to_le_bytes();
+        *locked_balance = self.locked_balance.to_le_bytes();
+        *boosted_share = self.boosted_share.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [UserInfoV3](struct.UserInfoV3.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, UserInfoV3::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            wallet,
+            farm_id,
+            deposit_balance,
+            reward_debt,
+            reward_debt_b,
+            lockup_end_timestamp,
+            locked_balance,
+            boosted_share,
+        ) = array_refs![input, 32, 32, 8, 16, 16, 8, 8, 8];
+        Ok(Self {
+            wallet: Pubkey::new_from_array(*wallet),
+            farm_id: Pubkey::new_from_array(*farm_id),
+            deposit_balance: u64::from_le_bytes(*deposit_balance),
+            reward_debt: u128::from_le_bytes(*reward_debt),
+            reward_debt_b: u128::from_le_bytes(*reward_debt_b),
+            lockup_end_timestamp: u64::from_le_bytes(*lockup_end_timestamp),
+            locked_balance: u64::from_le_bytes(*locked_balance),
+            boosted_share: u64::from_le_bytes(*boosted_share),
+        })
+    }
+}
+
+impl From<UserInfo> for UserInfoV3 {
+    /// Single-reward user information, never locked nor boosted
+    fn from(user_info: UserInfo) -> Self {
+        UserInfoV2::from(user_info).into()
+    }
+}
+
+impl From<UserInfoV2> for UserInfoV3 {
+    /// Dual-reward user information, never locked nor boosted
+    fn from(user_info: UserInfoV2) -> Self {
+        Self {
+            wallet: user_info.wallet,
+            farm_id: user_info.farm_id,
+            deposit_balance: user_info.deposit_balance,
+            reward_debt: user_info.reward_debt,
+            reward_debt_b: user_info.reward_debt_b,
+            lockup_end_timestamp: 0,
+            locked_balance: 0,
+            boosted_share: user_info.deposit_balance,
+        }
+    }
+}
+
+/// All versions of the user farming information, told apart by account size
+#[derive(Clone, Debug, PartialEq)]
+pub enum UserInfoVersion {
+    /// Single-reward farms
+    UserInfo(UserInfo),
+    /// Dual-reward farms
+    UserInfoV2(UserInfoV2),
+    /// Farms with lockups
+    UserInfoV3(UserInfoV3),
+}
+
+impl UserInfoVersion {
+    /// Unpack a user information account based on its size
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        match input.len() {
+            UserInfo::LEN => Ok(Self::UserInfo(UserInfo::unpack(input)?)),
+            UserInfoV2::LEN => Ok(Self::UserInfoV2(UserInfoV2::unpack(input)?)),
+            UserInfoV3::LEN => Ok(Self::UserInfoV3(UserInfoV3::unpack(input)?)),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Whether the staked LP tokens can be withdrawn at `now`, always the
+    /// case for the layouts without lockup
+    pub fn is_unlocked(&self, now: u64) -> bool {
+        match self {
+            Self::UserInfo(_) | Self::UserInfoV2(_) => true,
+            Self::UserInfoV3(user_info) => user_info.is_unlocked(now),
+        }
+    }
+
+    /// User information in the latest layout
+    pub fn into_latest(self) -> UserInfoV3 {
+        match self {
+            Self::UserInfo(user_info) => user_info.into(),
+            Self::UserInfoV2(user_info) => user_info.into(),
+            Self::UserInfoV3(user_info) => user_info,
+        }
+    }
+
+    /// Pack user information in the layout given by the size of `dst`,
+    /// failing with `InvalidAccountData` when the layout has no room for a
+    /// field that is set, e.g. a lockup or a boost in the single-reward
+    /// layout
+    pub fn pack_latest(user_info: UserInfoV3, dst: &mut [u8]) -> Result<(), ProgramError> {
+        match dst.len() {
+            UserInfo::LEN => {
+                if user_info.reward_debt_b != 0
+                    || user_info.lockup_end_timestamp != 0
+                    || user_info.locked_balance != 0
+                    || user_info.boosted_share != user_info.deposit_balance
+                {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                UserInfo {
+                    wallet: user_info.wallet,
+                    farm_id: user_info.farm_id,
+                    deposit_balance: user_info.deposit_balance,
+                    reward_debt: user_info.reward_debt,
+                }
+                .pack_into_slice(dst);
+            }
+            UserInfoV2::LEN => {
+                if user_info.lockup_end_timestamp != 0
+                    || user_info.locked_balance != 0
+                    || user_info.boosted_share != user_info.deposit_balance
+                {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                UserInfoV2 {
+                    wallet: user_info.wallet,
+                    farm_id: user_info.farm_id,
+                    deposit_balance: user_info.deposit_balance,
+                    reward_debt: user_info.reward_debt,
+                    reward_debt_b: user_info.reward_debt_b,
+                }
+                .pack_into_slice(dst);
+            }
+            UserInfoV3::LEN => user_info.pack_into_slice(dst),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_farm_pool() -> FarmPool {
+        FarmPool {
+            is_allowed: true,
+            nonce: 254,
+            pool_lp_token_account: Pubkey::new_unique(),
+            pool_reward_token_account: Pubkey::new_unique(),
+            pool_mint_address: Pubkey::new_unique(),
+            reward_mint_address: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            fee_owner: Pubkey::new_unique(),
+            reward_per_share_net: 1 << 100,
+            last_timestamp: 1_700_000_100,
+            reward_per_timestamp: 1_000,
+            start_timestamp: 1_700_000_000,
+            end_timestamp: 1_700_086_400,
+        }
+    }
+
+    #[test]
+    fn test_farm_pool_pending_reward() {
+        // 1_000 rewards per second over a day
+        let start = 1_700_000_000;
+        let end = start + 86_400;
+        let m = REWARD_MULTIPLIER;
+        #[rustfmt::skip]
+        let cases = vec![
+            // (case, last_timestamp, reward_per_share_net, deposit, reward_debt, now, total_staked, pending)
+            ("zero stake", start, 0, 0, 0, start + 100, 1_000, Ok(0)),
+            ("nothing staked", start, 0, 1_000, 0, start + 100, 0, Ok(0)),
+            ("before start", start - 500, 0, 1_000, 0, start - 100, 1_000, Ok(0)),
+            ("at start", start, 0, 1_000, 0, start, 1_000, Ok(0)),
+            ("alone", start, 0, 1_000, 0, start + 100, 1_000, Ok(100_000)),
+            ("shared", start, 0, 1_000, 0, start + 100, 4_000, Ok(25_000)),
+            ("since the start", start - 500, 0, 1_000, 0, start + 100, 1_000, Ok(100_000)),
+            ("at end", start, 0, 1_000, 0, end, 1_000, Ok(86_400_000)),
+            ("after end", start, 0, 1_000, 0, end + 1_000, 1_000, Ok(86_400_000)),
+            ("distributed after end", end, 86_400 * m, 1_000, 0, end + 1_000, 1_000, Ok(86_400_000)),
+            ("distributed", start + 100, 100 * m, 1_000, 0, start + 200, 1_000, Ok(200_000)),
+            ("harvested", start + 100, 100 * m, 1_000, 100_000 * m, start + 200, 1_000, Ok(100_000)),
+            ("reward debt", start, 0, 1_000, 40_000 * m, start + 100, 1_000, Ok(60_000)),
+            ("reward debt > accrued", start, 0, 1_000, 100_000 * m + 1, start + 100, 1_000, Err(FarmError::MathOverflow)),
+            ("reward debt > distributed", start + 100, 100 * m, 1_000, 100_001 * m, start + 100, 1_000, Err(FarmError::MathOverflow)),
+            // REWARD_MULTIPLIER rounding: 1_000e9 / 3 per share, rounded down
+            ("third of a second", start, 0, 1, 0, start + 1, 3, Ok(333)),
+            ("7 / 7 of a second", start, 0, 7, 0, start + 1, 7, Ok(999)),
+            ("fraction of debt", start, 0, 1_000, 1, start + 100, 1_000, Ok(99_999)),
+            ("fraction of reward", start, 0, 1, 0, start + 1, 1_000_000, Ok(0)),
+        ];
+        for (
+            case,
+            last_timestamp,
+            reward_per_share_net,
+            deposit_balance,
+            reward_debt,
+            now,
+            total_staked,
+            expected,
+        ) in cases
+        {
+            let farm = FarmPool {
+                reward_per_share_net,
+                last_timestamp,
+                reward_per_timestamp: 1_000,
+                start_timestamp: start,
+                end_timestamp: end,
+                ..FarmPool::default()
+            };
+            let user = UserInfo {
+                deposit_balance,
+                reward_debt,
+                ..UserInfo::default()
+            };
+            assert_eq!(
+                farm.pending_reward(&user, now, total_staked),
+                expected,
+                "{}",
+                case
+            );
+            assert_eq!(
+                farm.pending_reward_at(&user, FarmTimestamp::from(now as i64), total_staked),
+                expected,
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn test_farm_timestamp() {
+        let start = FarmTimestamp::from(1_700_000_000);
+        let end = FarmTimestamp::from(1_700_086_400);
+        assert_eq!(i64::from(start), 1_700_000_000);
+        assert_eq!(start.unix_seconds(), 1_700_000_000);
+        assert_eq!(FarmTimestamp::try_from(1_700_000_000u64), Ok(start));
+        assert_eq!(
+            FarmTimestamp::try_from(i64::MAX as u64),
+            Ok(FarmTimestamp(i64::MAX))
+        );
+        assert_eq!(
+            FarmTimestamp::try_from(i64::MAX as u64 + 1),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+        assert_eq!(
+            FarmTimestamp::try_from(u64::MAX),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+
+        // a clock before the epoch is before any farm, not far after its end
+        let before_epoch = FarmTimestamp::from(-1);
+        assert_eq!(before_epoch.unix_seconds(), 0);
+        assert_eq!(FarmTimestamp::from(i64::MIN).unix_seconds(), 0);
+        assert!(before_epoch < FarmTimestamp::default());
+        assert!(before_epoch < start);
+        assert!(FarmTimestamp::from(1_699_999_999) < start);
+        assert!(FarmTimestamp::from(1_700_086_401) > end);
+        assert_eq!(FarmTimestamp::from(1_700_086_400), end);
+
+        assert_eq!(FarmTimestamp::validate_period(start, end), Ok(()));
+        for (start, end) in [
+            (start, start),
+            (end, start),
+            (FarmTimestamp(0), end),
+            (before_epoch, end),
+        ] {
+            assert_eq!(
+                FarmTimestamp::validate_period(start, end),
+                Err(FarmError::InvalidFarmPeriod),
+                "{:?} {:?}",
+                start,
+                end
+            );
+        }
+        assert_eq!(
+            FarmTimestamp::period_from_unix_seconds(1_700_000_000, 1_700_086_400),
+            Ok((start, end))
+        );
+        assert_eq!(
+            FarmTimestamp::period_from_unix_seconds(1_700_000_000, u64::MAX),
+            Err(FarmError::InvalidFarmPeriod)
+        );
+
+        // rewards before the start, during and after the end of the farm
+        let farm = FarmPool {
+            last_timestamp: start.unix_seconds(),
+            ..test_farm_pool()
+        };
+        let user = UserInfo {
+            deposit_balance: 1_000,
+            reward_debt: farm.reward_per_share_net * 1_000,
+            ..UserInfo::default()
+        };
+        let pending = |now: i64| farm.pending_reward_at(&user, FarmTimestamp::from(now), 1_000);
+        assert_eq!(pending(-1), Ok(0));
+        assert_eq!(pending(1_699_999_999), Ok(0));
+        assert_eq!(pending(1_700_000_100), Ok(100_000));
+        assert_eq!(pending(1_700_086_400), Ok(86_400_000));
+        assert_eq!(pending(1_700_086_401), Ok(86_400_000));
+        assert_eq!(pending(i64::MAX), Ok(86_400_000));
+    }
+
+    #[cfg(feature = "offchain")]
+    #[test]
+    fn test_farm_reward_shortfall() {
+        // 1_000 rewards per second owed from the last distribution, 100
+        // seconds after the start, until the end of the day long farm
+        let farm = test_farm_pool();
+        let now = 1_700_000_200;
+        let owed = 1_000 * 86_300;
+        let check = |vault, total_staked, now| {
+            (
+                farm_reward_shortfall(&farm, vault, total_staked, now),
+                check_farm_solvency(&farm, vault, total_staked, now),
+            )
+        };
+        // solvent, exactly solvent and insolvent
+        assert_eq!(check(owed + 1, 1_000, now), (Ok(0), Ok(())));
+        assert_eq!(check(owed, 1_000, now), (Ok(0), Ok(())));
+        assert_eq!(
+            check(owed - 1, 1_000, now),
+            (Ok(1), Err(FarmError::InsufficientReward))
+        );
+        assert_eq!(
+            check(0, 1_000, now),
+            (Ok(owed), Err(FarmError::InsufficientReward))
+        );
+        // an empty farm accrues nothing until now
+        assert_eq!(
+            check(0, 0, now),
+            (Ok(1_000 * 86_200), Err(FarmError::InsufficientReward))
+        );
+        // nothing accrues before the start
+        let not_started = FarmPool {
+            last_timestamp: 1_699_990_000,
+            ..farm.clone()
+        };
+        assert_eq!(
+            farm_reward_shortfall(&not_started, 0, 1_000, 1_699_995_000),
+            Ok(1_000 * 86_400)
+        );
+        // already ended, the rewards until the end are distributed
+        let ended = FarmPool {
+            last_timestamp: farm.end_timestamp,
+            ..farm.clone()
+        };
+        assert_eq!(check_farm_solvency(&ended, 0, 1_000, 1_700_100_000), Ok(()));
+        assert_eq!(farm_reward_shortfall(&ended, 0, 1_000, 1_700_100_000), Ok(0));
+        assert_eq!(farm_reward_shortfall(&farm, 0, 0, 1_700_100_000), Ok(0));
+        // the rewards of a staked farm not distributed before its end
+        assert_eq!(
+            check(owed - 1, 1_000, 1_700_100_000),
+            (Ok(1), Err(FarmError::InsufficientReward))
+        );
+
+        let overflow = FarmPool {
+            reward_per_timestamp: u128::MAX,
+            ..farm.clone()
+        };
+        assert_eq!(
+            farm_reward_shortfall(&overflow, 0, 1_000, now),
+            Err(FarmError::MathOverflow)
+        );
+        let above_u64 = FarmPool {
+            reward_per_timestamp: u128::from(u64::MAX),
+            ..farm
+        };
+        assert_eq!(
+            check_farm_solvency(&above_u64, u64::MAX, 1_000, now),
+            Err(FarmError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn test_farm_pool_v2_second_reward() {
+        let farm_pool = FarmPoolV2 {
+            farm_pool: test_farm_pool(),
+            multiplier_numerator: 3,
+            multiplier_denominator: 2,
+            max_lock_seconds: 86_400,
+            second_reward_mint_address: Pubkey::new_unique(),
+            pool_second_reward_token_account: Pubkey::new_unique(),
+            second_reward_per_share_net: (1 << 90) + 1,
+            second_last_timestamp: 1_700_000_050,
+            second_reward_per_timestamp: 2,
+            total_boosted_stake: 0,
+            total_reward_debt: 0,
+        };
+        let mut data = vec![0; FarmPoolV2::LEN];
+        FarmPoolV2::pack(farm_pool.clone(), &mut data).unwrap();
+        assert_eq!(
+            &data[306..338],
+            farm_pool.second_reward_mint_address.as_ref()
+        );
+        assert_eq!(
+            &data[338..370],
+            farm_pool.pool_second_reward_token_account.as_ref()
+        );
+        assert_eq!(data[370..386], ((1u128 << 90) + 1).to_le_bytes());
+        assert_eq!(data[386..394], 1_700_000_050u64.to_le_bytes());
+        assert_eq!(data[394..410], 2u128.to_le_bytes());
+        assert_eq!(FarmPoolV2::unpack(&data).unwrap(), farm_pool);
+        assert_eq!(
+            FarmPoolVersion::unpack(&data).unwrap(),
+            FarmPoolVersion::FarmPoolV2(farm_pool.clone())
+        );
+
+        // the second reward is distributed as a farm of its own
+        let second_reward_pool = farm_pool.second_reward_pool();
+        assert!(farm_pool.has_second_reward());
+        assert_eq!(
+            second_reward_pool.pool_reward_token_account,
+            farm_pool.pool_second_reward_token_account
+        );
+        assert_eq!(
+            second_reward_pool.reward_mint_address,
+            farm_pool.second_reward_mint_address
+        );
+        assert_eq!(second_reward_pool.last_timestamp, 1_700_000_050);
+        assert_eq!(second_reward_pool.end_timestamp, 1_700_086_400);
+        let mut updated = farm_pool.clone();
+        updated.set_second_reward_pool(&second_reward_pool);
+        assert_eq!(updated, farm_pool);
+
+        // the original layout has no room for it
+        let mut data = vec![0; FarmPool::LEN];
+        assert_eq!(
+            FarmPoolVersion::pack_latest(farm_pool.clone(), &mut data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let single_reward = FarmPoolV2::from(farm_pool.farm_pool.clone());
+        assert!(!single_reward.has_second_reward());
+        FarmPoolVersion::pack_latest(single_reward, &mut data).unwrap();
+        assert_eq!(FarmPool::unpack(&data).unwrap(), farm_pool.farm_pool);
+    }
+
+    #[test]
+    fn test_program_data_roundtrip() {
+        let program_data = FarmProgramData {
+            is_initialized: true,
+            super_owner: Pubkey::new_unique(),
+            fee_owner: Pubkey::new_unique(),
+            allowed_creator: Pubkey::new_unique(),
+            amm_program_id: Pubkey::new_unique(),
+            farm_fee: 5_000_000_000,
+            harvest_fee_numerator: 1,
+            harvest_fee_denominator: 1000,
+            pending_super_owner: Pubkey::new_unique(),
+        };
+        let mut data = [0; FarmProgramData::LEN];
+        FarmProgramData::pack(program_data.clone(), &mut data).unwrap();
+        assert_eq!(FarmProgramData::unpack(&data).unwrap(), program_data);
+
+        // a fresh account is not the program data yet
+        assert_eq!(
+            FarmProgramData::unpack(&[0; FarmProgramData::LEN]),
+            Err(ProgramError::UninitializedAccount)
+        );
+        data[0] = 2;
+        assert_eq!(
+            FarmProgramData::unpack(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_program_data_address() {
+        use std::str::FromStr;
+
+        let program_id = Pubkey::new_from_array([1; 32]);
+        assert_eq!(
+            program_id.to_string(),
+            "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"
+        );
+        let address = Pubkey::from_str("GyK331Zmcy9ovWVRWFh7mYaqFeDKQc4kuFnEWAmEmo9z").unwrap();
+        assert_eq!(find_program_data_address(&program_id), (address, 255));
+        assert_eq!(
+            Pubkey::create_program_address(&[b"program_data", &[255]], &program_id),
+            Ok(address)
+        );
+        assert_eq!(FarmProgramData::address(&program_id), address);
+        assert_ne!(
+            FarmProgramData::address(&Pubkey::new_from_array([2; 32])),
+            address
+        );
+    }
+
+    fn test_user_info() -> UserInfoV3 {
+        UserInfoV3 {
+            wallet: Pubkey::new_unique(),
+            farm_id: Pubkey::new_unique(),
+            deposit_balance: 1_500,
+            reward_debt: (1 << 80) + 3,
+            reward_debt_b: (1 << 70) + 5,
+            lockup_end_timestamp: 1_700_003_600,
+            locked_balance: 500,
+            boosted_share: 2_000,
+        }
+    }
+
+    #[test]
+    fn test_user_info_old_layouts_still_decode() {
+        let user_info = test_user_info();
+
+        let mut data = vec![0; UserInfo::LEN];
+        UserInfo {
+            wallet: user_info.wallet,
+            farm_id: user_info.farm_id,
+            deposit_balance: user_info.deposit_balance,
+            reward_debt: user_info.reward_debt,
+        }
+        .pack_into_slice(&mut data);
+        let latest = UserInfoVersion::unpack(&data).unwrap().into_latest();
+        assert_eq!(
+            latest,
+            UserInfoV3 {
+                reward_debt_b: 0,
+                lockup_end_timestamp: 0,
+                locked_balance: 0,
+                boosted_share: 1_500,
+                ..user_info.clone()
+            }
+        );
+        assert_eq!(latest.withdrawable_balance(0), 1_500);
+
+        let mut data = vec![0; UserInfoV2::LEN];
+        UserInfoV2 {
+            wallet: user_info.wallet,
+            farm_id: user_info.farm_id,
+            deposit_balance: user_info.deposit_balance,
+            reward_debt: user_info.reward_debt,
+            reward_debt_b: user_info.reward_debt_b,
+        }
+        .pack_into_slice(&mut data);
+        let latest = UserInfoVersion::unpack(&data).unwrap().into_latest();
+        assert_eq!(
+            latest,
+            UserInfoV3 {
+                lockup_end_timestamp: 0,
+                locked_balance: 0,
+                boosted_share: 1_500,
+                ..user_info.clone()
+            }
+        );
+        assert!(latest.is_unlocked(0));
+
+        // they are written back in their own layout, as long as nothing locks
+        let mut written = vec![0; UserInfoV2::LEN];
+        UserInfoVersion::pack_latest(latest.clone(), &mut written).unwrap();
+        assert_eq!(written, data);
+        let locked = UserInfoV3 {
+            locked_balance: 1,
+            ..latest.clone()
+        };
+        assert_eq!(
+            UserInfoVersion::pack_latest(locked.clone(), &mut written),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut written = vec![0; UserInfo::LEN];
+        assert_eq!(
+            UserInfoVersion::pack_latest(locked, &mut written),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let boosted = UserInfoV3 {
+            reward_debt_b: 0,
+            boosted_share: 1_501,
+            ..latest
+        };
+        assert_eq!(
+            UserInfoVersion::pack_latest(boosted, &mut written),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        assert_eq!(
+            UserInfoVersion::unpack(&[0; UserInfoV3::LEN - 8]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_user_info_v3_locked_balance() {
+        let user_info = test_user_info();
+        let mut data = vec![0; UserInfoV3::LEN];
+        UserInfoVersion::pack_latest(user_info.clone(), &mut data).unwrap();
+        assert_eq!(data[104..112], 1_700_003_600u64.to_le_bytes());
+        assert_eq!(data[112..120], 500u64.to_le_bytes());
+        assert_eq!(data[120..128], 2_000u64.to_le_bytes());
+        assert_eq!(
+            UserInfoVersion::unpack(&data).unwrap(),
+            UserInfoVersion::UserInfoV3(user_info.clone())
+        );
+
+        // only the locked part is held back, and only until the end
+        assert_eq!(user_info.withdrawable_balance(1_700_003_599), 1_000);
+        assert!(!user_info.is_unlocked(1_700_003_599));
+        assert_eq!(user_info.withdrawable_balance(1_700_003_600), 1_500);
+        assert!(user_info.is_unlocked(1_700_003_600));
+    }
+
+    #[test]
+    fn test_user_info_v3_lock() {
+        let mut user_info = UserInfoV3 {
+            deposit_balance: 1_000,
+            ..UserInfoV3::default()
+        };
+
+        // nothing deposited, nothing locked
+        user_info.lock(1_000, 0, 3_600).unwrap();
+        assert_eq!(user_info.lockup_end_timestamp, 0);
+        assert_eq!(user_info.withdrawable_balance(1_000), 1_000);
+
+        user_info.deposit_balance += 500;
+        user_info.lock(1_000, 500, 3_600).unwrap();
+        assert_eq!(user_info.locked_balance, 500);
+        assert_eq!(user_info.lockup_end_timestamp, 4_600);
+        assert_eq!(user_info.withdrawable_balance(1_000), 1_000);
+
+        // a shorter lockup joins the running one
+        user_info.deposit_balance += 200;
+        user_info.lock(2_000, 200, 60).unwrap();
+        assert_eq!(user_info.locked_balance, 700);
+        assert_eq!(user_info.lockup_end_timestamp, 4_600);
+        assert_eq!(user_info.withdrawable_balance(2_000), 1_000);
+
+        // an ended lockup no longer counts
+        user_info.deposit_balance += 100;
+        user_info.lock(5_000, 100, 60).unwrap();
+        assert_eq!(user_info.locked_balance, 100);
+        assert_eq!(user_info.lockup_end_timestamp, 5_060);
+        assert_eq!(user_info.withdrawable_balance(5_000), 1_700);
+
+        assert_eq!(user_info.lock(u64::MAX, 1, 1), Err(FarmError::MathOverflow));
+    }
+
+    #[test]
+    fn test_user_info_v3_withdrawn_share() {
+        let user_info = UserInfoV3 {
+            deposit_balance: 3,
+            boosted_share: 5,
+            ..UserInfoV3::default()
+        };
+        assert_eq!(user_info.withdrawn_share(0), Ok(0));
+        // 5 * 2 / 3 = 3.33 left, rounded down
+        assert_eq!(user_info.withdrawn_share(1), Ok(2));
+        assert_eq!(user_info.withdrawn_share(2), Ok(4));
+        assert_eq!(user_info.withdrawn_share(3), Ok(5));
+
+        let user_info = UserInfoV3 {
+            deposit_balance: u64::MAX,
+            boosted_share: u64::MAX,
+            ..UserInfoV3::default()
+        };
+        assert_eq!(user_info.withdrawn_share(1), Ok(1));
+    }
+
+    #[test]
+    fn test_farm_pool_v2_total_boosted_stake() {
+        let farm_pool = FarmPoolV2 {
+            multiplier_numerator: 2,
+            multiplier_denominator: 1,
+            max_lock_seconds: 86_400,
+            total_boosted_stake: 7_000,
+            total_reward_debt: (1 << 100) + 1,
+            ..FarmPoolV2::from(test_farm_pool())
+        };
+        let mut data = vec![0; FarmPoolV2::LEN];
+        FarmPoolVersion::pack_latest(farm_pool.clone(), &mut data).unwrap();
+        assert_eq!(data[410..418], 7_000u64.to_le_bytes());
+        assert_eq!(data[418..434], ((1u128 << 100) + 1).to_le_bytes());
+        let farm_version = FarmPoolVersion::unpack(&data).unwrap();
+        assert_eq!(farm_version.total_staked(5_000), 7_000);
+        assert_eq!(farm_version.into_latest(), farm_pool);
+
+        // the original layout shares the rewards by the staked LP tokens
+        let farm_pool = FarmPoolV2 {
+            total_boosted_stake: 5_000,
+            total_reward_debt: 1_000,
+            ..FarmPoolV2::from(farm_pool.farm_pool)
+        };
+        let mut data = vec![0; FarmPool::LEN];
+        FarmPoolVersion::pack_latest(farm_pool.clone(), &mut data).unwrap();
+        let farm_version = FarmPoolVersion::unpack(&data).unwrap();
+        assert_eq!(farm_version.total_staked(5_000), 5_000);
+        let farm_pool = farm_version.into_latest();
+        assert_eq!(farm_pool.total_boosted_stake, 0);
+        assert_eq!(farm_pool.total_reward_debt, 0);
+    }
+
+    #[test]
+    fn test_farm_pool_v2_unharvested_reward() {
+        let farm_pool = |reward_per_share_net, total_boosted_stake, total_reward_debt| FarmPoolV2 {
+            total_boosted_stake,
+            total_reward_debt,
+            ..FarmPoolV2::from(FarmPool {
+                reward_per_share_net,
+                ..test_farm_pool()
+            })
+        };
+        // 3_000 staked earned 2.5 rewards per token, 1_000 of them harvested
+        let rps = 2_500_000_000;
+        assert_eq!(
+            farm_pool(rps, 3_000, 1_000 * REWARD_MULTIPLIER).unharvested_reward(),
+            Ok(6_500)
+        );
+        assert_eq!(farm_pool(rps, 3_000, 0).unharvested_reward(), Ok(7_500));
+        assert_eq!(
+            farm_pool(rps, 3_000, 7_500 * REWARD_MULTIPLIER).unharvested_reward(),
+            Ok(0)
+        );
+        // fractions of a reward owed to the stakers are kept
+        assert_eq!(
+            farm_pool(rps, 3_000, 7_500 * REWARD_MULTIPLIER - 1).unharvested_reward(),
+            Ok(1)
+        );
+        assert_eq!(farm_pool(1, 1, 0).unharvested_reward(), Ok(1));
+        // nothing staked, or debts above the accrued rewards
+        assert_eq!(farm_pool(rps, 0, 0).unharvested_reward(), Ok(0));
+        assert_eq!(farm_pool(rps, 1, u128::MAX).unharvested_reward(), Ok(0));
+        assert_eq!(
+            farm_pool(u128::MAX, 2, 0).unharvested_reward(),
+            Err(FarmError::MathOverflow)
+        );
+        assert_eq!(
+            farm_pool(u128::MAX, 1, 0).unharvested_reward(),
+            Err(FarmError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn test_farm_pool_v2_pending_reward_boosted() {
+        let farm_pool = FarmPoolV2 {
+            multiplier_numerator: 2,
+            multiplier_denominator: 1,
+            max_lock_seconds: 86_400,
+            total_boosted_stake: 3_000,
+            ..FarmPoolV2::from(FarmPool {
+                reward_per_share_net: 0,
+                last_timestamp: 1_700_000_000,
+                ..test_farm_pool()
+            })
+        };
+        // 1_000 LP tokens locked for the longest weigh twice those without
+        // lockup, for the whole lockup and after it
+        let boosted = UserInfoV3 {
+            deposit_balance: 1_000,
+            boosted_share: 2_000,
+            lockup_end_timestamp: 1_700_086_400,
+            locked_balance: 1_000,
+            ..UserInfoV3::default()
+        };
+        let unboosted = UserInfoV3 {
+            deposit_balance: 1_000,
+            boosted_share: 1_000,
+            ..UserInfoV3::default()
+        };
+        for now in [1_700_000_300, 1_700_086_400] {
+            let boosted_reward = farm_pool.pending_reward(&boosted, now, 3_000).unwrap();
+            let unboosted_reward = farm_pool.pending_reward(&unboosted, now, 3_000).unwrap();
+            assert_eq!(boosted_reward, 2 * unboosted_reward);
+        }
+        assert_eq!(
+            farm_pool
+                .pending_reward(&boosted, 1_700_000_300, 3_000)
+                .unwrap(),
+            200_000
+        );
+    }
+}
@@ -0,0 +1,640 @@
+//! Test context shared by the farm program tests: the farm program loaded in
+//! a `ProgramTest` bank, with its program data account, LP and reward mints,
+//! and helpers to fund wallets, create token accounts and farms.
+
+// every test crate uses a different subset of the helpers
+#![allow(dead_code)]
+
+use cropper_farm_v1::{
+    instruction::{
+        add_reward, build_first_deposit, get_associated_token_address_with_program_id,
+        initialize_farm, initialize_farm_v2, second_reward_accounts, FirstDepositOptions,
+    },
+    processor::Processor,
+    state::{
+        find_executable_data_address, find_farm_authority, find_program_data_address,
+        find_user_info_address, FarmPool, FarmPoolV2, FarmPoolVersion, FarmProgramData, UserInfoV3,
+        UserInfoVersion,
+    },
+};
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+/// Lamports of the accounts added before the bank starts, rent exempt for
+/// any size used by the tests
+const ACCOUNT_LAMPORTS: u64 = 1_000_000_000;
+
+/// Lamports funding each test wallet
+pub const WALLET_LAMPORTS: u64 = 10_000_000_000;
+
+/// Farm fee set in the program data, in reward tokens
+pub const FARM_FEE: u64 = 5_000;
+
+/// Harvest fee set in the program data, 1%
+pub const HARVEST_FEE_NUMERATOR: u64 = 1;
+pub const HARVEST_FEE_DENOMINATOR: u64 = 100;
+
+/// Decimals of the test mints
+pub const DECIMALS: u8 = 6;
+
+/// Clock of the bank when the context is created
+pub const START_TIMESTAMP: i64 = 1_700_000_000;
+
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    Processor::process(program_id, accounts, input)
+}
+
+/// Metadata of the `ProgramData` account of a program in the upgradeable
+/// loader, the bincode encoding of `UpgradeableLoaderState::ProgramData`
+/// upgradeable by `upgrade_authority`
+fn executable_data(upgrade_authority: &Pubkey) -> Vec<u8> {
+    let mut data = vec![0; UpgradeableLoaderState::size_of_programdata_metadata()];
+    data[0] = 3;
+    data[12] = 1;
+    data[13..].copy_from_slice(upgrade_authority.as_ref());
+    data
+}
+
+/// Farm created by [FarmTestContext::create_farm]
+pub struct TestFarm {
+    pub farm_id: Pubkey,
+    pub authority: Pubkey,
+    pub nonce: u8,
+    pub creator: Keypair,
+    pub pool_lp_token_account: Pubkey,
+    pub pool_reward_token_account: Pubkey,
+    /// Second reward vault of a dual-reward farm
+    pub pool_second_reward_token_account: Option<Pubkey>,
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+}
+
+/// Farm program loaded in a bank, with its program data set
+pub struct FarmTestContext {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub program_data: Pubkey,
+    pub super_owner: Keypair,
+    pub fee_owner: Keypair,
+    pub allowed_creator: Keypair,
+    pub amm_program_id: Pubkey,
+    /// Amm pool account, owned by the amm program, of the LP mint
+    pub amm_id: Pubkey,
+    pub mint_authority: Keypair,
+    pub lp_mint: Pubkey,
+    pub reward_mint: Pubkey,
+    pub second_reward_mint: Pubkey,
+}
+
+impl FarmTestContext {
+    /// Starts a bank with the farm program, its program data account, the LP
+    /// and reward mints and the amm pool account
+    pub async fn new() -> Self {
+        Self::start(true).await
+    }
+
+    /// Same as [FarmTestContext::new], the program data account being left
+    /// to create by `SetProgramData`
+    pub async fn without_program_data() -> Self {
+        Self::start(false).await
+    }
+
+    async fn start(with_program_data: bool) -> Self {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "cropper_farm_v1",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let super_owner = Keypair::new();
+        let fee_owner = Keypair::new();
+        let allowed_creator = Keypair::new();
+        let mint_authority = Keypair::new();
+        let amm_program_id = Pubkey::new_unique();
+
+        // the super owner is the upgrade authority of the program, which
+        // creates the program data
+        program_test.add_account(
+            find_executable_data_address(&program_id),
+            program_account(
+                executable_data(&super_owner.pubkey()),
+                &bpf_loader_upgradeable::id(),
+            ),
+        );
+        let (program_data, _) = find_program_data_address(&program_id);
+        if with_program_data {
+            let mut data = vec![0; FarmProgramData::LEN];
+            FarmProgramData::pack(
+                FarmProgramData {
+                    is_initialized: true,
+                    super_owner: super_owner.pubkey(),
+                    fee_owner: fee_owner.pubkey(),
+                    allowed_creator: allowed_creator.pubkey(),
+                    amm_program_id,
+                    farm_fee: FARM_FEE,
+                    harvest_fee_numerator: HARVEST_FEE_NUMERATOR,
+                    harvest_fee_denominator: HARVEST_FEE_DENOMINATOR,
+                    pending_super_owner: Pubkey::default(),
+                },
+                &mut data,
+            )
+            .unwrap();
+            program_test.add_account(program_data, program_account(data, &program_id));
+        }
+
+        let amm_id = Pubkey::new_unique();
+        program_test.add_account(amm_id, program_account(vec![0; 8], &amm_program_id));
+
+        let lp_mint = Pubkey::new_unique();
+        let reward_mint = Pubkey::new_unique();
+        let second_reward_mint = Pubkey::new_unique();
+        for mint in [lp_mint, reward_mint, second_reward_mint].iter() {
+            let mut data = vec![0; Mint::LEN];
+            Mint::pack(
+                Mint {
+                    mint_authority: COption::Some(mint_authority.pubkey()),
+                    supply: 0,
+                    decimals: DECIMALS,
+                    is_initialized: true,
+                    freeze_authority: COption::None,
+                },
+                &mut data,
+            )
+            .unwrap();
+            program_test.add_account(*mint, program_account(data, &spl_token::id()));
+        }
+
+        for wallet in [&super_owner, &fee_owner, &allowed_creator].iter() {
+            program_test.add_account(
+                wallet.pubkey(),
+                Account::new(WALLET_LAMPORTS, 0, &solana_program::system_program::id()),
+            );
+        }
+
+        let context = program_test.start_with_context().await;
+        context.set_sysvar(&Clock {
+            unix_timestamp: START_TIMESTAMP,
+            ..Clock::default()
+        });
+        Self {
+            context,
+            program_id,
+            program_data,
+            super_owner,
+            fee_owner,
+            allowed_creator,
+            amm_program_id,
+            amm_id,
+            mint_authority,
+            lp_mint,
+            reward_mint,
+            second_reward_mint,
+        }
+    }
+
+    /// Sends `instructions` in a single transaction paid by the context
+    /// payer, signed by `signers` as well
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let blockhash = self
+            .context
+            .banks_client
+            .get_latest_blockhash()
+            .await
+            .unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Sends `instructions` like [Self::process], returning the log messages
+    /// of the transaction
+    pub async fn process_with_logs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Vec<String>, BanksClientError> {
+        let blockhash = self
+            .context
+            .banks_client
+            .get_latest_blockhash()
+            .await
+            .unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        let processed = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await?;
+        processed.result?;
+        Ok(processed
+            .metadata
+            .map(|metadata| metadata.log_messages)
+            .unwrap_or_default())
+    }
+
+    /// Moves the clock of the bank to `unix_timestamp`
+    pub fn set_clock(&mut self, unix_timestamp: i64) {
+        self.context.set_sysvar(&Clock {
+            unix_timestamp,
+            ..Clock::default()
+        });
+    }
+
+    /// Creates a wallet funded with [WALLET_LAMPORTS]
+    pub async fn create_wallet(&mut self) -> Keypair {
+        let wallet = Keypair::new();
+        let instruction = system_instruction::transfer(
+            &self.context.payer.pubkey(),
+            &wallet.pubkey(),
+            WALLET_LAMPORTS,
+        );
+        self.process(&[instruction], &[]).await.unwrap();
+        wallet
+    }
+
+    /// Creates a token account of `mint` owned by `owner`, at a new address
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let instructions = [
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(TokenAccount::LEN),
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ];
+        self.process(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    /// Creates the associated token account of `mint` of `wallet`, unless it
+    /// exists
+    pub async fn create_ata(&mut self, wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+        let instruction =
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                &self.context.payer.pubkey(),
+                wallet,
+                mint,
+                &spl_token::id(),
+            );
+        self.process(&[instruction], &[]).await.unwrap();
+        get_associated_token_address_with_program_id(wallet, mint, &spl_token::id())
+    }
+
+    /// Mints `amount` tokens of `mint` to `account`
+    pub async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        let instruction = spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            account,
+            &self.mint_authority.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        let mint_authority = Keypair::from_bytes(&self.mint_authority.to_bytes()).unwrap();
+        self.process(&[instruction], &[&mint_authority])
+            .await
+            .unwrap();
+    }
+
+    /// Token account state of `account`
+    pub async fn token_account(&mut self, account: &Pubkey) -> TokenAccount {
+        let account = self.get_account(account).await;
+        TokenAccount::unpack(&account.data).unwrap()
+    }
+
+    /// Token balance of `account`
+    pub async fn token_balance(&mut self, account: &Pubkey) -> u64 {
+        self.token_account(account).await.amount
+    }
+
+    /// Account at `address`, which must exist
+    pub async fn get_account(&mut self, address: &Pubkey) -> Account {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("no account at {}", address))
+    }
+
+    /// Overwrites the balance of the spl-token account at `account`, e.g. to
+    /// leave a reward vault short of the rewards it owes
+    pub async fn set_token_balance(&mut self, account: &Pubkey, amount: u64) {
+        let mut token_account = self.token_account(account).await;
+        token_account.amount = amount;
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(token_account, &mut data).unwrap();
+        self.set_account(account, data, &spl_token::id());
+    }
+
+    /// Replaces the account at `address` with one owned by `owner` holding
+    /// `data`
+    pub fn set_account(&mut self, address: &Pubkey, data: Vec<u8>, owner: &Pubkey) {
+        self.context
+            .set_account(address, &program_account(data, owner).into());
+    }
+
+    /// Stored program data
+    pub async fn program_data(&mut self) -> FarmProgramData {
+        let program_data = self.program_data;
+        let account = self.get_account(&program_data).await;
+        FarmProgramData::unpack(&account.data).unwrap()
+    }
+
+    /// Stored farm pool of `farm_id`, in any layout
+    pub async fn farm_pool(&mut self, farm_id: &Pubkey) -> FarmPool {
+        let account = self.get_account(farm_id).await;
+        FarmPoolVersion::unpack(&account.data)
+            .unwrap()
+            .into_latest()
+            .farm_pool
+    }
+
+    /// Farm pool state of `farm_id`, in the latest layout
+    pub async fn farm_pool_v2(&mut self, farm_id: &Pubkey) -> FarmPoolV2 {
+        let account = self.get_account(farm_id).await;
+        FarmPoolVersion::unpack(&account.data)
+            .unwrap()
+            .into_latest()
+    }
+
+    /// Creates a farm account of `space` bytes owned by the farm program,
+    /// with its LP and reward vaults owned by the farm authority, then
+    /// initializes it for `creator` over `[start, end)`
+    pub async fn create_farm_with_creator(
+        &mut self,
+        creator: Keypair,
+        space: usize,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<TestFarm, BanksClientError> {
+        self.create_farm_with_second_reward(creator, space, false, start_timestamp, end_timestamp)
+            .await
+    }
+
+    /// Same as [FarmTestContext::create_farm_with_creator], the farm
+    /// distributing the second reward mint as well when `second_reward` is
+    /// set
+    pub async fn create_farm_with_second_reward(
+        &mut self,
+        creator: Keypair,
+        space: usize,
+        second_reward: bool,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<TestFarm, BanksClientError> {
+        let farm = Keypair::new();
+        let (authority, nonce) = find_farm_authority(&self.program_id, &farm.pubkey());
+        let lp_mint = self.lp_mint;
+        let reward_mint = self.reward_mint;
+        let second_reward_mint = self.second_reward_mint;
+        let pool_lp_token_account = self.create_token_account(&lp_mint, &authority).await;
+        let pool_reward_token_account = self.create_token_account(&reward_mint, &authority).await;
+        let pool_second_reward_token_account = if second_reward {
+            Some(
+                self.create_token_account(&second_reward_mint, &authority)
+                    .await,
+            )
+        } else {
+            None
+        };
+
+        let initialize = match &pool_second_reward_token_account {
+            Some(pool_second_reward_token_account) => initialize_farm_v2(
+                &farm.pubkey(),
+                &authority,
+                &creator.pubkey(),
+                &pool_lp_token_account,
+                &pool_reward_token_account,
+                &lp_mint,
+                &reward_mint,
+                &self.amm_id,
+                &self.program_data,
+                true,
+                Some((&second_reward_mint, pool_second_reward_token_account)),
+                nonce,
+                start_timestamp,
+                end_timestamp,
+                &self.program_id,
+            ),
+            None => initialize_farm(
+                &farm.pubkey(),
+                &authority,
+                &creator.pubkey(),
+                &pool_lp_token_account,
+                &pool_reward_token_account,
+                &lp_mint,
+                &reward_mint,
+                &self.amm_id,
+                &self.program_data,
+                true,
+                nonce,
+                start_timestamp,
+                end_timestamp,
+                &self.program_id,
+            ),
+        };
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let instructions = [
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &farm.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                &self.program_id,
+            ),
+            initialize.unwrap(),
+        ];
+        self.process(&instructions, &[&farm, &creator]).await?;
+        Ok(TestFarm {
+            farm_id: farm.pubkey(),
+            authority,
+            nonce,
+            creator,
+            pool_lp_token_account,
+            pool_reward_token_account,
+            pool_second_reward_token_account,
+            start_timestamp,
+            end_timestamp,
+        })
+    }
+
+    /// Creates a farm of the allowed creator, with the original layout,
+    /// starting now and lasting a day
+    pub async fn create_farm(&mut self) -> TestFarm {
+        let creator = Keypair::from_bytes(&self.allowed_creator.to_bytes()).unwrap();
+        let start = START_TIMESTAMP as u64;
+        self.create_farm_with_creator(creator, FarmPool::LEN, start, start + 86_400)
+            .await
+            .unwrap()
+    }
+
+    /// Creates a dual-reward farm of the allowed creator, starting now and
+    /// lasting a day
+    pub async fn create_dual_reward_farm(&mut self) -> TestFarm {
+        let creator = Keypair::from_bytes(&self.allowed_creator.to_bytes()).unwrap();
+        let start = START_TIMESTAMP as u64;
+        self.create_farm_with_second_reward(creator, FarmPoolV2::LEN, true, start, start + 86_400)
+            .await
+            .unwrap()
+    }
+
+    /// Adds `amount` rewards to `farm`, minted to a reward account of its
+    /// creator
+    pub async fn add_reward(
+        &mut self,
+        farm: &TestFarm,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let reward_mint = self.reward_mint;
+        let creator_reward_account = self.create_ata(&farm.creator.pubkey(), &reward_mint).await;
+        self.mint_to(&reward_mint, &creator_reward_account, amount)
+            .await;
+        let instruction = add_reward(
+            &farm.farm_id,
+            &farm.authority,
+            &farm.creator.pubkey(),
+            &creator_reward_account,
+            &farm.pool_reward_token_account,
+            &farm.pool_lp_token_account,
+            &self.lp_mint,
+            &self.program_data,
+            &spl_token::id(),
+            amount,
+            &self.program_id,
+        )
+        .unwrap();
+        let creator = Keypair::from_bytes(&farm.creator.to_bytes()).unwrap();
+        self.process(&[instruction], &[&creator]).await
+    }
+
+    /// Creates a wallet holding `amount` LP tokens, staked in `farm` by
+    /// [build_first_deposit], with the second reward accounts of a
+    /// dual-reward farm
+    pub async fn create_staker(
+        &mut self,
+        farm: &TestFarm,
+        amount: u64,
+    ) -> Result<Keypair, BanksClientError> {
+        let wallet = self.create_wallet().await;
+        let lp_mint = self.lp_mint;
+        let user_lp_token_account = self.create_ata(&wallet.pubkey(), &lp_mint).await;
+        self.mint_to(&lp_mint, &user_lp_token_account, amount).await;
+        let farm_pool = self.farm_pool(&farm.farm_id).await;
+        let first_deposit = build_first_deposit(
+            &farm.farm_id,
+            &farm_pool,
+            &wallet.pubkey(),
+            &self.program_data,
+            FirstDepositOptions::default(),
+            amount,
+            &self.program_id,
+        )
+        .unwrap();
+        let mut instructions = first_deposit.instructions;
+        if farm.pool_second_reward_token_account.is_some() {
+            let second_reward_mint = self.second_reward_mint;
+            let fee_owner = self.fee_owner.pubkey();
+            self.create_ata(&wallet.pubkey(), &second_reward_mint).await;
+            self.create_ata(&fee_owner, &second_reward_mint).await;
+            let farm_pool = self.farm_pool_v2(&farm.farm_id).await;
+            let program_id = self.program_id;
+            let deposit = instructions
+                .iter_mut()
+                .rfind(|instruction| instruction.program_id == program_id)
+                .unwrap();
+            deposit
+                .accounts
+                .extend(second_reward_accounts(&farm_pool, &wallet.pubkey()));
+        }
+        self.process(&instructions, &[&wallet]).await?;
+        Ok(wallet)
+    }
+
+    /// User information of `wallet` in `farm`, in the latest layout
+    pub async fn user_info(&mut self, farm: &TestFarm, wallet: &Pubkey) -> UserInfoV3 {
+        let (user_info_address, _) =
+            find_user_info_address(&self.program_id, &farm.farm_id, wallet);
+        let account = self.get_account(&user_info_address).await;
+        UserInfoVersion::unpack(&account.data)
+            .unwrap()
+            .into_latest()
+    }
+
+    /// Balance of the associated token account of `mint` of `wallet`
+    pub async fn ata_balance(&mut self, wallet: &Pubkey, mint: &Pubkey) -> u64 {
+        let ata = get_associated_token_address_with_program_id(wallet, mint, &spl_token::id());
+        self.token_balance(&ata).await
+    }
+}
+
+/// Account owned by `owner` holding `data`
+fn program_account(data: Vec<u8>, owner: &Pubkey) -> Account {
+    Account {
+        lamports: ACCOUNT_LAMPORTS,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Error of the failing instruction of a transaction
+pub fn instruction_error(result: Result<(), BanksClientError>) -> InstructionError {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, error) => error,
+        error => panic!("unexpected transaction error {:?}", error),
+    }
+}
@@ -0,0 +1,432 @@
+//! Measures the compute units consumed by the farm instructions, from which
+//! the `COMPUTE_UNITS_*` constants of the instruction module are derived.
+//!
+//! The farm program is only metered when it runs as SBF, natively only its
+//! token and system program invocations are:
+//!
+//! ```text
+//! cargo test-sbf --test compute_units -- --ignored --nocapture
+//! ```
+//!
+//! Each instruction is measured alone in a transaction of the bank, after
+//! the instructions of other programs its builder returns, and the derived
+//! constants are printed after the measurements. The test fails when an
+//! estimate or a constant is below its measurement.
+
+mod common;
+
+use common::{FarmTestContext, TestFarm, START_TIMESTAMP};
+use cropper_farm_v1::instruction::{
+    accept_super_owner, add_second_reward, create_user_info_account, deposit_with_atas,
+    emergency_withdraw_auto_authority, initialize_program, pay_farm_fee, propose_super_owner,
+    second_reward_accounts, set_allowed_creator, set_farm_fee, set_fee_owner, set_harvest_fee,
+    set_reward_rate, withdraw_with_atas, FarmInstruction, COMPUTE_UNITS_BASE, COMPUTE_UNITS_OTHER,
+    COMPUTE_UNITS_PER_CPI,
+};
+use cropper_farm_v1::state::{find_user_info_address, FarmPool};
+use solana_program::{instruction::Instruction, program_pack::Pack};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use std::convert::{TryFrom, TryInto};
+
+/// Units consumed by an instruction, with the number of program invocations
+/// its estimate accounts for
+struct Measurement {
+    instruction: FarmInstruction,
+    units: u64,
+}
+
+impl Measurement {
+    fn invocations(&self) -> u64 {
+        u64::from(
+            (self.instruction.compute_unit_estimate() - COMPUTE_UNITS_BASE) / COMPUTE_UNITS_PER_CPI,
+        )
+    }
+}
+
+/// Measurements of the farm instructions, and of the instructions of other
+/// programs returned by their builders
+#[derive(Default)]
+struct Measurements {
+    farm: Vec<Measurement>,
+    other: Vec<u64>,
+}
+
+/// Units consumed by `instruction` alone in a transaction, processed once
+/// measured
+async fn units(test: &mut FarmTestContext, instruction: &Instruction, signers: &[&Keypair]) -> u64 {
+    let payer = &test.context.payer;
+    let blockhash = test
+        .context
+        .banks_client
+        .get_latest_blockhash()
+        .await
+        .unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        std::slice::from_ref(instruction),
+        Some(&payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    let simulation = test
+        .context
+        .banks_client
+        .simulate_transaction(transaction.clone())
+        .await
+        .unwrap();
+    simulation.result.unwrap().unwrap();
+    test.context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    simulation.simulation_details.unwrap().units_consumed
+}
+
+/// Measures the instructions of other programs in `instructions`, e.g. the
+/// associated token account creations, then the farm instruction, each in
+/// a transaction of its own signed by `signers`
+async fn measure(
+    test: &mut FarmTestContext,
+    instructions: Vec<Instruction>,
+    signers: &[&Keypair],
+    measurements: &mut Measurements,
+) {
+    let program_id = test.program_id;
+    let (farm, other): (Vec<_>, Vec<_>) = instructions
+        .into_iter()
+        .partition(|instruction| instruction.program_id == program_id);
+    for instruction in other.iter() {
+        let units = units(test, instruction, signers).await;
+        measurements.other.push(units);
+    }
+    let [instruction]: [Instruction; 1] = farm.try_into().unwrap();
+    let units = units(test, &instruction, signers).await;
+    let instruction = FarmInstruction::try_from(instruction.data.as_slice()).unwrap();
+    measurements.farm.push(Measurement { instruction, units });
+}
+
+/// Harvests the rewards of `wallet` in `farm`, depositing `amount` more LP
+/// tokens, with the second reward accounts of a dual-reward farm
+async fn deposit(
+    test: &mut FarmTestContext,
+    farm: &TestFarm,
+    wallet: &Keypair,
+    amount: u64,
+    measurements: &mut Measurements,
+) {
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let mut instructions = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        true,
+        amount,
+        &test.program_id,
+    )
+    .unwrap();
+    if farm.pool_second_reward_token_account.is_some() {
+        let farm_pool = test.farm_pool_v2(&farm.farm_id).await;
+        instructions
+            .last_mut()
+            .unwrap()
+            .accounts
+            .extend(second_reward_accounts(&farm_pool, &wallet.pubkey()));
+    }
+    measure(test, instructions, &[wallet], measurements).await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn measure_compute_units() {
+    let mut test = FarmTestContext::new().await;
+    let mut measurements = Measurements::default();
+    let program_id = test.program_id;
+    let program_data = test.program_data;
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+
+    // program data
+    let instruction = initialize_program(
+        &program_data,
+        &super_owner.pubkey(),
+        test.fee_owner.pubkey(),
+        test.allowed_creator.pubkey(),
+        test.amm_program_id,
+        common::FARM_FEE,
+        common::HARVEST_FEE_NUMERATOR,
+        common::HARVEST_FEE_DENOMINATOR,
+        &program_id,
+    )
+    .unwrap();
+    measure(
+        &mut test,
+        vec![instruction],
+        &[&super_owner],
+        &mut measurements,
+    )
+    .await;
+    let fee_owner = test.fee_owner.pubkey();
+    let allowed_creator = test.allowed_creator.pubkey();
+    let instructions = [
+        set_fee_owner(
+            &program_data,
+            &super_owner.pubkey(),
+            &fee_owner,
+            &program_id,
+        ),
+        set_allowed_creator(
+            &program_data,
+            &super_owner.pubkey(),
+            &allowed_creator,
+            &program_id,
+        ),
+        set_farm_fee(
+            &program_data,
+            &super_owner.pubkey(),
+            common::FARM_FEE,
+            &program_id,
+        ),
+        set_harvest_fee(
+            &program_data,
+            &super_owner.pubkey(),
+            common::HARVEST_FEE_NUMERATOR,
+            common::HARVEST_FEE_DENOMINATOR,
+            &program_id,
+        ),
+    ];
+    for instruction in instructions {
+        measure(
+            &mut test,
+            vec![instruction.unwrap()],
+            &[&super_owner],
+            &mut measurements,
+        )
+        .await;
+    }
+    let new_super_owner = test.create_wallet().await;
+    let instruction = propose_super_owner(
+        &program_data,
+        &super_owner.pubkey(),
+        &new_super_owner.pubkey(),
+        &program_id,
+    )
+    .unwrap();
+    measure(
+        &mut test,
+        vec![instruction],
+        &[&super_owner],
+        &mut measurements,
+    )
+    .await;
+    let instruction =
+        accept_super_owner(&program_data, &new_super_owner.pubkey(), &program_id).unwrap();
+    measure(
+        &mut test,
+        vec![instruction],
+        &[&new_super_owner],
+        &mut measurements,
+    )
+    .await;
+
+    // farm creation, by another creator paying the farm fee
+    let start = START_TIMESTAMP as u64;
+    let creator = test.create_wallet().await;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPool::LEN, start, start + 86_400)
+        .await
+        .unwrap();
+    let fee_mint = test.reward_mint;
+    let creator_fee_account = test.create_ata(&farm.creator.pubkey(), &fee_mint).await;
+    test.mint_to(&fee_mint, &creator_fee_account, common::FARM_FEE)
+        .await;
+    let fee_ata = test.create_ata(&fee_owner, &fee_mint).await;
+    let instruction = pay_farm_fee(
+        &farm.farm_id,
+        &farm.authority,
+        &farm.creator.pubkey(),
+        &creator_fee_account,
+        &fee_ata,
+        &program_data,
+        &spl_token::id(),
+        common::FARM_FEE,
+        &program_id,
+    )
+    .unwrap();
+    measure(
+        &mut test,
+        vec![instruction],
+        &[&farm.creator],
+        &mut measurements,
+    )
+    .await;
+    let instruction = set_reward_rate(
+        &farm.farm_id,
+        &farm.authority,
+        &farm.creator.pubkey(),
+        &farm.pool_lp_token_account,
+        &farm.pool_reward_token_account,
+        &program_data,
+        0,
+        &program_id,
+    )
+    .unwrap();
+    measure(
+        &mut test,
+        vec![instruction],
+        &[&farm.creator],
+        &mut measurements,
+    )
+    .await;
+
+    // staking in a funded single reward farm
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * 86_400).await.unwrap();
+    let wallet = test.create_wallet().await;
+    let instruction =
+        create_user_info_account(&farm.farm_id, &wallet.pubkey(), &program_id).unwrap();
+    measure(&mut test, vec![instruction], &[&wallet], &mut measurements).await;
+    let lp_mint = test.lp_mint;
+    let user_lp_token_account = test.create_ata(&wallet.pubkey(), &lp_mint).await;
+    test.mint_to(&lp_mint, &user_lp_token_account, 1_000).await;
+    deposit(&mut test, &farm, &wallet, 1_000, &mut measurements).await;
+    test.set_clock(START_TIMESTAMP + 100);
+    deposit(&mut test, &farm, &wallet, 0, &mut measurements).await;
+    test.set_clock(START_TIMESTAMP + 200);
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let instructions = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &program_data,
+        false,
+        400,
+        &program_id,
+    )
+    .unwrap();
+    measure(&mut test, instructions, &[&wallet], &mut measurements).await;
+    let (user_info_address, _) =
+        find_user_info_address(&program_id, &farm.farm_id, &wallet.pubkey());
+    let instruction = emergency_withdraw_auto_authority(
+        &farm.farm_id,
+        &wallet.pubkey(),
+        &user_info_address,
+        &user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &program_id,
+    )
+    .unwrap();
+    measure(&mut test, vec![instruction], &[&wallet], &mut measurements).await;
+
+    // harvesting both rewards of a dual-reward farm
+    let farm = test.create_dual_reward_farm().await;
+    test.add_reward(&farm, 1_000 * 86_400).await.unwrap();
+    let second_reward_mint = test.second_reward_mint;
+    let creator = farm.creator.pubkey();
+    let creator_second_reward_account = test.create_ata(&creator, &second_reward_mint).await;
+    test.mint_to(
+        &second_reward_mint,
+        &creator_second_reward_account,
+        2 * 86_400,
+    )
+    .await;
+    let instruction = add_second_reward(
+        &farm.farm_id,
+        &farm.authority,
+        &creator,
+        &creator_second_reward_account,
+        &farm.pool_second_reward_token_account.unwrap(),
+        &farm.pool_lp_token_account,
+        &test.lp_mint,
+        &program_data,
+        &spl_token::id(),
+        2 * 86_400,
+        &program_id,
+    )
+    .unwrap();
+    measure(
+        &mut test,
+        vec![instruction],
+        &[&farm.creator],
+        &mut measurements,
+    )
+    .await;
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    test.create_ata(&fee_owner, &second_reward_mint).await;
+    test.set_clock(START_TIMESTAMP + 300);
+    deposit(&mut test, &farm, &wallet, 0, &mut measurements).await;
+
+    report(&measurements);
+}
+
+/// Prints the measurements and the constants derived from them, failing
+/// when an estimate is below its measurement
+fn report(measurements: &Measurements) {
+    println!(
+        "{:<32}{:>12}{:>10}{:>10}",
+        "instruction", "invocations", "units", "estimate"
+    );
+    for measurement in measurements.farm.iter() {
+        println!(
+            "{:<32}{:>12}{:>10}{:>10}",
+            format!("{:?}", measurement.instruction)
+                .split(['(', ' '])
+                .next()
+                .unwrap(),
+            measurement.invocations(),
+            measurement.units,
+            measurement.instruction.compute_unit_estimate(),
+        );
+    }
+    println!(
+        "{:<32}{:>12}{:>10}",
+        "other programs",
+        "",
+        measurements.other.iter().max().unwrap()
+    );
+
+    // the base is the most expensive instruction invoking no program, an
+    // invocation the most the other instructions spend over the base
+    let base = measurements
+        .farm
+        .iter()
+        .filter(|measurement| measurement.invocations() == 0)
+        .map(|measurement| measurement.units)
+        .max()
+        .unwrap();
+    let per_cpi = measurements
+        .farm
+        .iter()
+        .filter(|measurement| measurement.invocations() > 0)
+        .map(|measurement| {
+            let invocations = measurement.invocations();
+            measurement.units.saturating_sub(base).div_ceil(invocations)
+        })
+        .max()
+        .unwrap();
+    println!("COMPUTE_UNITS_BASE >= {}", base);
+    println!("COMPUTE_UNITS_PER_CPI >= {}", per_cpi);
+    println!(
+        "COMPUTE_UNITS_OTHER >= {}",
+        measurements.other.iter().max().unwrap()
+    );
+    assert!(u64::from(COMPUTE_UNITS_BASE) >= base);
+    assert!(u64::from(COMPUTE_UNITS_PER_CPI) >= per_cpi);
+
+    for measurement in measurements.farm.iter() {
+        assert!(
+            u64::from(measurement.instruction.compute_unit_estimate()) >= measurement.units,
+            "{:?} estimate is below the {} units measured",
+            measurement.instruction,
+            measurement.units
+        );
+    }
+    for units in measurements.other.iter() {
+        assert!(u64::from(COMPUTE_UNITS_OTHER) >= *units);
+    }
+}
@@ -0,0 +1,1889 @@
+//! Builders of the farm instructions submitted to the farm program in a
+//! `ProgramTest` bank, see [common::FarmTestContext]
+
+mod common;
+
+use common::{
+    instruction_error, FarmTestContext, TestFarm, FARM_FEE, HARVEST_FEE_DENOMINATOR,
+    HARVEST_FEE_NUMERATOR, START_TIMESTAMP,
+};
+use cropper_farm_v1::{
+    error::FarmError,
+    instruction::{
+        accept_super_owner, add_second_reward, close_dual_reward_farm, close_farm,
+        create_user_info_account, deposit_with_atas, deposit_with_referral,
+        emergency_withdraw_auto_authority, get_associated_token_address_with_program_id,
+        initialize_program, pay_farm_fee, propose_super_owner, second_reward_accounts,
+        set_allowed_creator, set_boost_params, set_farm_fee, set_farm_period, set_fee_owner,
+        set_harvest_fee, set_reward_rate, with_compute_budget, withdraw_with_atas, FarmInstruction,
+    },
+    logs::FARM_LOG_PREFIX,
+    state::{
+        find_user_info_address, FarmPool, FarmPoolV2, FarmProgramData, UserInfoV2, UserInfoV3,
+    },
+};
+use solana_program::{
+    instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_instruction,
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token::state::Mint;
+
+/// Day long farm period of [FarmTestContext::create_farm]
+const FARM_DURATION: u64 = 86_400;
+
+fn farm_error(error: FarmError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+#[tokio::test]
+async fn test_context() {
+    let mut test = FarmTestContext::new().await;
+
+    let program_data = test.program_data().await;
+    assert_eq!(program_data.super_owner, test.super_owner.pubkey());
+    assert_eq!(program_data.fee_owner, test.fee_owner.pubkey());
+    assert_eq!(program_data.allowed_creator, test.allowed_creator.pubkey());
+    assert_eq!(program_data.amm_program_id, test.amm_program_id);
+    assert_eq!(program_data.farm_fee, FARM_FEE);
+
+    let amm_id = test.amm_id;
+    assert_eq!(test.get_account(&amm_id).await.owner, test.amm_program_id);
+    let lp_mint = test.lp_mint;
+    let mint = Mint::unpack(&test.get_account(&lp_mint).await.data).unwrap();
+    assert_eq!(mint.mint_authority.unwrap(), test.mint_authority.pubkey());
+
+    let wallet = test.create_wallet().await;
+    let ata = test.create_ata(&wallet.pubkey(), &lp_mint).await;
+    test.mint_to(&lp_mint, &ata, 1_000).await;
+    let account = test.token_account(&ata).await;
+    assert_eq!(account.owner, wallet.pubkey());
+    assert_eq!(account.amount, 1_000);
+}
+
+#[tokio::test]
+async fn test_set_program_data() {
+    let mut test = FarmTestContext::new().await;
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+    let fee_owner = Pubkey::new_unique();
+    let allowed_creator = Pubkey::new_unique();
+    let amm_program_id = Pubkey::new_unique();
+
+    let instruction = initialize_program(
+        &test.program_data,
+        &super_owner.pubkey(),
+        fee_owner,
+        allowed_creator,
+        amm_program_id,
+        1_000,
+        3,
+        1_000,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[instruction], &[&super_owner]).await.unwrap();
+    assert_eq!(
+        test.program_data().await,
+        FarmProgramData {
+            is_initialized: true,
+            super_owner: super_owner.pubkey(),
+            fee_owner,
+            allowed_creator,
+            amm_program_id,
+            farm_fee: 1_000,
+            harvest_fee_numerator: 3,
+            harvest_fee_denominator: 1_000,
+            pending_super_owner: Pubkey::default(),
+        }
+    );
+
+    // only the stored super owner updates the program data
+    let other = test.create_wallet().await;
+    let instruction = initialize_program(
+        &test.program_data,
+        &other.pubkey(),
+        other.pubkey(),
+        other.pubkey(),
+        amm_program_id,
+        0,
+        0,
+        1,
+        &test.program_id,
+    )
+    .unwrap();
+    assert_eq!(
+        instruction_error(test.process(&[instruction], &[&other]).await),
+        farm_error(FarmError::NotAllowed)
+    );
+    assert_eq!(test.program_data().await.fee_owner, fee_owner);
+}
+
+#[tokio::test]
+async fn test_set_program_data_initializes() {
+    let mut test = FarmTestContext::without_program_data().await;
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+    let (fee_owner, allowed_creator) = (test.fee_owner.pubkey(), test.allowed_creator.pubkey());
+    let (amm_program_id, program_id) = (test.amm_program_id, test.program_id);
+    let set_program_data = |program_data: &Pubkey, super_owner: &Pubkey| {
+        initialize_program(
+            program_data,
+            super_owner,
+            fee_owner,
+            allowed_creator,
+            amm_program_id,
+            FARM_FEE,
+            HARVEST_FEE_NUMERATOR,
+            HARVEST_FEE_DENOMINATOR,
+            &program_id,
+        )
+        .unwrap()
+    };
+
+    // a program owned account at any other address is not the program data
+    let other = Keypair::new();
+    let rent = test.context.banks_client.get_rent().await.unwrap();
+    let create = system_instruction::create_account(
+        &test.context.payer.pubkey(),
+        &other.pubkey(),
+        rent.minimum_balance(FarmProgramData::LEN),
+        FarmProgramData::LEN as u64,
+        &test.program_id,
+    );
+    let instruction = set_program_data(&other.pubkey(), &super_owner.pubkey());
+    let result = test
+        .process(&[create, instruction], &[&other, &super_owner])
+        .await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidSeeds);
+
+    // only the upgrade authority of the program creates it, before anybody
+    // else can
+    let intruder = test.create_wallet().await;
+    let instruction = set_program_data(&test.program_data, &intruder.pubkey());
+    let result = test.process(&[instruction], &[&intruder]).await;
+    assert_eq!(instruction_error(result), farm_error(FarmError::NotAllowed));
+    let mut instruction = set_program_data(&test.program_data, &intruder.pubkey());
+    instruction.accounts[4].pubkey = test.create_wallet().await.pubkey();
+    let result = test.process(&[instruction], &[&intruder]).await;
+    assert_eq!(
+        instruction_error(result),
+        InstructionError::InvalidAccountData
+    );
+
+    let instruction = set_program_data(&test.program_data, &super_owner.pubkey());
+    test.process(&[instruction], &[&super_owner]).await.unwrap();
+    let program_data = test.program_data().await;
+    assert_eq!(program_data.super_owner, super_owner.pubkey());
+    assert_eq!(program_data.farm_fee, FARM_FEE);
+
+    // once created, only the super owner sets it
+    let instruction = set_program_data(&test.program_data, &intruder.pubkey());
+    let result = test.process(&[instruction], &[&intruder]).await;
+    assert_eq!(instruction_error(result), farm_error(FarmError::NotAllowed));
+}
+
+#[tokio::test]
+async fn test_set_program_data_prefunded_address() {
+    let mut test = FarmTestContext::without_program_data().await;
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+
+    // lamports sent to the program data address do not block its creation
+    let rent = test.context.banks_client.get_rent().await.unwrap();
+    let payer = test.context.payer.pubkey();
+    let transfer =
+        system_instruction::transfer(&payer, &test.program_data, rent.minimum_balance(0));
+    test.process(&[transfer], &[]).await.unwrap();
+
+    let instruction = initialize_program(
+        &test.program_data,
+        &super_owner.pubkey(),
+        test.fee_owner.pubkey(),
+        test.allowed_creator.pubkey(),
+        test.amm_program_id,
+        FARM_FEE,
+        HARVEST_FEE_NUMERATOR,
+        HARVEST_FEE_DENOMINATOR,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[instruction], &[&super_owner]).await.unwrap();
+
+    let program_data = test.program_data;
+    let account = test.get_account(&program_data).await;
+    assert_eq!(account.owner, test.program_id);
+    assert!(rent.is_exempt(account.lamports, FarmProgramData::LEN));
+    assert_eq!(test.program_data().await.super_owner, super_owner.pubkey());
+}
+
+#[tokio::test]
+async fn test_super_owner_rotation() {
+    let mut test = FarmTestContext::new().await;
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+    let new_super_owner = test.create_wallet().await;
+
+    let propose = propose_super_owner(
+        &test.program_data,
+        &super_owner.pubkey(),
+        &new_super_owner.pubkey(),
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[propose], &[&super_owner]).await.unwrap();
+    assert_eq!(
+        test.program_data().await.pending_super_owner,
+        new_super_owner.pubkey()
+    );
+
+    let accept = accept_super_owner(
+        &test.program_data,
+        &new_super_owner.pubkey(),
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[accept], &[&new_super_owner]).await.unwrap();
+    let program_data = test.program_data().await;
+    assert_eq!(program_data.super_owner, new_super_owner.pubkey());
+    assert_eq!(program_data.pending_super_owner, Pubkey::default());
+}
+
+#[tokio::test]
+async fn test_program_data_setters() {
+    let mut test = FarmTestContext::new().await;
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+    let fee_owner = Pubkey::new_unique();
+    let allowed_creator = Pubkey::new_unique();
+
+    let instructions = [
+        set_fee_owner(
+            &test.program_data,
+            &super_owner.pubkey(),
+            &fee_owner,
+            &test.program_id,
+        )
+        .unwrap(),
+        set_allowed_creator(
+            &test.program_data,
+            &super_owner.pubkey(),
+            &allowed_creator,
+            &test.program_id,
+        )
+        .unwrap(),
+        set_farm_fee(
+            &test.program_data,
+            &super_owner.pubkey(),
+            7_000,
+            &test.program_id,
+        )
+        .unwrap(),
+        set_harvest_fee(
+            &test.program_data,
+            &super_owner.pubkey(),
+            2,
+            1_000,
+            &test.program_id,
+        )
+        .unwrap(),
+    ];
+    test.process(&instructions, &[&super_owner]).await.unwrap();
+
+    let program_data = test.program_data().await;
+    assert_eq!(program_data.fee_owner, fee_owner);
+    assert_eq!(program_data.allowed_creator, allowed_creator);
+    assert_eq!(program_data.farm_fee, 7_000);
+    assert_eq!(program_data.harvest_fee_numerator, 2);
+    assert_eq!(program_data.harvest_fee_denominator, 1_000);
+}
+
+#[tokio::test]
+async fn test_initialize_farm() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+
+    let account = test.get_account(&farm.farm_id).await;
+    assert_eq!(account.owner, test.program_id);
+    assert_eq!(account.data.len(), FarmPool::LEN);
+    let start = START_TIMESTAMP as u64;
+    assert_eq!(
+        test.farm_pool(&farm.farm_id).await,
+        FarmPool {
+            // created by the allowed creator
+            is_allowed: true,
+            nonce: farm.nonce,
+            pool_lp_token_account: farm.pool_lp_token_account,
+            pool_reward_token_account: farm.pool_reward_token_account,
+            pool_mint_address: test.lp_mint,
+            reward_mint_address: test.reward_mint,
+            token_program_id: spl_token::id(),
+            owner: test.allowed_creator.pubkey(),
+            fee_owner: test.fee_owner.pubkey(),
+            reward_per_share_net: 0,
+            last_timestamp: start,
+            reward_per_timestamp: 0,
+            start_timestamp: start,
+            end_timestamp: start + FARM_DURATION,
+        }
+    );
+
+    // other creators pay the farm fee first
+    let creator = test.create_wallet().await;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPoolV2::LEN, start + 10, start + 20)
+        .await
+        .unwrap();
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    assert!(!farm_pool.is_allowed);
+    assert_eq!(farm_pool.owner, farm.creator.pubkey());
+}
+
+#[tokio::test]
+async fn test_initialize_farm_checks() {
+    let mut test = FarmTestContext::new().await;
+    let start = START_TIMESTAMP as u64;
+
+    // neither of the known layouts
+    let creator = test.create_wallet().await;
+    let result = test
+        .create_farm_with_creator(creator, FarmPool::LEN + 1, start, start + 10)
+        .await;
+    assert_eq!(
+        instruction_error(result.map(|_| ())),
+        InstructionError::InvalidAccountData
+    );
+
+    // the LP tokens must come from the amm of the program data
+    let amm_program_id = Pubkey::new_unique();
+    let super_owner = Keypair::from_bytes(&test.super_owner.to_bytes()).unwrap();
+    let instruction = initialize_program(
+        &test.program_data,
+        &super_owner.pubkey(),
+        test.fee_owner.pubkey(),
+        test.allowed_creator.pubkey(),
+        amm_program_id,
+        FARM_FEE,
+        HARVEST_FEE_NUMERATOR,
+        HARVEST_FEE_DENOMINATOR,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[instruction], &[&super_owner]).await.unwrap();
+    let creator = test.create_wallet().await;
+    let result = test
+        .create_farm_with_creator(creator, FarmPool::LEN, start, start + 10)
+        .await;
+    assert_eq!(
+        instruction_error(result.map(|_| ())),
+        InstructionError::IncorrectProgramId
+    );
+}
+
+#[tokio::test]
+async fn test_create_user_info() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    let wallet = test.create_wallet().await;
+
+    let instruction =
+        create_user_info_account(&farm.farm_id, &wallet.pubkey(), &test.program_id).unwrap();
+    test.process(&[instruction], &[&wallet]).await.unwrap();
+
+    let (user_info_address, _) =
+        find_user_info_address(&test.program_id, &farm.farm_id, &wallet.pubkey());
+    let account = test.get_account(&user_info_address).await;
+    assert_eq!(account.owner, test.program_id);
+    let user_info = UserInfoV3::unpack(&account.data).unwrap();
+    assert_eq!(user_info.wallet, wallet.pubkey());
+    assert_eq!(user_info.farm_id, farm.farm_id);
+    assert_eq!(user_info.deposit_balance, 0);
+}
+
+#[tokio::test]
+async fn test_pay_farm_fee() {
+    let mut test = FarmTestContext::new().await;
+    let start = START_TIMESTAMP as u64;
+    let creator = test.create_wallet().await;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPool::LEN, start, start + FARM_DURATION)
+        .await
+        .unwrap();
+
+    // staking waits for the farm fee
+    let lp_mint = test.lp_mint;
+    let result = test.create_staker(&farm, 1_000).await;
+    assert_eq!(
+        instruction_error(result.map(|_| ())),
+        farm_error(FarmError::NotAllowed)
+    );
+
+    let fee_mint = test.reward_mint;
+    let creator_fee_account = test.create_ata(&farm.creator.pubkey(), &fee_mint).await;
+    test.mint_to(&fee_mint, &creator_fee_account, FARM_FEE)
+        .await;
+    let fee_owner = test.fee_owner.pubkey();
+    let fee_ata = test.create_ata(&fee_owner, &fee_mint).await;
+    let pay = |amount| {
+        pay_farm_fee(
+            &farm.farm_id,
+            &farm.authority,
+            &farm.creator.pubkey(),
+            &creator_fee_account,
+            &fee_ata,
+            &test.program_data,
+            &spl_token::id(),
+            amount,
+            &test.program_id,
+        )
+        .unwrap()
+    };
+    let (wrong_fee, fee) = (pay(FARM_FEE - 1), pay(FARM_FEE));
+    assert_eq!(
+        instruction_error(test.process(&[wrong_fee], &[&farm.creator]).await),
+        farm_error(FarmError::InvalidFarmFee)
+    );
+    test.process(std::slice::from_ref(&fee), &[&farm.creator])
+        .await
+        .unwrap();
+    assert!(test.farm_pool(&farm.farm_id).await.is_allowed);
+    assert_eq!(test.token_balance(&fee_ata).await, FARM_FEE);
+    assert_eq!(test.token_balance(&creator_fee_account).await, 0);
+
+    // paid once, the compute budget keeps the retry from being a duplicate
+    // of the first payment
+    test.mint_to(&fee_mint, &creator_fee_account, FARM_FEE)
+        .await;
+    let fee = with_compute_budget(vec![fee], &test.program_id, 10);
+    assert_eq!(
+        instruction_error(test.process(&fee, &[&farm.creator]).await),
+        farm_error(FarmError::NotAllowed)
+    );
+
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    assert_eq!(
+        test.user_info(&farm, &wallet.pubkey())
+            .await
+            .deposit_balance,
+        1_000
+    );
+    assert_eq!(test.ata_balance(&wallet.pubkey(), &lp_mint).await, 0);
+}
+
+#[tokio::test]
+async fn test_pay_farm_fee_to_fee_owner() {
+    let mut test = FarmTestContext::new().await;
+    let start = START_TIMESTAMP as u64;
+    let creator = test.create_wallet().await;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPool::LEN, start, start + FARM_DURATION)
+        .await
+        .unwrap();
+
+    let fee_mint = test.reward_mint;
+    let creator_fee_account = test.create_ata(&farm.creator.pubkey(), &fee_mint).await;
+    test.mint_to(&fee_mint, &creator_fee_account, FARM_FEE)
+        .await;
+    let instruction = pay_farm_fee(
+        &farm.farm_id,
+        &farm.authority,
+        &farm.creator.pubkey(),
+        &creator_fee_account,
+        &creator_fee_account,
+        &test.program_data,
+        &spl_token::id(),
+        FARM_FEE,
+        &test.program_id,
+    )
+    .unwrap();
+    assert_eq!(
+        instruction_error(test.process(&[instruction], &[&farm.creator]).await),
+        farm_error(FarmError::WrongFeeAccount)
+    );
+}
+
+#[tokio::test]
+async fn test_pay_farm_fee_rejects_other_program_data() {
+    let mut test = FarmTestContext::new().await;
+    let start = START_TIMESTAMP as u64;
+    let creator = test.create_wallet().await;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPool::LEN, start, start + FARM_DURATION)
+        .await
+        .unwrap();
+    let fee_mint = test.reward_mint;
+    let creator_fee_account = test.create_ata(&farm.creator.pubkey(), &fee_mint).await;
+    test.mint_to(&fee_mint, &creator_fee_account, FARM_FEE)
+        .await;
+
+    // a program owned account created by the creator, holding a config with a
+    // token fee paid back to the creator
+    let fake_program_data = Keypair::new();
+    let rent = test.context.banks_client.get_rent().await.unwrap();
+    let create = system_instruction::create_account(
+        &farm.creator.pubkey(),
+        &fake_program_data.pubkey(),
+        rent.minimum_balance(FarmProgramData::LEN),
+        FarmProgramData::LEN as u64,
+        &test.program_id,
+    );
+    test.process(&[create], &[&farm.creator, &fake_program_data])
+        .await
+        .unwrap();
+    let mut data = vec![0; FarmProgramData::LEN];
+    FarmProgramData::pack(
+        FarmProgramData {
+            fee_owner: farm.creator.pubkey(),
+            farm_fee: 1,
+            ..test.program_data().await
+        },
+        &mut data,
+    )
+    .unwrap();
+    let program_id = test.program_id;
+    test.set_account(&fake_program_data.pubkey(), data, &program_id);
+
+    let instruction = pay_farm_fee(
+        &farm.farm_id,
+        &farm.authority,
+        &farm.creator.pubkey(),
+        &creator_fee_account,
+        &creator_fee_account,
+        &fake_program_data.pubkey(),
+        &spl_token::id(),
+        1,
+        &test.program_id,
+    )
+    .unwrap();
+    assert_eq!(
+        instruction_error(test.process(&[instruction], &[&farm.creator]).await),
+        InstructionError::InvalidSeeds
+    );
+    assert!(!test.farm_pool(&farm.farm_id).await.is_allowed);
+    assert_eq!(test.token_balance(&creator_fee_account).await, FARM_FEE);
+}
+
+#[tokio::test]
+async fn test_add_reward() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+
+    let reward_mint = test.reward_mint;
+    let creator = farm.creator.pubkey();
+    test.add_reward(&farm, 2 * FARM_DURATION + 1).await.unwrap();
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    // the rounding remainder stays with the creator
+    assert_eq!(farm_pool.reward_per_timestamp, 2);
+    assert_eq!(
+        test.token_balance(&farm.pool_reward_token_account).await,
+        2 * FARM_DURATION
+    );
+    assert_eq!(test.ata_balance(&creator, &reward_mint).await, 1);
+
+    // half way, the rest of the farm gets the added reward
+    test.set_clock(START_TIMESTAMP + FARM_DURATION as i64 / 2);
+    test.add_reward(&farm, FARM_DURATION - 1).await.unwrap();
+    assert_eq!(test.farm_pool(&farm.farm_id).await.reward_per_timestamp, 3);
+    assert_eq!(
+        test.token_balance(&farm.pool_reward_token_account).await,
+        2 * FARM_DURATION + FARM_DURATION / 2
+    );
+    assert_eq!(
+        test.ata_balance(&creator, &reward_mint).await,
+        1 + FARM_DURATION / 2 - 1
+    );
+
+    // a reward below one token per remaining second adds nothing
+    assert_eq!(
+        instruction_error(test.add_reward(&farm, FARM_DURATION / 2 - 1).await),
+        farm_error(FarmError::RewardBelowRate)
+    );
+    assert_eq!(test.farm_pool(&farm.farm_id).await.reward_per_timestamp, 3);
+
+    // only the creator adds rewards, before the end of the farm
+    let other = TestFarm {
+        creator: test.create_wallet().await,
+        ..farm
+    };
+    assert_eq!(
+        instruction_error(test.add_reward(&other, 1_000).await),
+        farm_error(FarmError::NotAllowed)
+    );
+    let farm = TestFarm {
+        creator: Keypair::from_bytes(&test.allowed_creator.to_bytes()).unwrap(),
+        ..other
+    };
+    test.set_clock(START_TIMESTAMP + FARM_DURATION as i64);
+    assert_eq!(
+        instruction_error(test.add_reward(&farm, 1_000).await),
+        farm_error(FarmError::FarmEnded)
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_harvest_withdraw() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    // 1_000 rewards per second
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let lp_mint = test.lp_mint;
+    let reward_mint = test.reward_mint;
+    let fee_owner = test.fee_owner.pubkey();
+
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let user_info = test.user_info(&farm, &wallet.pubkey()).await;
+    assert_eq!(user_info.deposit_balance, 1_000);
+    assert_eq!(user_info.reward_debt, 0);
+    assert_eq!(test.token_balance(&farm.pool_lp_token_account).await, 1_000);
+
+    // harvest: 100 seconds alone in the farm, less the 1% harvest fee
+    test.set_clock(START_TIMESTAMP + 100);
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let harvest = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        0,
+        &test.program_id,
+    )
+    .unwrap();
+    let logs = test.process_with_logs(&harvest, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        99_000
+    );
+    // the harvest is logged by the farm program, net of the fee
+    let harvest_log = format!(
+        "Program log: {} harvest reward=99000 farm={}",
+        FARM_LOG_PREFIX, farm.farm_id
+    );
+    let logged = logs.iter().position(|line| *line == harvest_log).unwrap();
+    let invoke = format!("Program {} invoke [1]", test.program_id);
+    let success = format!("Program {} success", test.program_id);
+    assert!(logs[..logged].contains(&invoke));
+    assert!(logs[logged..].contains(&success));
+    assert_eq!(
+        logs.iter().filter(|line| line.contains("harvest")).count(),
+        1
+    );
+    assert_eq!(test.ata_balance(&fee_owner, &reward_mint).await, 1_000);
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    assert_eq!(farm_pool.last_timestamp, START_TIMESTAMP as u64 + 100);
+    assert_eq!(
+        farm_pool.reward_per_share_net,
+        100 * 1_000_000_000_000 / 1_000
+    );
+
+    // a second staker shares the next 100 seconds
+    let other = test.create_staker(&farm, 3_000).await.unwrap();
+    test.set_clock(START_TIMESTAMP + 200);
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        400,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&withdraw, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        99_000 + 24_750
+    );
+    assert_eq!(test.ata_balance(&wallet.pubkey(), &lp_mint).await, 400);
+    assert_eq!(
+        test.user_info(&farm, &wallet.pubkey())
+            .await
+            .deposit_balance,
+        600
+    );
+    assert_eq!(test.token_balance(&farm.pool_lp_token_account).await, 3_600);
+
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &other.pubkey(),
+        &test.program_data,
+        false,
+        3_000,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&withdraw, &[&other]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&other.pubkey(), &reward_mint).await,
+        74_250
+    );
+    assert_eq!(test.ata_balance(&other.pubkey(), &lp_mint).await, 3_000);
+    assert_eq!(
+        test.user_info(&farm, &other.pubkey()).await.deposit_balance,
+        0
+    );
+
+    // more than the deposit
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        601,
+        &test.program_id,
+    )
+    .unwrap();
+    assert_eq!(
+        instruction_error(test.process(&withdraw, &[&wallet]).await),
+        InstructionError::InsufficientFunds
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_with_referral() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let (lp_mint, reward_mint) = (test.lp_mint, test.reward_mint);
+    let fee_owner = test.fee_owner.pubkey();
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let referral = Pubkey::new_unique();
+    let referral_reward_token_account = test.create_ata(&referral, &reward_mint).await;
+    let other_reward_token_account = test.create_ata(&Pubkey::new_unique(), &reward_mint).await;
+    let user_lp_token_account = test.create_ata(&wallet.pubkey(), &lp_mint).await;
+    test.mint_to(&lp_mint, &user_lp_token_account, 500).await;
+    let ata = |owner: &Pubkey| {
+        get_associated_token_address_with_program_id(owner, &reward_mint, &spl_token::id())
+    };
+    let deposit = |referral_reward_token_account: &Pubkey, amount| {
+        deposit_with_referral(
+            &farm.farm_id,
+            &farm.authority,
+            &wallet.pubkey(),
+            &find_user_info_address(&test.program_id, &farm.farm_id, &wallet.pubkey()).0,
+            &user_lp_token_account,
+            &farm.pool_lp_token_account,
+            &ata(&wallet.pubkey()),
+            &farm.pool_reward_token_account,
+            &lp_mint,
+            &ata(&fee_owner),
+            &test.program_data,
+            &spl_token::id(),
+            &referral,
+            referral_reward_token_account,
+            amount,
+            &test.program_id,
+        )
+        .unwrap()
+    };
+    let (other, with_referral) = (
+        deposit(&other_reward_token_account, 500),
+        deposit(&referral_reward_token_account, 500),
+    );
+
+    // the referral reward token account must be owned by the referral
+    test.set_clock(START_TIMESTAMP + 100);
+    assert_eq!(
+        instruction_error(test.process(&[other], &[&wallet]).await),
+        InstructionError::InvalidAccountData
+    );
+
+    // the referral gets half the 1% harvest fee of the 100_000 rewards
+    test.process(&[with_referral], &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        99_000
+    );
+    assert_eq!(
+        test.token_balance(&referral_reward_token_account).await,
+        500
+    );
+    assert_eq!(test.ata_balance(&fee_owner, &reward_mint).await, 500);
+    assert_eq!(
+        test.user_info(&farm, &wallet.pubkey())
+            .await
+            .deposit_balance,
+        1_500
+    );
+    assert_eq!(test.token_balance(&farm.pool_lp_token_account).await, 1_500);
+}
+
+#[tokio::test]
+async fn test_deposit_requires_lp_owner() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    let lp_mint = test.lp_mint;
+
+    // a wallet left an approval of its LP tokens to the farm authority
+    let victim = test.create_wallet().await;
+    let victim_lp_token_account = test.create_ata(&victim.pubkey(), &lp_mint).await;
+    test.mint_to(&lp_mint, &victim_lp_token_account, 500).await;
+    let approve = spl_token::instruction::approve(
+        &spl_token::id(),
+        &victim_lp_token_account,
+        &farm.authority,
+        &victim.pubkey(),
+        &[],
+        500,
+    )
+    .unwrap();
+    test.process(&[approve], &[&victim]).await.unwrap();
+
+    // another staker cannot deposit these LP tokens as its own
+    let attacker = test.create_staker(&farm, 1).await.unwrap();
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let mut instructions = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &attacker.pubkey(),
+        &test.program_data,
+        false,
+        500,
+        &test.program_id,
+    )
+    .unwrap();
+    instructions.last_mut().unwrap().accounts[4].pubkey = victim_lp_token_account;
+    assert_eq!(
+        instruction_error(test.process(&instructions, &[&attacker]).await),
+        InstructionError::Custom(spl_token::error::TokenError::OwnerMismatch as u32)
+    );
+    assert_eq!(test.token_balance(&victim_lp_token_account).await, 500);
+    assert_eq!(
+        test.user_info(&farm, &attacker.pubkey())
+            .await
+            .deposit_balance,
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_period() {
+    let mut test = FarmTestContext::new().await;
+    let start = START_TIMESTAMP as u64;
+    let creator = Keypair::from_bytes(&test.allowed_creator.to_bytes()).unwrap();
+    let farm = test
+        .create_farm_with_creator(creator, FarmPool::LEN, start + 100, start + 200)
+        .await
+        .unwrap();
+
+    let result = test.create_staker(&farm, 1_000).await;
+    assert_eq!(
+        instruction_error(result.map(|_| ())),
+        farm_error(FarmError::FarmNotStarted)
+    );
+
+    test.set_clock(START_TIMESTAMP + 200);
+    let result = test.create_staker(&farm, 1_000).await;
+    assert_eq!(
+        instruction_error(result.map(|_| ())),
+        farm_error(FarmError::FarmEnded)
+    );
+}
+
+#[tokio::test]
+async fn test_set_farm_period() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    // 1_000 rewards per second
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let reward_mint = test.reward_mint;
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let (program_data, program_id) = (test.program_data, test.program_id);
+    let start = START_TIMESTAMP as u64;
+    let set_period = |start_timestamp, end_timestamp| {
+        set_farm_period(
+            &farm.farm_id,
+            &farm.authority,
+            &farm.creator.pubkey(),
+            &farm.pool_lp_token_account,
+            &program_data,
+            start_timestamp,
+            end_timestamp,
+            &program_id,
+        )
+        .unwrap()
+    };
+
+    // 100 seconds accrued, then the farm ends 50 seconds later
+    test.set_clock(START_TIMESTAMP + 100);
+    for (start_timestamp, end_timestamp) in [(start, start + 100), (start + 1, start + 150)] {
+        assert_eq!(
+            instruction_error(
+                test.process(
+                    &[set_period(start_timestamp, end_timestamp)],
+                    &[&farm.creator]
+                )
+                .await
+            ),
+            farm_error(FarmError::InvalidFarmPeriod)
+        );
+    }
+    test.process(&[set_period(start, start + 150)], &[&farm.creator])
+        .await
+        .unwrap();
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    assert_eq!(farm_pool.end_timestamp, start + 150);
+    assert_eq!(farm_pool.last_timestamp, start + 100);
+
+    // once over, extending the farm does not pay for the 50 seconds it was
+    // ended, the rewards accrued before both changes being kept
+    test.set_clock(START_TIMESTAMP + 200);
+    test.process(&[set_period(start, start + 300)], &[&farm.creator])
+        .await
+        .unwrap();
+    test.set_clock(START_TIMESTAMP + 250);
+    let harvest = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        0,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&harvest, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        200_000 - 2_000
+    );
+}
+
+#[tokio::test]
+async fn test_set_reward_rate_requires_funded_vault() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    let (program_data, program_id) = (test.program_data, test.program_id);
+    let set_rate = |reward_per_second| {
+        set_reward_rate(
+            &farm.farm_id,
+            &farm.authority,
+            &farm.creator.pubkey(),
+            &farm.pool_lp_token_account,
+            &farm.pool_reward_token_account,
+            &program_data,
+            reward_per_second,
+            &program_id,
+        )
+        .unwrap()
+    };
+
+    // nothing in the vault
+    assert_eq!(
+        instruction_error(test.process(&[set_rate(1_000)], &[&farm.creator]).await),
+        farm_error(FarmError::InsufficientReward)
+    );
+
+    // the vault funds 1_000 rewards per second for the whole farm
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    assert_eq!(
+        instruction_error(test.process(&[set_rate(1_001)], &[&farm.creator]).await),
+        farm_error(FarmError::InsufficientReward)
+    );
+    test.process(&[set_rate(500)], &[&farm.creator])
+        .await
+        .unwrap();
+
+    // half way, the vault funds twice the rate for the rest of the farm
+    test.set_clock(START_TIMESTAMP + FARM_DURATION as i64 / 2);
+    test.process(&[set_rate(2_000)], &[&farm.creator])
+        .await
+        .unwrap();
+    assert_eq!(
+        test.farm_pool(&farm.farm_id).await.reward_per_timestamp,
+        2_000
+    );
+}
+
+#[tokio::test]
+async fn test_set_reward_rate_keeps_unharvested_rewards() {
+    let mut test = FarmTestContext::new().await;
+    let creator = Keypair::from_bytes(&test.allowed_creator.to_bytes()).unwrap();
+    let start = START_TIMESTAMP as u64;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPoolV2::LEN, start, start + FARM_DURATION)
+        .await
+        .unwrap();
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let reward_mint = test.reward_mint;
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let (program_data, program_id) = (test.program_data, test.program_id);
+    let set_rate = |reward_per_second| {
+        set_reward_rate(
+            &farm.farm_id,
+            &farm.authority,
+            &farm.creator.pubkey(),
+            &farm.pool_lp_token_account,
+            &farm.pool_reward_token_account,
+            &program_data,
+            reward_per_second,
+            &program_id,
+        )
+        .unwrap()
+    };
+
+    // half way, the vault holds the rewards of the first half for the
+    // staker, the other half funding the same rate only
+    let half = FARM_DURATION / 2;
+    test.set_clock(START_TIMESTAMP + half as i64);
+    assert_eq!(
+        instruction_error(test.process(&[set_rate(1_001)], &[&farm.creator]).await),
+        farm_error(FarmError::InsufficientReward)
+    );
+    test.process(&[set_rate(1_000)], &[&farm.creator])
+        .await
+        .unwrap();
+
+    // once harvested, the rewards left fund the same rate
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let harvest = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        0,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&harvest, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        1_000 * half * 99 / 100
+    );
+    assert_eq!(
+        test.token_balance(&farm.pool_reward_token_account).await,
+        1_000 * half
+    );
+    let farm_pool_v2 = test.farm_pool_v2(&farm.farm_id).await;
+    assert_eq!(farm_pool_v2.unharvested_reward(), Ok(0));
+    assert_eq!(
+        instruction_error(test.process(&[set_rate(1_001)], &[&farm.creator]).await),
+        farm_error(FarmError::InsufficientReward)
+    );
+    test.process(&[set_rate(999)], &[&farm.creator])
+        .await
+        .unwrap();
+
+    // an emergency withdrawal forfeits the rewards accrued since
+    test.set_clock(START_TIMESTAMP + half as i64 + 100);
+    let lp_mint = test.lp_mint;
+    let (user_info_address, _) =
+        find_user_info_address(&program_id, &farm.farm_id, &wallet.pubkey());
+    let withdraw = emergency_withdraw_auto_authority(
+        &farm.farm_id,
+        &wallet.pubkey(),
+        &user_info_address,
+        &get_associated_token_address_with_program_id(
+            &wallet.pubkey(),
+            &lp_mint,
+            &spl_token::id(),
+        ),
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &program_id,
+    )
+    .unwrap();
+    test.process(&[withdraw], &[&wallet]).await.unwrap();
+    let farm_pool_v2 = test.farm_pool_v2(&farm.farm_id).await;
+    assert_eq!(farm_pool_v2.total_boosted_stake, 0);
+    assert_eq!(farm_pool_v2.total_reward_debt, 0);
+    assert_eq!(farm_pool_v2.unharvested_reward(), Ok(0));
+}
+
+#[tokio::test]
+async fn test_set_reward_rate_original_layout() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let set_rate = |reward_per_second| {
+        set_reward_rate(
+            &farm.farm_id,
+            &farm.authority,
+            &farm.creator.pubkey(),
+            &farm.pool_lp_token_account,
+            &farm.pool_reward_token_account,
+            &test.program_data,
+            reward_per_second,
+            &test.program_id,
+        )
+        .unwrap()
+    };
+    let (with_stake, without_stake) = (set_rate(1_000), set_rate(999));
+
+    // the rewards owed to its stakers are not tracked
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    test.set_clock(START_TIMESTAMP + 100);
+    assert_eq!(
+        instruction_error(test.process(&[with_stake], &[&farm.creator]).await),
+        InstructionError::InvalidAccountData
+    );
+
+    // nothing is owed once nothing is staked
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        1_000,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&withdraw, &[&wallet]).await.unwrap();
+    test.process(&[without_stake], &[&farm.creator])
+        .await
+        .unwrap();
+    assert_eq!(
+        test.farm_pool(&farm.farm_id).await.reward_per_timestamp,
+        999
+    );
+}
+
+#[tokio::test]
+async fn test_harvest_insufficient_reward() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let lp_mint = test.lp_mint;
+    let reward_mint = test.reward_mint;
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+
+    // 10_000 rewards pending, the vault only holds 4_000 of them
+    test.set_clock(START_TIMESTAMP + 10);
+    test.set_token_balance(&farm.pool_reward_token_account, 4_000)
+        .await;
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let harvest = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        0,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&harvest, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        3_960
+    );
+    assert_eq!(test.token_balance(&farm.pool_reward_token_account).await, 0);
+
+    // the rest stays owed until the vault is refilled
+    let pool_reward_token_account = farm.pool_reward_token_account;
+    test.mint_to(&reward_mint, &pool_reward_token_account, 6_000)
+        .await;
+    let harvest = with_compute_budget(harvest, &test.program_id, 10);
+    test.process(&harvest, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        3_960 + 5_940
+    );
+
+    // an empty vault does not keep the LP tokens from being withdrawn
+    test.set_clock(START_TIMESTAMP + 20);
+    test.set_token_balance(&farm.pool_reward_token_account, 0)
+        .await;
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        1_000,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&withdraw, &[&wallet]).await.unwrap();
+    assert_eq!(test.ata_balance(&wallet.pubkey(), &lp_mint).await, 1_000);
+    assert_eq!(
+        test.user_info(&farm, &wallet.pubkey())
+            .await
+            .deposit_balance,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_emergency_withdraw() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let lp_mint = test.lp_mint;
+    let reward_mint = test.reward_mint;
+
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let other = test.create_staker(&farm, 3_000).await.unwrap();
+    test.set_clock(START_TIMESTAMP + 100);
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let harvest = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        0,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&harvest, &[&wallet]).await.unwrap();
+    let harvested = test.ata_balance(&wallet.pubkey(), &reward_mint).await;
+    assert_ne!(test.user_info(&farm, &wallet.pubkey()).await.reward_debt, 0);
+
+    // a dual-reward user info in the previous layout, with a second reward
+    // debt
+    let (user_info_address, _) =
+        find_user_info_address(&test.program_id, &farm.farm_id, &wallet.pubkey());
+    let mut data = vec![0; UserInfoV2::LEN];
+    UserInfoV2 {
+        wallet: wallet.pubkey(),
+        farm_id: farm.farm_id,
+        deposit_balance: 1_000,
+        reward_debt: 100 * 1_000_000_000_000,
+        reward_debt_b: 7 * 1_000_000_000,
+    }
+    .pack_into_slice(&mut data);
+    let program_id = test.program_id;
+    test.set_account(&user_info_address, data, &program_id);
+
+    let user_lp_token_account =
+        get_associated_token_address_with_program_id(&wallet.pubkey(), &lp_mint, &spl_token::id());
+    let emergency = |owner: &Pubkey| {
+        emergency_withdraw_auto_authority(
+            &farm.farm_id,
+            owner,
+            &user_info_address,
+            &user_lp_token_account,
+            &farm.pool_lp_token_account,
+            &spl_token::id(),
+            &program_id,
+        )
+        .unwrap()
+    };
+
+    // only the owner of the user info withdraws
+    assert_eq!(
+        instruction_error(test.process(&[emergency(&other.pubkey())], &[&other]).await),
+        farm_error(FarmError::NotAllowed)
+    );
+
+    test.process(&[emergency(&wallet.pubkey())], &[&wallet])
+        .await
+        .unwrap();
+    assert_eq!(test.ata_balance(&wallet.pubkey(), &lp_mint).await, 1_000);
+    assert_eq!(test.token_balance(&farm.pool_lp_token_account).await, 3_000);
+    // the pending rewards are forfeited
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        harvested
+    );
+    // every debt is cleared, in the layout of the account
+    let account = test.get_account(&user_info_address).await;
+    assert_eq!(account.data.len(), UserInfoV2::LEN);
+    assert_eq!(
+        UserInfoV2::unpack_from_slice(&account.data).unwrap(),
+        UserInfoV2 {
+            wallet: wallet.pubkey(),
+            farm_id: farm.farm_id,
+            deposit_balance: 0,
+            reward_debt: 0,
+            reward_debt_b: 0,
+        }
+    );
+
+    // the latest layout
+    let user_lp_token_account =
+        get_associated_token_address_with_program_id(&other.pubkey(), &lp_mint, &spl_token::id());
+    let (user_info_address, _) =
+        find_user_info_address(&program_id, &farm.farm_id, &other.pubkey());
+    let instruction = emergency_withdraw_auto_authority(
+        &farm.farm_id,
+        &other.pubkey(),
+        &user_info_address,
+        &user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &program_id,
+    )
+    .unwrap();
+    test.process(&[instruction], &[&other]).await.unwrap();
+    assert_eq!(test.ata_balance(&other.pubkey(), &lp_mint).await, 3_000);
+    let user_info = test.user_info(&farm, &other.pubkey()).await;
+    assert_eq!(user_info.deposit_balance, 0);
+    assert_eq!(user_info.reward_debt, 0);
+    assert_eq!(user_info.reward_debt_b, 0);
+}
+
+/// The rewards accrued with a stake leaving a farm of the original layout
+/// are distributed first, leaving those of the other stakers unchanged
+#[tokio::test]
+async fn test_emergency_withdraw_keeps_pending_rewards() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let lp_mint = test.lp_mint;
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let other = test.create_staker(&farm, 3_000).await.unwrap();
+    let now = START_TIMESTAMP as u64 + 100;
+    test.set_clock(now as i64);
+
+    let pending = |farm_pool: FarmPool, user_info: &UserInfoV3, total_staked: u64| {
+        FarmPoolV2::from(farm_pool)
+            .pending_reward(user_info, now, total_staked)
+            .unwrap()
+    };
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let other_info = test.user_info(&farm, &other.pubkey()).await;
+    let pending_before = pending(farm_pool, &other_info, 4_000);
+    assert_eq!(pending_before, 75_000);
+
+    let (user_info_address, _) =
+        find_user_info_address(&test.program_id, &farm.farm_id, &wallet.pubkey());
+    let instruction = emergency_withdraw_auto_authority(
+        &farm.farm_id,
+        &wallet.pubkey(),
+        &user_info_address,
+        &get_associated_token_address_with_program_id(&wallet.pubkey(), &lp_mint, &spl_token::id()),
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[instruction], &[&wallet]).await.unwrap();
+
+    let account = test.get_account(&farm.farm_id).await;
+    assert_eq!(account.data.len(), FarmPool::LEN);
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    assert_eq!(farm_pool.last_timestamp, now);
+    let total_staked = test.token_balance(&farm.pool_lp_token_account).await;
+    assert_eq!(total_staked, 3_000);
+    assert_eq!(
+        pending(farm_pool, &other_info, total_staked),
+        pending_before
+    );
+}
+
+/// Deposits `amount` more LP tokens of `wallet`, minted for it, locked for
+/// `lockup_seconds`
+async fn lock_deposit(
+    test: &mut FarmTestContext,
+    farm: &TestFarm,
+    wallet: &Keypair,
+    amount: u64,
+    lockup_seconds: u64,
+) -> Result<(), BanksClientError> {
+    let lp_mint = test.lp_mint;
+    let user_lp_token_account =
+        get_associated_token_address_with_program_id(&wallet.pubkey(), &lp_mint, &spl_token::id());
+    test.mint_to(&lp_mint, &user_lp_token_account, amount).await;
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let mut instructions = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        amount,
+        &test.program_id,
+    )
+    .unwrap();
+    instructions.last_mut().unwrap().data = FarmInstruction::DepositWithLockup {
+        amount,
+        lockup_seconds,
+    }
+    .pack()
+    .unwrap();
+    test.process(&instructions, &[wallet]).await
+}
+
+/// Withdraws `amount` LP tokens of `wallet`
+async fn withdraw(
+    test: &mut FarmTestContext,
+    farm: &TestFarm,
+    wallet: &Keypair,
+    amount: u64,
+) -> Result<(), BanksClientError> {
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        amount,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&withdraw, &[wallet]).await
+}
+
+#[tokio::test]
+async fn test_deposit_with_lockup() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let lp_mint = test.lp_mint;
+
+    // only the deposited amount is locked
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    test.set_clock(START_TIMESTAMP + 100);
+    lock_deposit(&mut test, &farm, &wallet, 500, 3_600)
+        .await
+        .unwrap();
+    let user_info = test.user_info(&farm, &wallet.pubkey()).await;
+    assert_eq!(user_info.deposit_balance, 1_500);
+    assert_eq!(user_info.locked_balance, 500);
+    assert_eq!(
+        user_info.lockup_end_timestamp,
+        START_TIMESTAMP as u64 + 100 + 3_600
+    );
+
+    assert_eq!(
+        instruction_error(withdraw(&mut test, &farm, &wallet, 1_001).await),
+        farm_error(FarmError::DepositLocked)
+    );
+    assert_eq!(
+        instruction_error(withdraw(&mut test, &farm, &wallet, 1_501).await),
+        InstructionError::InsufficientFunds
+    );
+    // harvesting withdraws nothing
+    withdraw(&mut test, &farm, &wallet, 0).await.unwrap();
+    withdraw(&mut test, &farm, &wallet, 1_000).await.unwrap();
+    assert_eq!(test.ata_balance(&wallet.pubkey(), &lp_mint).await, 1_000);
+
+    // the emergency withdrawal ignores the lockup
+    let (user_info_address, _) =
+        find_user_info_address(&test.program_id, &farm.farm_id, &wallet.pubkey());
+    let user_lp_token_account =
+        get_associated_token_address_with_program_id(&wallet.pubkey(), &lp_mint, &spl_token::id());
+    let emergency = emergency_withdraw_auto_authority(
+        &farm.farm_id,
+        &wallet.pubkey(),
+        &user_info_address,
+        &user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[emergency], &[&wallet]).await.unwrap();
+    assert_eq!(test.ata_balance(&wallet.pubkey(), &lp_mint).await, 1_500);
+    let user_info = test.user_info(&farm, &wallet.pubkey()).await;
+    assert_eq!(user_info.deposit_balance, 0);
+    assert_eq!(user_info.locked_balance, 0);
+    assert_eq!(user_info.lockup_end_timestamp, 0);
+
+    // everything is withdrawable once the lockup ends
+    let other = test.create_staker(&farm, 1_000).await.unwrap();
+    lock_deposit(&mut test, &farm, &other, 1_000, 60)
+        .await
+        .unwrap();
+    test.set_clock(START_TIMESTAMP + 159);
+    assert_eq!(
+        instruction_error(withdraw(&mut test, &farm, &other, 1_001).await),
+        farm_error(FarmError::DepositLocked)
+    );
+    test.set_clock(START_TIMESTAMP + 160);
+    withdraw(&mut test, &farm, &other, 2_000).await.unwrap();
+    assert_eq!(test.ata_balance(&other.pubkey(), &lp_mint).await, 2_000);
+}
+
+#[tokio::test]
+async fn test_boosted_farm() {
+    let mut test = FarmTestContext::new().await;
+    let creator = Keypair::from_bytes(&test.allowed_creator.to_bytes()).unwrap();
+    let start = START_TIMESTAMP as u64;
+    let farm = test
+        .create_farm_with_creator(creator, FarmPoolV2::LEN, start, start + FARM_DURATION)
+        .await
+        .unwrap();
+    // deposits locked for an hour or more weigh twice their amount
+    let creator = Keypair::from_bytes(&farm.creator.to_bytes()).unwrap();
+    let boost = set_boost_params(
+        &farm.farm_id,
+        &farm.authority,
+        &creator.pubkey(),
+        &test.program_data,
+        2,
+        1,
+        3_600,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[boost], &[&creator]).await.unwrap();
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let reward_mint = test.reward_mint;
+    let lp_mint = test.lp_mint;
+
+    // no boost without lockup, the full boost for the longest one
+    let unboosted = test.create_staker(&farm, 1_000).await.unwrap();
+    let boosted = test.create_staker(&farm, 0).await.unwrap();
+    lock_deposit(&mut test, &farm, &boosted, 1_000, 3_600)
+        .await
+        .unwrap();
+    assert_eq!(
+        test.user_info(&farm, &unboosted.pubkey())
+            .await
+            .boosted_share,
+        1_000
+    );
+    assert_eq!(
+        test.user_info(&farm, &boosted.pubkey()).await.boosted_share,
+        2_000
+    );
+    assert_eq!(
+        test.farm_pool_v2(&farm.farm_id).await.total_boosted_stake,
+        3_000
+    );
+
+    // the rewards are shared by stake weight, less the 1% harvest fee
+    test.set_clock(START_TIMESTAMP + 300);
+    withdraw(&mut test, &farm, &unboosted, 0).await.unwrap();
+    withdraw(&mut test, &farm, &boosted, 0).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&unboosted.pubkey(), &reward_mint).await,
+        99_000
+    );
+    assert_eq!(
+        test.ata_balance(&boosted.pubkey(), &reward_mint).await,
+        198_000
+    );
+
+    // the boost stays once the lockup ends, the weight left by a
+    // withdrawal keeping it
+    test.set_clock(START_TIMESTAMP + 3_600);
+    withdraw(&mut test, &farm, &boosted, 500).await.unwrap();
+    assert_eq!(test.ata_balance(&boosted.pubkey(), &lp_mint).await, 500);
+    let user_info = test.user_info(&farm, &boosted.pubkey()).await;
+    assert_eq!(user_info.deposit_balance, 500);
+    assert_eq!(user_info.boosted_share, 1_000);
+    assert_eq!(
+        test.farm_pool_v2(&farm.farm_id).await.total_boosted_stake,
+        2_000
+    );
+
+    // the emergency withdrawal takes the stake weight out of the farm
+    let (user_info_address, _) =
+        find_user_info_address(&test.program_id, &farm.farm_id, &unboosted.pubkey());
+    let user_lp_token_account = get_associated_token_address_with_program_id(
+        &unboosted.pubkey(),
+        &lp_mint,
+        &spl_token::id(),
+    );
+    let emergency = emergency_withdraw_auto_authority(
+        &farm.farm_id,
+        &unboosted.pubkey(),
+        &user_info_address,
+        &user_lp_token_account,
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[emergency], &[&unboosted]).await.unwrap();
+    let farm_pool = test.farm_pool_v2(&farm.farm_id).await;
+    assert_eq!(farm_pool.total_boosted_stake, 1_000);
+    assert_eq!(
+        farm_pool.farm_pool.last_timestamp,
+        START_TIMESTAMP as u64 + 3_600
+    );
+    assert_eq!(
+        test.user_info(&farm, &unboosted.pubkey())
+            .await
+            .boosted_share,
+        0
+    );
+
+    // new parameters leave the weight of earlier deposits
+    let boost = set_boost_params(
+        &farm.farm_id,
+        &farm.authority,
+        &creator.pubkey(),
+        &test.program_data,
+        1,
+        1,
+        3_600,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[boost], &[&creator]).await.unwrap();
+    withdraw(&mut test, &farm, &boosted, 0).await.unwrap();
+    assert_eq!(
+        test.user_info(&farm, &boosted.pubkey()).await.boosted_share,
+        1_000
+    );
+}
+
+#[tokio::test]
+async fn test_dual_reward_farm() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_dual_reward_farm().await;
+    let pool_second_reward_token_account = farm.pool_second_reward_token_account.unwrap();
+    let farm_pool = test.farm_pool_v2(&farm.farm_id).await;
+    let second_reward_mint = test.second_reward_mint;
+    assert_eq!(farm_pool.second_reward_mint_address, second_reward_mint);
+    assert_eq!(
+        farm_pool.pool_second_reward_token_account,
+        pool_second_reward_token_account
+    );
+    assert_eq!(farm_pool.second_last_timestamp, START_TIMESTAMP as u64);
+
+    // 1_000 rewards and 2 second rewards per second
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let creator = farm.creator.pubkey();
+    let creator_second_reward_account = test.create_ata(&creator, &second_reward_mint).await;
+    test.mint_to(
+        &second_reward_mint,
+        &creator_second_reward_account,
+        2 * FARM_DURATION,
+    )
+    .await;
+    let add = |pool_second_reward_token_account: &Pubkey| {
+        add_second_reward(
+            &farm.farm_id,
+            &farm.authority,
+            &creator,
+            &creator_second_reward_account,
+            pool_second_reward_token_account,
+            &farm.pool_lp_token_account,
+            &test.lp_mint,
+            &test.program_data,
+            &spl_token::id(),
+            2 * FARM_DURATION,
+            &test.program_id,
+        )
+        .unwrap()
+    };
+    let (wrong_vault, instruction) = (
+        add(&farm.pool_reward_token_account),
+        add(&pool_second_reward_token_account),
+    );
+    // the second reward goes to its own vault only
+    assert_eq!(
+        instruction_error(test.process(&[wrong_vault], &[&farm.creator]).await),
+        InstructionError::InvalidAccountData
+    );
+    test.process(&[instruction], &[&farm.creator])
+        .await
+        .unwrap();
+    let farm_pool = test.farm_pool_v2(&farm.farm_id).await;
+    assert_eq!(farm_pool.second_reward_per_timestamp, 2);
+    assert_eq!(farm_pool.farm_pool.reward_per_timestamp, 1_000);
+    assert_eq!(
+        test.token_balance(&pool_second_reward_token_account).await,
+        2 * FARM_DURATION
+    );
+
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    test.set_clock(START_TIMESTAMP + 100);
+    let farm_pool = test.farm_pool_v2(&farm.farm_id).await;
+    let harvest = deposit_with_atas(
+        &farm.farm_id,
+        &farm_pool.farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        0,
+        &test.program_id,
+    )
+    .unwrap();
+    // the second reward accounts are required
+    assert_eq!(
+        instruction_error(test.process(&harvest, &[&wallet]).await),
+        InstructionError::NotEnoughAccountKeys
+    );
+    let mut harvest = harvest;
+    harvest[0]
+        .accounts
+        .extend(second_reward_accounts(&farm_pool, &wallet.pubkey()));
+    test.process(&harvest, &[&wallet]).await.unwrap();
+    let reward_mint = test.reward_mint;
+    let fee_owner = test.fee_owner.pubkey();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        99_000
+    );
+    // 200 second rewards, less the 1% harvest fee
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &second_reward_mint)
+            .await,
+        198
+    );
+    assert_eq!(test.ata_balance(&fee_owner, &second_reward_mint).await, 2);
+    let farm_pool = test.farm_pool_v2(&farm.farm_id).await;
+    assert_eq!(
+        farm_pool.second_last_timestamp,
+        START_TIMESTAMP as u64 + 100
+    );
+    assert_eq!(
+        farm_pool.second_reward_per_share_net,
+        200 * 1_000_000_000 / 1_000
+    );
+    assert_eq!(
+        test.user_info(&farm, &wallet.pubkey()).await.reward_debt_b,
+        200 * 1_000_000_000
+    );
+
+    // withdrawing harvests both rewards
+    test.set_clock(START_TIMESTAMP + 200);
+    let mut withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool.farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        1_000,
+        &test.program_id,
+    )
+    .unwrap();
+    withdraw[0]
+        .accounts
+        .extend(second_reward_accounts(&farm_pool, &wallet.pubkey()));
+    test.process(&withdraw, &[&wallet]).await.unwrap();
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &reward_mint).await,
+        2 * 99_000
+    );
+    assert_eq!(
+        test.ata_balance(&wallet.pubkey(), &second_reward_mint)
+            .await,
+        2 * 198
+    );
+
+    // closing returns what is left of both rewards
+    test.set_clock(farm.end_timestamp as i64 + 1);
+    let creator_reward_account = test.create_ata(&creator, &reward_mint).await;
+    let close = close_dual_reward_farm(
+        &farm.farm_id,
+        &farm.authority,
+        &creator,
+        &farm.pool_reward_token_account,
+        &creator_reward_account,
+        &farm.pool_lp_token_account,
+        &pool_second_reward_token_account,
+        &creator_second_reward_account,
+        &spl_token::id(),
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&[close], &[&farm.creator]).await.unwrap();
+    assert_eq!(
+        test.token_balance(&creator_second_reward_account).await,
+        2 * FARM_DURATION - 400
+    );
+    assert_eq!(
+        test.token_balance(&pool_second_reward_token_account).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_close_farm() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    test.add_reward(&farm, 1_000 * FARM_DURATION).await.unwrap();
+    let reward_mint = test.reward_mint;
+    let creator = farm.creator.pubkey();
+    let creator_reward_account = test.create_ata(&creator, &reward_mint).await;
+    let wallet = test.create_staker(&farm, 1_000).await.unwrap();
+    let close = close_farm(
+        &farm.farm_id,
+        &farm.authority,
+        &creator,
+        &farm.pool_reward_token_account,
+        &creator_reward_account,
+        &farm.pool_lp_token_account,
+        &spl_token::id(),
+        &test.program_id,
+    )
+    .unwrap();
+
+    // not while the farm runs, nor at its end timestamp, each attempt
+    // under a new blockhash not to get the status of the previous one
+    for now in [
+        START_TIMESTAMP + 100,
+        START_TIMESTAMP + FARM_DURATION as i64,
+    ] {
+        test.set_clock(now);
+        test.context.get_new_latest_blockhash().await.unwrap();
+        assert_eq!(
+            instruction_error(
+                test.process(std::slice::from_ref(&close), &[&farm.creator])
+                    .await
+            ),
+            farm_error(FarmError::FarmNotEnded)
+        );
+    }
+    // nor while LP tokens are staked
+    test.set_clock(START_TIMESTAMP + FARM_DURATION as i64 + 1);
+    test.context.get_new_latest_blockhash().await.unwrap();
+    assert_eq!(
+        instruction_error(
+            test.process(std::slice::from_ref(&close), &[&farm.creator])
+                .await
+        ),
+        farm_error(FarmError::FarmNotEmpty)
+    );
+
+    let farm_pool = test.farm_pool(&farm.farm_id).await;
+    let withdraw = withdraw_with_atas(
+        &farm.farm_id,
+        &farm_pool,
+        &wallet.pubkey(),
+        &test.program_data,
+        false,
+        1_000,
+        &test.program_id,
+    )
+    .unwrap();
+    test.process(&withdraw, &[&wallet]).await.unwrap();
+    // the staker harvested the whole farm, less the harvest fee
+    let harvested = test.ata_balance(&wallet.pubkey(), &reward_mint).await;
+    let fee_owner = test.fee_owner.pubkey();
+    let fee = test.ata_balance(&fee_owner, &reward_mint).await;
+    assert_eq!(harvested + fee, 1_000 * FARM_DURATION);
+
+    test.context.get_new_latest_blockhash().await.unwrap();
+    test.process(&[close], &[&farm.creator]).await.unwrap();
+    assert_eq!(test.token_balance(&farm.pool_reward_token_account).await, 0);
+    assert!(test
+        .context
+        .banks_client
+        .get_account(farm.farm_id)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_add_second_reward_single_reward_farm() {
+    let mut test = FarmTestContext::new().await;
+    let farm = test.create_farm().await;
+    let second_reward_mint = test.second_reward_mint;
+    let creator = farm.creator.pubkey();
+    let creator_second_reward_account = test.create_ata(&creator, &second_reward_mint).await;
+    test.mint_to(&second_reward_mint, &creator_second_reward_account, 1_000)
+        .await;
+    let authority = farm.authority;
+    let pool_second_reward_token_account = test
+        .create_token_account(&second_reward_mint, &authority)
+        .await;
+    let instruction = add_second_reward(
+        &farm.farm_id,
+        &farm.authority,
+        &creator,
+        &creator_second_reward_account,
+        &pool_second_reward_token_account,
+        &farm.pool_lp_token_account,
+        &test.lp_mint,
+        &test.program_data,
+        &spl_token::id(),
+        1_000,
+        &test.program_id,
+    )
+    .unwrap();
+    assert_eq!(
+        instruction_error(test.process(&[instruction], &[&farm.creator]).await),
+        InstructionError::InvalidAccountData
+    );
+}